@@ -1,8 +1,360 @@
-use calamine::{open_workbook, Reader, Xlsx};
+use calamine::{open_workbook, DataType, Range, Reader, Xlsx};
 use linfa::prelude::*;
-use linfa_clustering::KMeans;
-use ndarray::Array2;
+use linfa_clustering::{Dbscan, KMeans};
+use ndarray::{Array1, Array2, Axis};
+use rand_xoshiro::rand_core::SeedableRng;
+use rand_xoshiro::Xoshiro256Plus;
+use std::collections::HashMap;
 use std::error::Error;
+use std::fs::File;
+use std::io::Write;
+use std::ops::RangeInclusive;
+
+use shared::percentile::percentile;
+
+const N_FEATURES: usize = 2;
+const BASE_SEED: u64 = 42;
+
+// Feature rows that survived loading, plus how many were skipped and why
+struct LoadedFeatures {
+    data: Array2<f64>,
+    blank_rows: usize,
+    invalid_rows: usize,
+}
+
+// Float cells as-is, integers widened, and text parsed after dropping
+// thousands separators; anything else is not a usable number
+fn cell_to_f64(cell: &DataType) -> Option<f64> {
+    if let Some(value) = cell.get_float() {
+        return Some(value);
+    }
+    match cell {
+        DataType::Int(i) => Some(*i as f64),
+        DataType::String(s) => s.trim().replace(',', "").parse::<f64>().ok(),
+        _ => None,
+    }
+}
+
+fn is_blank(cell: Option<&DataType>) -> bool {
+    match cell {
+        None | Some(DataType::Empty) => true,
+        Some(DataType::String(s)) => s.trim().is_empty(),
+        _ => false,
+    }
+}
+
+// Read the first N_FEATURES columns below the header row. Blank rows and rows
+// with a non-numeric cell are skipped and counted instead of aborting the run.
+fn load_features(range: &Range<DataType>, n_clusters: usize) -> Result<LoadedFeatures, Box<dyn Error>> {
+    let mut values = Vec::new();
+    let mut blank_rows = 0;
+    let mut invalid_rows = 0;
+
+    for row in range.rows().skip(1) {
+        if (0..N_FEATURES).all(|i| is_blank(row.get(i))) {
+            blank_rows += 1;
+            continue;
+        }
+
+        let parsed: Option<Vec<f64>> = (0..N_FEATURES)
+            .map(|i| row.get(i).and_then(cell_to_f64))
+            .collect();
+        match parsed {
+            Some(features) => values.extend(features),
+            None => invalid_rows += 1,
+        }
+    }
+
+    let valid_rows = values.len() / N_FEATURES;
+    if valid_rows < n_clusters {
+        return Err(format!(
+            "Only {} valid rows ({} blank, {} non-numeric skipped); need at least {} for {} clusters",
+            valid_rows, blank_rows, invalid_rows, n_clusters, n_clusters
+        ).into());
+    }
+
+    let data = Array2::from_shape_vec((valid_rows, N_FEATURES), values)?;
+    Ok(LoadedFeatures { data, blank_rows, invalid_rows })
+}
+
+// The value after `flag` on the command line, if it is given
+fn value_of<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|a| a == flag)
+        .and_then(|i| args.get(i + 1))
+        .map(String::as_str)
+}
+
+// --algorithm kmeans (default) or dbscan with --eps and --min-points
+#[derive(Debug, PartialEq)]
+enum Algorithm {
+    KMeans,
+    Dbscan { eps: f64, min_points: usize },
+}
+
+impl Algorithm {
+    fn from_args(args: &[String]) -> Result<Self, Box<dyn Error>> {
+        match value_of(args, "--algorithm").unwrap_or("kmeans") {
+            "kmeans" => Ok(Algorithm::KMeans),
+            "dbscan" => Ok(Algorithm::Dbscan {
+                eps: value_of(args, "--eps").unwrap_or("0.5").parse()?,
+                min_points: value_of(args, "--min-points").unwrap_or("5").parse()?,
+            }),
+            other => Err(format!("Unknown --algorithm '{}' (expected kmeans or dbscan)", other).into()),
+        }
+    }
+}
+
+// Density-based labels; None marks noise points that belong to no cluster
+fn dbscan_labels(data: &Array2<f64>, eps: f64, min_points: usize) -> Result<Vec<Option<usize>>, Box<dyn Error>> {
+    let labels = Dbscan::params(min_points)
+        .tolerance(eps)
+        .transform(data)?;
+    Ok(labels.to_vec())
+}
+
+// One row per point with its features and cluster, `unassigned` ("noise",
+// "outlier") for points in no cluster
+fn write_assignments(data: &Array2<f64>, labels: &[Option<usize>], unassigned: &str, output_path: &str) -> Result<(), Box<dyn Error>> {
+    let mut file = File::create(output_path)?;
+    let feature_headers: Vec<String> = (1..=N_FEATURES).map(|i| format!("feature_{}", i)).collect();
+    writeln!(file, "row,{},cluster", feature_headers.join(","))?;
+    for (i, (point, label)) in data.rows().into_iter().zip(labels).enumerate() {
+        let features: Vec<String> = point.iter().map(|v| v.to_string()).collect();
+        let cluster = label.map_or_else(|| unassigned.to_string(), |c| c.to_string());
+        writeln!(file, "{},{},{}", i + 1, features.join(","), cluster)?;
+    }
+    Ok(())
+}
+
+// --strip-outliers {none,iqr,zscore} with --outlier-k K: before KMeans is
+// fitted, drop every row with a feature outside the fence, Q1 - K*IQR to
+// Q3 + K*IQR (K = 1.5 by default) or K standard deviations from the mean
+// (K = 3 by default), so extreme scores can't pull the centroids
+#[derive(Debug, PartialEq)]
+enum OutlierFence {
+    None,
+    Iqr { k: f64 },
+    ZScore { k: f64 },
+}
+
+impl OutlierFence {
+    fn from_args(args: &[String]) -> Result<Self, Box<dyn Error>> {
+        let k = |default: f64| -> Result<f64, Box<dyn Error>> {
+            match value_of(args, "--outlier-k") {
+                None => Ok(default),
+                Some(value) => match value.parse::<f64>() {
+                    Ok(k) if k > 0.0 => Ok(k),
+                    _ => Err(format!("Invalid --outlier-k '{}' (expected a positive number)", value).into()),
+                },
+            }
+        };
+
+        match value_of(args, "--strip-outliers").unwrap_or("none") {
+            "none" => Ok(OutlierFence::None),
+            "iqr" => Ok(OutlierFence::Iqr { k: k(1.5)? }),
+            "zscore" => Ok(OutlierFence::ZScore { k: k(3.0)? }),
+            other => Err(format!("Unknown --strip-outliers '{}' (expected none, iqr or zscore)", other).into()),
+        }
+    }
+}
+
+// true for every row with at least one feature outside the fence
+fn outlier_rows(data: &Array2<f64>, fence: &OutlierFence) -> Vec<bool> {
+    let mut outliers = vec![false; data.nrows()];
+    if data.nrows() < 2 {
+        return outliers;
+    }
+    for column in data.columns() {
+        let (low, high) = match *fence {
+            OutlierFence::None => return outliers,
+            OutlierFence::Iqr { k } => {
+                let mut sorted = column.to_vec();
+                sorted.sort_by(|a, b| a.total_cmp(b));
+                let (q1, q3) = (percentile(&sorted, 25.0), percentile(&sorted, 75.0));
+                (q1 - k * (q3 - q1), q3 + k * (q3 - q1))
+            }
+            OutlierFence::ZScore { k } => {
+                let n = column.len() as f64;
+                let mean = column.sum() / n;
+                let std_dev = (column.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt();
+                (mean - k * std_dev, mean + k * std_dev)
+            }
+        };
+        for (outlier, &value) in outliers.iter_mut().zip(column.iter()) {
+            *outlier |= value < low || value > high;
+        }
+    }
+    outliers
+}
+
+fn nearest_centroid(point: ndarray::ArrayView1<f64>, centroids: &Array2<f64>) -> usize {
+    centroids.rows()
+        .into_iter()
+        .map(|centroid| squared_distance(point, centroid))
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map_or(0, |(i, _)| i)
+}
+
+// KMeans fitted on the rows inside the fence. Stripped rows get no cluster,
+// or with --assign-outliers the cluster of their nearest centroid. Returns
+// the centroids, one label per row of `data` and how many rows were stripped.
+type StrippedFit = (Array2<f64>, Vec<Option<usize>>, usize);
+
+fn kmeans_without_outliers(
+    data: &Array2<f64>,
+    k: usize,
+    fence: &OutlierFence,
+    assign_outliers: bool,
+) -> Result<StrippedFit, Box<dyn Error>> {
+    let outliers = outlier_rows(data, fence);
+    let kept: Vec<usize> = (0..data.nrows()).filter(|&i| !outliers[i]).collect();
+    let removed = data.nrows() - kept.len();
+    if kept.len() < k {
+        return Err(format!(
+            "Only {} rows left after stripping {} outliers; need at least {} for {} clusters",
+            kept.len(), removed, k, k
+        ).into());
+    }
+
+    let (centroids, kept_labels) = fit_labels(&data.select(Axis(0), &kept), k, BASE_SEED)?;
+    let mut labels: Vec<Option<usize>> = vec![None; data.nrows()];
+    for (&row, &label) in kept.iter().zip(kept_labels.iter()) {
+        labels[row] = Some(label);
+    }
+    if assign_outliers {
+        for (row, label) in labels.iter_mut().enumerate() {
+            if outliers[row] {
+                *label = Some(nearest_centroid(data.row(row), &centroids));
+            }
+        }
+    }
+    Ok((centroids, labels, removed))
+}
+
+struct ElbowPoint {
+    k: usize,
+    inertia: f64,
+    // Undefined for a single cluster
+    silhouette: Option<f64>,
+}
+
+// "2..10" -> 2..=10 (both ends included)
+fn parse_k_range(value: &str) -> Result<RangeInclusive<usize>, Box<dyn Error>> {
+    let (start, end) = value.split_once("..")
+        .ok_or_else(|| format!("Invalid --k-range '{}' (expected e.g. 2..10)", value))?;
+    let start: usize = start.trim().parse()?;
+    let end: usize = end.trim().parse()?;
+    if start == 0 || start > end {
+        return Err(format!("Invalid --k-range '{}' (need 1 <= start <= end)", value).into());
+    }
+    Ok(start..=end)
+}
+
+fn fit_labels(data: &Array2<f64>, k: usize, seed: u64) -> Result<(Array2<f64>, Array1<usize>), Box<dyn Error>> {
+    let rng = Xoshiro256Plus::seed_from_u64(seed);
+    let model = KMeans::params_with_rng(k, rng)
+        .max_n_iterations(100)
+        .fit(&DatasetBase::from(data.clone()))?;
+    let labels = model.predict(data);
+    Ok((model.centroids().clone(), labels))
+}
+
+fn squared_distance(a: ndarray::ArrayView1<f64>, b: ndarray::ArrayView1<f64>) -> f64 {
+    a.iter().zip(b.iter()).map(|(x, y)| (x - y).powi(2)).sum()
+}
+
+// Sum of squared distances from every point to its assigned centroid
+fn inertia(data: &Array2<f64>, centroids: &Array2<f64>, labels: &Array1<usize>) -> f64 {
+    data.rows()
+        .into_iter()
+        .zip(labels.iter())
+        .map(|(point, &label)| squared_distance(point, centroids.row(label)))
+        .sum()
+}
+
+// Mean silhouette over all points: (b - a) / max(a, b), where a is the mean
+// distance to the point's own cluster and b to the nearest other cluster
+fn silhouette_score(data: &Array2<f64>, labels: &Array1<usize>) -> Option<f64> {
+    let n = data.nrows();
+    let n_clusters = labels.iter().max().map_or(0, |&m| m + 1);
+    if n_clusters < 2 || n < 2 {
+        return None;
+    }
+
+    let mut total = 0.0;
+    for i in 0..n {
+        let mut sums = vec![0.0; n_clusters];
+        let mut counts = vec![0usize; n_clusters];
+        for j in 0..n {
+            if i != j {
+                sums[labels[j]] += squared_distance(data.row(i), data.row(j)).sqrt();
+                counts[labels[j]] += 1;
+            }
+        }
+
+        let own = labels[i];
+        if counts[own] == 0 {
+            // Singleton clusters contribute 0 by convention
+            continue;
+        }
+        let a = sums[own] / counts[own] as f64;
+        let b = (0..n_clusters)
+            .filter(|&c| c != own && counts[c] > 0)
+            .map(|c| sums[c] / counts[c] as f64)
+            .fold(f64::INFINITY, f64::min);
+        if b.is_finite() {
+            total += (b - a) / a.max(b);
+        }
+    }
+    Some(total / n as f64)
+}
+
+fn k_sweep(data: &Array2<f64>, ks: RangeInclusive<usize>) -> Result<Vec<ElbowPoint>, Box<dyn Error>> {
+    ks.map(|k| {
+        let (centroids, labels) = fit_labels(data, k, BASE_SEED)?;
+        Ok(ElbowPoint {
+            k,
+            inertia: inertia(data, &centroids, &labels),
+            silhouette: silhouette_score(data, &labels),
+        })
+    })
+    .collect()
+}
+
+fn write_elbow_csv(points: &[ElbowPoint], output_path: &str) -> Result<(), Box<dyn Error>> {
+    let mut file = File::create(output_path)?;
+    writeln!(file, "k,inertia,silhouette")?;
+    for point in points {
+        let silhouette = point.silhouette.map_or_else(String::new, |s| s.to_string());
+        writeln!(file, "{},{},{}", point.k, point.inertia, silhouette)?;
+    }
+    Ok(())
+}
+
+// Relabel clusters in order of first appearance so identical partitions
+// compare equal regardless of which number KMeans gave each cluster
+fn canonical_labels(labels: &Array1<usize>) -> Vec<usize> {
+    let mut mapping: HashMap<usize, usize> = HashMap::new();
+    labels.iter()
+        .map(|&label| {
+            let next = mapping.len();
+            *mapping.entry(label).or_insert(next)
+        })
+        .collect()
+}
+
+// Fraction of `runs` differently seeded fits that reproduce the most common partition
+fn cluster_stability(data: &Array2<f64>, k: usize, runs: usize) -> Result<f64, Box<dyn Error>> {
+    let mut partitions: HashMap<Vec<usize>, usize> = HashMap::new();
+    for run in 0..runs {
+        let (_, labels) = fit_labels(data, k, BASE_SEED + run as u64)?;
+        *partitions.entry(canonical_labels(&labels)).or_insert(0) += 1;
+    }
+    let most_common = partitions.values().copied().max().unwrap_or(0);
+    Ok(most_common as f64 / runs.max(1) as f64)
+}
 
 fn main() -> Result<(), Box<dyn Error>> {
     // Load the Excel file
@@ -15,31 +367,239 @@ fn main() -> Result<(), Box<dyn Error>> {
         .ok_or("Cannot find 'Sheet1'")?
         .map_err(|e| Box::new(e) as Box<dyn Error>)?;
 
+    let args: Vec<String> = std::env::args().collect();
+    let algorithm = Algorithm::from_args(&args)?;
+    // --strip-outliers only applies to KMeans; DBSCAN already leaves such
+    // points out as noise. --assign-outliers gives the stripped rows their
+    // nearest centroid in cluster_assignments.csv.
+    let fence = OutlierFence::from_args(&args)?;
+    let assign_outliers = args.iter().any(|a| a == "--assign-outliers");
+
+    // Define KMeans parameters and create model
+    let n_clusters = 5;
+    let min_rows = match algorithm {
+        Algorithm::KMeans => n_clusters,
+        Algorithm::Dbscan { min_points, .. } => min_points,
+    };
+
     // Collect the data into an array
-    let mut data = Vec::new();
-    for row in range.rows().skip(1) {
-        let self_esteem_level = row[0].get_float().ok_or("Invalid data")?;
-        let adhd_type = row[1].get_float().ok_or("Invalid data")?;
-        data.push(self_esteem_level);
-        data.push(adhd_type);
+    let features = load_features(&range, min_rows)?;
+    if features.blank_rows > 0 || features.invalid_rows > 0 {
+        println!("Skipped {} blank and {} non-numeric rows", features.blank_rows, features.invalid_rows);
     }
+    let data = features.data;
 
-    // Convert data to ndarray
-    let data = Array2::from_shape_vec((data.len() / 2, 2), data)?;
-    let dataset = DatasetBase::from(data.clone());
+    let labels: Vec<Option<usize>> = match algorithm {
+        Algorithm::KMeans if fence != OutlierFence::None => {
+            let (centroids, labels, removed) = kmeans_without_outliers(&data, n_clusters, &fence, assign_outliers)?;
+            println!("Stripped {} of {} rows as outliers ({:?}) before fitting", removed, data.nrows(), fence);
+            println!("Clustering completed with {} clusters", n_clusters);
+            println!("Centroids:\n{}", centroids);
+            labels
+        }
+        Algorithm::KMeans => {
+            // Same seeded fit as --strip-outliers, so both paths are reproducible
+            let (centroids, predictions) = fit_labels(&data, n_clusters, BASE_SEED)?;
 
-    // Define KMeans parameters and create model
-    let n_clusters = 5;
-    let model = KMeans::params(n_clusters)
-        .max_n_iterations(100)
-        .fit(&data)?;
+            // Print basic clustering results
+            println!("Clustering completed with {} clusters", n_clusters);
+            println!("Centroids:\n{}", centroids);
+            predictions.iter().map(|&label| Some(label)).collect()
+        }
+        Algorithm::Dbscan { eps, min_points } => {
+            let labels = dbscan_labels(&data, eps, min_points)?;
+            let found = labels.iter().flatten().max().map_or(0, |&m| m + 1);
+            let noise = labels.iter().filter(|l| l.is_none()).count();
+            println!("DBSCAN (eps = {}, min points = {}) found {} clusters and {} noise points",
+                eps, min_points, found, noise);
+            labels
+        }
+    };
+    let unassigned = match algorithm {
+        Algorithm::KMeans => "outlier",
+        Algorithm::Dbscan { .. } => "noise",
+    };
+    write_assignments(&data, &labels, unassigned, "cluster_assignments.csv")?;
+    println!("Cluster assignments saved to cluster_assignments.csv");
 
-    // Predict the clusters
-    let predictions = model.predict(&data);
+    // --k-range 2..10 writes (k, inertia, silhouette) for an elbow plot;
+    // --stability-runs R refits R times with different seeds
+    if let Some(range) = value_of(&args, "--k-range") {
+        let points = k_sweep(&data, parse_k_range(range)?)?;
+        write_elbow_csv(&points, "elbow_plot.csv")?;
+        println!("Elbow data for k = {} saved to elbow_plot.csv", range);
+    }
 
-    // Print basic clustering results
-    println!("Clustering completed with {} clusters", n_clusters);
-    println!("Centroids:\n{}", model.centroids());
+    if let Some(runs) = value_of(&args, "--stability-runs") {
+        let runs: usize = runs.parse()?;
+        let stability = cluster_stability(&data, n_clusters, runs)?;
+        println!("Stability: {:.0}% of {} runs gave the same partition", stability * 100.0, runs);
+    }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sheet(rows: &[[DataType; 2]]) -> Range<DataType> {
+        let mut range = Range::new((0, 0), (rows.len() as u32, 1));
+        range.set_value((0, 0), DataType::String("self_esteem".to_string()));
+        range.set_value((0, 1), DataType::String("adhd_type".to_string()));
+        for (r, row) in rows.iter().enumerate() {
+            for (c, cell) in row.iter().enumerate() {
+                range.set_value((r as u32 + 1, c as u32), cell.clone());
+            }
+        }
+        range
+    }
+
+    #[test]
+    fn test_loader_skips_blank_and_text_rows() {
+        let range = sheet(&[
+            [DataType::Float(1.0), DataType::Float(1.0)],
+            [DataType::Float(1.2), DataType::Int(1)],
+            [DataType::Empty, DataType::Empty],
+            [DataType::String("n/a".to_string()), DataType::Float(2.0)],
+            [DataType::String("1,000".to_string()), DataType::Float(9.0)],
+            [DataType::Float(1001.0), DataType::Float(9.5)],
+        ]);
+
+        let features = load_features(&range, 2).unwrap();
+        assert_eq!(features.blank_rows, 1);
+        assert_eq!(features.invalid_rows, 1);
+        assert_eq!(features.data.nrows(), 4);
+        assert_eq!(features.data[[2, 0]], 1000.0);
+
+        let model = KMeans::params(2)
+            .max_n_iterations(100)
+            .fit(&DatasetBase::from(features.data.clone()))
+            .unwrap();
+        assert_eq!(model.centroids().nrows(), 2);
+    }
+
+    #[test]
+    fn test_loader_aborts_with_too_few_rows() {
+        let range = sheet(&[
+            [DataType::Float(1.0), DataType::Float(1.0)],
+            [DataType::Empty, DataType::Empty],
+        ]);
+        assert!(load_features(&range, 5).is_err());
+    }
+
+    // Two tight, far-apart blobs of 20 points each
+    fn two_blobs() -> Array2<f64> {
+        let mut values = Vec::new();
+        for (cx, cy) in [(0.0, 0.0), (50.0, 50.0)] {
+            for i in 0..20 {
+                let angle = i as f64 * 0.7;
+                values.push(cx + angle.cos() * (1.0 + (i % 3) as f64 * 0.3));
+                values.push(cy + angle.sin() * (1.0 + (i % 4) as f64 * 0.2));
+            }
+        }
+        Array2::from_shape_vec((40, 2), values).unwrap()
+    }
+
+    #[test]
+    fn test_elbow_sweep_on_two_blobs() {
+        let data = two_blobs();
+        let points = k_sweep(&data, 1..=5).unwrap();
+
+        for pair in points.windows(2) {
+            assert!(pair[1].inertia <= pair[0].inertia + 1e-9, "k={} -> k={}", pair[0].k, pair[1].k);
+        }
+
+        // The drop from k=1 to k=2 dwarfs every later one
+        let drop_to_2 = points[0].inertia - points[1].inertia;
+        let drop_to_3 = points[1].inertia - points[2].inertia;
+        assert!(drop_to_2 > 50.0 * drop_to_3);
+
+        assert!(points[0].silhouette.is_none());
+        assert!(points[1].silhouette.unwrap() > 0.9);
+        assert!(cluster_stability(&data, 2, 5).unwrap() == 1.0);
+    }
+
+    #[test]
+    fn test_parse_k_range() {
+        assert_eq!(parse_k_range("2..10").unwrap(), 2..=10);
+        assert!(parse_k_range("5..2").is_err());
+        assert!(parse_k_range("0..3").is_err());
+    }
+
+    #[test]
+    fn test_dbscan_separates_blobs_and_flags_noise() {
+        let mut values: Vec<f64> = two_blobs().iter().copied().collect();
+        // Isolated points well away from both blobs and from each other
+        for (x, y) in [(25.0, 0.0), (0.0, 30.0), (30.0, -20.0)] {
+            values.push(x);
+            values.push(y);
+        }
+        let data = Array2::from_shape_vec((43, 2), values).unwrap();
+
+        let labels = dbscan_labels(&data, 2.0, 3).unwrap();
+
+        let first_blob = labels[0].expect("blob point marked as noise");
+        let second_blob = labels[20].expect("blob point marked as noise");
+        assert_ne!(first_blob, second_blob);
+        assert!(labels[..20].iter().all(|&l| l == Some(first_blob)));
+        assert!(labels[20..40].iter().all(|&l| l == Some(second_blob)));
+        assert!(labels[40..].iter().all(|l| l.is_none()));
+    }
+
+    #[test]
+    fn test_algorithm_from_args() {
+        let args: Vec<String> = ["cluster", "--algorithm", "dbscan", "--eps", "0.8", "--min-points", "4"]
+            .iter().map(|s| s.to_string()).collect();
+        assert_eq!(Algorithm::from_args(&args).unwrap(), Algorithm::Dbscan { eps: 0.8, min_points: 4 });
+        assert_eq!(Algorithm::from_args(&[]).unwrap(), Algorithm::KMeans);
+    }
+
+    #[test]
+    fn test_strip_outliers_before_kmeans() {
+        let blobs = two_blobs();
+        let mut values: Vec<f64> = blobs.iter().copied().collect();
+        for (x, y) in [(200.0, -150.0), (-180.0, 220.0), (300.0, 300.0)] {
+            values.push(x);
+            values.push(y);
+        }
+        let data = Array2::from_shape_vec((43, 2), values).unwrap();
+
+        let outliers = outlier_rows(&data, &OutlierFence::Iqr { k: 1.5 });
+        assert!(outliers[..40].iter().all(|&o| !o));
+        assert!(outliers[40..].iter().all(|&o| o));
+
+        let (stripped, labels, removed) = kmeans_without_outliers(&data, 2, &OutlierFence::Iqr { k: 1.5 }, false).unwrap();
+        assert_eq!(removed, 3);
+        assert!(labels[40..].iter().all(|l| l.is_none()));
+        let (_, assigned, _) = kmeans_without_outliers(&data, 2, &OutlierFence::Iqr { k: 1.5 }, true).unwrap();
+        assert_eq!(assigned[42], assigned[20]);
+        let (unstripped, _) = fit_labels(&data, 2, BASE_SEED).unwrap();
+
+        // Distance from each blob's centre to the closest centroid
+        let centres = [
+            blobs.slice(ndarray::s![..20, ..]).mean_axis(Axis(0)).unwrap(),
+            blobs.slice(ndarray::s![20.., ..]).mean_axis(Axis(0)).unwrap(),
+        ];
+        let miss = |centroids: &Array2<f64>| -> f64 {
+            centres.iter()
+                .map(|c| squared_distance(c.view(), centroids.row(nearest_centroid(c.view(), centroids))).sqrt())
+                .sum()
+        };
+        assert!(miss(&stripped) < 1e-3, "{}", stripped);
+        assert!(miss(&unstripped) > 1.0, "{}", unstripped);
+    }
+
+    #[test]
+    fn test_outlier_fence_from_args() {
+        let args = |flags: &[&str]| -> Vec<String> { flags.iter().map(|s| s.to_string()).collect() };
+        assert_eq!(OutlierFence::from_args(&[]).unwrap(), OutlierFence::None);
+        assert_eq!(OutlierFence::from_args(&args(&["--strip-outliers", "zscore"])).unwrap(), OutlierFence::ZScore { k: 3.0 });
+        assert_eq!(
+            OutlierFence::from_args(&args(&["--strip-outliers", "iqr", "--outlier-k", "3"])).unwrap(),
+            OutlierFence::Iqr { k: 3.0 }
+        );
+        assert!(OutlierFence::from_args(&args(&["--strip-outliers", "mad"])).is_err());
+        assert!(OutlierFence::from_args(&args(&["--strip-outliers", "iqr", "--outlier-k", "-1"])).is_err());
+    }
+}
@@ -2,12 +2,79 @@ use std::error::Error;
 use std::fs;
 use std::path::{Path, PathBuf};
 use csv::Writer;
-use std::collections::HashMap;
+use glob::glob;
+use std::collections::{BTreeMap, HashMap};
+use std::time::SystemTime;
+use shared::preview::pad_to_width;
+use strsim::levenshtein;
+use walkdir::WalkDir;
+
+const DEFAULT_INPUT_DIR: &str = "/home/aricept094/mydata/casia2-4/combined_data";
 
 #[derive(Debug)]
 struct FileInfo {
+    // Path relative to the scanned directory, e.g. sub/P_001_2020_01_L_002.csv
     filename: String,
+    // Base name as written in this file's name, e.g. P_001_2020_01
+    base: String,
     sequence: u32,
+    modified: Option<SystemTime>,
+}
+
+// Which file of a duplicate group survives
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum KeepPolicy {
+    Lowest,
+    Highest,
+    Newest,
+}
+
+impl KeepPolicy {
+    fn name(&self) -> &'static str {
+        match self {
+            KeepPolicy::Lowest => "lowest",
+            KeepPolicy::Highest => "highest",
+            KeepPolicy::Newest => "newest",
+        }
+    }
+
+    // Order a group so the file to keep comes first
+    fn sort_group(&self, files: &mut [FileInfo]) {
+        match self {
+            KeepPolicy::Lowest => files.sort_by_key(|f| f.sequence),
+            KeepPolicy::Highest => files.sort_by_key(|f| std::cmp::Reverse(f.sequence)),
+            // Files without an mtime sort last; ties fall back to the lower sequence
+            KeepPolicy::Newest => files.sort_by(|a, b| {
+                b.modified.cmp(&a.modified).then(a.sequence.cmp(&b.sequence))
+            }),
+        }
+    }
+
+    fn parse(value: &str) -> Result<Self, String> {
+        match value.to_ascii_lowercase().as_str() {
+            "lowest" => Ok(KeepPolicy::Lowest),
+            "highest" => Ok(KeepPolicy::Highest),
+            "newest" => Ok(KeepPolicy::Newest),
+            other => Err(format!("Unknown --keep policy '{}' (expected lowest, highest or newest)", other)),
+        }
+    }
+
+    fn reason(&self, keep: &FileInfo, remove: &FileInfo, eye: &str) -> String {
+        let (keep_label, remove_label) = match self {
+            KeepPolicy::Lowest => ("lower", "higher"),
+            KeepPolicy::Highest => ("higher", "lower"),
+            KeepPolicy::Newest => ("newer", "older"),
+        };
+        format!(
+            "Keep sequence {} ({}) vs {} ({}) for eye {} [keep={}]",
+            keep.sequence,
+            keep_label,
+            remove.sequence,
+            remove_label,
+            eye,
+            self.name()
+        )
+    }
 }
 
 #[derive(Debug)]
@@ -17,89 +84,280 @@ struct DuplicateReport {
     reason: String,
 }
 
-// Helper function to parse file components
-fn parse_filename(filename: &str) -> Option<(String, String, u32)> {
-    let parts: Vec<&str> = filename.split('_').collect();
-    if parts.len() >= 5 {
-        // Extract the base part (everything before L/R), the eye indicator (L/R), and the sequence number
-        let eye_indicator = parts[4].to_string(); // This should be L or R
-        let sequence_str = parts[5].to_string(); // This should be the sequence number (e.g., 002)
-        let base_name = parts[..4].join("_"); // Join everything before the eye indicator
-        
-        // Parse sequence number, removing any non-numeric characters
-        if let Ok(sequence) = sequence_str.chars()
-            .filter(|c| c.is_digit(10))
-            .collect::<String>()
-            .parse::<u32>() {
-            Some((base_name, eye_indicator, sequence))
-        } else {
-            None
+// The duplicates found in each scanned directory
+type ReportsByDir = Vec<(PathBuf, Vec<DuplicateReport>)>;
+
+#[derive(Debug, Default, PartialEq)]
+struct EyeSummary {
+    groups: usize,
+    groups_with_duplicates: usize,
+    files_marked_for_removal: usize,
+}
+
+// Totals for QA before any file is deleted
+#[derive(Debug, Default)]
+struct DedupSummary {
+    total_files: usize,
+    distinct_groups: usize,
+    groups_with_duplicates: usize,
+    files_marked_for_removal: usize,
+    by_eye: BTreeMap<String, EyeSummary>,
+}
+
+// How base names are compared when grouping: exactly (the default), or with
+// --fuzzy-base case-folded and whitespace-collapsed, and with
+// --base-distance N also up to N edits apart (Levenshtein) after that
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum BaseMatch {
+    #[default]
+    Exact,
+    Fuzzy { max_distance: usize },
+}
+
+impl BaseMatch {
+    fn from_args(args: &[String]) -> Result<Self, String> {
+        if !args.iter().any(|a| a == "--fuzzy-base") {
+            return Ok(BaseMatch::Exact);
+        }
+        let max_distance = match args.iter().position(|a| a == "--base-distance") {
+            None => 0,
+            Some(i) => {
+                let value = args.get(i + 1).map(String::as_str).unwrap_or("");
+                value.parse::<usize>()
+                    .map_err(|_| format!("Invalid --base-distance '{}' (expected a number of edits)", value))?
+            }
+        };
+        Ok(BaseMatch::Fuzzy { max_distance })
+    }
+
+    // The group key of `base`, given the keys already in use next to it
+    fn group_key(&self, base: &str, existing: &[String]) -> String {
+        match self {
+            BaseMatch::Exact => base.to_string(),
+            BaseMatch::Fuzzy { max_distance } => {
+                let normalized = base.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+                existing.iter()
+                    .find(|key| levenshtein(key, &normalized) <= *max_distance)
+                    .cloned()
+                    .unwrap_or(normalized)
+            }
+        }
+    }
+}
+
+// Grouping key for files whose name carries none of the eye tokens
+const NO_EYE: &str = "none";
+
+// Eye indicators recognised in file names: --eye-tokens OD,OS,L,R (default L,R)
+fn eye_tokens_from_args(args: &[String]) -> Vec<String> {
+    args.iter()
+        .position(|a| a == "--eye-tokens")
+        .and_then(|i| args.get(i + 1))
+        .map(|list| list.split(',')
+            .map(|token| token.trim().to_string())
+            .filter(|token| !token.is_empty())
+            .collect())
+        .unwrap_or_else(|| vec!["L".to_string(), "R".to_string()])
+}
+
+// Split a name like P_001_2020_01_L_002.csv into (base name, eye, sequence).
+// The sequence is the last token with a digit in it; tokens after it (e.g. a
+// trailing _copy) are ignored, as they always were. The eye is the last token
+// before the sequence matching one of eye_tokens, wherever it sits, and
+// everything else before the sequence is the base name.
+fn parse_filename(filename: &str, eye_tokens: &[String]) -> Option<(String, String, u32)> {
+    let stem = Path::new(filename).file_stem()?.to_str()?;
+    let mut parts: Vec<&str> = stem.split('_').collect();
+    let sequence_position = parts.iter().rposition(|part| part.chars().any(|c| c.is_ascii_digit()))?;
+    if sequence_position == 0 {
+        return None;
+    }
+    parts.truncate(sequence_position + 1);
+
+    // Parse sequence number, removing any non-numeric characters
+    let sequence = parts.pop()?
+        .chars()
+        .filter(|c| c.is_ascii_digit())
+        .collect::<String>()
+        .parse::<u32>()
+        .ok()?;
+
+    let eye_position = parts.iter()
+        .rposition(|part| eye_tokens.iter().any(|token| token.eq_ignore_ascii_case(part)));
+    let eye_indicator = match eye_position {
+        Some(position) => parts.remove(position).to_uppercase(),
+        None => NO_EYE.to_string(),
+    };
+    if parts.is_empty() {
+        return None;
+    }
+
+    Some((parts.join("_"), eye_indicator, sequence))
+}
+
+// Directories to scan: every --dir, plus every directory matching --glob.
+// Without either, the default combined-data directory.
+fn scan_dirs_from_args(args: &[String]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut dirs = Vec::new();
+    for (i, arg) in args.iter().enumerate() {
+        let value = || args.get(i + 1).ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--dir" => dirs.push(PathBuf::from(value()?)),
+            "--glob" => {
+                let mut matched: Vec<PathBuf> = glob(value()?)?
+                    .filter_map(Result::ok)
+                    .filter(|path| path.is_dir())
+                    .collect();
+                matched.sort();
+                dirs.extend(matched);
+            }
+            _ => {}
         }
-    } else {
-        None
     }
+    if dirs.is_empty() {
+        dirs.push(PathBuf::from(DEFAULT_INPUT_DIR));
+    }
+    Ok(dirs)
 }
 
-fn find_duplicates(dir_path: &Path) -> Result<Vec<DuplicateReport>, Box<dyn Error>> {
-    let mut csv_files: Vec<String> = Vec::new();
-    for entry in fs::read_dir(dir_path)? {
+// CSV files anywhere below dir_path, named by their path relative to it
+fn find_duplicates(
+    dir_path: &Path,
+    policy: KeepPolicy,
+    eye_tokens: &[String],
+    base_match: BaseMatch,
+) -> Result<(Vec<DuplicateReport>, DedupSummary), Box<dyn Error>> {
+    let mut csv_files: Vec<(String, Option<SystemTime>)> = Vec::new();
+    for entry in WalkDir::new(dir_path).sort_by_file_name() {
         let entry = entry?;
         let path = entry.path();
-        if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("csv") {
-            if let Some(file_name) = path.file_name().and_then(|s| s.to_str()) {
-                csv_files.push(file_name.to_string());
+        if entry.file_type().is_file() && path.extension().and_then(|s| s.to_str()).is_some_and(|ext| ext.eq_ignore_ascii_case("csv")) {
+            if let Some(relative) = path.strip_prefix(dir_path).ok().and_then(|p| p.to_str()) {
+                let modified = entry.metadata().ok().and_then(|m| m.modified().ok());
+                csv_files.push((relative.to_string(), modified));
             }
         }
     }
 
-    // Group files by base name and eye indicator
-    let mut file_groups: HashMap<(String, String), Vec<FileInfo>> = HashMap::new();
+    Ok(group_duplicates(csv_files, policy, eye_tokens, base_match))
+}
+
+fn group_duplicates(
+    csv_files: Vec<(String, Option<SystemTime>)>,
+    policy: KeepPolicy,
+    eye_tokens: &[String],
+    base_match: BaseMatch,
+) -> (Vec<DuplicateReport>, DedupSummary) {
+    let mut summary = DedupSummary {
+        total_files: csv_files.len(),
+        ..Default::default()
+    };
+
+    // Group files by subdirectory, base name and eye indicator; the same name
+    // in two subdirectories is two scans, not a duplicate
+    let mut file_groups: HashMap<(PathBuf, String, String), Vec<FileInfo>> = HashMap::new();
+    // Base keys already used per (subdirectory, eye), for fuzzy matching
+    let mut base_keys: HashMap<(PathBuf, String), Vec<String>> = HashMap::new();
     
-    for filename in csv_files {
-        if let Some((base, eye, sequence)) = parse_filename(&filename) {
-            let key = (base, eye);
-            file_groups.entry(key).or_default().push(FileInfo {
+    for (filename, modified) in csv_files {
+        if let Some((base, eye, sequence)) = parse_filename(&filename, eye_tokens) {
+            let subdir = Path::new(&filename).parent().map(Path::to_path_buf).unwrap_or_default();
+            let keys = base_keys.entry((subdir.clone(), eye.clone())).or_default();
+            let base_key = base_match.group_key(&base, keys);
+            if !keys.contains(&base_key) {
+                keys.push(base_key.clone());
+            }
+            file_groups.entry((subdir, base_key, eye)).or_default().push(FileInfo {
                 filename: filename.clone(),
+                base,
                 sequence,
+                modified,
             });
         }
     }
 
     let mut duplicate_reports = Vec::new();
+    summary.distinct_groups = file_groups.len();
     
     // Process each group to identify files to keep and remove
-    for ((_base, eye), mut files) in file_groups {
+    for ((_subdir, _base, eye), mut files) in file_groups {
+        let eye_summary = summary.by_eye.entry(eye.clone()).or_default();
+        eye_summary.groups += 1;
+
         if files.len() > 1 {
-            // Sort by sequence number
-            files.sort_by_key(|f| f.sequence);
+            summary.groups_with_duplicates += 1;
+            summary.files_marked_for_removal += files.len() - 1;
+            eye_summary.groups_with_duplicates += 1;
+            eye_summary.files_marked_for_removal += files.len() - 1;
+
+            // Order by the keep policy
+            policy.sort_group(&mut files);
             
-            // Keep the lowest sequence number, mark others for removal
+            // Keep the first file, mark others for removal
             let keep_file = &files[0];
             for remove_file in files.iter().skip(1) {
+                let mut reason = policy.reason(keep_file, remove_file, &eye);
+                // Only --fuzzy-base groups files whose base names differ
+                if keep_file.base != remove_file.base {
+                    reason.push_str(&format!(" [base distance {}]", levenshtein(&keep_file.base, &remove_file.base)));
+                }
                 duplicate_reports.push(DuplicateReport {
                     keep_file: keep_file.filename.clone(),
                     remove_file: remove_file.filename.clone(),
-                    reason: format!(
-                        "Keep sequence {} (lower) vs {} (higher) for eye {}",
-                        keep_file.sequence,
-                        remove_file.sequence,
-                        eye
-                    ),
+                    reason,
                 });
             }
         }
     }
     
-    Ok(duplicate_reports)
+    (duplicate_reports, summary)
+}
+
+fn print_summary(summary: &DedupSummary) {
+    println!("\nDuplicate Scan Summary:");
+    println!("Total CSV files:           {}", summary.total_files);
+    println!("Distinct (base, eye) groups: {}", summary.distinct_groups);
+    println!("Groups with duplicates:    {}", summary.groups_with_duplicates);
+    println!("Files marked for removal:  {}", summary.files_marked_for_removal);
+    for (eye, eye_summary) in &summary.by_eye {
+        println!(
+            "  Eye {}: {} groups, {} with duplicates, {} files to remove",
+            eye,
+            eye_summary.groups,
+            eye_summary.groups_with_duplicates,
+            eye_summary.files_marked_for_removal
+        );
+    }
+}
+
+fn write_summary_csv(summary: &DedupSummary, output_path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_path(output_path)?;
+    wtr.write_record(["Eye", "Groups", "Groups With Duplicates", "Files Marked For Removal"])?;
+    for (eye, eye_summary) in &summary.by_eye {
+        wtr.write_record([
+            eye.as_str(),
+            &eye_summary.groups.to_string(),
+            &eye_summary.groups_with_duplicates.to_string(),
+            &eye_summary.files_marked_for_removal.to_string(),
+        ])?;
+    }
+    wtr.write_record([
+        "All",
+        &summary.distinct_groups.to_string(),
+        &summary.groups_with_duplicates.to_string(),
+        &summary.files_marked_for_removal.to_string(),
+    ])?;
+    wtr.flush()?;
+    Ok(())
 }
 
 fn write_csv_report(reports: &[DuplicateReport], output_path: &Path) -> Result<(), Box<dyn Error>> {
     let mut wtr = Writer::from_path(output_path)?;
     // Write CSV header
-    wtr.write_record(&["Keep File", "Remove File", "Reason"])?;
+    wtr.write_record(["Keep File", "Remove File", "Reason"])?;
     // Write report data
     for report in reports {
-        wtr.write_record(&[
+        wtr.write_record([
             &report.keep_file,
             &report.remove_file,
             &report.reason,
@@ -109,6 +367,31 @@ fn write_csv_report(reports: &[DuplicateReport], output_path: &Path) -> Result<(
     Ok(())
 }
 
+// --merge-report: one report for every scanned directory, with a Directory column
+fn write_merged_csv_report(reports: &ReportsByDir, output_path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_path(output_path)?;
+    wtr.write_record(["Directory", "Keep File", "Remove File", "Reason"])?;
+    for (dir, dir_reports) in reports {
+        let dir = dir.display().to_string();
+        for report in dir_reports {
+            wtr.write_record([&dir, &report.keep_file, &report.remove_file, &report.reason])?;
+        }
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+// Per-directory report names use the dataset folder, so .../casia2-4/combined_data
+// reports to duplicate_removal_report_casia2-4.csv
+fn report_label(dir: &Path) -> String {
+    let name = |path: &Path| path.file_name().map(|n| n.to_string_lossy().into_owned());
+    match name(dir) {
+        Some(dir_name) if dir_name == "combined_data" => dir.parent().and_then(name).unwrap_or(dir_name),
+        Some(dir_name) => dir_name,
+        None => "root".to_string(),
+    }
+}
+
 // Function to actually remove the files
 fn remove_duplicate_files(dir_path: &Path, reports: &[DuplicateReport]) -> Result<(), Box<dyn Error>> {
     for report in reports {
@@ -121,45 +404,383 @@ fn remove_duplicate_files(dir_path: &Path, reports: &[DuplicateReport]) -> Resul
     Ok(())
 }
 
+// Settings shared by every scanned directory
+struct RunOptions {
+    keep_policy: KeepPolicy,
+    eye_tokens: Vec<String>,
+    // --fuzzy-base / --base-distance
+    base_match: BaseMatch,
+    // --write-summary: also write the per-eye summary of each directory as CSV
+    write_summary: bool,
+    // --merge-report: one report for all directories instead of one each
+    merge_report: bool,
+    // --dry-run: write the reports but delete nothing
+    dry_run: bool,
+}
+
+impl RunOptions {
+    fn from_args(args: &[String]) -> Result<Self, Box<dyn Error>> {
+        // --keep lowest (first scan, default), highest (re-scan) or newest (file mtime)
+        let keep_policy = match args.iter().position(|a| a == "--keep") {
+            Some(i) => KeepPolicy::parse(args.get(i + 1).map(String::as_str).unwrap_or(""))?,
+            None => KeepPolicy::Lowest,
+        };
+        Ok(RunOptions {
+            keep_policy,
+            eye_tokens: eye_tokens_from_args(args),
+            base_match: BaseMatch::from_args(args)?,
+            write_summary: args.iter().any(|a| a == "--write-summary"),
+            merge_report: args.iter().any(|a| a == "--merge-report"),
+            dry_run: args.iter().any(|a| a == "--dry-run"),
+        })
+    }
+}
+
+// `{: <50}` pads by char count, which misaligns Persian file names (ZWNJ,
+// lam-alef) and CJK, so the columns are padded by terminal width instead
+fn print_report(reports: &[DuplicateReport]) {
+    println!("\nDuplicate Files Report:");
+    println!("------------------------------------------------------------------");
+    println!("{} | {} | {}", pad_to_width("Keep File", 50), pad_to_width("Remove File", 50), pad_to_width("Reason", 30));
+    println!("------------------------------------------------------------------");
+    for report in reports {
+        println!("{} | {} | {}",
+            pad_to_width(&report.keep_file, 50),
+            pad_to_width(&report.remove_file, 50),
+            pad_to_width(&report.reason, 30)
+        );
+    }
+    println!("------------------------------------------------------------------");
+}
+
+// Scan every directory, write the report(s) to output_dir and, unless this
+// is a dry run, delete the duplicates. Returns the reports by directory.
+fn dedupe_directories(
+    input_dirs: &[PathBuf],
+    output_dir: &Path,
+    options: &RunOptions,
+) -> Result<ReportsByDir, Box<dyn Error>> {
+    for input_dir in input_dirs {
+        if !input_dir.is_dir() {
+            return Err(format!("Input directory '{}' does not exist or is not a directory.", input_dir.display()).into());
+        }
+    }
+    if !options.merge_report {
+        let mut seen: HashMap<String, &Path> = HashMap::new();
+        for input_dir in input_dirs {
+            if let Some(other) = seen.insert(report_label(input_dir), input_dir) {
+                return Err(format!(
+                    "'{}' and '{}' would share a report file (use --merge-report)",
+                    other.display(), input_dir.display()
+                ).into());
+            }
+        }
+    }
+
+    fs::create_dir_all(output_dir)?;
+    let mut all_reports = Vec::new();
+    for input_dir in input_dirs {
+        println!("Scanning for duplicate CSV files in: {} (keep {})", input_dir.display(), options.keep_policy.name());
+        let (duplicate_reports, summary) = find_duplicates(input_dir, options.keep_policy, &options.eye_tokens, options.base_match)?;
+
+        print_summary(&summary);
+        let label = report_label(input_dir);
+        if options.write_summary {
+            let summary_path = output_dir.join(format!("duplicate_summary_{}.csv", label));
+            write_summary_csv(&summary, &summary_path)?;
+            println!("Summary written to: {}", summary_path.display());
+        }
+
+        if duplicate_reports.is_empty() {
+            println!("No duplicate CSV files found.");
+        } else {
+            if !options.merge_report {
+                let output_file_path = output_dir.join(format!("duplicate_removal_report_{}.csv", label));
+                println!("Found duplicate CSV files. Writing report to: {}", output_file_path.display());
+                write_csv_report(&duplicate_reports, &output_file_path)?;
+            }
+            print_report(&duplicate_reports);
+        }
+        all_reports.push((input_dir.clone(), duplicate_reports));
+    }
+
+    if options.merge_report && all_reports.iter().any(|(_, reports)| !reports.is_empty()) {
+        let output_file_path = output_dir.join("duplicate_removal_report.csv");
+        println!("Writing merged report to: {}", output_file_path.display());
+        write_merged_csv_report(&all_reports, &output_file_path)?;
+    }
+
+    for (input_dir, reports) in &all_reports {
+        if reports.is_empty() {
+            continue;
+        }
+        if options.dry_run {
+            println!("Dry run: {} file(s) in {} left in place.", reports.len(), input_dir.display());
+        } else {
+            println!("\nRemoving duplicate files in {}...", input_dir.display());
+            remove_duplicate_files(input_dir, reports)?;
+            println!("Duplicate files have been removed.");
+        }
+    }
+
+    Ok(all_reports)
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
-    let input_dir = Path::new("/home/aricept094/mydata/casia2-4/combined_data");
     let output_dir = Path::new("/home/aricept094/mydata/ANOVA");
-    
-    if !input_dir.exists() || !input_dir.is_dir() {
-        eprintln!("Error: Input directory '{}' does not exist or is not a directory.", input_dir.display());
+    let args: Vec<String> = std::env::args().collect();
+    let options = RunOptions::from_args(&args)?;
+    let input_dirs = scan_dirs_from_args(&args)?;
+
+    if let Err(e) = dedupe_directories(&input_dirs, output_dir, &options) {
+        eprintln!("Error: {}", e);
         return Ok(());
     }
     
-    fs::create_dir_all(output_dir)?;
-    let output_file_path = output_dir.join("duplicate_removal_report_casia2-4.csv");
-    
-    println!("Scanning for duplicate CSV files in: {}", input_dir.display());
-    let duplicate_reports = find_duplicates(input_dir)?;
-    
-    if duplicate_reports.is_empty() {
-        println!("No duplicate CSV files found.");
-    } else {
-        println!("Found duplicate CSV files. Writing report to: {}", output_file_path.display());
-        write_csv_report(&duplicate_reports, &output_file_path)?;
-        
-        println!("\nDuplicate Files Report:");
-        println!("------------------------------------------------------------------");
-        println!("{: <50} | {: <50} | {: <30}", "Keep File", "Remove File", "Reason");
-        println!("------------------------------------------------------------------");
-        for report in &duplicate_reports {
-            println!("{: <50} | {: <50} | {: <30}", 
-                report.keep_file, 
-                report.remove_file, 
-                report.reason
-            );
-        }
-        println!("------------------------------------------------------------------");
-        
-        println!("\nRemoving duplicate files...");
-        remove_duplicate_files(input_dir, &duplicate_reports)?;
-        println!("Duplicate files have been removed.");
-    }
-    
     println!("Process completed.");
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::preview::display_width;
+
+    #[test]
+    fn test_summary_counts_duplicates_per_eye() {
+        let files = vec![
+            "P_001_2020_01_L_001.csv",
+            "P_001_2020_01_L_002.csv",
+            "P_001_2020_01_R_001.csv",
+            "P_001_2020_01_R_002.csv",
+            "P_001_2020_01_R_003.csv",
+            "P_002_2020_01_L_001.csv",
+        ];
+        let (reports, summary) = group_duplicates(
+            files.into_iter().map(|f| (f.to_string(), None)).collect(),
+            KeepPolicy::Lowest,
+            &eye_tokens_from_args(&[]),
+            BaseMatch::Exact,
+        );
+
+        assert_eq!(reports.len(), 3);
+        assert_eq!(summary.total_files, 6);
+        assert_eq!(summary.distinct_groups, 3);
+        assert_eq!(summary.groups_with_duplicates, 2);
+        assert_eq!(summary.files_marked_for_removal, 3);
+        assert_eq!(summary.by_eye["L"], EyeSummary { groups: 2, groups_with_duplicates: 1, files_marked_for_removal: 1 });
+        assert_eq!(summary.by_eye["R"], EyeSummary { groups: 1, groups_with_duplicates: 1, files_marked_for_removal: 2 });
+    }
+
+    // Sequences 1..3 where sequence 2 was written most recently
+    fn policy_group() -> Vec<(String, Option<SystemTime>)> {
+        let at = |secs| Some(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs));
+        vec![
+            ("P_001_2020_01_L_003.csv".to_string(), at(2_000)),
+            ("P_001_2020_01_L_001.csv".to_string(), at(1_000)),
+            ("P_001_2020_01_L_002.csv".to_string(), at(3_000)),
+        ]
+    }
+
+    fn kept_and_removed(policy: KeepPolicy) -> (String, Vec<String>) {
+        let (reports, _) = group_duplicates(policy_group(), policy, &eye_tokens_from_args(&[]), BaseMatch::Exact);
+        assert_eq!(reports.len(), 2);
+        assert!(reports.iter().all(|r| r.reason.contains(&format!("[keep={}]", policy.name()))));
+        let mut removed: Vec<String> = reports.iter().map(|r| r.remove_file.clone()).collect();
+        removed.sort();
+        (reports[0].keep_file.clone(), removed)
+    }
+
+    #[test]
+    fn test_keep_lowest_policy() {
+        let (kept, removed) = kept_and_removed(KeepPolicy::Lowest);
+        assert_eq!(kept, "P_001_2020_01_L_001.csv");
+        assert_eq!(removed, vec!["P_001_2020_01_L_002.csv", "P_001_2020_01_L_003.csv"]);
+    }
+
+    #[test]
+    fn test_keep_highest_policy() {
+        let (kept, removed) = kept_and_removed(KeepPolicy::Highest);
+        assert_eq!(kept, "P_001_2020_01_L_003.csv");
+        assert_eq!(removed, vec!["P_001_2020_01_L_001.csv", "P_001_2020_01_L_002.csv"]);
+    }
+
+    #[test]
+    fn test_keep_newest_policy() {
+        let (kept, removed) = kept_and_removed(KeepPolicy::Newest);
+        assert_eq!(kept, "P_001_2020_01_L_002.csv");
+        assert_eq!(removed, vec!["P_001_2020_01_L_001.csv", "P_001_2020_01_L_003.csv"]);
+    }
+
+    #[test]
+    fn test_keep_policy_parse() {
+        assert_eq!(KeepPolicy::parse("Highest"), Ok(KeepPolicy::Highest));
+        assert!(KeepPolicy::parse("oldest").is_err());
+    }
+
+    #[test]
+    fn test_keep_newest_reads_file_mtime() {
+        let dir = std::env::temp_dir().join(format!("dedup_newest_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, modified) in policy_group() {
+            let file = fs::File::create(dir.join(&name)).unwrap();
+            file.set_modified(modified.unwrap()).unwrap();
+        }
+
+        let (reports, _) = find_duplicates(&dir, KeepPolicy::Newest, &eye_tokens_from_args(&[]), BaseMatch::Exact).unwrap();
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(reports.len(), 2);
+        assert!(reports.iter().all(|r| r.keep_file == "P_001_2020_01_L_002.csv"));
+    }
+
+    #[test]
+    fn test_od_os_eye_tokens() {
+        let eye_tokens = eye_tokens_from_args(&["--eye-tokens".to_string(), "OD,OS,L,R".to_string()]);
+        assert_eq!(
+            parse_filename("P_001_OD_2020_01_002.csv", &eye_tokens),
+            Some(("P_001_2020_01".to_string(), "OD".to_string(), 2))
+        );
+
+        let files = vec![
+            "P_001_2020_01_OD_001.csv",
+            "P_001_2020_01_OD_002.csv",
+            "P_001_2020_01_os_001.csv",
+        ];
+        let (reports, summary) = group_duplicates(
+            files.into_iter().map(|f| (f.to_string(), None)).collect(),
+            KeepPolicy::Lowest,
+            &eye_tokens,
+            BaseMatch::Exact,
+        );
+
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].remove_file, "P_001_2020_01_OD_002.csv");
+        assert_eq!(summary.by_eye["OD"].groups_with_duplicates, 1);
+        assert_eq!(summary.by_eye["OS"].groups, 1);
+    }
+
+    #[test]
+    fn test_filename_without_eye_grouped_by_base_name() {
+        let eye_tokens = eye_tokens_from_args(&[]);
+        assert_eq!(
+            parse_filename("P_001_2020_01_003.csv", &eye_tokens),
+            Some(("P_001_2020_01".to_string(), NO_EYE.to_string(), 3))
+        );
+        // A trailing token after the sequence is ignored, as it always was
+        assert_eq!(
+            parse_filename("P_001_2020_01_L_002_copy.csv", &eye_tokens),
+            Some(("P_001_2020_01".to_string(), "L".to_string(), 2))
+        );
+        assert_eq!(parse_filename("P_copy.csv", &eye_tokens), None);
+
+        let files = vec!["P_001_2020_01_001.csv", "P_001_2020_01_002.csv", "P_001_2020_01_L_003.csv"];
+        let (reports, summary) = group_duplicates(
+            files.into_iter().map(|f| (f.to_string(), None)).collect(),
+            KeepPolicy::Lowest,
+            &eye_tokens,
+            BaseMatch::Exact,
+        );
+
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].keep_file, "P_001_2020_01_001.csv");
+        assert_eq!(summary.distinct_groups, 2);
+        assert_eq!(summary.by_eye[NO_EYE].files_marked_for_removal, 1);
+    }
+
+    #[test]
+    fn test_two_directories_dry_run_reports_both_and_deletes_nothing() {
+        let root = std::env::temp_dir().join(format!("dedup_multi_dir_{}", std::process::id()));
+        let first = root.join("casia1-2").join("combined_data");
+        let second = root.join("casia2-4").join("combined_data");
+        let output_dir = root.join("reports");
+        let files = [
+            first.join("P_001_2020_01_L_001.csv"),
+            first.join("P_001_2020_01_L_002.csv"),
+            // Same name in a subdirectory: a separate group, not a duplicate
+            first.join("rescans").join("P_001_2020_01_L_001.csv"),
+            second.join("P_002_2020_01_R_001.csv"),
+            second.join("P_002_2020_01_R_003.csv"),
+        ];
+        for file in &files {
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(file, "a,b\n1,2\n").unwrap();
+        }
+
+        let args: Vec<String> = ["--glob", root.join("*").join("combined_data").to_str().unwrap()]
+            .iter().map(|s| s.to_string()).collect();
+        let dirs = scan_dirs_from_args(&args).unwrap();
+        assert_eq!(dirs, vec![first.clone(), second.clone()]);
+        assert_eq!(report_label(&second), "casia2-4");
+
+        let options = RunOptions {
+            keep_policy: KeepPolicy::Lowest,
+            eye_tokens: eye_tokens_from_args(&[]),
+            base_match: BaseMatch::Exact,
+            write_summary: false,
+            merge_report: true,
+            dry_run: true,
+        };
+        let reports = dedupe_directories(&dirs, &output_dir, &options).unwrap();
+        let merged = fs::read_to_string(output_dir.join("duplicate_removal_report.csv")).unwrap();
+        let all_exist = files.iter().all(|file| file.exists());
+
+        let separate_args: Vec<String> = ["--dry-run", "--write-summary"].iter().map(|s| s.to_string()).collect();
+        let separate = RunOptions::from_args(&separate_args).unwrap();
+        dedupe_directories(&dirs, &output_dir, &separate).unwrap();
+        let per_dir_reports = ["casia1-2", "casia2-4"].iter()
+            .all(|label| output_dir.join(format!("duplicate_removal_report_{}.csv", label)).exists());
+        let summaries = ["casia1-2", "casia2-4"].iter()
+            .all(|label| output_dir.join(format!("duplicate_summary_{}.csv", label)).exists());
+        fs::remove_dir_all(&root).ok();
+
+        assert!(all_exist, "a dry run deletes nothing");
+        assert!(per_dir_reports);
+        assert!(summaries, "--write-summary writes one summary per directory");
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].1.len(), 1);
+        assert_eq!(reports[0].1[0].remove_file, "P_001_2020_01_L_002.csv");
+        assert_eq!(reports[1].1.len(), 1);
+        assert_eq!(reports[1].1[0].remove_file, "P_002_2020_01_R_003.csv");
+        assert_eq!(merged.lines().count(), 3);
+        assert!(merged.contains(&first.display().to_string()));
+        assert!(merged.contains(&second.display().to_string()));
+    }
+
+    #[test]
+    fn test_persian_names_pad_to_display_width() {
+        // 14 chars but 13 columns: the ZWNJ takes none, so it needs 7 spaces, not 6
+        let name = "بیمار\u{200C}ها_L.csv";
+        assert_eq!(display_width(&pad_to_width(name, 20)), 20);
+        assert_eq!(pad_to_width(name, 20).len(), name.len() + 7);
+        // Longer names are left as they are
+        assert_eq!(pad_to_width(name, 4), name);
+    }
+
+    #[test]
+    fn test_fuzzy_base_groups_names_differing_in_case() {
+        let files = || vec![
+            ("P_001_2020_01_L_001.csv".to_string(), None),
+            ("p_001_2020_01_L_002.csv".to_string(), None),
+            // A typo in the patient id: one edit away
+            ("P_0O1_2020_01_L_003.csv".to_string(), None),
+        ];
+        let eye_tokens = eye_tokens_from_args(&[]);
+
+        let (exact, _) = group_duplicates(files(), KeepPolicy::Lowest, &eye_tokens, BaseMatch::Exact);
+        let (fuzzy, summary) = group_duplicates(files(), KeepPolicy::Lowest, &eye_tokens, BaseMatch::Fuzzy { max_distance: 0 });
+        let (distance, _) = group_duplicates(files(), KeepPolicy::Lowest, &eye_tokens, BaseMatch::Fuzzy { max_distance: 1 });
+
+        assert!(exact.is_empty());
+        assert_eq!(summary.distinct_groups, 2);
+        assert_eq!(fuzzy.len(), 1);
+        assert_eq!(fuzzy[0].keep_file, "P_001_2020_01_L_001.csv");
+        assert_eq!(fuzzy[0].remove_file, "p_001_2020_01_L_002.csv");
+        assert!(fuzzy[0].reason.ends_with("[keep=lowest] [base distance 1]"), "{}", fuzzy[0].reason);
+        assert_eq!(distance.len(), 2);
+
+        let args: Vec<String> = ["--fuzzy-base", "--base-distance", "2"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(BaseMatch::from_args(&args), Ok(BaseMatch::Fuzzy { max_distance: 2 }));
+        assert_eq!(BaseMatch::from_args(&args[1..]), Ok(BaseMatch::Exact));
+    }
+}
@@ -1,9 +1,102 @@
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::error::Error;
-use csv::{Reader, Writer};
+use csv::{Reader, StringRecord, Writer};
 use std::collections::HashSet;
 
+mod onehot;
+
+use shared::discover::{check_required_columns, no_input_files, required_columns_from_args, HeaderMatch, InputFilter};
+use onehot::OneHotColumn;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Contains,
+}
+
+// Symbol operators, two-character ones first so "<=" isn't read as "<"
+const SYMBOL_OPS: [(&str, CompareOp); 6] = [
+    ("==", CompareOp::Eq),
+    ("!=", CompareOp::Ne),
+    ("<=", CompareOp::Le),
+    (">=", CompareOp::Ge),
+    ("<", CompareOp::Lt),
+    (">", CompareOp::Gt),
+];
+
+// A row condition given with --where "col OP value", e.g. "Normalized_Radius > 0.5".
+// Values compare as numbers when both sides parse, otherwise as strings.
+#[derive(Debug, Clone, PartialEq)]
+struct Predicate {
+    column: String,
+    op: CompareOp,
+    value: String,
+}
+
+impl Predicate {
+    fn parse(expr: &str) -> Result<Self, Box<dyn Error>> {
+        let (column, op, value) = if let Some((column, value)) = expr.split_once(" contains ") {
+            (column, CompareOp::Contains, value)
+        } else {
+            let (pos, symbol, op) = SYMBOL_OPS.iter()
+                .filter_map(|&(symbol, op)| expr.find(symbol).map(|pos| (pos, symbol, op)))
+                .min_by_key(|&(pos, _, _)| pos)
+                .ok_or_else(|| format!("--where '{}' has no operator (expected ==, !=, <, >, <=, >= or contains)", expr))?;
+            (&expr[..pos], op, &expr[pos + symbol.len()..])
+        };
+
+        let column = column.trim();
+        if column.is_empty() {
+            return Err(format!("--where '{}' is missing a column name", expr).into());
+        }
+        let value = value.trim();
+        let value = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value);
+
+        Ok(Predicate { column: column.to_string(), op, value: value.to_string() })
+    }
+
+    fn from_args(args: &[String]) -> Result<Option<Self>, Box<dyn Error>> {
+        match args.iter().position(|a| a == "--where") {
+            Some(i) => {
+                let expr = args.get(i + 1).ok_or("--where needs an expression")?;
+                Ok(Some(Predicate::parse(expr)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn matches(&self, cell: &str) -> bool {
+        let cell = cell.trim();
+        if self.op == CompareOp::Contains {
+            return cell.contains(self.value.as_str());
+        }
+
+        let ordering = match (cell.parse::<f64>(), self.value.parse::<f64>()) {
+            (Ok(a), Ok(b)) => match a.partial_cmp(&b) {
+                Some(ordering) => ordering,
+                None => return self.op == CompareOp::Ne,
+            },
+            _ => cell.cmp(self.value.as_str()),
+        };
+
+        match self.op {
+            CompareOp::Eq => ordering.is_eq(),
+            CompareOp::Ne => ordering.is_ne(),
+            CompareOp::Lt => ordering.is_lt(),
+            CompareOp::Gt => ordering.is_gt(),
+            CompareOp::Le => ordering.is_le(),
+            CompareOp::Ge => ordering.is_ge(),
+            CompareOp::Contains => unreachable!(),
+        }
+    }
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     // Define the allowed Radial_Index values
     let allowed_values: HashSet<String> = vec!["1", "4", "8", "12", "16", "24", "28", "32"]
@@ -18,16 +111,24 @@ fn main() -> Result<(), Box<dyn Error>> {
     // Create output directory if it doesn't exist
     fs::create_dir_all(output_dir)?;
 
+    let args: Vec<String> = std::env::args().collect();
+    let input_filter = InputFilter::from_args(&args);
+    let required_columns = required_columns_from_args(&args);
+    let predicate = Predicate::from_args(&args)?;
+    let one_hot = OneHotColumn::from_args(&args)?;
+    let header_match = HeaderMatch::from_args(&args);
+
     // Get all CSV files in the input directory
-    let entries = fs::read_dir(input_dir)?;
-
-    for entry in entries {
-        let entry = entry?;
-        let path = entry.path();
-        
-        if path.extension().and_then(|s| s.to_str()) == Some("csv") {
-            process_file(&path, &allowed_values, output_dir)?;
-        }
+    let mut files = input_filter.input_files(input_dir)?;
+    // With --recursive the output folder is inside the input one
+    files.retain(|path| !path.starts_with(output_dir));
+    if let Some((message, code)) = no_input_files(input_dir, &files) {
+        eprintln!("{}", message);
+        std::process::exit(code);
+    }
+
+    for path in &files {
+        process_file(path, &allowed_values, &required_columns, predicate.as_ref(), one_hot.as_ref(), header_match, output_dir)?;
     }
 
     Ok(())
@@ -36,6 +137,10 @@ fn main() -> Result<(), Box<dyn Error>> {
 fn process_file(
     input_path: &PathBuf,
     allowed_values: &HashSet<String>,
+    required_columns: &[String],
+    predicate: Option<&Predicate>,
+    one_hot: Option<&OneHotColumn>,
+    header_match: HeaderMatch,
     output_dir: &Path,
 ) -> Result<(), Box<dyn Error>> {
     // Create reader for input file
@@ -49,28 +154,173 @@ fn process_file(
     
     let output_path = output_dir.join(filename);
     
+    // Validate headers before creating the output file
+    let headers = reader.headers()?.clone();
+    check_required_columns(&headers, required_columns, header_match, input_path)?;
+
+    // Column the --where predicate reads, resolved once per file
+    let predicate_index = match predicate {
+        Some(predicate) => Some(header_match.position(&headers, &predicate.column)
+            .ok_or_else(|| format!("--where column '{}' not found in {}", predicate.column, input_path.display()))?),
+        None => None,
+    };
+    // The --one-hot column as this file spells it
+    let one_hot_column = match one_hot {
+        Some(one_hot) => Some(header_match.position(&headers, &one_hot.column)
+            .map(|i| headers[i].to_string())
+            .ok_or_else(|| format!("--one-hot column '{}' not found in {}", one_hot.column, input_path.display()))?),
+        None => None,
+    };
+
     // Create writer for output file
     let mut writer = Writer::from_path(&output_path)?;
     
-    // Write headers
-    let headers = reader.headers()?.clone();
-    writer.write_record(&headers)?;
+    // Write headers (with --one-hot they change, so they're written after the rows are read)
+    if one_hot.is_none() {
+        writer.write_record(&headers)?;
+    }
     
     // Find index of Radial_Index column
-    let radial_index = headers.iter()
-        .position(|header| header == "Radial_Index")
+    let radial_index = header_match.position(&headers, "Radial_Index")
         .ok_or("Radial_Index column not found")?;
 
+    // With --one-hot the kept rows are held back: the categories are only known after the last one
+    let mut held_back: Vec<StringRecord> = Vec::new();
+
     // Process records
     for result in reader.records() {
         let record = result?;
         if let Some(value) = record.get(radial_index) {
-            if allowed_values.contains(value) {
-                writer.write_record(&record)?;
+            let keep = match (predicate, predicate_index) {
+                (Some(predicate), Some(i)) => predicate.matches(record.get(i).unwrap_or("")),
+                _ => true,
+            };
+            if allowed_values.contains(value) && keep {
+                if one_hot.is_some() {
+                    held_back.push(record);
+                } else {
+                    writer.write_record(&record)?;
+                }
             }
         }
     }
 
+    if let (Some(one_hot), Some(column)) = (one_hot, &one_hot_column) {
+        let (encoded_headers, encoded_records) =
+            onehot::one_hot(&headers, &held_back, column, one_hot.max_categories)?;
+        writer.write_record(&encoded_headers)?;
+        for record in &encoded_records {
+            writer.write_record(record)?;
+        }
+    }
+    writer.flush()?;
+
     println!("Processed: {}", filename);
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_required_column_is_reported() {
+        let dir = std::env::temp_dir().join(format!("csv_filter_require_{}", std::process::id()));
+        let output_dir = dir.join("limited");
+        fs::create_dir_all(&output_dir).unwrap();
+        let input = dir.join("P_001.csv");
+        fs::write(&input, "Radial_Index,Axial\n1,42.1\n").unwrap();
+
+        let allowed: HashSet<String> = ["1".to_string()].into_iter().collect();
+        let required = vec!["Radial_Index".to_string(), "Elevation".to_string(), "Pachymetry".to_string()];
+        let err = process_file(&input, &allowed, &required, None, None, HeaderMatch::Exact, &output_dir).unwrap_err();
+        let output_written = output_dir.join("P_001.csv").exists();
+        fs::remove_dir_all(&dir).ok();
+
+        let message = err.to_string();
+        assert!(message.contains("missing required column(s): Elevation, Pachymetry"), "{}", message);
+        assert!(!output_written);
+    }
+
+    #[test]
+    fn test_where_predicate_filters_rows() {
+        let dir = std::env::temp_dir().join(format!("csv_filter_where_{}", std::process::id()));
+        let output_dir = dir.join("limited");
+        fs::create_dir_all(&output_dir).unwrap();
+        let input = dir.join("P_001.csv");
+        fs::write(&input, "Radial_Index,Normalized_Radius,Eye\n1,0.25,OD right\n4,0.5,OS left\n8,0.75,OD right\n12,1,OS left\n").unwrap();
+        let allowed: HashSet<String> = ["1", "4", "8", "12"].iter().map(|s| s.to_string()).collect();
+
+        let numeric = Predicate::parse("Normalized_Radius > 0.5").unwrap();
+        assert_eq!(numeric.op, CompareOp::Gt);
+        process_file(&input, &allowed, &[], Some(&numeric), None, HeaderMatch::Exact, &output_dir).unwrap();
+        let numeric_output = fs::read_to_string(output_dir.join("P_001.csv")).unwrap();
+
+        let text = Predicate::parse("Eye contains OS").unwrap();
+        assert_eq!(text.op, CompareOp::Contains);
+        process_file(&input, &allowed, &[], Some(&text), None, HeaderMatch::Exact, &output_dir).unwrap();
+        let text_output = fs::read_to_string(output_dir.join("P_001.csv")).unwrap();
+
+        let missing = Predicate::parse("Elevation >= 1").unwrap();
+        let err = process_file(&input, &allowed, &[], Some(&missing), None, HeaderMatch::Exact, &output_dir).unwrap_err();
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(numeric_output, "Radial_Index,Normalized_Radius,Eye\n8,0.75,OD right\n12,1,OS left\n");
+        assert_eq!(text_output, "Radial_Index,Normalized_Radius,Eye\n4,0.5,OS left\n12,1,OS left\n");
+        assert!(err.to_string().contains("--where column 'Elevation' not found"));
+    }
+
+    #[test]
+    fn test_predicate_parsing() {
+        assert_eq!(Predicate::parse("Radial_Index<=8").unwrap(),
+            Predicate { column: "Radial_Index".to_string(), op: CompareOp::Le, value: "8".to_string() });
+        assert_eq!(Predicate::parse("Eye != \"OD\"").unwrap().value, "OD");
+        assert!(Predicate::parse("Radial_Index 8").is_err());
+        assert!(Predicate::parse("== 8").is_err());
+        // Numeric when both sides parse, string comparison otherwise
+        assert!(Predicate::parse("x > 9").unwrap().matches("10"));
+        assert!(!Predicate::parse("x > 9").unwrap().matches("10.0e-3"));
+        assert!(Predicate::parse("Eye < OS").unwrap().matches("OD"));
+        assert!(Predicate::parse("x == 0.50").unwrap().matches("0.5"));
+    }
+
+    #[test]
+    fn test_one_hot_column_in_filtered_output() {
+        let dir = std::env::temp_dir().join(format!("csv_filter_one_hot_{}", std::process::id()));
+        let output_dir = dir.join("limited");
+        fs::create_dir_all(&output_dir).unwrap();
+        let input = dir.join("P_001.csv");
+        fs::write(&input, "Radial_Index,Eye,Axial\n1,OD,42.1\n2,OS,42.5\n4,OS,43.0\n8,OU,41.7\n").unwrap();
+        let allowed: HashSet<String> = ["1", "4", "8"].iter().map(|s| s.to_string()).collect();
+
+        let args: Vec<String> = ["csv_filter", "--one-hot", "Eye", "--max-categories", "3"]
+            .iter().map(|s| s.to_string()).collect();
+        let one_hot = OneHotColumn::from_args(&args).unwrap().unwrap();
+        process_file(&input, &allowed, &[], None, Some(&one_hot), HeaderMatch::Exact, &output_dir).unwrap();
+        let output = fs::read_to_string(output_dir.join("P_001.csv")).unwrap();
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(output, "Radial_Index,Eye=OD,Eye=OS,Eye=OU,Axial\n1,1,0,0,42.1\n4,0,1,0,43.0\n8,0,0,1,41.7\n");
+        assert!(OneHotColumn::from_args(&[]).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_case_insensitive_headers_find_differently_cased_columns() {
+        let dir = std::env::temp_dir().join(format!("csv_filter_case_{}", std::process::id()));
+        let output_dir = dir.join("limited");
+        fs::create_dir_all(&output_dir).unwrap();
+        let input = dir.join("P_001.csv");
+        fs::write(&input, " radial_index ,AXIAL\n1,42.1\n2,42.5\n").unwrap();
+        let allowed: HashSet<String> = ["1".to_string()].into_iter().collect();
+        let required = vec!["Axial".to_string()];
+
+        let exact = process_file(&input, &allowed, &required, None, None, HeaderMatch::Exact, &output_dir);
+        let args: Vec<String> = ["csv_filter", "--case-insensitive-headers"].iter().map(|s| s.to_string()).collect();
+        process_file(&input, &allowed, &required, None, None, HeaderMatch::from_args(&args), &output_dir).unwrap();
+        let output = fs::read_to_string(output_dir.join("P_001.csv")).unwrap();
+        fs::remove_dir_all(&dir).ok();
+
+        assert!(exact.unwrap_err().to_string().contains("missing required column(s): Axial"));
+        assert_eq!(output, " radial_index ,AXIAL\n1,42.1\n");
+    }
+}
@@ -0,0 +1,126 @@
+// One-hot encoding of a low-cardinality categorical column, so the filtered
+// files can go straight into the logistic model: the column is replaced in
+// place by one 0/1 indicator column per category, named `col=value`.
+
+use std::collections::BTreeSet;
+use std::error::Error;
+use csv::StringRecord;
+
+const DEFAULT_MAX_CATEGORIES: usize = 10;
+
+// --one-hot <col> with an optional --max-categories N (default 10)
+pub struct OneHotColumn {
+    pub column: String,
+    pub max_categories: usize,
+}
+
+impl OneHotColumn {
+    pub fn from_args(args: &[String]) -> Result<Option<Self>, Box<dyn Error>> {
+        let value_of = |flag: &str| args.iter()
+            .position(|a| a == flag)
+            .map(|i| args.get(i + 1).map(String::as_str).unwrap_or(""));
+
+        let column = match value_of("--one-hot") {
+            None => return Ok(None),
+            Some("") => return Err("--one-hot needs a column name".into()),
+            Some(column) => column.to_string(),
+        };
+        let max_categories = match value_of("--max-categories") {
+            None => DEFAULT_MAX_CATEGORIES,
+            Some(n) => n.parse::<usize>()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or_else(|| format!("Invalid --max-categories '{}'", n))?,
+        };
+
+        Ok(Some(OneHotColumn { column, max_categories }))
+    }
+}
+
+// The categories are the distinct non-blank values of the column, in sorted
+// order. A value outside them (a blank cell) gets all zeros. Fails when the
+// column is missing or has more than max_categories distinct values.
+pub fn one_hot(
+    headers: &StringRecord,
+    records: &[StringRecord],
+    column: &str,
+    max_categories: usize,
+) -> Result<(StringRecord, Vec<StringRecord>), Box<dyn Error>> {
+    let index = headers.iter()
+        .position(|header| header == column)
+        .ok_or_else(|| format!("--one-hot column '{}' not found", column))?;
+
+    let categories: BTreeSet<&str> = records.iter()
+        .filter_map(|record| record.get(index))
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .collect();
+    if categories.len() > max_categories {
+        return Err(format!(
+            "--one-hot column '{}' has {} distinct values (more than {})",
+            column, categories.len(), max_categories
+        ).into());
+    }
+
+    let mut new_headers = StringRecord::new();
+    for (i, header) in headers.iter().enumerate() {
+        if i == index {
+            for category in &categories {
+                new_headers.push_field(&format!("{}={}", column, category));
+            }
+        } else {
+            new_headers.push_field(header);
+        }
+    }
+
+    let new_records = records.iter()
+        .map(|record| {
+            let value = record.get(index).map(str::trim).unwrap_or("");
+            let mut new_record = StringRecord::new();
+            for (i, field) in record.iter().enumerate() {
+                if i == index {
+                    for category in &categories {
+                        new_record.push_field(if *category == value { "1" } else { "0" });
+                    }
+                } else {
+                    new_record.push_field(field);
+                }
+            }
+            new_record
+        })
+        .collect();
+
+    Ok((new_headers, new_records))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_three_value_column_becomes_three_indicators() {
+        let headers = StringRecord::from(vec!["id", "eye", "k"]);
+        let records = vec![
+            StringRecord::from(vec!["1", "OD", "42.1"]),
+            StringRecord::from(vec!["2", "OS", "43.0"]),
+            StringRecord::from(vec!["3", "OU", "41.7"]),
+            StringRecord::from(vec!["4", " OS ", "42.8"]),
+            StringRecord::from(vec!["5", "", "44.0"]),
+        ];
+
+        let (new_headers, new_records) = one_hot(&headers, &records, "eye", 3).unwrap();
+
+        assert_eq!(new_headers, StringRecord::from(vec!["id", "eye=OD", "eye=OS", "eye=OU", "k"]));
+        let rows: Vec<Vec<&str>> = new_records.iter().map(|r| r.iter().collect()).collect();
+        assert_eq!(rows, vec![
+            vec!["1", "1", "0", "0", "42.1"],
+            vec!["2", "0", "1", "0", "43.0"],
+            vec!["3", "0", "0", "1", "41.7"],
+            vec!["4", "0", "1", "0", "42.8"],
+            vec!["5", "0", "0", "0", "44.0"],
+        ]);
+
+        assert!(one_hot(&headers, &records, "eye", 2).is_err());
+        assert!(one_hot(&headers, &records, "side", 3).is_err());
+    }
+}
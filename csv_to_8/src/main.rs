@@ -2,9 +2,11 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use std::error::Error;
 use csv::{Reader, Writer};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use rayon::prelude::*;
 
+use shared::discover::{check_required_columns, no_input_files, required_columns_from_args, HeaderMatch, InputFilter};
+
 fn main() -> Result<(), Box<dyn Error>> {
     // Define the Radial_Index values we want to separate
     let radial_indices = vec![1, 4, 8, 12, 16, 20, 24,];
@@ -21,28 +23,150 @@ fn main() -> Result<(), Box<dyn Error>> {
         fs::create_dir_all(&dir_path)?;
     }
 
-    // Process each CSV file in the input directory in parallel
-    let entries = fs::read_dir(input_dir)?
-        .collect::<Result<Vec<_>, _>>()?;
+    let args: Vec<String> = std::env::args().collect();
+    let input_filter = InputFilter::from_args(&args);
+    let required_columns = required_columns_from_args(&args);
+    let header_match = HeaderMatch::from_args(&args);
+    let min_group_size = match args.iter().position(|a| a == "--min-group-size") {
+        Some(i) => {
+            let value = args.get(i + 1).map(String::as_str).unwrap_or("");
+            Some(value.parse::<usize>().map_err(|_| format!("Invalid --min-group-size '{}' (expected a row count)", value))?)
+        }
+        None => None,
+    };
 
-    entries.par_iter().for_each(|entry| {
-        let path = entry.path();
+    // Process each CSV file in the input directory in parallel
+    let mut files = input_filter.input_files(input_dir)?;
+    // With --recursive the radial_N output folders are inside the input one
+    files.retain(|path| !radial_indices.iter().any(|index| path.starts_with(base_output_dir.join(format!("radial_{}", index)))));
+    if let Some((message, code)) = no_input_files(input_dir, &files) {
+        eprintln!("{}", message);
+        std::process::exit(code);
+    }
 
-        if path.extension().and_then(|s| s.to_str()) == Some("csv") {
-            if let Err(e) = process_file(&path, &radial_indices, base_output_dir) {
-                eprintln!("Error processing file {:?}: {}", path.file_name().unwrap(), e);
-            }
+    files.par_iter().for_each(|path| {
+        let result = process_file(path, &radial_indices, &required_columns, header_match, base_output_dir)
+            .and_then(|counts| {
+                let removed = match min_group_size {
+                    Some(min) => remove_small_groups(path, base_output_dir, &counts, min)?,
+                    None => Vec::new(),
+                };
+                // One print per file, so parallel tables don't interleave
+                print!("{}", group_count_table(path, &counts, &removed));
+                Ok(())
+            });
+        if let Err(e) = result {
+            eprintln!("Error processing file {:?}: {}", path.file_name().unwrap(), e);
         }
     });
 
+    // --merge-outputs: concatenate each radial_N folder into all_patients.csv
+    if args.iter().any(|a| a == "--merge-outputs") {
+        for &index in &radial_indices {
+            let dir_path = base_output_dir.join(format!("radial_{}", index));
+            let rows = merge_radial_outputs(&dir_path)?;
+            println!("Merged {} rows into {:?}", rows, dir_path.join(MERGED_FILE_NAME));
+        }
+    }
+
     Ok(())
 }
 
+const MERGED_FILE_NAME: &str = "all_patients.csv";
+
+// Concatenate every split file in one radial folder, writing the header once.
+// Columns are aligned by name, so files with a different column order (or a
+// column the others lack) still line up; missing cells are left empty.
+fn merge_radial_outputs(dir_path: &Path) -> Result<usize, Box<dyn Error>> {
+    let mut inputs: Vec<PathBuf> = fs::read_dir(dir_path)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    inputs.retain(|path| {
+        path.is_file()
+            && path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("csv"))
+            && path.file_name().is_some_and(|name| name != MERGED_FILE_NAME)
+    });
+    inputs.sort();
+
+    let mut merged_headers: Vec<String> = Vec::new();
+    for path in &inputs {
+        for header in Reader::from_path(path)?.headers()? {
+            if !merged_headers.iter().any(|h| h == header) {
+                merged_headers.push(header.to_string());
+            }
+        }
+    }
+
+    let mut writer = Writer::from_path(dir_path.join(MERGED_FILE_NAME))?;
+    writer.write_record(&merged_headers)?;
+
+    let mut rows = 0;
+    for path in &inputs {
+        let mut reader = Reader::from_path(path)?;
+        let headers = reader.headers()?.clone();
+        let positions: Vec<Option<usize>> = merged_headers.iter()
+            .map(|name| headers.iter().position(|h| h == name))
+            .collect();
+
+        for result in reader.records() {
+            let record = result?;
+            writer.write_record(positions.iter()
+                .map(|pos| pos.and_then(|i| record.get(i)).unwrap_or("")))?;
+            rows += 1;
+        }
+    }
+
+    writer.flush()?;
+    Ok(rows)
+}
+
+// Rows written to each radial_N file of one input, by Radial_Index
+type GroupCounts = BTreeMap<i32, usize>;
+
+fn split_file_path(input_path: &Path, base_output_dir: &Path, index: i32) -> Result<PathBuf, Box<dyn Error>> {
+    let file_stem = input_path
+        .file_stem()
+        .ok_or("Invalid filename")?
+        .to_str()
+        .ok_or("Invalid UTF-8 in filename")?;
+    Ok(base_output_dir.join(format!("radial_{}", index)).join(format!("{}.csv", file_stem)))
+}
+
+// --min-group-size N: delete the split files of this input with fewer than N
+// rows (a truncated scan), which downstream ANOVA can't use. Returns the
+// indices removed.
+fn remove_small_groups(
+    input_path: &Path,
+    base_output_dir: &Path,
+    counts: &GroupCounts,
+    min_group_size: usize,
+) -> Result<Vec<i32>, Box<dyn Error>> {
+    let mut removed = Vec::new();
+    for (&index, &rows) in counts {
+        if rows < min_group_size {
+            fs::remove_file(split_file_path(input_path, base_output_dir, index)?)?;
+            removed.push(index);
+        }
+    }
+    Ok(removed)
+}
+
+fn group_count_table(input_path: &Path, counts: &GroupCounts, removed: &[i32]) -> String {
+    let mut table = format!("Rows per Radial_Index in {:?}:\n", input_path.file_name().unwrap_or_default());
+    for (index, rows) in counts {
+        let note = if removed.contains(index) { "  removed (below --min-group-size)" } else { "" };
+        table.push_str(&format!("  {:>6} {:>8}{}\n", index, rows, note));
+    }
+    table
+}
+
 fn process_file(
     input_path: &PathBuf,
     radial_indices: &[i32],
+    required_columns: &[String],
+    header_match: HeaderMatch,
     base_output_dir: &Path,
-) -> Result<(), Box<dyn Error>> {
+) -> Result<GroupCounts, Box<dyn Error>> {
     println!("Processing file: {:?}", input_path.file_name().unwrap());
 
     // Create a reader
@@ -50,28 +174,20 @@ fn process_file(
 
     // Get headers
     let headers = reader.headers()?.clone();
+    check_required_columns(&headers, required_columns, header_match, input_path)?;
 
     // Find Radial_Index column
-    let radial_index_col = headers
-        .iter()
-        .position(|header| header == "Radial_Index")
+    let radial_index_col = header_match.position(&headers, "Radial_Index")
         .ok_or("Radial_Index column not found")?;
 
     // Create a HashMap to store writers for each Radial_Index
     let mut writers: HashMap<i32, Writer<std::fs::File>> = HashMap::new();
 
-    // Get the original filename without extension
-    let file_stem = input_path
-        .file_stem()
-        .ok_or("Invalid filename")?
-        .to_str()
-        .ok_or("Invalid UTF-8 in filename")?;
+    let mut counts: GroupCounts = radial_indices.iter().map(|&index| (index, 0)).collect();
 
     // Initialize writers for each Radial_Index
     for &index in radial_indices {
-        let output_dir = base_output_dir.join(format!("radial_{}", index));
-        let output_path = output_dir.join(format!("{}.csv", file_stem));
-        let writer = Writer::from_path(output_path)?;
+        let writer = Writer::from_path(split_file_path(input_path, base_output_dir, index)?)?;
         writers.insert(index, writer);
     }
 
@@ -87,11 +203,106 @@ fn process_file(
             if let Ok(index) = value.parse::<i32>() {
                 if let Some(writer) = writers.get_mut(&index) {
                     writer.write_record(&record)?;
+                    *counts.entry(index).or_insert(0) += 1;
                 }
             }
         }
     }
 
+    for writer in writers.values_mut() {
+        writer.flush()?;
+    }
+
     println!("Finished processing: {:?}", input_path.file_name().unwrap());
-    Ok(())
-}
\ No newline at end of file
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_required_column_is_reported() {
+        let dir = std::env::temp_dir().join(format!("csv_to_8_require_{}", std::process::id()));
+        fs::create_dir_all(dir.join("radial_1")).unwrap();
+        let input = dir.join("P_001.csv");
+        fs::write(&input, "Radial_Index,Axial\n1,42.1\n").unwrap();
+
+        let required = vec!["Radial_Index".to_string(), "Elevation".to_string()];
+        let err = process_file(&input, &[1], &required, HeaderMatch::Exact, &dir).unwrap_err();
+        let output_written = dir.join("radial_1").join("P_001.csv").exists();
+        fs::remove_dir_all(&dir).ok();
+
+        let message = err.to_string();
+        assert!(message.contains("missing required column(s): Elevation"), "{}", message);
+        assert!(!output_written);
+    }
+
+    #[test]
+    fn test_merge_outputs_concatenates_patients() {
+        let dir = std::env::temp_dir().join(format!("csv_to_8_merge_{}", std::process::id()));
+        fs::create_dir_all(dir.join("radial_1")).unwrap();
+        fs::create_dir_all(dir.join("radial_4")).unwrap();
+        let first = dir.join("P_001.csv");
+        let second = dir.join("P_002.csv");
+        fs::write(&first, "Radial_Index,Axial\n1,42.1\n4,43.0\n1,42.3\n").unwrap();
+        fs::write(&second, "Axial,Radial_Index\n41.7,1\n44.2,4\n").unwrap();
+
+        process_file(&first, &[1, 4], &[], HeaderMatch::Exact, &dir).unwrap();
+        process_file(&second, &[1, 4], &[], HeaderMatch::Exact, &dir).unwrap();
+        let rows = merge_radial_outputs(&dir.join("radial_1")).unwrap();
+        let merged = fs::read_to_string(dir.join("radial_1").join(MERGED_FILE_NAME)).unwrap();
+        // Merging again must not pick up the previous all_patients.csv
+        let rows_again = merge_radial_outputs(&dir.join("radial_1")).unwrap();
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(rows, 3);
+        assert_eq!(rows_again, 3);
+        assert_eq!(merged.matches("Radial_Index").count(), 1);
+        assert_eq!(merged, "Radial_Index,Axial\n1,42.1\n1,42.3\n1,41.7\n");
+    }
+
+    #[test]
+    fn test_min_group_size_removes_truncated_index() {
+        let dir = std::env::temp_dir().join(format!("csv_to_8_min_group_{}", std::process::id()));
+        fs::create_dir_all(dir.join("radial_1")).unwrap();
+        fs::create_dir_all(dir.join("radial_32")).unwrap();
+        let input = dir.join("P_001.csv");
+        let mut content = "Radial_Index,Axial\n".to_string();
+        for i in 0..12 {
+            content.push_str(&format!("1,42.{}\n", i));
+        }
+        content.push_str("32,44.0\n32,44.1\n");
+        fs::write(&input, content).unwrap();
+
+        let counts = process_file(&input, &[1, 32], &[], HeaderMatch::Exact, &dir).unwrap();
+        let removed = remove_small_groups(&input, &dir, &counts, 10).unwrap();
+        let table = group_count_table(&input, &counts, &removed);
+        let kept = dir.join("radial_1").join("P_001.csv").exists();
+        let truncated = dir.join("radial_32").join("P_001.csv").exists();
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(counts, GroupCounts::from([(1, 12), (32, 2)]));
+        assert_eq!(removed, vec![32]);
+        assert!(kept);
+        assert!(!truncated);
+        assert!(table.contains("     32        2  removed"), "{}", table);
+        assert!(table.contains("      1       12\n"), "{}", table);
+    }
+
+    #[test]
+    fn test_case_insensitive_headers_find_radial_index() {
+        let dir = std::env::temp_dir().join(format!("csv_to_8_case_{}", std::process::id()));
+        fs::create_dir_all(dir.join("radial_1")).unwrap();
+        let input = dir.join("P_001.csv");
+        fs::write(&input, " radial_INDEX ,Axial\n1,42.1\n4,43.0\n").unwrap();
+
+        let exact = process_file(&input, &[1], &[], HeaderMatch::Exact, &dir);
+        let args: Vec<String> = ["csv_to_8", "--case-insensitive-headers"].iter().map(|s| s.to_string()).collect();
+        let counts = process_file(&input, &[1], &[], HeaderMatch::from_args(&args), &dir).unwrap();
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(exact.unwrap_err().to_string(), "Radial_Index column not found");
+        assert_eq!(counts, GroupCounts::from([(1, 1)]));
+    }
+}
@@ -1,10 +1,20 @@
-use csv::Reader;
+use csv::{Reader, StringRecord};
 use rayon::prelude::*;
 use serde::Deserialize;
+use statrs::distribution::{ContinuousCDF, StudentsT};
 use statrs::statistics::{Data, Distribution, OrderStatistics};
 use std::error::Error;
 use std::fs::File;
 
+use shared::fourier::real_dft;
+use shared::locale::{parse_number, NumberLocale};
+use shared::percentile::QuantileMethod;
+
+const COEF_NAMES: [&str; 11] = [
+    "coef_a0", "coef_am1", "coef_bm1", "coef_am2", "coef_bm2",
+    "coef_am3", "coef_bm3", "coef_am4", "coef_bm4", "coef_am5", "coef_bm5"
+];
+
 #[derive(Debug, Deserialize)]
 struct Record {
     coef_a0: f64,
@@ -20,15 +30,14 @@ struct Record {
     coef_bm5: f64,
 }
 
-fn calculate_statistics(data: &[f64]) -> Result<Statistics, Box<dyn Error>> {
+fn calculate_statistics(data: &[f64], method: QuantileMethod) -> Result<Statistics, Box<dyn Error>> {
     let mut sorted_data = data.to_vec();
     sorted_data.par_sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
 
     let mut data_stats = Data::new(data.to_vec());
 
-    // Calculate quartiles
-    let q1_idx = (data.len() as f64 * 0.25).floor() as usize;
-    let q3_idx = (data.len() as f64 * 0.75).floor() as usize;
+    let q1 = method.quantile(&sorted_data, 0.25);
+    let q3 = method.quantile(&sorted_data, 0.75);
 
     Ok(Statistics {
         mean: data_stats.mean().unwrap(),
@@ -38,7 +47,9 @@ fn calculate_statistics(data: &[f64]) -> Result<Statistics, Box<dyn Error>> {
             min: *data.iter().min_by(|a, b| a.partial_cmp(b).unwrap()).unwrap(),
             max: *data.iter().max_by(|a, b| a.partial_cmp(b).unwrap()).unwrap(),
         },
-        iqr: sorted_data[q3_idx] - sorted_data[q1_idx],
+        q1,
+        q3,
+        iqr: q3 - q1,
         skewness: calculate_skewness(data),
         kurtosis: calculate_kurtosis(data),
     })
@@ -50,6 +61,8 @@ struct Statistics {
     median: f64,
     std_dev: f64,
     range: Range,
+    q1: f64,
+    q3: f64,
     iqr: f64,
     skewness: f64,
     kurtosis: f64,
@@ -79,36 +92,207 @@ fn calculate_kurtosis(data: &[f64]) -> f64 {
     (data.par_iter().map(|x| ((x - mean) / std_dev).powi(4)).sum::<f64>() / n) - 3.0 // Excess kurtosis
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let file_path = "/home/aricept094/python/fourier_analysis_1d_meridian_results('Meridian_Angle_Rad')['Elevation_Anterior_Scaled']_all_patinets.csv";
+// Welch's t-test for two samples with possibly unequal sizes and variances.
+// Returns the t statistic and the two-sided p-value.
+fn welch_t_test(a: &[f64], b: &[f64]) -> Result<(f64, f64), Box<dyn Error>> {
+    if a.len() < 2 || b.len() < 2 {
+        return Err("Welch's t-test needs at least two values in each sample".into());
+    }
+
+    let n_a = a.len() as f64;
+    let n_b = b.len() as f64;
+    let mean_a = a.iter().sum::<f64>() / n_a;
+    let mean_b = b.iter().sum::<f64>() / n_b;
+    let var_a = a.iter().map(|x| (x - mean_a).powi(2)).sum::<f64>() / (n_a - 1.0);
+    let var_b = b.iter().map(|x| (x - mean_b).powi(2)).sum::<f64>() / (n_b - 1.0);
+
+    let se_a = var_a / n_a;
+    let se_b = var_b / n_b;
+    let se = (se_a + se_b).sqrt();
+    if se == 0.0 {
+        // Both samples are constant: identical means are indistinguishable,
+        // different means are infinitely significant.
+        return Ok(if mean_a == mean_b { (0.0, 1.0) } else { (f64::INFINITY, 0.0) });
+    }
+
+    let t = (mean_a - mean_b) / se;
+    let df = (se_a + se_b).powi(2)
+        / (se_a.powi(2) / (n_a - 1.0) + se_b.powi(2) / (n_b - 1.0));
+
+    let dist = StudentsT::new(0.0, 1.0, df)?;
+    let p = 2.0 * (1.0 - dist.cdf(t.abs()));
+    Ok((t, p))
+}
+
+fn read_records(file_path: &str, locale: &NumberLocale) -> Result<Vec<Record>, Box<dyn Error>> {
     let file = File::open(file_path)?;
     let mut rdr = Reader::from_reader(file);
+    if *locale == NumberLocale::default() {
+        let records: Vec<Record> = rdr.deserialize().par_bridge().collect::<Result<_, _>>()?;
+        return Ok(records);
+    }
+
+    // Rewrite each field in plain dot-decimal form before deserializing; fields
+    // that don't parse are left alone so serde reports them
+    let headers = rdr.headers()?.clone();
+    let records: Vec<Record> = rdr.records().par_bridge().map(|result| {
+        let record = result?;
+        let normalized: StringRecord = record.iter()
+            .map(|field| parse_number(field, locale).map_or_else(|| field.to_string(), |v| v.to_string()))
+            .collect();
+        normalized.deserialize(Some(&headers))
+    }).collect::<Result<_, csv::Error>>()?;
+    Ok(records)
+}
+
+// --rings: the files hold raw rings instead of fitted coefficients, one row
+// per eye with its values at evenly spaced meridians. Each ring is fitted with
+// the first five harmonics, the coefficients grid_fix_multi writes.
+fn records_from_rings(file_path: &str, locale: &NumberLocale) -> Result<Vec<Record>, Box<dyn Error>> {
+    let mut rdr = Reader::from_reader(File::open(file_path)?);
+    rdr.records().map(|result| {
+        let record = result?;
+        let ring = record.iter()
+            .map(|field| parse_number(field, locale).ok_or_else(|| format!("{}: '{}' is not a number", file_path, field)))
+            .collect::<Result<Vec<f64>, _>>()?;
+        // Five harmonics need more than ten meridians, or they alias
+        if ring.len() <= 10 {
+            return Err(format!("{}: a ring needs more than 10 values, found {}", file_path, ring.len()).into());
+        }
+        let (a0, a, b, _) = real_dft(&ring, 5);
+        Ok(Record {
+            coef_a0: a0,
+            coef_am1: a[0],
+            coef_bm1: b[0],
+            coef_am2: a[1],
+            coef_bm2: b[1],
+            coef_am3: a[2],
+            coef_bm3: b[2],
+            coef_am4: a[3],
+            coef_bm4: b[3],
+            coef_am5: a[4],
+            coef_bm5: b[4],
+        })
+    }).collect()
+}
+
+fn load_records(file_path: &str, locale: &NumberLocale, from_rings: bool) -> Result<Vec<Record>, Box<dyn Error>> {
+    if from_rings {
+        records_from_rings(file_path, locale)
+    } else {
+        read_records(file_path, locale)
+    }
+}
+
+// Extract a single coefficient (by its index in COEF_NAMES) into its own vector
+fn coefficient_values(records: &[Record], i: usize) -> Vec<f64> {
+    match i {
+        0 => records.iter().map(|r| r.coef_a0).collect(),
+        1 => records.iter().map(|r| r.coef_am1).collect(),
+        2 => records.iter().map(|r| r.coef_bm1).collect(),
+        3 => records.iter().map(|r| r.coef_am2).collect(),
+        4 => records.iter().map(|r| r.coef_bm2).collect(),
+        5 => records.iter().map(|r| r.coef_am3).collect(),
+        6 => records.iter().map(|r| r.coef_bm3).collect(),
+        7 => records.iter().map(|r| r.coef_am4).collect(),
+        8 => records.iter().map(|r| r.coef_bm4).collect(),
+        9 => records.iter().map(|r| r.coef_am5).collect(),
+        10 => records.iter().map(|r| r.coef_bm5).collect(),
+        _ => unreachable!(),
+    }
+}
+
+#[derive(Debug)]
+struct Comparison {
+    coef_name: &'static str,
+    stats_a: Statistics,
+    stats_b: Statistics,
+    mean_difference: f64,
+    t_statistic: f64,
+    p_value: f64,
+}
+
+fn compare_records(records_a: &[Record], records_b: &[Record], method: QuantileMethod) -> Result<Vec<Comparison>, Box<dyn Error>> {
+    let mut comparisons = Vec::with_capacity(COEF_NAMES.len());
+    for (i, coef_name) in COEF_NAMES.iter().enumerate() {
+        let data_a = coefficient_values(records_a, i);
+        let data_b = coefficient_values(records_b, i);
+        let stats_a = calculate_statistics(&data_a, method)?;
+        let stats_b = calculate_statistics(&data_b, method)?;
+        let (t_statistic, p_value) = welch_t_test(&data_a, &data_b)?;
 
-    let records: Vec<Record> = rdr.deserialize().par_bridge().collect::<Result<_, _>>()?;
-
-    // Extract individual coefficients into separate vectors
-    let coef_names = vec![
-        "coef_a0", "coef_am1", "coef_bm1", "coef_am2", "coef_bm2",
-        "coef_am3", "coef_bm3", "coef_am4", "coef_bm4", "coef_am5", "coef_bm5"
-    ];
-
-    let stats: Vec<_> = coef_names.par_iter().enumerate().map(|(i, coef_name)| {
-        let data: Vec<f64> = match i {
-            0 => records.iter().map(|r| r.coef_a0).collect(),
-            1 => records.iter().map(|r| r.coef_am1).collect(),
-            2 => records.iter().map(|r| r.coef_bm1).collect(),
-            3 => records.iter().map(|r| r.coef_am2).collect(),
-            4 => records.iter().map(|r| r.coef_bm2).collect(),
-            5 => records.iter().map(|r| r.coef_am3).collect(),
-            6 => records.iter().map(|r| r.coef_bm3).collect(),
-            7 => records.iter().map(|r| r.coef_am4).collect(),
-            8 => records.iter().map(|r| r.coef_bm4).collect(),
-            9 => records.iter().map(|r| r.coef_am5).collect(),
-            10 => records.iter().map(|r| r.coef_bm5).collect(),
-            _ => unreachable!(),
-        };
-
-        let stats = calculate_statistics(&data).unwrap();
+        comparisons.push(Comparison {
+            coef_name,
+            mean_difference: stats_a.mean - stats_b.mean,
+            stats_a,
+            stats_b,
+            t_statistic,
+            p_value,
+        });
+    }
+    Ok(comparisons)
+}
+
+fn print_comparison(file_a: &str, file_b: &str, n_a: usize, n_b: usize, comparisons: &[Comparison]) {
+    println!("Comparing {} (n = {}) against {} (n = {})", file_a, n_a, file_b, n_b);
+    println!("\n{:<10} {:>12} {:>12} {:>12} {:>12} {:>12} {:>10} {:>10}",
+             "Coef", "Mean A", "SD A", "Mean B", "SD B", "Mean Diff", "t", "p");
+    for c in comparisons {
+        println!("{:<10} {:>12.4} {:>12.4} {:>12.4} {:>12.4} {:>12.4} {:>10.4} {:>10.4}",
+                 c.coef_name,
+                 c.stats_a.mean, c.stats_a.std_dev,
+                 c.stats_b.mean, c.stats_b.std_dev,
+                 c.mean_difference, c.t_statistic, c.p_value);
+    }
+}
+
+// --compare <file>: a second coefficient file (e.g. the keratoconus cohort)
+// to test against the main one instead of printing its statistics
+fn compare_path_from_args(args: &[String]) -> Result<Option<&str>, Box<dyn Error>> {
+    match args.iter().position(|a| a == "--compare") {
+        Some(i) => Ok(Some(args.get(i + 1).ok_or("--compare needs a path")?.as_str())),
+        None => Ok(None),
+    }
+}
+
+fn run_comparison(
+    file_a: &str,
+    file_b: &str,
+    locale: &NumberLocale,
+    method: QuantileMethod,
+    from_rings: bool,
+) -> Result<Vec<Comparison>, Box<dyn Error>> {
+    let records_a = load_records(file_a, locale, from_rings)?;
+    let records_b = load_records(file_b, locale, from_rings)?;
+    let comparisons = compare_records(&records_a, &records_b, method)?;
+    print_comparison(file_a, file_b, records_a.len(), records_b.len(), &comparisons);
+    Ok(comparisons)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    // --rings <file>: fit the coefficients from raw rings in <file> (and in the
+    // --compare file) instead of reading them from the exported results
+    let rings_path = match args.iter().position(|a| a == "--rings") {
+        Some(i) => Some(args.get(i + 1).ok_or("--rings needs a path")?.as_str()),
+        None => None,
+    };
+    let from_rings = rings_path.is_some();
+    let file_path = rings_path.unwrap_or("/home/aricept094/python/fourier_analysis_1d_meridian_results('Meridian_Angle_Rad')['Elevation_Anterior_Scaled']_all_patinets.csv");
+    let compare_path = compare_path_from_args(&args)?;
+    let locale = NumberLocale::from_args(&args)?;
+    let quantile_method = QuantileMethod::from_args(&args)?;
+
+    if let Some(other_path) = compare_path {
+        run_comparison(file_path, other_path, &locale, quantile_method, from_rings)?;
+        return Ok(());
+    }
+
+    let records = load_records(file_path, &locale, from_rings)?;
+
+    let stats: Vec<_> = COEF_NAMES.par_iter().enumerate().map(|(i, coef_name)| {
+        let data = coefficient_values(&records, i);
+        let stats = calculate_statistics(&data, quantile_method).unwrap();
         (coef_name, stats)
     }).collect();
     
@@ -118,10 +302,131 @@ fn main() -> Result<(), Box<dyn Error>> {
         println!("Median: {:.4}", stats.median);
         println!("Standard Deviation: {:.4}", stats.std_dev);
         println!("Range: {:.4} to {:.4}", stats.range.min, stats.range.max);
+        println!("Q1 / Q3: {:.4} / {:.4}", stats.q1, stats.q3);
         println!("Interquartile Range: {:.4}", stats.iqr);
         println!("Skewness: {:.4}", stats.skewness);
         println!("Kurtosis: {:.4}", stats.kurtosis);
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn write_coefficient_file(name: &str, rows: usize, a0_shift: f64) -> String {
+        let path = std::env::temp_dir().join(format!("descriptive_{}_{}.csv", name, std::process::id()));
+        let mut file = File::create(&path).unwrap();
+        writeln!(file, "{}", COEF_NAMES.join(",")).unwrap();
+        for i in 0..rows {
+            let noise = (i as f64 * 0.731).sin();
+            let mut row = vec![(10.0 + a0_shift + noise).to_string()];
+            row.extend((1..COEF_NAMES.len()).map(|_| noise.to_string()));
+            writeln!(file, "{}", row.join(",")).unwrap();
+        }
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_compare_detects_shifted_coefficient() {
+        let path_a = write_coefficient_file("normal", 40, 0.0);
+        let path_b = write_coefficient_file("shifted", 55, 3.0);
+        let records_a = read_records(&path_a, &NumberLocale::default()).unwrap();
+        let records_b = read_records(&path_b, &NumberLocale::default()).unwrap();
+
+        let comparisons = compare_records(&records_a, &records_b, QuantileMethod::default()).unwrap();
+        let a0 = comparisons.iter().find(|c| c.coef_name == "coef_a0").unwrap();
+        let am1 = comparisons.iter().find(|c| c.coef_name == "coef_am1").unwrap();
+
+        assert!(a0.mean_difference < -2.5);
+        assert!(a0.p_value < 1e-6, "p = {}", a0.p_value);
+        assert!(am1.p_value > 0.2, "p = {}", am1.p_value);
+
+        std::fs::remove_file(path_a).ok();
+        std::fs::remove_file(path_b).ok();
+    }
+
+    #[test]
+    fn test_compare_flag_runs_comparison() {
+        let path_a = write_coefficient_file("flag_a", 30, 0.0);
+        let path_b = write_coefficient_file("flag_b", 30, 3.0);
+        let args: Vec<String> = ["descriptive", "--compare", path_b.as_str()].iter().map(|s| s.to_string()).collect();
+
+        let compare_path = compare_path_from_args(&args).unwrap();
+        let comparisons = run_comparison(&path_a, compare_path.unwrap(), &NumberLocale::default(), QuantileMethod::default(), false).unwrap();
+        std::fs::remove_file(&path_a).ok();
+        std::fs::remove_file(&path_b).ok();
+
+        assert_eq!(comparisons.len(), COEF_NAMES.len());
+        assert!(comparisons[0].mean_difference < -2.5);
+        assert!(compare_path_from_args(&args[..2]).is_err());
+        assert_eq!(compare_path_from_args(&args[..1]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_read_records_with_comma_decimal() {
+        let path = std::env::temp_dir().join(format!("descriptive_comma_{}.csv", std::process::id()));
+        let mut file = File::create(&path).unwrap();
+        writeln!(file, "{}", COEF_NAMES.join(",")).unwrap();
+        let row: Vec<String> = (0..COEF_NAMES.len()).map(|i| format!("\"1.23{},5\"", i)).collect();
+        writeln!(file, "{}", row.join(",")).unwrap();
+        drop(file);
+
+        let locale = NumberLocale { decimal: ',', thousands: Some('.') };
+        let records = read_records(path.to_str().unwrap(), &locale).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].coef_a0, 1230.5);
+        assert_eq!(records[0].coef_bm5, 12310.5);
+    }
+
+    #[test]
+    fn test_rings_are_fitted_into_coefficients() {
+        let path = std::env::temp_dir().join(format!("descriptive_rings_{}.csv", std::process::id()));
+        let mut file = File::create(&path).unwrap();
+        let meridians = 32;
+        writeln!(file, "{}", (1..=meridians).map(|j| format!("m{}", j)).collect::<Vec<_>>().join(",")).unwrap();
+        for amplitude in [3.0, 4.0] {
+            let ring: Vec<String> = (0..meridians)
+                .map(|j| (5.0 + amplitude * (2.0 * std::f64::consts::PI * j as f64 / meridians as f64).cos()).to_string())
+                .collect();
+            writeln!(file, "{}", ring.join(",")).unwrap();
+        }
+        drop(file);
+
+        let records = load_records(path.to_str().unwrap(), &NumberLocale::default(), true).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(records.len(), 2);
+        assert!((records[0].coef_a0 - 5.0).abs() < 1e-9);
+        assert!((records[0].coef_am1 - 3.0).abs() < 1e-9);
+        assert!((records[1].coef_am1 - 4.0).abs() < 1e-9);
+        assert!(records.iter().all(|r| r.coef_bm1.abs() < 1e-9 && r.coef_am2.abs() < 1e-9));
+    }
+
+    #[test]
+    fn test_quantile_methods_match_numpy() {
+        // np.quantile([1..10], [0.25, 0.75], method=...): positions 2.25 and 6.75
+        let data: Vec<f64> = [7.0, 2.0, 10.0, 4.0, 1.0, 9.0, 3.0, 6.0, 8.0, 5.0].to_vec();
+        let expected = [
+            ("linear", 3.25, 7.75),
+            ("lower", 3.0, 7.0),
+            ("higher", 4.0, 8.0),
+            ("nearest", 3.0, 8.0),
+            ("midpoint", 3.5, 7.5),
+        ];
+        for (name, q1, q3) in expected {
+            let stats = calculate_statistics(&data, QuantileMethod::parse(name).unwrap()).unwrap();
+            assert_eq!((stats.q1, stats.q3, stats.iqr), (q1, q3, q3 - q1), "{}", name);
+        }
+
+        // Positions 0.5 and 1.5: numpy's nearest rounds both to the even rank
+        let sorted = [10.0, 20.0, 30.0];
+        assert_eq!(QuantileMethod::Nearest.quantile(&sorted, 0.25), 10.0);
+        assert_eq!(QuantileMethod::Nearest.quantile(&sorted, 0.75), 30.0);
+        assert!(QuantileMethod::parse("hazen").is_err());
+    }
+}
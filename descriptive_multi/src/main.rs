@@ -1,12 +1,17 @@
 use csv::{Reader, WriterBuilder};
 use serde::Deserialize;
+use statrs::distribution::{ContinuousCDF, StudentsT};
 use statrs::statistics::{Data, Distribution};
+use std::collections::{BTreeMap, HashSet};
 use std::error::Error;
 use std::fs::{File, OpenOptions};
 use std::path::Path;
 use glob::glob;
 use rayon::prelude::*;
 
+use shared::number_format::precision_from_args;
+use shared::percentile::QuantileMethod;
+
 #[derive(Debug, Deserialize)]
 struct Record {
     dc_component: Option<f64>,
@@ -18,6 +23,7 @@ struct Record {
 
 #[derive(Debug)]
 struct Statistics {
+    n: usize,
     mean: f64,
     std_dev: f64,
     range: Range,
@@ -32,6 +38,7 @@ struct Range {
 fn calculate_statistics(data: &[f64]) -> Result<Statistics, Box<dyn Error>> {
     let data_stats = Data::new(data.to_vec());
     Ok(Statistics {
+        n: data.len(),
         mean: data_stats.mean().unwrap(),
         std_dev: data_stats.std_dev().unwrap(),
         range: Range {
@@ -41,6 +48,125 @@ fn calculate_statistics(data: &[f64]) -> Result<Statistics, Box<dyn Error>> {
     })
 }
 
+// One column pooled over every file, weighting each file by its sample size
+#[derive(Debug)]
+struct PooledStatistics {
+    n: usize,
+    files: usize,
+    mean: f64,
+    std_dev: f64,
+    ci_lower: f64,
+    ci_upper: f64,
+}
+
+// Mean and sample SD of all values combined, rebuilt from the per-file n,
+// mean and SD, with a 95% Student t interval for the pooled mean
+fn pool_statistics(stats: &[&Statistics]) -> Result<PooledStatistics, Box<dyn Error>> {
+    let n: usize = stats.iter().map(|s| s.n).sum();
+    if n == 0 {
+        return Err("No values to pool".into());
+    }
+    let mean = stats.iter().map(|s| s.n as f64 * s.mean).sum::<f64>() / n as f64;
+
+    // Within-file plus between-file sums of squares
+    let sum_squares: f64 = stats.iter()
+        .map(|s| {
+            let within = if s.n > 1 { (s.n - 1) as f64 * s.std_dev.powi(2) } else { 0.0 };
+            within + s.n as f64 * (s.mean - mean).powi(2)
+        })
+        .sum();
+
+    let (std_dev, ci_lower, ci_upper) = if n > 1 {
+        let std_dev = (sum_squares / (n - 1) as f64).sqrt();
+        let t = StudentsT::new(0.0, 1.0, (n - 1) as f64)?.inverse_cdf(0.975);
+        let margin = t * std_dev / (n as f64).sqrt();
+        (std_dev, mean - margin, mean + margin)
+    } else {
+        (0.0, mean, mean)
+    };
+
+    Ok(PooledStatistics { n, files: stats.len(), mean, std_dev, ci_lower, ci_upper })
+}
+
+// Decimal places of every computed statistic in the outputs without --precision
+const DEFAULT_PRECISION: usize = 4;
+
+fn write_pooled(results: &[(String, String, Statistics)], output_path: &str, precision: usize) -> Result<(), Box<dyn Error>> {
+    let mut by_column: BTreeMap<&str, Vec<&Statistics>> = BTreeMap::new();
+    for (_, column_name, stat) in results {
+        by_column.entry(column_name.as_str()).or_default().push(stat);
+    }
+
+    std::fs::write(output_path, [0xEF, 0xBB, 0xBF])?;
+    let mut wtr = WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(OpenOptions::new().append(true).open(output_path)?);
+
+    wtr.write_record(["Column", "Files", "N", "Pooled Mean", "Pooled SD", "95% CI Lower", "95% CI Upper"])?;
+    for (column_name, stats) in by_column {
+        let pooled = pool_statistics(&stats)?;
+        wtr.write_record(&[
+            column_name.to_string(),
+            pooled.files.to_string(),
+            pooled.n.to_string(),
+            format!("{:.*}", precision, pooled.mean),
+            format!("{:.*}", precision, pooled.std_dev),
+            format!("{:.*}", precision, pooled.ci_lower),
+            format!("{:.*}", precision, pooled.ci_upper),
+        ])?;
+    }
+
+    wtr.flush()?;
+    println!("Pooled statistics saved to {}", output_path);
+    Ok(())
+}
+
+// A value outside the Tukey fences of its column; row is the 1-based data row
+#[derive(Debug)]
+struct Outlier {
+    column: String,
+    row: usize,
+    value: f64,
+    lower_fence: f64,
+    upper_fence: f64,
+}
+
+// Q1 - 1.5 IQR and Q3 + 1.5 IQR; None when the IQR is zero, since a column
+// that is mostly one value would otherwise flag every other value
+fn tukey_fences(data: &[f64], method: QuantileMethod) -> Option<(f64, f64)> {
+    if data.len() < 4 {
+        return None;
+    }
+    let mut sorted = data.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let q1 = method.quantile(&sorted, 0.25);
+    let q3 = method.quantile(&sorted, 0.75);
+    let iqr = q3 - q1;
+    if iqr <= 0.0 {
+        return None;
+    }
+    Some((q1 - 1.5 * iqr, q3 + 1.5 * iqr))
+}
+
+fn find_outliers(column: &str, values: &[(usize, f64)], method: QuantileMethod) -> Vec<Outlier> {
+    let data: Vec<f64> = values.iter().map(|&(_, v)| v).collect();
+    let (lower_fence, upper_fence) = match tukey_fences(&data, method) {
+        Some(fences) => fences,
+        None => return Vec::new(),
+    };
+
+    values.iter()
+        .filter(|&&(_, value)| value < lower_fence || value > upper_fence)
+        .map(|&(row, value)| Outlier {
+            column: column.to_string(),
+            row,
+            value,
+            lower_fence,
+            upper_fence,
+        })
+        .collect()
+}
+
 fn get_radius_label(filename: &str) -> String {
     match filename {
         f if f.contains("radial_4") => "radius 0.5mm".to_string(),
@@ -53,73 +179,307 @@ fn get_radius_label(filename: &str) -> String {
     }
 }
 
-fn analyze_file(file_path: &Path) -> Result<Vec<(String, Statistics)>, Box<dyn Error>> {
+// --pool-by {file,radius,all}: which files have their values combined before
+// the statistics are computed. Outliers are still found per file, since their
+// row numbers only make sense within one file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PoolBy {
+    File,
+    Radius,
+    All,
+}
+
+const ALL_RADII_LABEL: &str = "all radii";
+
+impl PoolBy {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "file" => Ok(PoolBy::File),
+            "radius" => Ok(PoolBy::Radius),
+            "all" => Ok(PoolBy::All),
+            other => Err(format!("Unknown --pool-by '{}' (expected file, radius or all)", other)),
+        }
+    }
+
+    fn from_args(args: &[String]) -> Result<Self, String> {
+        match args.iter().position(|a| a == "--pool-by") {
+            Some(i) => PoolBy::parse(args.get(i + 1).ok_or("--pool-by needs file, radius or all")?),
+            None => Ok(PoolBy::File),
+        }
+    }
+}
+
+// Non-missing values of each column of one file
+type FileColumns = Vec<(&'static str, Vec<f64>)>;
+
+struct FileAnalysis {
+    columns: FileColumns,
+    outliers: Vec<Outlier>,
+}
+
+// Non-missing values of one field, each paired with its 1-based data row
+fn column_values(records: &[Record], field: impl Fn(&Record) -> Option<f64>) -> Vec<(usize, f64)> {
+    records.iter()
+        .enumerate()
+        .filter_map(|(i, r)| field(r).map(|v| (i + 1, v)))
+        .collect()
+}
+
+fn analyze_file(file_path: &Path, method: QuantileMethod) -> Result<FileAnalysis, Box<dyn Error>> {
     let file = File::open(file_path)?;
     let mut rdr = Reader::from_reader(file);
     let records: Vec<Record> = rdr.deserialize().collect::<Result<_, _>>()?;
 
     let columns = vec![
-        ("dc_component", records.iter().filter_map(|r| r.dc_component).collect::<Vec<_>>()),
-        ("component_1_amplitude", records.iter().filter_map(|r| r.component_1_amplitude).collect()),
-        ("component_2_amplitude", records.iter().filter_map(|r| r.component_2_amplitude).collect()),
-        ("higher_order_amplitude_sum", records.iter().filter_map(|r| r.higher_order_amplitude_sum).collect()),
-        ("r2_score", records.iter().filter_map(|r| r.r2_score).collect()),
+        ("dc_component", column_values(&records, |r| r.dc_component)),
+        ("component_1_amplitude", column_values(&records, |r| r.component_1_amplitude)),
+        ("component_2_amplitude", column_values(&records, |r| r.component_2_amplitude)),
+        ("higher_order_amplitude_sum", column_values(&records, |r| r.higher_order_amplitude_sum)),
+        ("r2_score", column_values(&records, |r| r.r2_score)),
     ];
 
-    let stats: Vec<_> = columns.into_iter()
-        .filter(|(_, data)| !data.is_empty())
-        .map(|(name, data)| {
-            let stats = calculate_statistics(&data).unwrap();
-            (name.to_string(), stats)
+    let mut outliers = Vec::new();
+    let columns = columns.into_iter()
+        .map(|(name, values)| {
+            outliers.extend(find_outliers(name, &values, method));
+            (name, values.into_iter().map(|(_, v)| v).collect())
         })
         .collect();
 
-    Ok(stats)
+    Ok(FileAnalysis { columns, outliers })
 }
 
-fn format_statistics(stat: &Statistics) -> String {
+// (label, column, statistics) rows from (radius label, columns) per file.
+// With --pool-by file every file is its own group; otherwise the values of
+// files sharing a radius (or every file) are concatenated first.
+fn pool_columns(files: Vec<(String, FileColumns)>, pool_by: PoolBy) -> Vec<(String, String, Statistics)> {
+    let mut groups: Vec<(String, FileColumns)> = Vec::new();
+    for (radius_label, columns) in files {
+        let label = match pool_by {
+            PoolBy::All => ALL_RADII_LABEL.to_string(),
+            PoolBy::File | PoolBy::Radius => radius_label,
+        };
+        let existing = match pool_by {
+            PoolBy::File => None,
+            PoolBy::Radius | PoolBy::All => groups.iter_mut().find(|(l, _)| *l == label),
+        };
+        match existing {
+            Some((_, group_columns)) => {
+                for (name, values) in columns {
+                    match group_columns.iter_mut().find(|(n, _)| *n == name) {
+                        Some((_, group_values)) => group_values.extend(values),
+                        None => group_columns.push((name, values)),
+                    }
+                }
+            }
+            None => groups.push((label, columns)),
+        }
+    }
+
+    groups.into_iter()
+        .flat_map(|(label, columns)| {
+            columns.into_iter()
+                .filter(|(_, values)| !values.is_empty())
+                .map(move |(name, values)| (label.clone(), name.to_string(), calculate_statistics(&values).unwrap()))
+        })
+        .collect()
+}
+
+// Value is written as read; only the fences are rounded
+fn write_outliers(outliers: &[(String, Outlier)], output_path: &str, precision: usize) -> Result<(), Box<dyn Error>> {
+    std::fs::write(output_path, [0xEF, 0xBB, 0xBF])?;
+    let mut wtr = WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(OpenOptions::new().append(true).open(output_path)?);
+
+    wtr.write_record(["File", "Column", "Row", "Value", "Lower Fence", "Upper Fence"])?;
+    for (file_name, outlier) in outliers {
+        wtr.write_record(&[
+            file_name.clone(),
+            outlier.column.clone(),
+            outlier.row.to_string(),
+            outlier.value.to_string(),
+            format!("{:.*}", precision, outlier.lower_fence),
+            format!("{:.*}", precision, outlier.upper_fence),
+        ])?;
+    }
+
+    wtr.flush()?;
+    println!("{} outliers saved to {}", outliers.len(), output_path);
+    Ok(())
+}
+
+fn format_statistics(stat: &Statistics, precision: usize) -> String {
     // Using Unicode escape sequence for ± symbol
-    format!("{:.4} \u{00B1} {:.4} [{:.4} - {:.4}]",
-            stat.mean,
-            stat.std_dev,
-            stat.range.min,
-            stat.range.max
+    format!("{:.*} \u{00B1} {:.*} [{:.*} - {:.*}]",
+            precision, stat.mean,
+            precision, stat.std_dev,
+            precision, stat.range.min,
+            precision, stat.range.max
     )
 }
 
+const RESULTS_HEADER: [&str; 3] = ["Radius", "Column", "Statistics"];
+// --append adds the directory the rows came from, so runs can share one file
+const APPEND_HEADER: [&str; 4] = ["Radius", "Column", "Statistics", "Source Directory"];
+
+// The output's header, or None when there is no output yet
+fn read_existing_header(output_path: &str) -> Result<Option<Vec<String>>, Box<dyn Error>> {
+    if !Path::new(output_path).exists() {
+        return Ok(None);
+    }
+    let mut reader = Reader::from_path(output_path)?;
+    let header = reader.headers()?.iter()
+        .map(|h| h.trim_start_matches('\u{FEFF}').to_string())
+        .collect();
+    Ok(Some(header))
+}
+
+// (source, radius, column) of a results row
+type RowKey = (String, String, String);
+
+// The keys of every row already in an --append output
+fn existing_row_keys(output_path: &str) -> Result<HashSet<RowKey>, Box<dyn Error>> {
+    let mut reader = Reader::from_path(output_path)?;
+    let mut keys = HashSet::new();
+    for record in reader.records() {
+        let record = record?;
+        keys.insert((record[3].to_string(), record[0].to_string(), record[1].to_string()));
+    }
+    Ok(keys)
+}
+
+// Writes the sorted results to a fresh file, or with `append` adds them to an
+// existing output with a matching header (creating it if missing). `dedupe`
+// skips rows whose (source, radius, column) is already there, so re-running a
+// directory adds nothing. Returns how many rows were written.
+fn write_results(
+    results: Vec<(String, String, Statistics)>,
+    output_path: &str,
+    source_dir: &str,
+    append: bool,
+    dedupe: bool,
+    precision: usize,
+) -> Result<usize, Box<dyn Error>> {
+    let mut seen = HashSet::new();
+    let existing = if append { read_existing_header(output_path)? } else { None };
+    match &existing {
+        Some(header) if header.as_slice() != APPEND_HEADER => {
+            return Err(format!(
+                "cannot --append to {}: its header is {:?}, expected {:?}",
+                output_path, header, APPEND_HEADER
+            ).into());
+        }
+        Some(_) if dedupe => seen = existing_row_keys(output_path)?,
+        _ => {}
+    }
+
+    if existing.is_none() {
+        // Write BOM for UTF-8
+        std::fs::write(output_path, [0xEF, 0xBB, 0xBF])?;
+    }
+
+    // Create final writer
+    let mut final_wtr = WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(OpenOptions::new()
+            .append(true)
+            .open(output_path)?);
+
+    // Write headers
+    if existing.is_none() {
+        if append {
+            final_wtr.write_record(APPEND_HEADER)?;
+        } else {
+            final_wtr.write_record(RESULTS_HEADER)?;
+        }
+    }
+
+    // Write sorted results
+    let mut written = 0;
+    for (radius, column_name, stat) in results {
+        if dedupe && !seen.insert((source_dir.to_string(), radius.clone(), column_name.clone())) {
+            continue;
+        }
+        let mut record = vec![
+            radius,
+            column_name,
+            format_statistics(&stat, precision),
+        ];
+        if append {
+            record.push(source_dir.to_string());
+        }
+        final_wtr.write_record(&record)?;
+        written += 1;
+    }
+
+    final_wtr.flush()?;
+    Ok(written)
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     let dir_path = "/home/aricept094/mydata/sheets/combined_data/radial_results/casia_less_than_1/Pachymetry_Value";
     let pattern = format!("{}/*.csv", dir_path);
-
+    // --outliers <path>: also list the rows outside each column's Tukey fences
+    let args: Vec<String> = std::env::args().collect();
+    let outliers_path = args.iter()
+        .position(|a| a == "--outliers")
+        .and_then(|i| args.get(i + 1));
+    // --pooled <path>: size-weighted mean, SD and 95% CI per column across all files
+    let pooled_path = args.iter()
+        .position(|a| a == "--pooled")
+        .and_then(|i| args.get(i + 1));
+    // --append: add to an existing output instead of replacing it;
+    // --dedupe: skip rows this directory already added
+    let append = args.iter().any(|a| a == "--append");
+    let dedupe = args.iter().any(|a| a == "--dedupe");
+    let precision = precision_from_args(&args)?.unwrap_or(DEFAULT_PRECISION);
+    let pool_by = PoolBy::from_args(&args)?;
+    // --quantile-method: how Q1 and Q3 of the --outliers fences are taken
+    let quantile_method = QuantileMethod::from_args(&args)?;
+    let output_path = "analysis_results_casia_less_than_1_Pachymetry_Value.csv";
 
     // Collect paths first to parallelize
     let paths: Vec<_> = glob(&pattern)?.filter_map(Result::ok).collect();
 
     // Process each file in parallel using rayon and collect results
-    let results: Vec<Vec<(String, String, Statistics)>> = paths.par_iter()
+    type FileResults = (Option<(String, FileColumns)>, Vec<(String, Outlier)>);
+    let results: Vec<FileResults> = paths.par_iter()
         .map(|path| {
             let file_name = path.file_name().unwrap().to_string_lossy();
             let radius_label = get_radius_label(&file_name);
             println!("Processing file: {} ({})", file_name, radius_label);
 
-            match analyze_file(path) {
-                Ok(stats) => {
-                    stats.into_iter().map(|(column_name, stat)| (radius_label.clone(), column_name, stat)).collect()
-                },
+            match analyze_file(path, quantile_method) {
+                Ok(FileAnalysis { columns, outliers }) => (
+                    Some((radius_label, columns)),
+                    outliers.into_iter().map(|outlier| (file_name.to_string(), outlier)).collect(),
+                ),
                 Err(e) => {
                     eprintln!("Error processing file {}: {}", file_name, e);
-                    Vec::new() // Return empty Vec in case of error to continue processing other files
+                    (None, Vec::new()) // Return nothing in case of error to continue processing other files
                 },
             }
         })
         .collect();
 
     // Flatten the results from parallel processing
-    let mut all_results: Vec<(String, String, Statistics)> = results.into_iter().flatten().collect();
+    let (results, outliers): (Vec<_>, Vec<_>) = results.into_iter().unzip();
+    let mut all_results = pool_columns(results.into_iter().flatten().collect(), pool_by);
+
+    if let Some(outliers_path) = outliers_path {
+        let mut all_outliers: Vec<(String, Outlier)> = outliers.into_iter().flatten().collect();
+        all_outliers.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.column.cmp(&b.1.column)).then(a.1.row.cmp(&b.1.row)));
+        write_outliers(&all_outliers, outliers_path, precision)?;
+    }
+
+    if let Some(pooled_path) = pooled_path {
+        write_pooled(&all_results, pooled_path, precision)?;
+    }
 
 
     // Sort results
-    let radius_order = vec![
+    let radius_order = [
         "radius 0.5mm",
         "radius 1mm",
         "radius 1.5mm",
@@ -135,31 +495,133 @@ fn main() -> Result<(), Box<dyn Error>> {
             .then_with(|| a.1.cmp(&b.1))
     });
 
-    // Write BOM for UTF-8
-    std::fs::write("analysis_results_casia_less_than_1_Pachymetry_Value.csv", [0xEF, 0xBB, 0xBF])?;
+    let written = write_results(all_results, output_path, dir_path, append, dedupe, precision)?;
+    println!("Analysis complete. {} rows saved to {}", written, output_path);
+    Ok(())
+}
 
-    // Create final writer
-    let mut final_wtr = WriterBuilder::new()
-        .has_headers(false)
-        .from_writer(OpenOptions::new()
-            .write(true)
-            .append(true)
-            .open("analysis_results_casia_less_than_1_Pachymetry_Value.csv")?);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // Write headers
-    final_wtr.write_record(&["Radius", "Column", "Statistics"])?;
+    #[test]
+    fn test_single_outlier_reported_with_fences() {
+        let values: Vec<(usize, f64)> = [10.0, 12.0, 11.0, 13.0, 12.0, 11.0, 10.0, 95.0]
+            .iter().enumerate().map(|(i, &v)| (i + 1, v)).collect();
 
-    // Write sorted results
-    for (radius, column_name, stat) in all_results {
-        let record = vec![
-            radius,
-            column_name,
-            format_statistics(&stat),
+        let outliers = find_outliers("dc_component", &values, QuantileMethod::Linear);
+
+        // Sorted: 10 10 11 11 12 12 13 95 -> Q1 = 10.75, Q3 = 12.25, IQR = 1.5
+        assert_eq!(outliers.len(), 1);
+        assert_eq!(outliers[0].column, "dc_component");
+        assert_eq!((outliers[0].row, outliers[0].value), (8, 95.0));
+        assert!((outliers[0].lower_fence - 8.5).abs() < 1e-12);
+        assert!((outliers[0].upper_fence - 14.5).abs() < 1e-12);
+
+        // --quantile-method midpoint: Q1 = 10.5, Q3 = 12.5, IQR = 2
+        let midpoint = find_outliers("dc_component", &values, QuantileMethod::Midpoint);
+        assert_eq!((midpoint[0].lower_fence, midpoint[0].upper_fence), (7.5, 15.5));
+
+        let constant: Vec<(usize, f64)> = (1..=10).map(|i| (i, if i == 10 { 7.0 } else { 3.0 })).collect();
+        assert!(find_outliers("r2_score", &constant, QuantileMethod::Linear).is_empty());
+    }
+
+    #[test]
+    fn test_pooled_mean_matches_combined_data() {
+        let small = [1.0, 2.0, 3.0];
+        let large = [10.0, 20.0, 30.0, 40.0, 50.0, 60.0, 70.0];
+        let combined: Vec<f64> = small.iter().chain(&large).copied().collect();
+
+        let stats_small = calculate_statistics(&small).unwrap();
+        let stats_large = calculate_statistics(&large).unwrap();
+        let pooled = pool_statistics(&[&stats_small, &stats_large]).unwrap();
+        let expected = calculate_statistics(&combined).unwrap();
+
+        assert_eq!(pooled.n, 10);
+        assert!((pooled.mean - expected.mean).abs() < 1e-12);
+        assert!((pooled.std_dev - expected.std_dev).abs() < 1e-9);
+        // The unweighted mean of the two file means would be 21
+        assert!((pooled.mean - 28.6).abs() < 1e-12);
+        assert!(pooled.ci_lower < pooled.mean && pooled.mean < pooled.ci_upper);
+        assert!(((pooled.ci_upper - pooled.mean) - (pooled.mean - pooled.ci_lower)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_append_with_dedupe_adds_each_directory_once() {
+        let output = std::env::temp_dir().join(format!("descriptive_append_{}.csv", std::process::id()));
+        let output = output.to_str().unwrap();
+        let run = || vec![
+            ("radius 1mm".to_string(), "dc_component".to_string(), calculate_statistics(&[1.0, 2.0, 3.0]).unwrap()),
+            ("radius 2mm".to_string(), "dc_component".to_string(), calculate_statistics(&[4.0, 5.0]).unwrap()),
         ];
-        final_wtr.write_record(&record)?;
+
+        assert_eq!(write_results(run(), output, "casia1-2", true, true, DEFAULT_PRECISION).unwrap(), 2);
+        assert_eq!(write_results(run(), output, "casia1-2", true, true, DEFAULT_PRECISION).unwrap(), 0);
+        assert_eq!(write_results(run(), output, "casia2-4", true, true, DEFAULT_PRECISION).unwrap(), 2);
+        let deduped = std::fs::read_to_string(output).unwrap();
+        assert_eq!(write_results(run(), output, "casia2-4", true, false, DEFAULT_PRECISION).unwrap(), 2);
+        let duplicated = std::fs::read_to_string(output).unwrap();
+
+        // A fresh (non-append) output has no source column to append to
+        write_results(run(), output, "casia1-2", false, false, DEFAULT_PRECISION).unwrap();
+        let refused = write_results(run(), output, "casia1-2", true, true, DEFAULT_PRECISION);
+        std::fs::remove_file(output).ok();
+
+        let lines: Vec<&str> = deduped.lines().collect();
+        assert_eq!(lines[0], "\u{FEFF}Radius,Column,Statistics,Source Directory");
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines.iter().filter(|l| l.ends_with(",casia1-2")).count(), 2);
+        assert_eq!(lines.iter().filter(|l| l.ends_with(",casia2-4")).count(), 2);
+        assert_eq!(duplicated.lines().count(), 7);
+        assert!(refused.is_err());
     }
 
-    final_wtr.flush()?;
-    println!("Analysis complete. Results saved to analysis_results_Pachymetry_Value.csv");
-    Ok(())
-}
\ No newline at end of file
+    #[test]
+    fn test_precision_sets_decimal_places() {
+        let stat = calculate_statistics(&[1.0, 2.0, 4.0]).unwrap();
+        assert_eq!(format_statistics(&stat, DEFAULT_PRECISION), "2.3333 \u{00B1} 1.5275 [1.0000 - 4.0000]");
+        assert_eq!(format_statistics(&stat, 2), "2.33 \u{00B1} 1.53 [1.00 - 4.00]");
+
+        let args: Vec<String> = ["descriptive_multi", "--precision", "2"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(precision_from_args(&args), Ok(Some(2)));
+        assert_eq!(precision_from_args(&args[..1]), Ok(None));
+        assert!(precision_from_args(&["--precision".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_pool_by_radius_combines_files_with_same_label() {
+        let dir = std::env::temp_dir();
+        let paths = [
+            dir.join(format!("descriptive_pool_{}_radial_8_a.csv", std::process::id())),
+            dir.join(format!("descriptive_pool_{}_radial_8_b.csv", std::process::id())),
+        ];
+        std::fs::write(&paths[0], "dc_component,component_1_amplitude,component_2_amplitude,higher_order_amplitude_sum,r2_score\n1,,,,\n2,,,,\n").unwrap();
+        std::fs::write(&paths[1], "dc_component,component_1_amplitude,component_2_amplitude,higher_order_amplitude_sum,r2_score\n3,,,,\n6,,,,\n").unwrap();
+
+        let files: Vec<_> = paths.iter()
+            .map(|path| {
+                let label = get_radius_label(&path.file_name().unwrap().to_string_lossy());
+                (label, analyze_file(path, QuantileMethod::Linear).unwrap().columns)
+            })
+            .collect();
+        for path in &paths {
+            std::fs::remove_file(path).ok();
+        }
+
+        let per_file = pool_columns(files.iter().map(|(l, c)| (l.clone(), c.clone())).collect(), PoolBy::File);
+        assert_eq!(per_file.len(), 2);
+
+        let pooled = pool_columns(files, PoolBy::Radius);
+        assert_eq!(pooled.len(), 1);
+        let (label, column, stat) = &pooled[0];
+        let expected = calculate_statistics(&[1.0, 2.0, 3.0, 6.0]).unwrap();
+        assert_eq!((label.as_str(), column.as_str()), ("radius 1mm", "dc_component"));
+        assert_eq!(stat.n, 4);
+        assert!((stat.mean - expected.mean).abs() < 1e-12);
+        assert!((stat.std_dev - expected.std_dev).abs() < 1e-12);
+        assert_eq!((stat.range.min, stat.range.max), (1.0, 6.0));
+
+        assert_eq!(PoolBy::parse("all"), Ok(PoolBy::All));
+        assert!(PoolBy::parse("patient").is_err());
+    }
+}
@@ -3,11 +3,37 @@ use std::error::Error;
 use std::fs::{self, File};
 use std::io::{BufRead, BufReader, Write};
 use std::path::Path;
+use csv::{ReaderBuilder, WriterBuilder};
+
+// --policy {rename,drop,coalesce}: what happens to the 2nd, 3rd, ... column
+// with the same heading
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum DuplicatePolicy {
+    // Keep every column, renamed to name_2, name_3, ...
+    #[default]
+    Rename,
+    // Keep only the first column of each name
+    Drop,
+    // One column per name holding, row by row, the first non-empty value
+    // among the columns of that name
+    Coalesce,
+}
+
+impl DuplicatePolicy {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "rename" => Ok(DuplicatePolicy::Rename),
+            "drop" => Ok(DuplicatePolicy::Drop),
+            "coalesce" => Ok(DuplicatePolicy::Coalesce),
+            other => Err(format!("Unknown --policy '{}' (expected rename, drop or coalesce)", other)),
+        }
+    }
+}
 
 fn rename_duplicate_headings(filepath: &str) -> Result<(), Box<dyn Error>> {
     // 1. Read the first line (headings) from the file.
     let path = Path::new(filepath);
-    let file = File::open(&path)?;
+    let file = File::open(path)?;
     let reader = BufReader::new(file);
     let mut lines = reader.lines();
 
@@ -57,7 +83,73 @@ fn rename_duplicate_headings(filepath: &str) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+// Drop and coalesce change the data rows too, so unlike renaming they need
+// every row parsed rather than just the first line
+fn merge_duplicate_columns(filepath: &str, policy: DuplicatePolicy) -> Result<(), Box<dyn Error>> {
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(filepath)?;
+    let mut records = reader.records();
+
+    let headings: Vec<String> = match records.next() {
+        Some(record) => record?.iter().map(|s| s.trim().to_string()).collect(),
+        None => return Ok(()), // Empty file, nothing to do
+    };
+
+    // The column positions of each distinct heading, in order of first appearance
+    let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+    for (index, heading) in headings.iter().enumerate() {
+        match groups.iter_mut().find(|(name, _)| name == heading) {
+            Some((_, indices)) => indices.push(index),
+            None => groups.push((heading.clone(), vec![index])),
+        }
+    }
+    let duplicate_count = headings.len() - groups.len();
+    println!("Number of duplicate headings detected in {}: {}", filepath, duplicate_count);
+
+    let temp_filepath = format!("{}.tmp", filepath);
+    let mut writer = WriterBuilder::new().flexible(true).from_path(&temp_filepath)?;
+    writer.write_record(groups.iter().map(|(name, _)| name))?;
+
+    for record in records {
+        let record = record?;
+        let row = groups.iter().map(|(_, indices)| {
+            let mut values = indices.iter().map(|&i| record.get(i).unwrap_or(""));
+            match policy {
+                DuplicatePolicy::Coalesce => values.find(|v| !v.trim().is_empty()).unwrap_or(""),
+                _ => values.next().unwrap_or(""),
+            }
+        });
+        writer.write_record(row)?;
+    }
+    writer.flush()?;
+    drop(writer);
+
+    fs::rename(temp_filepath, filepath)?;
+    Ok(())
+}
+
+fn resolve_duplicate_headings(filepath: &str, policy: DuplicatePolicy) -> Result<(), Box<dyn Error>> {
+    match policy {
+        DuplicatePolicy::Rename => rename_duplicate_headings(filepath),
+        DuplicatePolicy::Drop | DuplicatePolicy::Coalesce => merge_duplicate_columns(filepath, policy),
+    }
+}
+
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let policy = match args.iter().position(|a| a == "--policy") {
+        Some(i) => match DuplicatePolicy::parse(args.get(i + 1).map(String::as_str).unwrap_or("")) {
+            Ok(policy) => policy,
+            Err(err) => {
+                eprintln!("{}", err);
+                return;
+            }
+        },
+        None => DuplicatePolicy::Rename,
+    };
+
     let filepaths = [
         "demographic.csv",
         "IUIO.csv",
@@ -74,10 +166,41 @@ fn main() {
     for filepath in filepaths.iter() {
         let full_filepath = format!("{}{}", base_path, filepath); // Construct the full file path
 
-        if let Err(err) = rename_duplicate_headings(&full_filepath) {
+        if let Err(err) = resolve_duplicate_headings(&full_filepath, policy) {
             eprintln!("Error processing file {}: {}", full_filepath, err);
         } else {
             println!("Successfully processed file: {}", full_filepath);
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_policy(policy: DuplicatePolicy) -> String {
+        let path = std::env::temp_dir().join(format!("duplicate_headings_{:?}_{}.csv", policy, std::process::id()));
+        fs::write(&path, "id,age,city,age\n1,30,Tehran,\n2,,Shiraz,41\n3,35,Tabriz,36\n").unwrap();
+        resolve_duplicate_headings(path.to_str().unwrap(), policy).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).ok();
+        content
+    }
+
+    #[test]
+    fn test_rename_policy_numbers_later_duplicates() {
+        assert_eq!(run_policy(DuplicatePolicy::Rename), "id,age,city,age_2\n1,30,Tehran,\n2,,Shiraz,41\n3,35,Tabriz,36\n");
+    }
+
+    #[test]
+    fn test_drop_policy_keeps_first_column() {
+        assert_eq!(run_policy(DuplicatePolicy::Drop), "id,age,city\n1,30,Tehran\n2,,Shiraz\n3,35,Tabriz\n");
+    }
+
+    #[test]
+    fn test_coalesce_policy_takes_first_non_empty_value() {
+        assert_eq!(run_policy(DuplicatePolicy::Coalesce), "id,age,city\n1,30,Tehran\n2,41,Shiraz\n3,35,Tabriz\n");
+        assert_eq!(DuplicatePolicy::parse("coalesce"), Ok(DuplicatePolicy::Coalesce));
+        assert!(DuplicatePolicy::parse("merge").is_err());
+    }
+}
@@ -3,7 +3,7 @@ use std::fs::File;
 use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::path::Path;
 
-fn add_IUIO_to_headings(file_path: &str) -> Result<(), Box<dyn Error>> {
+fn add_iuio_to_headings(file_path: &str) -> Result<(), Box<dyn Error>> {
     // Check if the file exists
     if !Path::new(file_path).exists() {
         return Err(format!("File not found: {}", file_path).into());
@@ -42,7 +42,7 @@ fn add_IUIO_to_headings(file_path: &str) -> Result<(), Box<dyn Error>> {
 
 fn main() {
     let file_path = "/home/aricept094/mydata/PCO/IUIO.csv";
-    match add_IUIO_to_headings(file_path) {
+    match add_iuio_to_headings(file_path) {
         Ok(_) => println!("Successfully added 'IUIO' to headings."),
         Err(e) => eprintln!("Error: {}", e),
     }
@@ -1,15 +1,79 @@
-use calamine::{Reader, open_workbook, Xlsx, DataType};
+use calamine::{Reader, open_workbook, Xlsx, Data, DataType, Range};
 use std::collections::HashMap;
 use std::time::Instant;
 use std::fs::File;
 use std::io::Write;
 
+use shared::layout::SheetLayout;
+
+// excel_transform drops columns that are at least this percent empty
+const DEFAULT_DROP_THRESHOLD: f64 = 70.0;
+
 #[derive(Debug)]
 struct EmptyAnalysis {
     empty_percentages: Vec<(String, f64)>,
     total_cells: usize,
 }
 
+// Overall sparsity of the columns (or rows) of one analysis
+#[derive(Debug, PartialEq)]
+struct EmptySummary {
+    min: f64,
+    max: f64,
+    mean: f64,
+    median: f64,
+    // How many are at least `threshold` percent empty, i.e. what
+    // excel_transform would drop at that threshold
+    at_or_above_threshold: usize,
+    threshold: f64,
+}
+
+impl EmptyAnalysis {
+    fn summary(&self, threshold: f64) -> EmptySummary {
+        let mut sorted: Vec<f64> = self.empty_percentages.iter().map(|(_, p)| *p).collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let n = sorted.len();
+        let median = match n {
+            0 => 0.0,
+            _ if n % 2 == 1 => sorted[n / 2],
+            _ => (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0,
+        };
+
+        EmptySummary {
+            min: sorted.first().copied().unwrap_or(0.0),
+            max: sorted.last().copied().unwrap_or(0.0),
+            mean: if n == 0 { 0.0 } else { sorted.iter().sum::<f64>() / n as f64 },
+            median,
+            at_or_above_threshold: sorted.iter().filter(|&&p| p >= threshold).count(),
+            threshold,
+        }
+    }
+}
+
+fn print_summary(label: &str, total: usize, summary: &EmptySummary) {
+    println!(
+        "{}: min {:.2}%, max {:.2}%, mean {:.2}%, median {:.2}% empty; {} of {} would be dropped at {:.2}%",
+        label, summary.min, summary.max, summary.mean, summary.median,
+        summary.at_or_above_threshold, total, summary.threshold
+    );
+}
+
+// Overall totals only, for --count-only
+#[derive(Debug, PartialEq)]
+struct EmptyCount {
+    total_cells: usize,
+    empty_cells: usize,
+}
+
+impl EmptyCount {
+    fn empty_percentage(&self) -> f64 {
+        if self.total_cells == 0 {
+            return 0.0;
+        }
+        self.empty_cells as f64 / self.total_cells as f64 * 100.0
+    }
+}
+
 fn save_to_csv(analysis: &EmptyAnalysis, filename: &str) -> std::io::Result<()> {
     let mut file = File::create(filename)?;
 
@@ -38,23 +102,60 @@ fn column_name(col_idx: usize) -> String {
     col_str
 }
 
-fn analyze_excel(filepath: &str) -> Result<(EmptyAnalysis, EmptyAnalysis), Box<dyn std::error::Error>> {
+fn analyze_excel(filepath: &str, layout: SheetLayout) -> Result<(EmptyAnalysis, EmptyAnalysis), Box<dyn std::error::Error>> {
     let timer = Instant::now();
     println!("Analyzing file: {}", filepath);
 
+    let sheet = open_first_sheet(filepath)?;
+    let analysis = analyze_range(&sheet, layout)?;
+
+    println!("Analysis completed in {:?}", timer.elapsed());
+
+    Ok(analysis)
+}
+
+fn open_first_sheet(filepath: &str) -> Result<Range<Data>, Box<dyn std::error::Error>> {
     let mut workbook: Xlsx<_> = open_workbook(filepath)?;
     let sheet = workbook.worksheet_range_at(0)
         .ok_or("No sheet found")??;
+    Ok(sheet)
+}
+
+// Same data area as analyze_range, but a single counter instead of the
+// per-row/per-column vectors, labels and sorting
+fn count_empty(sheet: &Range<Data>, layout: SheetLayout) -> Result<EmptyCount, Box<dyn std::error::Error>> {
+    let data_start = layout.data_start().min(sheet.height());
+    let height = sheet.height() - data_start;
+    let width = sheet.width();
+
+    if height == 0 || width == 0 {
+        return Err("No data rows left after skipping header/metadata rows".into());
+    }
+
+    let empty_cells = sheet.rows()
+        .skip(data_start)
+        .map(|row| row.iter().filter(|cell| cell.is_empty()).count())
+        .sum();
 
-    let height = sheet.height();
+    Ok(EmptyCount { total_cells: width * height, empty_cells })
+}
+
+fn analyze_range(sheet: &Range<Data>, layout: SheetLayout) -> Result<(EmptyAnalysis, EmptyAnalysis), Box<dyn std::error::Error>> {
+    // Only rows below the header (or below the skipped rows) count as data
+    let data_start = layout.data_start().min(sheet.height());
+    let height = sheet.height() - data_start;
     let width = sheet.width();
 
+    if height == 0 || width == 0 {
+        return Err("No data rows left after skipping header/metadata rows".into());
+    }
+
     // Initialize counters
     let mut column_empty = vec![0; width];
     let mut row_empty = vec![0; height];
 
     // Count empty cells
-    for (row_idx, row) in sheet.rows().enumerate() {
+    for (row_idx, row) in sheet.rows().skip(data_start).enumerate() {
         for (col_idx, cell) in row.iter().enumerate() {
             if cell.is_empty() {
                 column_empty[col_idx] += 1;
@@ -75,7 +176,7 @@ fn analyze_excel(filepath: &str) -> Result<(EmptyAnalysis, EmptyAnalysis), Box<d
     let mut row_percentages: Vec<(String, f64)> = row_empty.iter().enumerate()
         .map(|(idx, &empty)| {
             let percentage = (empty as f64 / width as f64) * 100.0;
-            (format!("Row {}", idx + 1), percentage)
+            (format!("Row {}", data_start + idx + 1), percentage)
         })
         .collect();
 
@@ -85,8 +186,6 @@ fn analyze_excel(filepath: &str) -> Result<(EmptyAnalysis, EmptyAnalysis), Box<d
 
     let total_cells = width * height;
 
-    println!("Analysis completed in {:?}", timer.elapsed());
-
     Ok((
         EmptyAnalysis {
             empty_percentages: column_percentages,
@@ -105,9 +204,35 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     ]);
 
+    let args: Vec<String> = std::env::args().collect();
+    // --skip-rows / --header-row: title/metadata rows above the real header
+    let layout = SheetLayout::from_args(&args)?;
+
+    // --count-only: just the total and overall empty percentage, no CSVs
+    let count_only = args.iter().any(|a| a == "--count-only");
+    // --threshold <percent>: drop threshold the summary counts against
+    let threshold = match args.iter().position(|a| a == "--threshold") {
+        None => DEFAULT_DROP_THRESHOLD,
+        Some(i) => {
+            let value = args.get(i + 1).map(String::as_str).unwrap_or("");
+            value.parse::<f64>()
+                .ok()
+                .filter(|t| (0.0..=100.0).contains(t))
+                .ok_or_else(|| format!("Invalid --threshold '{}' (expected 0-100)", value))?
+        }
+    };
+
     for (file_name, file_path) in files {
         println!("\nAnalyzing {}", file_name);
-        match analyze_excel(file_path) {
+        if count_only {
+            match open_first_sheet(file_path).and_then(|sheet| count_empty(&sheet, layout)) {
+                Ok(count) => println!("Total cells: {}, empty: {} ({:.2}%)",
+                    count.total_cells, count.empty_cells, count.empty_percentage()),
+                Err(e) => println!("Error analyzing {}: {}", file_name, e),
+            }
+            continue;
+        }
+        match analyze_excel(file_path, layout) {
             Ok((column_analysis, row_analysis)) => {
                 // Create filenames for CSV output
                 let column_filename = format!("/home/aricept094/mydata/endometriosis/{}_columns_analysis.csv", file_name);
@@ -121,10 +246,88 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 println!("- {}", column_filename);
                 println!("- {}", row_filename);
                 println!("Total cells analyzed: {}", column_analysis.total_cells);
+                print_summary("Columns", column_analysis.empty_percentages.len(), &column_analysis.summary(threshold));
+                print_summary("Rows", row_analysis.empty_percentages.len(), &row_analysis.summary(threshold));
             }
             Err(e) => println!("Error analyzing {}: {}", file_name, e),
         }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two junk rows, then a header, then two data rows
+    fn sheet_with_title_rows() -> Range<Data> {
+        let mut range = Range::new((0, 0), (4, 1));
+        range.set_value((0, 0), Data::String("Exported report".to_string()));
+        range.set_value((1, 0), Data::String("Generated 2024-01-01".to_string()));
+        range.set_value((2, 0), Data::String("ID".to_string()));
+        range.set_value((2, 1), Data::String("Age".to_string()));
+        range.set_value((3, 0), Data::Int(1));
+        range.set_value((4, 0), Data::Int(2));
+        range.set_value((4, 1), Data::Int(30));
+        range
+    }
+
+    #[test]
+    fn test_header_row_excludes_metadata_from_denominator() {
+        let layout = SheetLayout { skip_rows: 2, header_row: Some(0) };
+        let (columns, rows) = analyze_range(&sheet_with_title_rows(), layout).unwrap();
+
+        let age = columns.empty_percentages.iter().find(|(name, _)| name == "B").unwrap();
+        assert_eq!(age.1, 50.0);
+        assert_eq!(columns.total_cells, 4);
+        // Row labels keep their position in the sheet
+        assert_eq!(rows.empty_percentages[0], ("Row 4".to_string(), 50.0));
+
+        let (columns, _) = analyze_range(&sheet_with_title_rows(), SheetLayout::default()).unwrap();
+        let age = columns.empty_percentages.iter().find(|(name, _)| name == "B").unwrap();
+        assert_eq!(age.1, 60.0);
+    }
+
+    #[test]
+    fn test_count_only_matches_full_analysis() {
+        for layout in [SheetLayout::default(), SheetLayout { skip_rows: 2, header_row: Some(0) }] {
+            let sheet = sheet_with_title_rows();
+            let (columns, _) = analyze_range(&sheet, layout).unwrap();
+            let count = count_empty(&sheet, layout).unwrap();
+
+            // Every column has the same number of cells, so the overall
+            // fraction is the mean of the column fractions
+            let overall = columns.empty_percentages.iter().map(|(_, p)| p).sum::<f64>()
+                / columns.empty_percentages.len() as f64;
+            assert_eq!(count.total_cells, columns.total_cells);
+            assert!((count.empty_percentage() - overall).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_summary_matches_hand_count() {
+        // Columns A..C of a 4-row sheet are 0%, 50% and 100% empty;
+        // the rows are 33.3%, 33.3%, 66.7% and 66.7% empty
+        let mut range = Range::new((0, 0), (3, 2));
+        for row in 0..4 {
+            range.set_value((row, 0), Data::Int(row as i64));
+        }
+        range.set_value((0, 1), Data::Int(1));
+        range.set_value((1, 1), Data::Int(1));
+        let (columns, rows) = analyze_range(&range, SheetLayout::default()).unwrap();
+
+        let summary = columns.summary(DEFAULT_DROP_THRESHOLD);
+        assert_eq!((summary.min, summary.max, summary.mean, summary.median), (0.0, 100.0, 50.0, 50.0));
+        assert_eq!(summary.at_or_above_threshold, 1);
+        assert_eq!(columns.summary(50.0).at_or_above_threshold, 2);
+
+        let summary = rows.summary(DEFAULT_DROP_THRESHOLD);
+        assert!((summary.min - 100.0 / 3.0).abs() < 1e-9);
+        assert!((summary.max - 200.0 / 3.0).abs() < 1e-9);
+        assert!((summary.mean - 50.0).abs() < 1e-9);
+        assert!((summary.median - 50.0).abs() < 1e-9);
+        assert_eq!(summary.at_or_above_threshold, 0);
+        assert_eq!(rows.summary(60.0).at_or_above_threshold, 2);
+    }
+}
@@ -1,11 +1,14 @@
 use std::collections::HashMap;
 use std::error::Error;
 use std::fs::File;
-use std::io::{BufReader, Read, Write};
-use csv::{Reader, Writer};
+use std::io::{BufReader, Write};
+use csv::Writer;
 use encoding_rs::UTF_8;
 use encoding_rs_io::DecodeReaderBytesBuilder;
-use itertools::Itertools;
+
+use shared::cells::normalize_cell;
+
+const DEFAULT_MIN_SIMILARITY: f64 = 95.0;
 
 #[derive(Debug)]
 struct Column {
@@ -14,23 +17,64 @@ struct Column {
     values: Vec<String>,
 }
 
-fn calculate_similarity(vec1: &[String], vec2: &[String]) -> f64 {
-    let len = vec1.len().min(vec2.len());
-    if len == 0 {
-        return 0.0;
+#[derive(Debug, Clone, Copy, Default)]
+struct CompareOptions {
+    // --ignore-empty: don't let empty==empty inflate similarity of sparse columns
+    ignore_empty: bool,
+    // --numeric-tolerance <eps>: cells that both parse as numbers match when
+    // within eps of each other, so "1.0" and "1.00" are the same value
+    numeric_tolerance: Option<f64>,
+}
+
+fn cells_match(a: &str, b: &str, numeric_tolerance: Option<f64>) -> bool {
+    // Identical text always matches, even "NaN" or "inf", which parse but
+    // never come within eps of themselves
+    if a == b {
+        return true;
+    }
+    if let Some(eps) = numeric_tolerance {
+        if let (Ok(x), Ok(y)) = (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+            return (x - y).abs() <= eps;
+        }
     }
+    false
+}
 
-    let matching = vec1.iter()
+// Returns the match percentage and how many positions were compared. With
+// ignore_empty, positions where either cell is blank count in neither.
+fn calculate_similarity(vec1: &[String], vec2: &[String], options: &CompareOptions) -> (f64, usize) {
+    let pairs = vec1.iter()
         .zip(vec2.iter())
-        .filter(|(a, b)| a == b)
-        .count();
+        .filter(|(a, b)| !options.ignore_empty || (!a.trim().is_empty() && !b.trim().is_empty()));
+
+    let mut compared = 0;
+    let mut matching = 0;
+    for (a, b) in pairs {
+        compared += 1;
+        if cells_match(a, b, options.numeric_tolerance) {
+            matching += 1;
+        }
+    }
+
+    if compared == 0 {
+        return (0.0, 0);
+    }
 
-    (matching as f64 / len as f64) * 100.0
+    ((matching as f64 / compared as f64) * 100.0, compared)
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
+#[derive(Debug)]
+struct ColumnMatch {
+    column: String,
+    best_match: Option<String>,
+    similarity: f64,
+    compared: usize,
+}
+
+// With normalize_whitespace, every cell goes through normalize_cell as it is read
+fn read_columns(path: &str, normalize_whitespace: bool) -> Result<Vec<Column>, Box<dyn Error>> {
     // Open the file with UTF-8 BOM detection
-    let file = File::open("/home/aricept094/mydata/PCO/sorted_columns_cleaned_output_good_targets.csv")?;
+    let file = File::open(path)?;
     let decoder = DecodeReaderBytesBuilder::new()
         .encoding(Some(UTF_8))
         .bom_sniffing(true)
@@ -59,22 +103,204 @@ fn main() -> Result<(), Box<dyn Error>> {
         let record = result?;
         for (idx, value) in record.iter().enumerate() {
             if idx < columns.len() {
-                columns[idx].values.push(value.to_string());
+                let value = if normalize_whitespace { normalize_cell(value) } else { value.to_string() };
+                columns[idx].values.push(value);
             }
         }
     }
 
+    Ok(columns)
+}
+
+// For each non-ID column of A, the most similar column of B after lining up
+// B's rows with A's by the shared ID column (IDs missing from B read as empty)
+fn best_matches(
+    columns_a: &[Column],
+    columns_b: &[Column],
+    id_column: &str,
+    options: &CompareOptions,
+) -> Result<Vec<ColumnMatch>, Box<dyn Error>> {
+    let ids_a = columns_a.iter()
+        .find(|c| c.header == id_column)
+        .ok_or_else(|| format!("ID column '{}' not found in the first file", id_column))?;
+    let ids_b = columns_b.iter()
+        .find(|c| c.header == id_column)
+        .ok_or_else(|| format!("ID column '{}' not found in the compare file", id_column))?;
+
+    let mut row_of_id: HashMap<&str, usize> = HashMap::new();
+    for (row, id) in ids_b.values.iter().enumerate() {
+        row_of_id.entry(id.as_str()).or_insert(row);
+    }
+
+    let aligned_b: Vec<(&str, Vec<String>)> = columns_b.iter()
+        .filter(|c| c.header != id_column)
+        .map(|c| {
+            let values = ids_a.values.iter()
+                .map(|id| row_of_id.get(id.as_str())
+                    .and_then(|&row| c.values.get(row))
+                    .cloned()
+                    .unwrap_or_default())
+                .collect();
+            (c.header.as_str(), values)
+        })
+        .collect();
+
+    let matches = columns_a.iter()
+        .filter(|c| c.header != id_column)
+        .map(|column| {
+            let mut best = ColumnMatch {
+                column: column.header.clone(),
+                best_match: None,
+                similarity: 0.0,
+                compared: 0,
+            };
+            for (header_b, values_b) in &aligned_b {
+                let (similarity, compared) = calculate_similarity(&column.values, values_b, options);
+                if best.best_match.is_none() || similarity > best.similarity {
+                    best.best_match = Some(header_b.to_string());
+                    best.similarity = similarity;
+                    best.compared = compared;
+                }
+            }
+            best
+        })
+        .collect();
+
+    Ok(matches)
+}
+
+fn find_root(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+// --summary-only: columns linked by similarity >= min_similarity, directly or
+// through other columns, as groups of positions in `columns`. Each group is in
+// original column order, so its first member is the suggested representative;
+// columns like no other come back as groups of one.
+fn duplicate_groups(columns: &[Column], options: &CompareOptions, min_similarity: f64) -> Vec<Vec<usize>> {
+    let mut parent: Vec<usize> = (0..columns.len()).collect();
+    for i in 0..columns.len() {
+        for j in (i + 1)..columns.len() {
+            let (similarity, compared) = calculate_similarity(&columns[i].values, &columns[j].values, options);
+            if compared > 0 && similarity >= min_similarity {
+                let (root_i, root_j) = (find_root(&mut parent, i), find_root(&mut parent, j));
+                parent[root_i.max(root_j)] = root_i.min(root_j);
+            }
+        }
+    }
+
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut group_of_root: HashMap<usize, usize> = HashMap::new();
+    let mut order: Vec<usize> = (0..columns.len()).collect();
+    order.sort_by_key(|&i| columns[i].original_index);
+    for i in order {
+        let root = find_root(&mut parent, i);
+        let group = *group_of_root.entry(root).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push(i);
+    }
+    groups
+}
+
+fn print_duplicate_groups(columns: &[Column], groups: &[Vec<usize>], min_similarity: f64) {
+    let duplicates: Vec<&Vec<usize>> = groups.iter().filter(|group| group.len() > 1).collect();
+    println!("Likely duplicate groups (similarity >= {}%): {}", min_similarity, duplicates.len());
+    for (n, group) in duplicates.iter().enumerate() {
+        let representative = &columns[group[0]];
+        println!("\nGroup {} ({} columns), keep '{}' (index {}):", n + 1, group.len(), representative.header, representative.original_index);
+        for &i in group.iter() {
+            println!("  {} (index {})", columns[i].header, columns[i].original_index);
+        }
+    }
+    println!("\n{} columns have no likely duplicate", groups.len() - duplicates.len());
+}
+
+fn write_best_matches(matches: &[ColumnMatch], output_path: &str) -> Result<(), Box<dyn Error>> {
+    let mut file = File::create(output_path)?;
+    file.write_all(&[0xEF, 0xBB, 0xBF])?;
+
+    let mut writer = Writer::from_writer(file);
+    writer.write_record(["Column", "Best Match", "Similarity %", "Compared Cells"])?;
+
+    for m in matches {
+        writer.write_record([
+            &m.column,
+            m.best_match.as_deref().unwrap_or(""),
+            &format!("{:.2}", m.similarity),
+            &m.compared.to_string(),
+        ])?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    let value_of = |flag: &str| args.iter()
+        .position(|a| a == flag)
+        .and_then(|i| args.get(i + 1))
+        .map(String::as_str);
+
+    let options = CompareOptions {
+        ignore_empty: args.iter().any(|a| a == "--ignore-empty"),
+        numeric_tolerance: value_of("--numeric-tolerance")
+            .map(|eps| eps.parse::<f64>()
+                .ok()
+                .filter(|eps| *eps >= 0.0)
+                .ok_or_else(|| format!("Invalid --numeric-tolerance '{}'", eps)))
+            .transpose()?,
+    };
+    // --compare-file <other.csv>: match columns across two files instead of within one
+    let compare_file = value_of("--compare-file");
+    let id_column = value_of("--id-column").unwrap_or("کد ملی");
+    // --normalize-whitespace: "A " and " A" compare equal to "A"
+    let normalize_whitespace = args.iter().any(|a| a == "--normalize-whitespace");
+    // --summary-only: print groups of likely duplicate columns instead of every pair;
+    // --min-similarity <pct> links two columns into a group (default 95)
+    let summary_only = args.iter().any(|a| a == "--summary-only");
+    let min_similarity = value_of("--min-similarity")
+        .map(|pct| pct.parse::<f64>()
+            .ok()
+            .filter(|pct| (0.0..=100.0).contains(pct))
+            .ok_or_else(|| format!("Invalid --min-similarity '{}' (expected 0-100)", pct)))
+        .transpose()?
+        .unwrap_or(DEFAULT_MIN_SIMILARITY);
+
+    let columns = read_columns("/home/aricept094/mydata/PCO/sorted_columns_cleaned_output_good_targets.csv", normalize_whitespace)?;
+
+    if let Some(other_path) = compare_file {
+        let other_columns = read_columns(other_path, normalize_whitespace)?;
+        let matches = best_matches(&columns, &other_columns, id_column, &options)?;
+        write_best_matches(&matches, "column_best_matches.csv")?;
+        println!("Cross-file analysis complete. Results saved to column_best_matches.csv");
+        return Ok(());
+    }
+
+    if summary_only {
+        let groups = duplicate_groups(&columns, &options, min_similarity);
+        print_duplicate_groups(&columns, &groups, min_similarity);
+        return Ok(());
+    }
+
     // Calculate similarities
     let mut similarities = Vec::new();
     for i in 0..columns.len() {
         for j in (i + 1)..columns.len() {
-            let similarity = calculate_similarity(&columns[i].values, &columns[j].values);
+            let (similarity, compared) = calculate_similarity(&columns[i].values, &columns[j].values, &options);
             similarities.push((
                 columns[i].header.clone(),
                 columns[j].header.clone(),
                 similarity,
                 columns[i].original_index,
-                columns[j].original_index
+                columns[j].original_index,
+                compared
             ));
         }
     }
@@ -88,22 +314,20 @@ fn main() -> Result<(), Box<dyn Error>> {
     });
 
     // Write results to CSV with UTF-8 BOM
-    let mut writer = Writer::from_path("column_similarities.csv")?;
-    
-    // Write UTF-8 BOM
     let mut file = File::create("column_similarities.csv")?;
     file.write_all(&[0xEF, 0xBB, 0xBF])?;
     
     let mut writer = Writer::from_writer(file);
-    writer.write_record(&["Column 1", "Column 2", "Similarity %", "Column 1 Index", "Column 2 Index"])?;
+    writer.write_record(["Column 1", "Column 2", "Similarity %", "Column 1 Index", "Column 2 Index", "Compared Cells"])?;
 
-    for (col1, col2, similarity, idx1, idx2) in similarities {
-        writer.write_record(&[
+    for (col1, col2, similarity, idx1, idx2, compared) in similarities {
+        writer.write_record([
             &col1,
             &col2,
             &format!("{:.2}", similarity),
             &idx1.to_string(),
             &idx2.to_string(),
+            &compared.to_string(),
         ])?;
     }
 
@@ -111,4 +335,111 @@ fn main() -> Result<(), Box<dyn Error>> {
     println!("Analysis complete. Results saved to column_similarities.csv");
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sparse_column(populated: &[&str]) -> Vec<String> {
+        let mut values = vec![String::new(); 200];
+        for (i, value) in populated.iter().enumerate() {
+            values[i] = value.to_string();
+        }
+        values
+    }
+
+    #[test]
+    fn test_ignore_empty_excludes_blank_positions() {
+        let col1 = sparse_column(&["1", "2", "3"]);
+        let col2 = sparse_column(&["1", "5", "6"]);
+
+        let (naive, naive_compared) = calculate_similarity(&col1, &col2, &CompareOptions::default());
+        assert_eq!(naive_compared, 200);
+        assert!(naive >= 99.0, "naive = {}", naive);
+
+        let ignore_empty = CompareOptions { ignore_empty: true, ..Default::default() };
+        let (ignored, compared) = calculate_similarity(&col1, &col2, &ignore_empty);
+        assert_eq!(compared, 3);
+        assert!((ignored - 100.0 / 3.0).abs() < 1e-9, "ignore-empty = {}", ignored);
+    }
+
+    #[test]
+    fn test_numeric_tolerance_ignores_float_formatting() {
+        let col1: Vec<String> = ["1.0", "2.5", "3", "0.1", "n/a"].iter().map(|s| s.to_string()).collect();
+        let col2: Vec<String> = ["1.00", "2.500", "3.0000000001", "0.10", "n/a"].iter().map(|s| s.to_string()).collect();
+
+        let (exact, _) = calculate_similarity(&col1, &col2, &CompareOptions::default());
+        assert!(exact < 100.0, "exact = {}", exact);
+
+        let tolerant = CompareOptions { numeric_tolerance: Some(1e-6), ..Default::default() };
+        let (similarity, compared) = calculate_similarity(&col1, &col2, &tolerant);
+        assert_eq!(compared, 5);
+        assert!((similarity - 100.0).abs() < 1e-9, "tolerant = {}", similarity);
+
+        assert!(!cells_match("1.0", "1.1", Some(1e-6)));
+        assert!(cells_match("NaN", "NaN", Some(1e-6)));
+        assert!(cells_match("inf", "inf", Some(1e-6)));
+        assert!(!cells_match("NaN", "nan", Some(1e-6)));
+    }
+
+    #[test]
+    fn test_compare_file_matches_renamed_column() {
+        let dir = std::env::temp_dir();
+        let path_a = dir.join(format!("similarity_a_{}.csv", std::process::id()));
+        let path_b = dir.join(format!("similarity_b_{}.csv", std::process::id()));
+        std::fs::write(&path_a, "id,Age,Weight\n1,34,70\n2,51,82\n3,29,65\n4,62,90\n").unwrap();
+        // Same patients in a different order, columns renamed and reordered
+        std::fs::write(&path_b, "Body_Mass,id,Years\n90,4,62\n70,1,34\n65,3,29\n82,2,51\n").unwrap();
+
+        let columns_a = read_columns(path_a.to_str().unwrap(), false).unwrap();
+        let columns_b = read_columns(path_b.to_str().unwrap(), false).unwrap();
+        let matches = best_matches(&columns_a, &columns_b, "id", &CompareOptions::default()).unwrap();
+        std::fs::remove_file(&path_a).ok();
+        std::fs::remove_file(&path_b).ok();
+
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].column, "Age");
+        assert_eq!(matches[0].best_match.as_deref(), Some("Years"));
+        assert_eq!(matches[1].column, "Weight");
+        assert_eq!(matches[1].best_match.as_deref(), Some("Body_Mass"));
+        assert_eq!(matches[1].similarity, 100.0);
+    }
+
+    #[test]
+    fn test_normalize_whitespace_makes_padded_cells_match() {
+        let path = std::env::temp_dir().join(format!("similarity_whitespace_{}.csv", std::process::id()));
+        std::fs::write(&path, "Eye,Side\n  OD  ,OD\nOS\u{200B},OS\nleft  eye,left eye\n").unwrap();
+
+        let raw = read_columns(path.to_str().unwrap(), false).unwrap();
+        let normalized = read_columns(path.to_str().unwrap(), true).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        let (raw_similarity, _) = calculate_similarity(&raw[0].values, &raw[1].values, &CompareOptions::default());
+        let (similarity, _) = calculate_similarity(&normalized[0].values, &normalized[1].values, &CompareOptions::default());
+        assert_eq!(raw_similarity, 0.0);
+        assert_eq!(similarity, 100.0);
+        assert_eq!(normalized[0].values, vec!["OD", "OS", "left eye"]);
+    }
+
+    #[test]
+    fn test_summary_groups_similar_columns() {
+        let path = std::env::temp_dir().join(format!("similarity_groups_{}.csv", std::process::id()));
+        // Age, age_copy and Age (years) agree on 19 of 20 rows; Weight on none
+        let mut content = "Age,Weight,age_copy,Age (years)\n".to_string();
+        for i in 0..20 {
+            let typo = if i == 3 { 99 } else { 30 + i };
+            content.push_str(&format!("{},{},{},{}\n", 30 + i, 60 + 2 * i, typo, 30 + i));
+        }
+        std::fs::write(&path, content).unwrap();
+        let columns = read_columns(path.to_str().unwrap(), false).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        let groups = duplicate_groups(&columns, &CompareOptions::default(), DEFAULT_MIN_SIMILARITY);
+        assert_eq!(groups, vec![vec![0, 2, 3], vec![1]]);
+        assert_eq!(columns[groups[0][0]].header, "Age");
+
+        let strict = duplicate_groups(&columns, &CompareOptions::default(), 100.0);
+        assert_eq!(strict, vec![vec![0, 3], vec![1], vec![2]]);
+    }
+}
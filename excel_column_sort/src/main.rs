@@ -1,11 +1,16 @@
+use std::borrow::Cow;
 use std::error::Error;
 use std::fs;
-use std::io::Write;
+use std::io::{self, Write};
 use csv::{ReaderBuilder, WriterBuilder};
 use encoding_rs::UTF_8;
 use encoding_rs_io::DecodeReaderBytesBuilder;
 use std::collections::HashMap;
 
+use shared::column_type::is_numeric_column;
+use shared::digits::normalize_persian_digits;
+use shared::preview;
+
 #[derive(Debug)]
 struct ColumnInfo {
     name: String,
@@ -15,6 +20,19 @@ struct ColumnInfo {
 fn main() -> Result<(), Box<dyn Error>> {
     let input_path = "/home/aricept094/mydata/endometriosis/merged_endometriosis_data_cleaned.csv";
     let output_path = "/home/aricept094/mydata/endometriosis/sorted_columns_output.csv";
+    // Persian digits are read as numbers unless --no-normalize-digits is given
+    let normalize_digits = !std::env::args().any(|a| a == "--no-normalize-digits");
+    // --preview N: print the header and first N written rows as a table
+    let args: Vec<String> = std::env::args().collect();
+    let preview_rows = preview::preview_rows_from_args(&args)?;
+    // --dry-run: print the old -> new column positions and stop before writing
+    let dry_run = args.iter().any(|a| a == "--dry-run");
+    // --order-file <txt>: headers (one per line) that lead the output in that
+    // order; the remaining columns follow in the type-based order
+    let order = match args.iter().position(|a| a == "--order-file") {
+        Some(i) => read_order_file(args.get(i + 1).ok_or("--order-file needs a path")?)?,
+        None => Vec::new(),
+    };
 
     // First pass: analyze all rows to determine column types accurately
     let file = fs::File::open(input_path)?;
@@ -42,7 +60,8 @@ fn main() -> Result<(), Box<dyn Error>> {
             let (numeric_count, total_count) = column_numeric_counts.get_mut(header).unwrap();
             if !value.is_empty() {
                 *total_count += 1;
-                if is_numeric_value(value) {
+                let value = if normalize_digits { normalize_persian_digits(value) } else { Cow::Borrowed(value) };
+                if is_numeric_value(&value) {
                     *numeric_count += 1;
                 }
             }
@@ -54,15 +73,9 @@ fn main() -> Result<(), Box<dyn Error>> {
         .iter()
         .map(|header| {
             let (numeric_count, total_count) = column_numeric_counts.get(header).unwrap();
-            let numeric_ratio = if *total_count > 0 {
-                *numeric_count as f64 / *total_count as f64
-            } else {
-                0.0
-            };
-            
             ColumnInfo {
                 name: header.to_string(),
-                is_numeric: numeric_ratio > 0.95  // 95% threshold for numeric classification
+                is_numeric: is_numeric_column(*numeric_count, *total_count),
             }
         })
         .collect();
@@ -75,6 +88,16 @@ fn main() -> Result<(), Box<dyn Error>> {
             a.is_numeric.cmp(&b.is_numeric)
         }
     });
+    let (column_info, unknown) = apply_order(column_info, &order);
+    if !unknown.is_empty() {
+        eprintln!("Warning: --order-file lists headers not in the input: {}", unknown.join(", "));
+    }
+
+    if dry_run {
+        write_reorder_plan(&mut io::stdout().lock(), &headers, &column_info)?;
+        println!("\nDry run: nothing written to {}", output_path);
+        return Ok(());
+    }
 
     // Print column classification for verification
     println!("\nColumn Classification:");
@@ -109,23 +132,82 @@ fn main() -> Result<(), Box<dyn Error>> {
         .flexible(true)
         .from_reader(transcoded);
 
-    // Write data with reordered columns
+    // Write data with reordered columns, keeping the first rows for --preview
+    let mut preview_records: Vec<Vec<String>> = Vec::new();
     for result in rdr.records() {
         let record = result?;
         let mut new_record: Vec<String> = Vec::new();
         
         for col in &column_info {
             let idx = headers.iter()
-                .position(|h| h == &col.name)
+                .position(|h| h == col.name)
                 .unwrap();
             new_record.push(record.get(idx).unwrap_or("").to_string());
         }
         
         writer.write_record(&new_record)?;
+        if preview_records.len() < preview_rows.unwrap_or(0) {
+            preview_records.push(new_record);
+        }
     }
 
     writer.flush()?;
     println!("\nCSV processed successfully! Output saved to: {}", output_path);
+
+    if let Some(n) = preview_rows {
+        println!();
+        preview::write_preview(&mut std::io::stdout().lock(), &new_headers, &preview_records, n)?;
+    }
+    Ok(())
+}
+
+// Header names from an order file, one per line; blank lines are ignored
+fn read_order_file(path: &str) -> io::Result<Vec<String>> {
+    let contents = fs::read_to_string(path)?;
+    Ok(contents.trim_start_matches('\u{feff}')
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+// Columns named in `order` first, in that order, then the rest as they were
+// sorted. Also returns the names in `order` that match no column.
+fn apply_order(column_info: Vec<ColumnInfo>, order: &[String]) -> (Vec<ColumnInfo>, Vec<String>) {
+    let mut remaining = column_info;
+    let mut ordered = Vec::with_capacity(remaining.len());
+    let mut unknown = Vec::new();
+    for name in order {
+        match remaining.iter().position(|col| &col.name == name) {
+            Some(idx) => ordered.push(remaining.remove(idx)),
+            // Listed twice is not unknown
+            None if ordered.iter().any(|col: &ColumnInfo| &col.name == name) => {}
+            None => unknown.push(name.clone()),
+        }
+    }
+    ordered.extend(remaining);
+    (ordered, unknown)
+}
+
+// One line per column in its new order: old -> new position (1-based), name
+// and classification
+fn write_reorder_plan<W: Write>(out: &mut W, headers: &csv::StringRecord, column_info: &[ColumnInfo]) -> io::Result<()> {
+    let moved = column_info.iter().enumerate()
+        .filter(|(new_idx, col)| headers.iter().position(|h| h == col.name) != Some(*new_idx))
+        .count();
+    writeln!(out, "\nColumn reorder ({} of {} columns move):", moved, column_info.len())?;
+    for (new_idx, col) in column_info.iter().enumerate() {
+        let old_idx = headers.iter().position(|h| h == col.name).unwrap();
+        writeln!(
+            out,
+            "{:>4} -> {:<4} {} ({})",
+            old_idx + 1,
+            new_idx + 1,
+            col.name,
+            if col.is_numeric { "numeric" } else { "categorical" }
+        )?;
+    }
     Ok(())
 }
 
@@ -148,4 +230,62 @@ fn is_numeric_value(value: &str) -> bool {
     }
 
     false
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_persian_digits_are_numeric() {
+        assert!(is_numeric_value(&normalize_persian_digits("۱۲۳")));
+        assert!(is_numeric_value(&normalize_persian_digits("۱٬۲۳۴")));
+        assert!(!is_numeric_value("۱۲۳"));
+    }
+
+    #[test]
+    fn test_dry_run_lists_old_and_new_positions() {
+        let headers = csv::StringRecord::from(vec!["Age", "City", "AMH", "Diagnosis"]);
+        let column_info = vec![
+            ColumnInfo { name: "City".to_string(), is_numeric: false },
+            ColumnInfo { name: "Diagnosis".to_string(), is_numeric: false },
+            ColumnInfo { name: "AMH".to_string(), is_numeric: true },
+            ColumnInfo { name: "Age".to_string(), is_numeric: true },
+        ];
+
+        let mut out = Vec::new();
+        write_reorder_plan(&mut out, &headers, &column_info).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().skip(1).collect();
+
+        assert_eq!(lines[0], "Column reorder (3 of 4 columns move):");
+        assert_eq!(&lines[1..], [
+            "   2 -> 1    City (categorical)",
+            "   4 -> 2    Diagnosis (categorical)",
+            "   3 -> 3    AMH (numeric)",
+            "   1 -> 4    Age (numeric)",
+        ]);
+    }
+
+    #[test]
+    fn test_order_file_pins_leading_columns() {
+        let path = std::env::temp_dir().join(format!("excel_column_sort_order_{}.txt", std::process::id()));
+        fs::write(&path, "\u{feff}AMH\n\nDiagnosis \nVisit Date\nAge\n").unwrap();
+        let order = read_order_file(path.to_str().unwrap()).unwrap();
+        fs::remove_file(&path).ok();
+
+        // Already in the type-based order: categorical, then numeric
+        let column_info = vec![
+            ColumnInfo { name: "City".to_string(), is_numeric: false },
+            ColumnInfo { name: "Diagnosis".to_string(), is_numeric: false },
+            ColumnInfo { name: "AMH".to_string(), is_numeric: true },
+            ColumnInfo { name: "Age".to_string(), is_numeric: true },
+            ColumnInfo { name: "BMI".to_string(), is_numeric: true },
+        ];
+        let (ordered, unknown) = apply_order(column_info, &order);
+
+        let names: Vec<&str> = ordered.iter().map(|col| col.name.as_str()).collect();
+        assert_eq!(names, ["AMH", "Diagnosis", "Age", "City", "BMI"]);
+        assert_eq!(unknown, ["Visit Date"]);
+    }
+}
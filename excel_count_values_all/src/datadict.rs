@@ -0,0 +1,116 @@
+// Data dictionary: one row per column combining the single-pass quality stats
+// with the numeric/categorical classification used by excel_column_sort.
+
+use std::error::Error;
+use std::fs::File;
+use std::io::Write;
+use csv::WriterBuilder;
+use shared::column_type::is_numeric_column;
+
+use super::{scan_columns, ColumnAccumulator, ColumnSelector, ScoreSettings};
+
+pub struct DictionaryEntry {
+    pub name: String,
+    pub inferred_type: &'static str,
+    pub unique_count: usize,
+    pub missing_percentage: f64,
+    pub sample_values: Vec<String>,
+    pub recommendation: String,
+}
+
+fn infer_column_type(column: &ColumnAccumulator, total_rows: usize) -> &'static str {
+    if is_numeric_column(column.numeric_count, total_rows - column.missing_count) {
+        "numeric"
+    } else {
+        "categorical"
+    }
+}
+
+pub fn build_data_dictionary(file_path: &str, normalize_digits: bool, delimiter: Option<u8>) -> Result<Vec<DictionaryEntry>, Box<dyn Error>> {
+    let scan = scan_columns(file_path, normalize_digits, false, &ColumnSelector::default(), delimiter)?;
+
+    let entries = scan.headers.iter()
+        .zip(&scan.columns)
+        .map(|(name, column)| {
+            let stats = column.to_stats(name, scan.total_rows, &ScoreSettings::default());
+            let missing_percentage = if scan.total_rows > 0 {
+                (stats.missing_count as f64 / scan.total_rows as f64 * 100.0).round()
+            } else {
+                0.0
+            };
+
+            DictionaryEntry {
+                name: name.clone(),
+                inferred_type: infer_column_type(column, scan.total_rows),
+                unique_count: stats.unique_count,
+                missing_percentage,
+                sample_values: column.sample_values.clone(),
+                recommendation: stats.recommendation,
+            }
+        })
+        .collect();
+
+    Ok(entries)
+}
+
+pub fn write_data_dictionary(file_path: &str, output_path: &str, normalize_digits: bool, delimiter: Option<u8>) -> Result<(), Box<dyn Error>> {
+    let entries = build_data_dictionary(file_path, normalize_digits, delimiter)?;
+
+    let mut file = File::create(output_path)?;
+    file.write_all(&[0xEF, 0xBB, 0xBF])?;
+
+    let mut writer = WriterBuilder::new()
+        .has_headers(true)
+        .from_writer(file);
+
+    writer.write_record([
+        "Column Name",
+        "Inferred Type",
+        "Unique Value Count",
+        "Missing %",
+        "Sample Values",
+        "Recommendation",
+    ])?;
+
+    for entry in entries {
+        writer.write_record(&[
+            entry.name,
+            entry.inferred_type.to_string(),
+            entry.unique_count.to_string(),
+            format!("{}%", entry.missing_percentage),
+            entry.sample_values.join(" | "),
+            entry.recommendation,
+        ])?;
+    }
+
+    writer.flush()?;
+    println!("Data dictionary saved to {}", output_path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_values_are_real_and_capped() {
+        let path = std::env::temp_dir().join(format!("datadict_{}.csv", std::process::id()));
+        let mut content = String::from("id,group\n");
+        for i in 1..=8 {
+            content.push_str(&format!("{},{}\n", i, if i % 2 == 0 { "a" } else { "" }));
+        }
+        std::fs::write(&path, content).unwrap();
+
+        let entries = build_data_dictionary(path.to_str().unwrap(), true, None).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        let id = &entries[0];
+        assert_eq!(id.inferred_type, "numeric");
+        assert_eq!(id.sample_values, vec!["1", "2", "3", "4", "5"]);
+
+        let group = &entries[1];
+        assert_eq!(group.inferred_type, "categorical");
+        assert_eq!(group.sample_values, vec!["a"]);
+        assert_eq!(group.missing_percentage, 50.0);
+    }
+}
@@ -1,11 +1,24 @@
-use std::collections::HashSet;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::error::Error;
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
-use csv::{ReaderBuilder, WriterBuilder};
-use encoding_rs::UTF_8;
+use clap::Parser;
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
 use encoding_rs_io::DecodeReaderBytesBuilder;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+mod datadict;
+mod profile;
+
+use shared::cells::normalize_cell;
+use shared::delimiter;
+use shared::digits::normalize_persian_digits;
+use shared::percentage::percentage;
 
 struct ColumnStats {
     name: String,
@@ -17,12 +30,78 @@ struct ColumnStats {
     quality_score: f64,
     variability_percentage: f64,  // Added field for value variability
     recommendation: String,
+    // Fewer non-missing values than --min-rows: scored NEUTRAL_QUALITY_SCORE
+    insufficient_data: bool,
+}
+
+// Score given to columns with too little data to judge, so they land in the
+// middle of the ranking instead of at either end
+const NEUTRAL_QUALITY_SCORE: f64 = 50.0;
+
+// --cardinality-model: how many distinct values a column of n non-missing
+// values is expected to have at most before it gets full cardinality credit
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum CardinalityModel {
+    #[default]
+    Sqrt,
+    Log,
+    Linear,
 }
 
-fn calculate_quality_score(stats: &ColumnStats) -> f64 {
+impl CardinalityModel {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "sqrt" => Ok(CardinalityModel::Sqrt),
+            "log" => Ok(CardinalityModel::Log),
+            "linear" => Ok(CardinalityModel::Linear),
+            other => Err(format!("Unknown --cardinality-model '{}' (expected sqrt, log or linear)", other)),
+        }
+    }
+
+    fn max_expected_unique(&self, non_missing_rows: usize) -> f64 {
+        let n = non_missing_rows as f64;
+        match self {
+            CardinalityModel::Sqrt => n.sqrt(),
+            // ln(1) is 0, and a single value can't expect fewer than one
+            CardinalityModel::Log => n.ln().max(1.0),
+            CardinalityModel::Linear => n,
+        }
+    }
+}
+
+// How columns are scored: --cardinality-model and --min-rows (0 = no minimum)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct ScoreSettings {
+    cardinality_model: CardinalityModel,
+    min_rows: usize,
+}
+
+// The weighted parts of the quality score, in score points; they add up to
+// the score before it is rounded. Written out by --explain.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct QualityComponents {
+    missing: f64,
+    zero_and_one: f64,
+    cardinality: f64,
+}
+
+impl QualityComponents {
+    fn total(&self) -> f64 {
+        self.missing + self.zero_and_one + self.cardinality
+    }
+}
+
+fn calculate_quality_score(stats: &ColumnStats, model: CardinalityModel) -> f64 {
+    if stats.insufficient_data {
+        return NEUTRAL_QUALITY_SCORE;
+    }
+    quality_components(stats, model).total().round()
+}
+
+fn quality_components(stats: &ColumnStats, model: CardinalityModel) -> QualityComponents {
     let non_missing_rows = stats.total_rows - stats.missing_count;
     if non_missing_rows == 0 {
-        return 0.0;
+        return QualityComponents::default();
     }
 
     // Calculate percentages
@@ -36,7 +115,7 @@ fn calculate_quality_score(stats: &ColumnStats) -> f64 {
     } else if stats.unique_count <= 5 {
         0.4
     } else {
-        let max_expected_unique = (non_missing_rows as f64).sqrt();
+        let max_expected_unique = model.max_expected_unique(non_missing_rows);
         let unique_ratio = (stats.unique_count as f64).min(max_expected_unique) / max_expected_unique;
         unique_ratio.powf(0.5)
     };
@@ -45,11 +124,11 @@ fn calculate_quality_score(stats: &ColumnStats) -> f64 {
     const ZERO_AND_ONE_WEIGHT: f64 = 0.30;
     const CARDINALITY_WEIGHT: f64 = 0.35;
 
-    let score = (1.0 - missing_percentage) * MISSING_WEIGHT +
-                (1.0 - (zero_percentage + one_percentage)) * ZERO_AND_ONE_WEIGHT +
-                cardinality_score * CARDINALITY_WEIGHT;
-
-    (score * 100.0).round()
+    QualityComponents {
+        missing: (1.0 - missing_percentage) * MISSING_WEIGHT * 100.0,
+        zero_and_one: (1.0 - (zero_percentage + one_percentage)) * ZERO_AND_ONE_WEIGHT * 100.0,
+        cardinality: cardinality_score * CARDINALITY_WEIGHT * 100.0,
+    }
 }
 
 fn calculate_variability_percentage(stats: &ColumnStats) -> f64 {
@@ -62,120 +141,441 @@ fn calculate_variability_percentage(stats: &ColumnStats) -> f64 {
     let variability = (stats.unique_count as f64 / non_missing_rows as f64 * 100.0).round();
     
     // Cap at 100% and ensure we don't return negative values
-    variability.min(100.0).max(0.0)
+    variability.clamp(0.0, 100.0)
+}
+
+// Message for a file with a header row but no data rows, which has nothing to
+// score (every percentage would be 0/0)
+fn no_data_rows(file_path: &str, total_rows: usize) -> Option<String> {
+    (total_rows == 0).then(|| format!("{}: file has a header but no data rows, nothing to analyze", file_path))
 }
 
 fn get_recommendation(stats: &ColumnStats) -> String {
     let non_missing_rows = stats.total_rows - stats.missing_count;
-    let missing_percentage = (stats.missing_count as f64 / stats.total_rows as f64 * 100.0).round();
-    let zero_percentage = (stats.zero_count as f64 / stats.total_rows as f64 * 100.0).round();
-    let one_percentage = (stats.one_count as f64 / stats.total_rows as f64 * 100.0).round();
-    let non_zero_one_percentage = ((non_missing_rows - stats.zero_count - stats.one_count) as f64 
-        / stats.total_rows as f64 * 100.0).round();
+    let missing_percentage = percentage(stats.missing_count, stats.total_rows);
+    let zero_percentage = percentage(stats.zero_count, stats.total_rows);
+    let one_percentage = percentage(stats.one_count, stats.total_rows);
+    let non_zero_one_percentage = percentage(non_missing_rows - stats.zero_count - stats.one_count, stats.total_rows);
 
     // Include variability in recommendations
-    if missing_percentage > 50.0 {
-        return "High missing values - Consider excluding".to_string();
+    if stats.insufficient_data {
+        "Insufficient data - Too few values to judge".to_string()
+    } else if missing_percentage > 50.0 {
+        "High missing values - Consider excluding".to_string()
     } else if zero_percentage + one_percentage > 70.0 {
-        return "Mostly zeros and ones - Consider excluding or special handling".to_string();
+        "Mostly zeros and ones - Consider excluding or special handling".to_string()
     } else if stats.unique_count == 1 {
-        return "Single value column - No variability".to_string();
+        "Single value column - No variability".to_string()
     } else if stats.unique_count == 2 {
-        return "Binary column - Very low variability".to_string();
+        "Binary column - Very low variability".to_string()
     } else if stats.variability_percentage < 1.0 {
-        return "Extremely low variability - Consider excluding".to_string();
+        "Extremely low variability - Consider excluding".to_string()
     } else if stats.variability_percentage > 90.0 {
-        return "High variability - Possible unique identifier".to_string();
+        "High variability - Possible unique identifier".to_string()
     } else if stats.unique_count <= 5 {
-        return "Low cardinality column - Limited variability".to_string();
+        "Low cardinality column - Limited variability".to_string()
     } else if non_zero_one_percentage < 20.0 {
-        return "Low information content - Review necessity".to_string();
+        "Low information content - Review necessity".to_string()
     } else if stats.quality_score > 80.0 {
-        return "Good quality - Use as is".to_string();
+        "Good quality - Use as is".to_string()
     } else if stats.quality_score > 60.0 {
-        return "Moderate quality - Consider cleaning".to_string();
+        "Moderate quality - Consider cleaning".to_string()
     } else {
-        return "Poor quality - Needs investigation".to_string();
+        "Poor quality - Needs investigation".to_string()
     }
 }
 
-fn analyze_csv(file_path: &str, output_path: &str) -> Result<(), Box<dyn Error>> {
-    let file = File::open(file_path)?;
-    let transcoded_reader = DecodeReaderBytesBuilder::new()
-        .encoding(None)
-        .build(file);
+// Highest quality first; NaN scores sink to the bottom and ties fall back to the
+// column name so the output order is the same on every run.
+fn compare_results(a: &ColumnStats, b: &ColumnStats) -> Ordering {
+    match (a.quality_score.is_nan(), b.quality_score.is_nan()) {
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (true, true) => Ordering::Equal,
+        (false, false) => b.quality_score.total_cmp(&a.quality_score),
+    }
+    .then_with(|| a.name.cmp(&b.name))
+}
 
-    let mut reader = ReaderBuilder::new()
-        .flexible(true)
-        .from_reader(transcoded_reader);
-
-    let headers = reader.headers()?.clone();
-    let column_count = headers.len();
-    let mut results = Vec::new();
-
-    for column_index in 0..column_count {
-        let mut unique_values = HashSet::new();
-        let mut missing_count = 0;
-        let mut zero_count = 0;
-        let mut one_count = 0;
-        let mut total_rows = 0;
-
-        let file = File::open(file_path)?;
-        let transcoded_reader = DecodeReaderBytesBuilder::new()
-            .encoding(None)
-            .build(file);
-        let mut reader = ReaderBuilder::new()
-            .flexible(true)
-            .from_reader(transcoded_reader);
-        reader.headers()?;
-
-        for record_result in reader.records() {
-            let record = record_result?;
-            total_rows += 1;
-            
-            if let Some(value) = record.get(column_index) {
-                let trimmed_value = value.trim();
-                if trimmed_value.is_empty() 
-                    || trimmed_value == " "
-                    || trimmed_value == "  "
-                    || trimmed_value == "   "
-                    || trimmed_value == "    " {
-                    missing_count += 1;
-                } else {
-                    if trimmed_value.chars().all(|c| c == '0' || c == '.') {
-                        zero_count += 1;
-                    }
-                    else if trimmed_value == "1" || trimmed_value == "1.0" || trimmed_value == "1.00" {
-                        one_count += 1;
-                    }
-                    unique_values.insert(value.to_string());
-                }
-            } else {
-                missing_count += 1;
+// Switches for the optional parts of the analysis
+#[derive(Default)]
+struct AnalysisOptions {
+    // --top-values N: also write the N most frequent values of every column
+    // to a `<output>_top_values.csv` file next to the main results
+    top_values: Option<usize>,
+    // --sample N: compute the stats on a uniform random sample of N rows
+    // (reservoir sampling), reproducible for a given --seed
+    sample: Option<usize>,
+    seed: u64,
+    // Read Persian/Arabic digits as ASCII unless --no-normalize-digits is
+    // given, as in excel_column_sort
+    normalize_digits: bool,
+    // --normalize-whitespace: trim cells, collapse inner runs of whitespace and
+    // drop zero-width characters before counting, so "A " and "A" are one value
+    normalize_whitespace: bool,
+    // --stream-output: write each column's row as soon as its stats are computed
+    // and flush as it goes, instead of collecting every row to sort by quality
+    // score first. Output appears earlier and no result rows are held in memory,
+    // but the rows come in file column order rather than best-first.
+    stream_output: bool,
+    // --explain: add a column per quality score component (missing, zero/one
+    // and cardinality contributions) after the score they add up to
+    explain: bool,
+    // --cardinality-model / --min-rows
+    score: ScoreSettings,
+    // --profile-only: print the quick file profile and skip the analysis
+    profile_only: bool,
+    // --include-columns / --exclude-columns: restrict which columns are tracked
+    columns: ColumnSelector,
+    // --parallel-columns: spread the columns over threads during the pass (see
+    // scan_columns_parallel); pays off for very wide files. Can't be combined
+    // with --sample, whose reservoir is already small.
+    parallel_columns: bool,
+    // --delimiter: field separator of the input; detected from the start of
+    // the file (comma, semicolon or tab) when not given
+    delimiter: Option<u8>,
+}
+
+// Column patterns are an exact header name, a glob with * and ?, or any part
+// of the header (handy for long Persian headers). An empty include list keeps
+// every column; excludes are applied after includes.
+#[derive(Default)]
+struct ColumnSelector {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl ColumnSelector {
+    fn validate(&self) -> Result<(), Box<dyn Error>> {
+        let conflicting: Vec<&str> = self.include.iter()
+            .filter(|pattern| self.exclude.contains(pattern))
+            .map(String::as_str)
+            .collect();
+
+        if conflicting.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Column pattern(s) both included and excluded: {}", conflicting.join(", ")).into())
+        }
+    }
+
+    fn is_selected(&self, header: &str) -> bool {
+        let included = self.include.is_empty()
+            || self.include.iter().any(|pattern| pattern_matches(pattern, header));
+        included && !self.exclude.iter().any(|pattern| pattern_matches(pattern, header))
+    }
+}
+
+fn pattern_matches(pattern: &str, header: &str) -> bool {
+    if pattern.contains(['*', '?']) {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let header: Vec<char> = header.chars().collect();
+        glob_matches(&pattern, &header)
+    } else {
+        header.contains(pattern)
+    }
+}
+
+fn glob_matches(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|skip| glob_matches(rest, &text[skip..])),
+        Some(('?', rest)) => !text.is_empty() && glob_matches(rest, &text[1..]),
+        Some((c, rest)) => text.first() == Some(c) && glob_matches(rest, &text[1..]),
+    }
+}
+
+// Per-column counters filled during a single pass over the records
+#[derive(Debug, Default, PartialEq)]
+struct ColumnAccumulator {
+    value_counts: HashMap<String, usize>,
+    missing_count: usize,
+    zero_count: usize,
+    one_count: usize,
+    numeric_count: usize,
+    sample_values: Vec<String>,
+}
+
+impl ColumnAccumulator {
+    // Values are counted as written; normalize_digits only changes how they
+    // are parsed as numbers
+    fn add(&mut self, value: Option<&str>, normalize_digits: bool) {
+        let value = match value {
+            Some(value) => value,
+            None => {
+                self.missing_count += 1;
+                return;
             }
+        };
+
+        let trimmed_value = value.trim();
+        if trimmed_value.is_empty() {
+            self.missing_count += 1;
+            return;
         }
 
+        let number_text = if normalize_digits {
+            normalize_persian_digits(trimmed_value)
+        } else {
+            Cow::Borrowed(trimmed_value)
+        };
+        // Only real numbers count: "0.00" is zero but "." and "0.0.0" are not
+        match number_text.parse::<f64>() {
+            Ok(0.0) => self.zero_count += 1,
+            Ok(1.0) => self.one_count += 1,
+            _ => {}
+        }
+        if is_numeric_value(&number_text) {
+            self.numeric_count += 1;
+        }
+        if self.sample_values.len() < MAX_SAMPLE_VALUES
+            && !self.sample_values.iter().any(|v| v == trimmed_value) {
+            self.sample_values.push(trimmed_value.to_string());
+        }
+        *self.value_counts.entry(value.to_string()).or_insert(0) += 1;
+    }
+
+    // Most frequent values first, ties broken by the value itself
+    fn top_values(&self, n: usize) -> Vec<(&str, usize)> {
+        let mut counts: Vec<(&str, usize)> = self.value_counts.iter()
+            .map(|(value, &count)| (value.as_str(), count))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        counts.truncate(n);
+        counts
+    }
+
+    fn to_stats(&self, name: &str, total_rows: usize, score: &ScoreSettings) -> ColumnStats {
         let mut column_stats = ColumnStats {
-            name: headers.get(column_index).unwrap_or("Unknown Column").to_string(),
-            unique_count: unique_values.len(),
-            missing_count,
-            zero_count,
-            one_count,
+            name: name.to_string(),
+            unique_count: self.value_counts.len(),
+            missing_count: self.missing_count,
+            zero_count: self.zero_count,
+            one_count: self.one_count,
             total_rows,
             quality_score: 0.0,
             variability_percentage: 0.0,
             recommendation: String::new(),
+            insufficient_data: total_rows - self.missing_count < score.min_rows,
         };
 
-        column_stats.quality_score = calculate_quality_score(&column_stats);
+        column_stats.quality_score = calculate_quality_score(&column_stats, score.cardinality_model);
         column_stats.variability_percentage = calculate_variability_percentage(&column_stats);
         column_stats.recommendation = get_recommendation(&column_stats);
-        
-        results.push(column_stats);
+        column_stats
+    }
+}
+
+const MAX_SAMPLE_VALUES: usize = 5;
+
+struct ColumnScan {
+    headers: Vec<String>,
+    // Position in the record of each tracked column
+    column_indices: Vec<usize>,
+    columns: Vec<ColumnAccumulator>,
+    total_rows: usize,
+    // Rows in the file when the stats come from a sample of them
+    sampled_from: Option<usize>,
+    normalize_digits: bool,
+    normalize_whitespace: bool,
+}
+
+impl ColumnScan {
+    fn new(headers: Vec<String>, normalize_digits: bool, normalize_whitespace: bool) -> Self {
+        let column_indices = (0..headers.len()).collect();
+        let columns = headers.iter().map(|_| ColumnAccumulator::default()).collect();
+        ColumnScan {
+            headers,
+            column_indices,
+            columns,
+            total_rows: 0,
+            sampled_from: None,
+            normalize_digits,
+            normalize_whitespace,
+        }
+    }
+
+    // Only the columns picked by the selector get an accumulator
+    fn with_selector(
+        headers: Vec<String>,
+        selector: &ColumnSelector,
+        normalize_digits: bool,
+        normalize_whitespace: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        let column_indices: Vec<usize> = (0..headers.len())
+            .filter(|&i| selector.is_selected(&headers[i]))
+            .collect();
+        if column_indices.is_empty() {
+            return Err("No columns left after applying --include-columns/--exclude-columns".into());
+        }
+
+        let headers = column_indices.iter().map(|&i| headers[i].clone()).collect();
+        let mut scan = ColumnScan::new(headers, normalize_digits, normalize_whitespace);
+        scan.column_indices = column_indices;
+        Ok(scan)
+    }
+
+    fn add_record(&mut self, record: &StringRecord) {
+        self.total_rows += 1;
+        for (&column_index, column) in self.column_indices.iter().zip(self.columns.iter_mut()) {
+            add_cell(column, record.get(column_index), self.normalize_digits, self.normalize_whitespace);
+        }
+    }
+
+    // Every column takes the whole batch on its own, in row order, so each
+    // accumulator ends up exactly as add_record would leave it. Accumulators are
+    // never shared between threads and nothing needs merging afterwards.
+    fn add_batch_parallel(&mut self, records: &[StringRecord]) {
+        self.total_rows += records.len();
+        let (normalize_digits, normalize_whitespace) = (self.normalize_digits, self.normalize_whitespace);
+        self.column_indices.par_iter()
+            .zip(self.columns.par_iter_mut())
+            .for_each(|(&column_index, column)| {
+                for record in records {
+                    add_cell(column, record.get(column_index), normalize_digits, normalize_whitespace);
+                }
+            });
+    }
+}
+
+fn add_cell(column: &mut ColumnAccumulator, value: Option<&str>, normalize_digits: bool, normalize_whitespace: bool) {
+    let value = match value {
+        Some(value) if normalize_whitespace => Some(Cow::Owned(normalize_cell(value))),
+        value => value.map(Cow::Borrowed),
+    };
+    column.add(value.as_deref(), normalize_digits);
+}
+
+fn open_reader(file_path: &str, delimiter: Option<u8>) -> Result<csv::Reader<impl std::io::Read>, Box<dyn Error>> {
+    let delimiter = delimiter::resolve_delimiter(file_path, delimiter)?;
+    let file = File::open(file_path)?;
+    let transcoded_reader = DecodeReaderBytesBuilder::new()
+        .encoding(None)
+        .build(file);
+
+    Ok(ReaderBuilder::new()
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_reader(transcoded_reader))
+}
+
+// Read the file once, feeding every cell to its column's accumulator
+fn scan_columns(
+    file_path: &str,
+    normalize_digits: bool,
+    normalize_whitespace: bool,
+    selector: &ColumnSelector,
+    delimiter: Option<u8>,
+) -> Result<ColumnScan, Box<dyn Error>> {
+    let mut reader = open_reader(file_path, delimiter)?;
+    let headers: Vec<String> = reader.headers()?.iter().map(String::from).collect();
+    let mut scan = ColumnScan::with_selector(headers, selector, normalize_digits, normalize_whitespace)?;
+
+    for record_result in reader.records() {
+        scan.add_record(&record_result?);
+    }
+
+    Ok(scan)
+}
+
+// Records read per batch by scan_columns_parallel
+const PARALLEL_BATCH_ROWS: usize = 1024;
+
+// scan_columns with the column work spread over threads: the file is still
+// read once, a batch of rows at a time, and each batch is handed to every
+// column in parallel. Gives the same ColumnScan as scan_columns.
+fn scan_columns_parallel(
+    file_path: &str,
+    normalize_digits: bool,
+    normalize_whitespace: bool,
+    selector: &ColumnSelector,
+    delimiter: Option<u8>,
+) -> Result<ColumnScan, Box<dyn Error>> {
+    let mut reader = open_reader(file_path, delimiter)?;
+    let headers: Vec<String> = reader.headers()?.iter().map(String::from).collect();
+    let mut scan = ColumnScan::with_selector(headers, selector, normalize_digits, normalize_whitespace)?;
+
+    let mut batch: Vec<StringRecord> = Vec::with_capacity(PARALLEL_BATCH_ROWS);
+    for record_result in reader.records() {
+        batch.push(record_result?);
+        if batch.len() == PARALLEL_BATCH_ROWS {
+            scan.add_batch_parallel(&batch);
+            batch.clear();
+        }
+    }
+    scan.add_batch_parallel(&batch);
+
+    Ok(scan)
+}
+
+// Same single pass, but only a reservoir of sample_size records is kept
+// (Algorithm R) and the accumulators are filled from it afterwards
+fn scan_columns_sampled(
+    file_path: &str,
+    sample_size: usize,
+    seed: u64,
+    normalize_digits: bool,
+    normalize_whitespace: bool,
+    selector: &ColumnSelector,
+    delimiter: Option<u8>,
+) -> Result<ColumnScan, Box<dyn Error>> {
+    let mut reader = open_reader(file_path, delimiter)?;
+    let headers: Vec<String> = reader.headers()?.iter().map(String::from).collect();
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut reservoir: Vec<StringRecord> = Vec::with_capacity(sample_size);
+    let mut rows_seen = 0;
+
+    for record_result in reader.records() {
+        let record = record_result?;
+        rows_seen += 1;
+        if reservoir.len() < sample_size {
+            reservoir.push(record);
+        } else {
+            let slot = rng.gen_range(0..rows_seen);
+            if slot < sample_size {
+                reservoir[slot] = record;
+            }
+        }
+    }
+
+    let mut scan = ColumnScan::with_selector(headers, selector, normalize_digits, normalize_whitespace)?;
+    for record in &reservoir {
+        scan.add_record(record);
+    }
+    scan.sampled_from = Some(rows_seen);
+    Ok(scan)
+}
+
+fn is_numeric_value(value: &str) -> bool {
+    if value.trim().is_empty() {
+        return false;
     }
 
-    results.sort_by(|a, b| b.quality_score.partial_cmp(&a.quality_score).unwrap());
+    // Remove thousand separators and try parsing
+    let cleaned_value = value.replace(',', "");
+    cleaned_value.parse::<f64>().is_ok() || cleaned_value.parse::<i64>().is_ok()
+}
+
+// analysis.csv -> analysis_sample1000.csv, so sampled results are never
+// mistaken for full-file ones
+fn sampled_output_path(output_path: &str, sample_size: usize) -> String {
+    let path = Path::new(output_path);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("analysis_results");
+    path.with_file_name(format!("{}_sample{}.csv", stem, sample_size))
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn top_values_path(output_path: &str) -> String {
+    let path = Path::new(output_path);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("analysis_results");
+    path.with_file_name(format!("{}_top_values.csv", stem))
+        .to_string_lossy()
+        .into_owned()
+}
 
+fn write_top_values(scan: &ColumnScan, n: usize, output_path: &str) -> Result<(), Box<dyn Error>> {
     let mut file = File::create(output_path)?;
     file.write_all(&[0xEF, 0xBB, 0xBF])?;
 
@@ -183,9 +583,109 @@ fn analyze_csv(file_path: &str, output_path: &str) -> Result<(), Box<dyn Error>>
         .has_headers(true)
         .from_writer(file);
 
-    writer.write_record(&[
-        "Column Name",
-        "Quality Score",
+    writer.write_record(["Column Name", "Rank", "Value", "Count"])?;
+
+    for (name, column) in scan.headers.iter().zip(&scan.columns) {
+        for (rank, (value, count)) in column.top_values(n).into_iter().enumerate() {
+            writer.write_record([
+                name.as_str(),
+                &(rank + 1).to_string(),
+                value,
+                &count.to_string(),
+            ])?;
+        }
+    }
+
+    writer.flush()?;
+    println!("Top {} values per column saved to {}", n, output_path);
+    Ok(())
+}
+
+// Rows written between flushes in --stream-output mode
+const STREAM_FLUSH_ROWS: usize = 100;
+
+// explain is the cardinality model to break the score down with, if --explain
+fn write_stats_row<W: std::io::Write>(writer: &mut csv::Writer<W>, stats: ColumnStats, explain: Option<CardinalityModel>) -> csv::Result<()> {
+    let missing_percentage = percentage(stats.missing_count, stats.total_rows);
+    let zero_percentage = percentage(stats.zero_count, stats.total_rows);
+    let one_percentage = percentage(stats.one_count, stats.total_rows);
+    let valid_percentage = percentage(stats.total_rows - stats.missing_count - stats.zero_count - stats.one_count, stats.total_rows);
+
+    let components = explain.map(|model| quality_components(&stats, model));
+
+    let mut record = vec![stats.name, format!("{:.1}", stats.quality_score)];
+    match components {
+        // The neutral score of an insufficient column has no breakdown
+        Some(_) if stats.insufficient_data => record.extend([String::new(), String::new(), String::new()]),
+        Some(components) => record.extend([
+            format!("{:.2}", components.missing),
+            format!("{:.2}", components.zero_and_one),
+            format!("{:.2}", components.cardinality),
+        ]),
+        None => {}
+    }
+    record.extend([
+        stats.unique_count.to_string(),
+        stats.missing_count.to_string(),
+        stats.zero_count.to_string(),
+        stats.one_count.to_string(),
+        stats.total_rows.to_string(),
+        format!("{}%", missing_percentage),
+        format!("{}%", zero_percentage),
+        format!("{}%", one_percentage),
+        format!("{}%", valid_percentage),
+        format!("{:.1}%", stats.variability_percentage),  // Added variability percentage
+        stats.recommendation,
+    ]);
+    writer.write_record(&record)
+}
+
+// Ok(false) when the file has a header but no data rows, and nothing was written
+fn analyze_csv(file_path: &str, output_path: &str, options: &AnalysisOptions) -> Result<bool, Box<dyn Error>> {
+    options.columns.validate()?;
+
+    let (scan, output_path) = match options.sample {
+        Some(n) => (
+            scan_columns_sampled(file_path, n, options.seed, options.normalize_digits, options.normalize_whitespace, &options.columns, options.delimiter)?,
+            sampled_output_path(output_path, n),
+        ),
+        None if options.parallel_columns => (
+            scan_columns_parallel(file_path, options.normalize_digits, options.normalize_whitespace, &options.columns, options.delimiter)?,
+            output_path.to_string(),
+        ),
+        None => (
+            scan_columns(file_path, options.normalize_digits, options.normalize_whitespace, &options.columns, options.delimiter)?,
+            output_path.to_string(),
+        ),
+    };
+    let output_path = output_path.as_str();
+
+    if let Some(message) = no_data_rows(file_path, scan.total_rows) {
+        println!("{}", message);
+        return Ok(false);
+    }
+
+    if let Some(rows_in_file) = scan.sampled_from {
+        println!("Stats computed on a random sample of {} of {} rows (seed {})",
+            scan.total_rows, rows_in_file, options.seed);
+    }
+
+    if let Some(n) = options.top_values {
+        write_top_values(&scan, n, &top_values_path(output_path))?;
+    }
+
+    let mut file = File::create(output_path)?;
+    file.write_all(&[0xEF, 0xBB, 0xBF])?;
+
+    let mut writer = WriterBuilder::new()
+        .has_headers(true)
+        .from_writer(file);
+
+    let mut header = vec!["Column Name", "Quality Score"];
+    if options.explain {
+        header.extend(["Missing Contribution", "Zero/One Contribution", "Cardinality Contribution"]);
+    }
+    header.extend([
         "Unique Value Count",
         "Missing Value Count",
         "Zero Value Count",
@@ -197,47 +697,507 @@ fn analyze_csv(file_path: &str, output_path: &str) -> Result<(), Box<dyn Error>>
         "Valid %",
         "Variability %",  // Added new column
         "Recommendation"
-    ])?;
-
-    for stats in results {
-        let missing_percentage = (stats.missing_count as f64 / stats.total_rows as f64 * 100.0).round();
-        let zero_percentage = (stats.zero_count as f64 / stats.total_rows as f64 * 100.0).round();
-        let one_percentage = (stats.one_count as f64 / stats.total_rows as f64 * 100.0).round();
-        let valid_percentage = ((stats.total_rows - stats.missing_count - stats.zero_count - stats.one_count) as f64 
-            / stats.total_rows as f64 * 100.0).round();
-
-        writer.write_record(&[
-            stats.name,
-            format!("{:.1}", stats.quality_score),
-            stats.unique_count.to_string(),
-            stats.missing_count.to_string(),
-            stats.zero_count.to_string(),
-            stats.one_count.to_string(),
-            stats.total_rows.to_string(),
-            format!("{}%", missing_percentage),
-            format!("{}%", zero_percentage),
-            format!("{}%", one_percentage),
-            format!("{}%", valid_percentage),
-            format!("{:.1}%", stats.variability_percentage),  // Added variability percentage
-            stats.recommendation,
-        ])?;
+    ]);
+    writer.write_record(&header)?;
+
+    let results = scan.headers.iter()
+        .zip(&scan.columns)
+        .map(|(name, column)| column.to_stats(name, scan.total_rows, &options.score));
+    let explain = options.explain.then_some(options.score.cardinality_model);
+
+    if options.stream_output {
+        for (i, stats) in results.enumerate() {
+            write_stats_row(&mut writer, stats, explain)?;
+            if (i + 1) % STREAM_FLUSH_ROWS == 0 {
+                writer.flush()?;
+            }
+        }
+    } else {
+        let mut results: Vec<ColumnStats> = results.collect();
+        results.sort_by(compare_results);
+        for stats in results {
+            write_stats_row(&mut writer, stats, explain)?;
+        }
     }
 
     writer.flush()?;
     println!("Results saved to {}", output_path);
-    Ok(())
+    Ok(true)
 }
 
-fn main() {
-    let input_file_path = "/home/aricept094/mydata/PCO/sorted_columns_cleaned_output_good_targets.csv";
-    let output_file_path = "/home/aricept094/mydata/PCO/analysis_results_all.csv";
+// Command line; see AnalysisOptions for what each option does
+#[derive(Debug, Parser)]
+#[command(about = "Score every column of a CSV on missing values, zeros/ones and cardinality")]
+struct Args {
+    /// CSV to analyze
+    #[arg(long)]
+    input: String,
+    /// Where the per-column results are written
+    #[arg(long)]
+    output: String,
+    /// Also write a one-row-per-column data dictionary here
+    #[arg(long)]
+    dictionary: Option<String>,
+    /// Also write the N most frequent values of every column
+    #[arg(long)]
+    top_values: Option<usize>,
+    /// Compute the stats on a uniform random sample of N rows
+    #[arg(long)]
+    sample: Option<usize>,
+    /// Seed of --sample
+    #[arg(long, default_value_t = 42)]
+    seed: u64,
+    /// Don't read Persian/Arabic digits as ASCII
+    #[arg(long = "no-normalize-digits", action = clap::ArgAction::SetFalse)]
+    normalize_digits: bool,
+    /// Trim cells, collapse inner whitespace and drop zero-width characters before counting
+    #[arg(long)]
+    normalize_whitespace: bool,
+    /// Write each column's row as soon as it is computed, in file column order
+    #[arg(long)]
+    stream_output: bool,
+    /// Add a column per quality score component
+    #[arg(long)]
+    explain: bool,
+    /// Expected distinct values of n rows before full cardinality credit: sqrt, log or linear
+    #[arg(long, value_parser = CardinalityModel::parse, default_value = "sqrt")]
+    cardinality_model: CardinalityModel,
+    /// Columns with fewer non-missing values get a neutral score (0 = no minimum)
+    #[arg(long, default_value_t = 0)]
+    min_rows: usize,
+    /// Print the quick file profile and skip the analysis
+    #[arg(long)]
+    profile_only: bool,
+    /// Only track these columns (names, globs or header fragments, comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    include_columns: Vec<String>,
+    /// Skip these columns
+    #[arg(long, value_delimiter = ',')]
+    exclude_columns: Vec<String>,
+    /// Spread the columns over threads during the pass (not with --sample)
+    #[arg(long, conflicts_with = "sample")]
+    parallel_columns: bool,
+    /// Field separator of the input (a character or "tab"); detected when not given
+    #[arg(long, value_parser = delimiter::parse_delimiter)]
+    delimiter: Option<u8>,
+}
+
+impl Args {
+    fn analysis_options(&self) -> AnalysisOptions {
+        AnalysisOptions {
+            top_values: self.top_values,
+            sample: self.sample,
+            seed: self.seed,
+            normalize_digits: self.normalize_digits,
+            normalize_whitespace: self.normalize_whitespace,
+            stream_output: self.stream_output,
+            explain: self.explain,
+            score: ScoreSettings { cardinality_model: self.cardinality_model, min_rows: self.min_rows },
+            profile_only: self.profile_only,
+            columns: ColumnSelector { include: self.include_columns.clone(), exclude: self.exclude_columns.clone() },
+            parallel_columns: self.parallel_columns,
+            delimiter: self.delimiter,
+        }
+    }
+}
+
+fn run(args: &Args) {
+    let input_file_path = args.input.as_str();
+    let output_file_path = args.output.as_str();
+    let dictionary_file_path = args.dictionary.as_deref();
+    let options = args.analysis_options();
 
     if !Path::new(input_file_path).exists() {
         println!("Error: Input file not found at {}", input_file_path);
         return;
     }
 
-    if let Err(err) = analyze_csv(input_file_path, output_file_path) {
-        println!("Error analyzing CSV: {}", err);
+    if options.profile_only {
+        match profile::profile_file(input_file_path, options.delimiter) {
+            Ok(file_profile) => profile::print_profile(input_file_path, &file_profile),
+            Err(err) => println!("Error profiling CSV: {}", err),
+        }
+        return;
+    }
+
+    match analyze_csv(input_file_path, output_file_path, &options) {
+        // A header-only file gets no output files at all, dictionary included
+        Ok(false) => return,
+        Ok(true) => {}
+        Err(err) => println!("Error analyzing CSV: {}", err),
+    }
+
+    if let Some(dictionary_path) = dictionary_file_path {
+        if let Err(err) = datadict::write_data_dictionary(input_file_path, dictionary_path, options.normalize_digits, options.delimiter) {
+            println!("Error writing data dictionary: {}", err);
+        }
+    }
+}
+
+fn main() {
+    run(&Args::parse());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats_with_score(name: &str, quality_score: f64) -> ColumnStats {
+        ColumnStats {
+            name: name.to_string(),
+            unique_count: 0,
+            missing_count: 0,
+            zero_count: 0,
+            one_count: 0,
+            total_rows: 0,
+            quality_score,
+            variability_percentage: 0.0,
+            recommendation: String::new(),
+            insufficient_data: false,
+        }
+    }
+
+    #[test]
+    fn test_equal_scores_are_ordered_by_name() {
+        let mut results = [
+            stats_with_score("beta", 75.0),
+            stats_with_score("alpha", 75.0),
+            stats_with_score("gamma", 90.0),
+        ];
+        results.sort_by(compare_results);
+        let names: Vec<&str> = results.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["gamma", "alpha", "beta"]);
+    }
+
+    #[test]
+    fn test_nan_score_sorts_last_without_panic() {
+        let mut results = [
+            stats_with_score("broken", f64::NAN),
+            stats_with_score("low", 10.0),
+            stats_with_score("high", 80.0),
+        ];
+        results.sort_by(compare_results);
+        let names: Vec<&str> = results.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["high", "low", "broken"]);
+    }
+
+    #[test]
+    fn test_header_only_file_is_reported_without_nan() {
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("count_values_header_only_{}.csv", std::process::id()));
+        let output = dir.join(format!("count_values_header_only_out_{}.csv", std::process::id()));
+        let dictionary = dir.join(format!("count_values_header_only_dict_{}.csv", std::process::id()));
+        std::fs::write(&input, "\u{FEFF}Age,AMH\n").unwrap();
+
+        let scan = scan_columns(input.to_str().unwrap(), true, false, &ColumnSelector::default(), None).unwrap();
+        let analyzed = analyze_csv(input.to_str().unwrap(), output.to_str().unwrap(), &AnalysisOptions::default()).unwrap();
+        run(&Args::try_parse_from([
+            "excel_count_values_all",
+            "--input", input.to_str().unwrap(),
+            "--output", output.to_str().unwrap(),
+            "--dictionary", dictionary.to_str().unwrap(),
+        ]).unwrap());
+        let output_written = output.exists() || dictionary.exists();
+        std::fs::remove_file(&input).ok();
+
+        assert_eq!(scan.total_rows, 0);
+        let message = no_data_rows("empty.csv", scan.total_rows).unwrap();
+        assert_eq!(message, "empty.csv: file has a header but no data rows, nothing to analyze");
+        assert!(no_data_rows("empty.csv", 1).is_none());
+        assert!(!analyzed);
+        assert!(!output_written);
+
+        // Each percentage is guarded on its own too
+        let stats = scan.columns[0].to_stats("Age", 0, &ScoreSettings::default());
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        write_stats_row(&mut writer, stats, None).unwrap();
+        let row = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert!(!row.contains("NaN"), "{}", row);
+        assert!(row.contains("0%,0%,0%,0%"), "{}", row);
+    }
+
+    #[test]
+    fn test_top_values_reports_most_frequent_first() {
+        let mut column = ColumnAccumulator::default();
+        for value in ["B", "A", "B", "A", "A", "A", "B", "A", "A", "A"] {
+            column.add(Some(value), false);
+        }
+        assert_eq!(column.top_values(1), vec![("A", 7)]);
+        assert_eq!(column.top_values(5), vec![("A", 7), ("B", 3)]);
+    }
+
+    #[test]
+    fn test_zero_and_one_counts_need_a_real_number() {
+        let mut column = ColumnAccumulator::default();
+        for value in ["0", "00", "0.0", "-0", "0.5", ".", "0.0.0", "1", "1.000", "01", "1.5"] {
+            column.add(Some(value), false);
+        }
+        assert_eq!(column.zero_count, 4);
+        assert_eq!(column.one_count, 3);
+    }
+
+    #[test]
+    fn test_seeded_sample_is_deterministic() {
+        let path = std::env::temp_dir().join(format!("count_values_sample_{}.csv", std::process::id()));
+        let mut content = String::from("id,group\n");
+        for i in 0..500 {
+            content.push_str(&format!("{},{}\n", i, i % 7));
+        }
+        std::fs::write(&path, content).unwrap();
+        let file_path = path.to_str().unwrap();
+
+        let first = scan_columns_sampled(file_path, 50, 7, true, false, &ColumnSelector::default(), None).unwrap();
+        let second = scan_columns_sampled(file_path, 50, 7, true, false, &ColumnSelector::default(), None).unwrap();
+        let everything = scan_columns_sampled(file_path, 1000, 7, true, false, &ColumnSelector::default(), None).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(first.total_rows, 50);
+        assert_eq!(first.sampled_from, Some(500));
+        assert_eq!(first.columns[0].value_counts.len(), 50);
+        assert_eq!(first.columns[0].value_counts, second.columns[0].value_counts);
+        assert_eq!(first.columns[1].value_counts, second.columns[1].value_counts);
+        assert_eq!(everything.total_rows, 500);
+        assert_eq!(sampled_output_path("/data/analysis.csv", 50), "/data/analysis_sample50.csv");
+    }
+
+    #[test]
+    fn test_include_columns_limits_output_rows() {
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("count_values_include_{}.csv", std::process::id()));
+        let output = dir.join(format!("count_values_include_{}_out.csv", std::process::id()));
+        std::fs::write(&input, "id,age,سن بیمار,weight,height\n1,30,۳۰,70,170\n2,41,۴۱,82,181\n").unwrap();
+
+        let options = AnalysisOptions {
+            columns: ColumnSelector {
+                include: vec!["سن".to_string(), "we*t".to_string()],
+                exclude: Vec::new(),
+            },
+            ..Default::default()
+        };
+        analyze_csv(input.to_str().unwrap(), output.to_str().unwrap(), &options).unwrap();
+        let mut reader = ReaderBuilder::new().from_path(&output).unwrap();
+        let names: Vec<String> = reader.records().map(|r| r.unwrap()[0].to_string()).collect();
+        std::fs::remove_file(&input).ok();
+        std::fs::remove_file(&output).ok();
+
+        assert_eq!(names.len(), 2);
+        assert!(names.contains(&"سن بیمار".to_string()));
+        assert!(names.contains(&"weight".to_string()));
+
+        let conflicting = ColumnSelector { include: vec!["age".to_string()], exclude: vec!["age".to_string()] };
+        assert!(conflicting.validate().is_err());
+        let excluding = ColumnSelector { include: Vec::new(), exclude: vec!["*ght".to_string()] };
+        assert!(!excluding.is_selected("height") && !excluding.is_selected("weight"));
+        assert!(excluding.is_selected("age"));
+    }
+
+    #[test]
+    fn test_persian_digits_count_as_numeric() {
+        assert!(is_numeric_value(&normalize_persian_digits("۱۲۳")));
+
+        let record = StringRecord::from(vec!["۱۲۳"]);
+        let mut normalized = ColumnScan::new(vec!["value".to_string()], true, false);
+        let mut raw = ColumnScan::new(vec!["value".to_string()], false, false);
+        normalized.add_record(&record);
+        raw.add_record(&record);
+
+        assert_eq!(normalized.columns[0].numeric_count, 1);
+        // The value itself is still counted as written
+        assert_eq!(normalized.columns[0].value_counts.get("۱۲۳"), Some(&1));
+        assert_eq!(normalized.columns[0].value_counts.get("123"), None);
+        assert_eq!(raw.columns[0].numeric_count, 0);
+    }
+
+    #[test]
+    fn test_normalize_whitespace_merges_padded_values() {
+        let records = [StringRecord::from(vec!["  A  "]), StringRecord::from(vec!["A"])];
+        let mut normalized = ColumnScan::new(vec!["value".to_string()], true, true);
+        let mut raw = ColumnScan::new(vec!["value".to_string()], true, false);
+        for record in &records {
+            normalized.add_record(record);
+            raw.add_record(record);
+        }
+
+        assert_eq!(normalized.columns[0].value_counts.len(), 1);
+        assert_eq!(normalized.columns[0].value_counts.get("A"), Some(&2));
+        assert_eq!(raw.columns[0].value_counts.len(), 2);
+    }
+
+    #[test]
+    fn test_stream_output_writes_the_same_rows() {
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("count_values_stream_{}.csv", std::process::id()));
+        let sorted_output = dir.join(format!("count_values_stream_{}_sorted.csv", std::process::id()));
+        let streamed_output = dir.join(format!("count_values_stream_{}_streamed.csv", std::process::id()));
+        let mut content = String::from("id,flag,empty,group,score\n");
+        for i in 0..40 {
+            content.push_str(&format!("{},{},,{},{}\n", i, i % 2, i % 3, i as f64 / 4.0));
+        }
+        std::fs::write(&input, content).unwrap();
+
+        analyze_csv(input.to_str().unwrap(), sorted_output.to_str().unwrap(), &AnalysisOptions::default()).unwrap();
+        let streaming = AnalysisOptions { stream_output: true, ..Default::default() };
+        analyze_csv(input.to_str().unwrap(), streamed_output.to_str().unwrap(), &streaming).unwrap();
+        let sorted = std::fs::read_to_string(&sorted_output).unwrap();
+        let streamed = std::fs::read_to_string(&streamed_output).unwrap();
+        std::fs::remove_file(&input).ok();
+        std::fs::remove_file(&sorted_output).ok();
+        std::fs::remove_file(&streamed_output).ok();
+
+        let mut sorted_lines: Vec<&str> = sorted.lines().collect();
+        let mut streamed_lines: Vec<&str> = streamed.lines().collect();
+        assert_eq!(sorted_lines.len(), 6);
+        // Streamed rows follow the file's column order
+        assert!(streamed_lines[1].starts_with("id,"));
+        assert!(streamed_lines[5].starts_with("score,"));
+        sorted_lines.sort();
+        streamed_lines.sort();
+        assert_eq!(sorted_lines, streamed_lines);
+    }
+
+    #[test]
+    fn test_explain_components_add_up_to_quality_score() {
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("count_values_explain_{}.csv", std::process::id()));
+        let output = dir.join(format!("count_values_explain_{}_out.csv", std::process::id()));
+        let mut content = String::from("id,flag,sparse,group\n");
+        for i in 0..50 {
+            let sparse = if i % 4 == 0 { (i * 7).to_string() } else { String::new() };
+            content.push_str(&format!("{},{},{},{}\n", i, i % 2, sparse, i % 4 + 2));
+        }
+        std::fs::write(&input, content).unwrap();
+
+        let options = AnalysisOptions { explain: true, ..Default::default() };
+        analyze_csv(input.to_str().unwrap(), output.to_str().unwrap(), &options).unwrap();
+        let mut reader = ReaderBuilder::new().from_path(&output).unwrap();
+        let headers = reader.headers().unwrap().clone();
+        let rows: Vec<StringRecord> = reader.records().map(|r| r.unwrap()).collect();
+        std::fs::remove_file(&input).ok();
+        std::fs::remove_file(&output).ok();
+
+        let component_headers: Vec<&str> = headers.iter().skip(2).take(3).collect();
+        assert_eq!(component_headers, vec!["Missing Contribution", "Zero/One Contribution", "Cardinality Contribution"]);
+        assert_eq!(rows.len(), 4);
+        for row in &rows {
+            let score: f64 = row[1].parse().unwrap();
+            let total: f64 = (2..5).map(|i| row[i].parse::<f64>().unwrap()).sum();
+            assert!((total - score).abs() <= 0.5 + 0.015, "{:?}", row);
+        }
+    }
+
+    fn accumulator_of(values: &[&str]) -> ColumnAccumulator {
+        let mut column = ColumnAccumulator::default();
+        for value in values {
+            column.add(Some(value), false);
+        }
+        column
+    }
+
+    #[test]
+    fn test_cardinality_model_changes_expected_unique() {
+        // 8 distinct values in 100 rows: 80% of sqrt(100), but above ln(100)
+        let values: Vec<String> = (0..100).map(|i| (i % 8 + 2).to_string()).collect();
+        let column = accumulator_of(&values.iter().map(String::as_str).collect::<Vec<_>>());
+
+        let sqrt = column.to_stats("k", 100, &ScoreSettings::default());
+        let log = column.to_stats("k", 100, &ScoreSettings { cardinality_model: CardinalityModel::Log, min_rows: 0 });
+        let sqrt_part = quality_components(&sqrt, CardinalityModel::Sqrt).cardinality;
+        let log_part = quality_components(&log, CardinalityModel::Log).cardinality;
+
+        assert!((sqrt_part - 0.8_f64.sqrt() * 35.0).abs() < 1e-9);
+        assert!((log_part - 35.0).abs() < 1e-9);
+        assert_eq!(sqrt.quality_score, 96.0);
+        assert_eq!(log.quality_score, 100.0);
+
+        let args = Args::try_parse_from(["excel_count_values_all", "--input", "in.csv", "--output", "out.csv", "--cardinality-model", "linear", "--min-rows", "5"]).unwrap();
+        assert_eq!(
+            args.analysis_options().score,
+            ScoreSettings { cardinality_model: CardinalityModel::Linear, min_rows: 5 }
+        );
+        assert!(Args::try_parse_from(["excel_count_values_all", "--input", "in.csv", "--output", "out.csv", "--cardinality-model", "cubic"]).is_err());
+    }
+
+    #[test]
+    fn test_three_row_column_is_insufficient() {
+        let column = accumulator_of(&["41.2", "43.9", "44.5"]);
+        let settings = ScoreSettings { min_rows: 10, ..Default::default() };
+
+        let flagged = column.to_stats("k", 3, &settings);
+        assert!(flagged.insufficient_data);
+        assert_eq!(flagged.quality_score, NEUTRAL_QUALITY_SCORE);
+        assert!(flagged.recommendation.starts_with("Insufficient data"));
+
+        let unflagged = column.to_stats("k", 3, &ScoreSettings::default());
+        assert!(!unflagged.insufficient_data);
+        assert_ne!(unflagged.quality_score, NEUTRAL_QUALITY_SCORE);
+    }
+
+    #[test]
+    fn test_parallel_scan_matches_serial_scan() {
+        let input = std::env::temp_dir().join(format!("count_values_wide_{}.csv", std::process::id()));
+        let columns = 300;
+        let header: Vec<String> = (0..columns).map(|c| format!("col_{}", c)).collect();
+        let mut content = header.join(",") + "\n";
+        // More rows than a batch, with blanks, zeros, ones, Persian digits and padded text
+        for row in 0..(PARALLEL_BATCH_ROWS * 2 + 37) {
+            let cells: Vec<String> = (0..columns)
+                .map(|c| match (row * 7 + c * 13) % 11 {
+                    0 => String::new(),
+                    1 => "0".to_string(),
+                    2 => "1".to_string(),
+                    3 => "۱۲".to_string(),
+                    4 => format!(" v{} ", row % 5),
+                    n => (row % (c + 2) + n).to_string(),
+                })
+                .collect();
+            content.push_str(&cells.join(","));
+            content.push('\n');
+        }
+        std::fs::write(&input, content).unwrap();
+
+        let selector = ColumnSelector { include: vec![], exclude: vec!["col_1?".to_string()] };
+        let file_path = input.to_str().unwrap();
+        let serial = scan_columns(file_path, true, true, &selector, None).unwrap();
+        let parallel = scan_columns_parallel(file_path, true, true, &selector, None).unwrap();
+        let again = scan_columns_parallel(file_path, true, true, &selector, None).unwrap();
+        std::fs::remove_file(&input).ok();
+
+        assert_eq!(parallel.total_rows, serial.total_rows);
+        assert_eq!(parallel.headers, serial.headers);
+        assert_eq!(parallel.column_indices, serial.column_indices);
+        assert_eq!(parallel.columns, serial.columns);
+        assert_eq!(again.columns, parallel.columns);
+    }
+
+    #[test]
+    fn test_help_and_missing_value() {
+        let help = Args::try_parse_from(["excel_count_values_all", "--help"]).unwrap_err();
+        assert_eq!(help.kind(), clap::error::ErrorKind::DisplayHelp);
+        assert_eq!(help.exit_code(), 0);
+        assert!(help.to_string().contains("--cardinality-model"));
+
+        let missing = Args::try_parse_from(["excel_count_values_all", "--input"]).unwrap_err();
+        assert_ne!(missing.exit_code(), 0);
+        assert!(missing.to_string().contains("--input"), "{}", missing);
+
+        // There is no default input or output file
+        let required = Args::try_parse_from(["excel_count_values_all", "--output", "out.csv"]).unwrap_err();
+        assert_eq!(required.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+        assert!(required.to_string().contains("--input"), "{}", required);
+        let required = Args::try_parse_from(["excel_count_values_all", "--input", "in.csv"]).unwrap_err();
+        assert_eq!(required.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+        assert!(required.to_string().contains("--output"), "{}", required);
+
+        let args = Args::try_parse_from(["excel_count_values_all", "--input", "in.csv", "--output", "out.csv", "--exclude-columns", "id,notes*", "--no-normalize-digits"]).unwrap();
+        let options = args.analysis_options();
+        assert_eq!(options.columns.exclude, vec!["id", "notes*"]);
+        assert!(!options.normalize_digits);
+        assert!(Args::try_parse_from(["excel_count_values_all", "--input", "in.csv", "--output", "out.csv"]).unwrap().normalize_digits);
+
+        // --parallel-columns would be silently ignored by the sampled scan
+        let conflict = Args::try_parse_from([
+            "excel_count_values_all", "--input", "in.csv", "--output", "out.csv", "--sample", "100", "--parallel-columns",
+        ]).unwrap_err();
+        assert_eq!(conflict.kind(), clap::error::ErrorKind::ArgumentConflict);
     }
-}
\ No newline at end of file
+}
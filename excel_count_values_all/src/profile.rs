@@ -0,0 +1,128 @@
+// Quick profile: one cheap pass that answers "what is in this file?" before
+// the full analysis runs (row and column counts, delimiter, BOM, ragged rows).
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use csv::ReaderBuilder;
+
+use shared::delimiter::{delimiter_name, resolve_delimiter};
+use super::is_numeric_value;
+
+const UTF8_BOM: [u8; 3] = [0xEF, 0xBB, 0xBF];
+
+pub struct FileProfile {
+    // Data rows, not counting the header
+    pub record_count: usize,
+    // Fewest and most fields seen on any row, header included
+    pub min_fields: usize,
+    pub max_fields: usize,
+    pub delimiter: u8,
+    pub has_bom: bool,
+    pub non_empty_cells: usize,
+    pub numeric_cells: usize,
+}
+
+impl FileProfile {
+    pub fn is_ragged(&self) -> bool {
+        self.min_fields != self.max_fields
+    }
+
+    // More than half of the non-empty data cells parse as numbers
+    pub fn is_numeric_heavy(&self) -> bool {
+        self.non_empty_cells > 0 && self.numeric_cells * 2 > self.non_empty_cells
+    }
+}
+
+// The delimiter is the --delimiter override, or detected the same way the
+// full analysis does
+pub fn profile_file(file_path: &str, delimiter: Option<u8>) -> Result<FileProfile, Box<dyn Error>> {
+    let delimiter = resolve_delimiter(file_path, delimiter)?;
+    let mut input = BufReader::new(File::open(file_path)?);
+
+    let has_bom = input.fill_buf()?.starts_with(&UTF8_BOM);
+    if has_bom {
+        input.consume(UTF8_BOM.len());
+    }
+
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_reader(input);
+
+    let mut profile = FileProfile {
+        record_count: 0,
+        min_fields: usize::MAX,
+        max_fields: 0,
+        delimiter,
+        has_bom,
+        non_empty_cells: 0,
+        numeric_cells: 0,
+    };
+
+    for (row_index, record_result) in reader.byte_records().enumerate() {
+        let record = record_result?;
+        profile.min_fields = profile.min_fields.min(record.len());
+        profile.max_fields = profile.max_fields.max(record.len());
+        if row_index == 0 {
+            continue;
+        }
+
+        profile.record_count += 1;
+        for field in record.iter() {
+            let value = String::from_utf8_lossy(field);
+            let value = value.trim();
+            if !value.is_empty() {
+                profile.non_empty_cells += 1;
+                if is_numeric_value(value) {
+                    profile.numeric_cells += 1;
+                }
+            }
+        }
+    }
+
+    if profile.max_fields == 0 {
+        profile.min_fields = 0;
+    }
+    Ok(profile)
+}
+
+pub fn print_profile(file_path: &str, profile: &FileProfile) {
+    println!("Profile of {}", file_path);
+    println!("  Records: {}", profile.record_count);
+    println!("  Fields per row: {}..{}{}", profile.min_fields, profile.max_fields,
+        if profile.is_ragged() { " (ragged)" } else { "" });
+    println!("  Delimiter: {}", delimiter_name(profile.delimiter));
+    println!("  UTF-8 BOM: {}", if profile.has_bom { "yes" } else { "no" });
+    println!("  Numeric cells: {} of {} non-empty{}", profile.numeric_cells, profile.non_empty_cells,
+        if profile.is_numeric_heavy() { " (numeric-heavy)" } else { "" });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ragged_file_profile() {
+        let path = std::env::temp_dir().join(format!("count_values_profile_{}.csv", std::process::id()));
+        let mut content = UTF8_BOM.to_vec();
+        content.extend_from_slice("id;age;score\n1;34;2.5\n2;41\n3;29;3.1;extra\n".as_bytes());
+        std::fs::write(&path, content).unwrap();
+
+        let profile = profile_file(path.to_str().unwrap(), None).unwrap();
+        // --delimiter wins over detection
+        let overridden = profile_file(path.to_str().unwrap(), Some(b',')).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(profile.record_count, 3);
+        assert_eq!((profile.min_fields, profile.max_fields), (2, 4));
+        assert!(profile.is_ragged());
+        assert!(profile.has_bom);
+        assert_eq!(profile.delimiter, b';');
+        assert_eq!((profile.numeric_cells, profile.non_empty_cells), (8, 9));
+        assert!(profile.is_numeric_heavy());
+        assert_eq!(overridden.delimiter, b',');
+        assert_eq!((overridden.min_fields, overridden.max_fields), (1, 1));
+    }
+}
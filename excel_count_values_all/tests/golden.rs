@@ -0,0 +1,25 @@
+// End-to-end run of the excel_count_values_all binary over tests/fixtures,
+// diffed against the committed output in tests/golden. After an intended
+// output change, run with UPDATE_GOLDEN=1 to rewrite the golden files and
+// review the diff.
+
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+
+use shared::golden::{assert_matches_golden, tests_dir};
+
+#[test]
+fn test_patients_match_golden() {
+    let output = std::env::temp_dir().join(format!("count_values_golden_{}.csv", std::process::id()));
+
+    Command::cargo_bin("excel_count_values_all").unwrap()
+        .arg("--input").arg(tests_dir().join("fixtures/patients.csv"))
+        .arg("--output").arg(&output)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Results saved to"));
+
+    assert_matches_golden(&output, &tests_dir().join("golden/patients_analysis.csv"));
+    fs::remove_file(&output).ok();
+}
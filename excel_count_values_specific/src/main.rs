@@ -1,12 +1,13 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 use std::error::Error;
 use std::fs::File;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use csv::{ReaderBuilder, WriterBuilder};
-use encoding_rs::UTF_8;
 use encoding_rs_io::DecodeReaderBytesBuilder;
 
+use shared::percentage::percentage;
+
 struct ColumnStats {
     name: String,
     unique_count: usize,
@@ -15,6 +16,8 @@ struct ColumnStats {
     total_rows: usize,
     quality_score: f64,
     recommendation: String,
+    // (value, count) of every non-missing value, most frequent first
+    value_counts: Vec<(String, usize)>,
 }
 
 fn calculate_quality_score(stats: &ColumnStats) -> f64 {
@@ -55,28 +58,28 @@ fn calculate_quality_score(stats: &ColumnStats) -> f64 {
 
 fn get_recommendation(stats: &ColumnStats) -> String {
     let non_missing_rows = stats.total_rows - stats.missing_count;
-    let missing_percentage = (stats.missing_count as f64 / stats.total_rows as f64 * 100.0).round();
-    let zero_percentage = (stats.zero_count as f64 / stats.total_rows as f64 * 100.0).round();
-    let non_zero_percentage = ((non_missing_rows - stats.zero_count) as f64 / stats.total_rows as f64 * 100.0).round();
+    let missing_percentage = percentage(stats.missing_count, stats.total_rows);
+    let zero_percentage = percentage(stats.zero_count, stats.total_rows);
+    let non_zero_percentage = percentage(non_missing_rows - stats.zero_count, stats.total_rows);
 
     if missing_percentage > 50.0 {
-        return "High missing values - Consider excluding".to_string();
+        "High missing values - Consider excluding".to_string()
     } else if zero_percentage > 70.0 {
-        return "Mostly zeros - Consider excluding or special handling".to_string();
+        "Mostly zeros - Consider excluding or special handling".to_string()
     } else if stats.unique_count == 1 {
-        return "Single value column - No variability".to_string();
+        "Single value column - No variability".to_string()
     } else if stats.unique_count == 2 {
-        return "Binary column - Very low variability".to_string();
+        "Binary column - Very low variability".to_string()
     } else if stats.unique_count <= 5 {
-        return "Low cardinality column - Limited variability".to_string();
+        "Low cardinality column - Limited variability".to_string()
     } else if non_zero_percentage < 20.0 {
-        return "Low information content - Review necessity".to_string();
+        "Low information content - Review necessity".to_string()
     } else if stats.quality_score > 80.0 {
-        return "Good quality - Use as is".to_string();
+        "Good quality - Use as is".to_string()
     } else if stats.quality_score > 60.0 {
-        return "Moderate quality - Consider cleaning".to_string();
+        "Moderate quality - Consider cleaning".to_string()
     } else {
-        return "Poor quality - Needs investigation".to_string();
+        "Poor quality - Needs investigation".to_string()
     }
 }
 
@@ -93,6 +96,12 @@ fn analyze_csv(file_path: &str, output_path: &str) -> Result<(), Box<dyn Error>>
 
     let headers = reader.headers()?.clone();
 
+    // Every percentage below would be 0/0
+    if reader.records().next().is_none() {
+        println!("{}: file has a header but no data rows, nothing to analyze", file_path);
+        return Ok(());
+    }
+
     let mut target_columns = Vec::new();
     for (index, header) in headers.iter().enumerate() {
         if header.contains("فولیکول") || header.contains("فولیکل") {
@@ -108,7 +117,7 @@ fn analyze_csv(file_path: &str, output_path: &str) -> Result<(), Box<dyn Error>>
     let mut results = Vec::new();
 
     for &column_index in &target_columns {
-        let mut unique_values = HashSet::new();
+        let mut value_counts: HashMap<String, usize> = HashMap::new();
         let mut missing_count = 0;
         let mut zero_count = 0;
         let mut total_rows = 0;
@@ -139,21 +148,25 @@ fn analyze_csv(file_path: &str, output_path: &str) -> Result<(), Box<dyn Error>>
                     if trimmed_value.chars().all(|c| c == '0' || c == '.') {
                         zero_count += 1;
                     }
-                    unique_values.insert(value.to_string());
+                    *value_counts.entry(value.to_string()).or_insert(0) += 1;
                 }
             } else {
                 missing_count += 1;
             }
         }
 
+        let mut value_counts: Vec<(String, usize)> = value_counts.into_iter().collect();
+        value_counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
         let column_stats = ColumnStats {
             name: headers.get(column_index).unwrap_or("Unknown Column").to_string(),
-            unique_count: unique_values.len(),
+            unique_count: value_counts.len(),
             missing_count,
             zero_count,
             total_rows,
             quality_score: 0.0, // Placeholder, will be calculated
             recommendation: String::new(), // Placeholder, will be calculated
+            value_counts,
         };
 
         let mut final_stats = column_stats;
@@ -164,7 +177,10 @@ fn analyze_csv(file_path: &str, output_path: &str) -> Result<(), Box<dyn Error>>
     }
 
     // Sort results by quality score in descending order
-    results.sort_by(|a, b| b.quality_score.partial_cmp(&a.quality_score).unwrap());
+    results.sort_by(|a, b| b.quality_score.total_cmp(&a.quality_score));
+
+    let frequencies_path = frequencies_path(output_path);
+    write_frequencies(&results, &frequencies_path)?;
 
     // Create output file and write UTF-8 BOM
     let mut file = File::create(output_path)?;
@@ -174,7 +190,7 @@ fn analyze_csv(file_path: &str, output_path: &str) -> Result<(), Box<dyn Error>>
         .has_headers(true)
         .from_writer(file);
 
-    writer.write_record(&[
+    writer.write_record([
         "Column Name",
         "Quality Score",
         "Unique Value Count",
@@ -188,10 +204,9 @@ fn analyze_csv(file_path: &str, output_path: &str) -> Result<(), Box<dyn Error>>
     ])?;
 
     for stats in results {
-        let missing_percentage = (stats.missing_count as f64 / stats.total_rows as f64 * 100.0).round();
-        let zero_percentage = (stats.zero_count as f64 / stats.total_rows as f64 * 100.0).round();
-        let valid_percentage = ((stats.total_rows - stats.missing_count - stats.zero_count) as f64 
-            / stats.total_rows as f64 * 100.0).round();
+        let missing_percentage = percentage(stats.missing_count, stats.total_rows);
+        let zero_percentage = percentage(stats.zero_count, stats.total_rows);
+        let valid_percentage = percentage(stats.total_rows - stats.missing_count - stats.zero_count, stats.total_rows);
 
         writer.write_record(&[
             stats.name,
@@ -209,6 +224,42 @@ fn analyze_csv(file_path: &str, output_path: &str) -> Result<(), Box<dyn Error>>
 
     writer.flush()?;
     println!("Results saved to {}", output_path);
+    println!("Value frequencies saved to {}", frequencies_path.display());
+    Ok(())
+}
+
+// analysis_results.csv -> analysis_results_frequencies.csv
+fn frequencies_path(output_path: &str) -> PathBuf {
+    let path = Path::new(output_path);
+    let stem = path.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!("{}_frequencies.csv", stem))
+}
+
+// How often each value occurs in every targeted column, so a default "0"
+// stuffed into many rows stands out. Percentages are of the column's
+// non-missing rows.
+fn write_frequencies(results: &[ColumnStats], path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut file = File::create(path)?;
+    file.write_all(&[0xEF, 0xBB, 0xBF])?;
+
+    let mut writer = WriterBuilder::new()
+        .has_headers(true)
+        .from_writer(file);
+
+    writer.write_record(["Column Name", "Value", "Count", "Percentage"])?;
+    for stats in results {
+        let non_missing_rows = stats.total_rows - stats.missing_count;
+        for (value, count) in &stats.value_counts {
+            writer.write_record(&[
+                stats.name.clone(),
+                value.clone(),
+                count.to_string(),
+                format!("{}%", percentage(*count, non_missing_rows)),
+            ])?;
+        }
+    }
+
+    writer.flush()?;
     Ok(())
 }
 
@@ -224,4 +275,45 @@ fn main() {
     if let Err(err) = analyze_csv(input_file_path, output_file_path) {
         println!("Error analyzing CSV: {}", err);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frequencies_sum_to_non_missing_rows() {
+        let dir = std::env::temp_dir().join(format!("count_values_specific_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("pco.csv");
+        std::fs::write(&input, "id,تعداد فولیکول راست,age\n1,0,30\n2,0,31\n3,12,\n4,,28\n5,0,29\n6,8,35\n7,12,33\n").unwrap();
+        let output = dir.join("analysis_results.csv");
+
+        analyze_csv(input.to_str().unwrap(), output.to_str().unwrap()).unwrap();
+        let written = std::fs::read_to_string(dir.join("analysis_results_frequencies.csv")).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+
+        let rows: Vec<Vec<&str>> = written.trim_start_matches('\u{feff}').lines().skip(1).map(|l| l.split(',').collect()).collect();
+        assert_eq!(rows[0], vec!["تعداد فولیکول راست", "0", "3", "50%"]);
+        assert_eq!(rows[1], vec!["تعداد فولیکول راست", "12", "2", "33%"]);
+        assert_eq!(rows.len(), 3);
+        let total: usize = rows.iter().map(|row| row[2].parse::<usize>().unwrap()).sum();
+        assert_eq!(total, 6);
+    }
+
+    #[test]
+    fn test_header_only_file_writes_nothing() {
+        let dir = std::env::temp_dir().join(format!("count_values_specific_empty_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("pco.csv");
+        std::fs::write(&input, "\u{FEFF}id,تعداد فولیکول راست\n").unwrap();
+        let output = dir.join("analysis_results.csv");
+
+        let result = analyze_csv(input.to_str().unwrap(), output.to_str().unwrap());
+        let written = output.exists() || frequencies_path(output.to_str().unwrap()).exists();
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert!(result.is_ok());
+        assert!(!written);
+    }
+}
@@ -1,37 +1,124 @@
 use calamine::{open_workbook_auto, Reader, DataType};
 use std::path::Path;
+use shared::excel_column::number_to_excel_column;
+
+// --sheet <name> limits output to one sheet, --grep <substr> keeps matching
+// headers only (case-insensitive), --with-index prints each header's position
+#[derive(Debug, Default)]
+struct HeadingOptions {
+    sheet: Option<String>,
+    grep: Option<String>,
+    with_index: bool,
+}
+
+impl HeadingOptions {
+    fn from_args(args: &[String]) -> Self {
+        let value_of = |flag: &str| args.iter()
+            .position(|a| a == flag)
+            .and_then(|i| args.get(i + 1))
+            .cloned();
+
+        HeadingOptions {
+            sheet: value_of("--sheet"),
+            grep: value_of("--grep"),
+            with_index: args.iter().any(|a| a == "--with-index"),
+        }
+    }
+}
+
+fn cell_to_heading(cell: &DataType) -> String {
+    match cell {
+        DataType::String(s) => s.clone(),
+        DataType::Int(i) => i.to_string(),
+        DataType::Float(f) => f.to_string(),
+        DataType::Bool(b) => b.to_string(),
+        DataType::Error(e) => format!("Error({:?})", e),
+        DataType::Empty => String::from("Empty"),
+        DataType::DateTime(_) => String::from("DateTime"),
+        DataType::DateTimeIso(_) => String::from("DateTimeIso"),
+        DataType::DurationIso(_) => String::from("DurationIso"),
+    }
+}
+
+// One output line per header that passes the --grep filter
+fn format_headings(headings: &[String], options: &HeadingOptions) -> Vec<String> {
+    let needle = options.grep.as_ref().map(|g| g.to_lowercase());
+
+    headings.iter()
+        .enumerate()
+        .filter(|(_, heading)| needle.as_ref().is_none_or(|n| heading.to_lowercase().contains(n)))
+        .map(|(idx, heading)| {
+            if options.with_index {
+                format!("{:>4} {:>4}  {}", idx, number_to_excel_column(idx), heading)
+            } else {
+                heading.clone()
+            }
+        })
+        .collect()
+}
 
 fn main() {
     // Specify the path to your Excel file
     let path = Path::new("/home/aricept094/mydata/First_Rabbit_series.xlsx");
 
+    let args: Vec<String> = std::env::args().collect();
+    let options = HeadingOptions::from_args(&args);
+
     // Open the Excel file
     let mut workbook = open_workbook_auto(path).expect("Cannot open Excel file");
 
     // Iterate over all sheets in the workbook
     for sheet_name in workbook.sheet_names().to_owned() {
-        println!("Sheet: {}", sheet_name);
+        if options.sheet.as_deref().is_some_and(|wanted| wanted != sheet_name) {
+            continue;
+        }
 
         // Read the sheet
         if let Some(Ok(range)) = workbook.worksheet_range(&sheet_name) {
             // Get the first row (headings)
             if let Some(first_row) = range.rows().next() {
-                let headings: Vec<String> = first_row.iter()
-                    .map(|cell| match cell {
-                        DataType::String(s) => s.clone(),
-                        DataType::Int(i) => i.to_string(),
-                        DataType::Float(f) => f.to_string(),
-                        DataType::Bool(b) => b.to_string(),
-                        DataType::Error(e) => format!("Error({:?})", e),
-                        DataType::Empty => String::from("Empty"),
-                        DataType::DateTime(_) => String::from("DateTime"),
-                        DataType::DateTimeIso(_) => String::from("DateTimeIso"),
-                        DataType::DurationIso(_) => String::from("DurationIso"),
-                    })
-                    .collect();
-
-                println!("Headings: {:?}", headings);
+                let headings: Vec<String> = first_row.iter().map(cell_to_heading).collect();
+                let lines = format_headings(&headings, &options);
+
+                if options.grep.is_some() {
+                    println!("Sheet: {} ({} of {} headers match)", sheet_name, lines.len(), headings.len());
+                } else {
+                    println!("Sheet: {} ({} headers)", sheet_name, headings.len());
+                }
+                for line in lines {
+                    println!("  {}", line);
+                }
             }
+        } else {
+            println!("Sheet: {} (unreadable)", sheet_name);
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headings(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_grep_filters_headers() {
+        let options = HeadingOptions { grep: Some("weight".to_string()), ..Default::default() };
+        let lines = format_headings(&headings(&["ID", "Body Weight", "Age", "weight_gain"]), &options);
+        assert_eq!(lines, vec!["Body Weight", "weight_gain"]);
+    }
+
+    #[test]
+    fn test_with_index_emits_excel_letters() {
+        let mut names: Vec<String> = (0..28).map(|i| format!("col{}", i)).collect();
+        names[0] = "ID".to_string();
+        let options = HeadingOptions { with_index: true, ..Default::default() };
+        let lines = format_headings(&names, &options);
+
+        assert_eq!(lines[0], "   0    A  ID");
+        assert_eq!(lines[25], "  25    Z  col25");
+        assert_eq!(lines[27], "  27   AB  col27");
+    }
+}
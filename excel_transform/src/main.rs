@@ -1,25 +1,172 @@
+use std::collections::HashSet;
 use std::time::Instant;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use csv::{ReaderBuilder, WriterBuilder};
-use std::fs::File;
-use std::io::{BufReader, BufWriter, Write};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Write};
 use encoding_rs_io::DecodeReaderBytesBuilder;
+use shared::excel_column::number_to_excel_column;
+use shared::preview;
+
+// Optional behaviour on top of the empty row/column filtering
+#[derive(Debug, Default)]
+struct TransformOptions {
+    transpose: bool, // --transpose: write the filtered matrix with rows and columns swapped
+    deduplicate_rows: bool, // --deduplicate-rows: drop repeated data rows, keeping the first
+    dedupe_key: Vec<String>, // --dedupe-key a,b: compare rows on these columns only (default: every cell)
+    preview: Option<usize>, // --preview N: print the header and first N written rows as a table
+    columns_report: Option<String>, // --columns-report <path>: list the kept columns with their original positions
+}
+
+impl TransformOptions {
+    fn from_args(args: &[String]) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(TransformOptions {
+            transpose: args.iter().any(|a| a == "--transpose"),
+            deduplicate_rows: args.iter().any(|a| a == "--deduplicate-rows"),
+            dedupe_key: args.iter()
+                .position(|a| a == "--dedupe-key")
+                .and_then(|i| args.get(i + 1))
+                .map(|list| list.split(',').map(|name| name.trim().to_string()).filter(|name| !name.is_empty()).collect())
+                .unwrap_or_default(),
+            preview: preview::preview_rows_from_args(args)?,
+            columns_report: match args.iter().position(|a| a == "--columns-report") {
+                Some(i) => Some(args.get(i + 1).ok_or("--columns-report needs a path")?.clone()),
+                None => None,
+            },
+        })
+    }
+}
+
+// Positions of the --dedupe-key columns in the header row
+fn dedupe_key_columns(header: &[String], names: &[String]) -> Result<Vec<usize>, Box<dyn std::error::Error>> {
+    names.iter()
+        .map(|name| header.iter()
+            .position(|h| h.trim() == name)
+            .ok_or_else(|| format!("--dedupe-key column '{}' not found", name).into()))
+        .collect()
+}
+
+// The rows whose signature (the key cells, or the whole row when there is no
+// key) hasn't been seen before, plus how many were dropped. The header row is
+// always kept.
+fn deduplicate_rows(data: &[Vec<String>], rows: &[usize], key_columns: &[usize]) -> (Vec<usize>, usize) {
+    let mut seen: HashSet<Vec<&str>> = HashSet::new();
+    let mut kept = Vec::with_capacity(rows.len());
+    let mut removed = 0;
+
+    for &row_idx in rows {
+        let row = &data[row_idx];
+        let signature: Vec<&str> = if key_columns.is_empty() {
+            row.iter().map(String::as_str).collect()
+        } else {
+            key_columns.iter().map(|&i| row.get(i).map_or("", String::as_str)).collect()
+        };
+        if row_idx == 0 || seen.insert(signature) {
+            kept.push(row_idx);
+        } else {
+            removed += 1;
+        }
+    }
 
-fn number_to_excel_column(mut n: usize) -> String {
-    let mut result = String::new();
-    n += 1;
+    (kept, removed)
+}
 
-    while n > 0 {
-        n -= 1;
-        let remainder = n % 26;
-        result.insert(0, (b'A' + remainder as u8) as char);
-        n /= 26;
+// One row per kept column so output columns can be traced back to the input:
+// original 0-based index, its Excel letter, the 0-based output position and
+// how empty it was
+fn write_columns_report(path: &str, columns_to_keep: &[usize], column_empty_percentages: &[(usize, f64)]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&[0xEF, 0xBB, 0xBF])?;
+    let mut writer = WriterBuilder::new().from_writer(file);
+    writer.write_record(["Original Index", "Excel Column", "New Position", "Empty %"])?;
+
+    for (new_position, &original_idx) in columns_to_keep.iter().enumerate() {
+        writer.write_record([
+            original_idx.to_string(),
+            number_to_excel_column(original_idx),
+            new_position.to_string(),
+            format!("{:.2}", column_empty_percentages[original_idx].1),
+        ])?;
     }
 
-    result
+    writer.flush()?;
+    Ok(())
+}
+
+// Rows become columns; ragged rows are padded with empty cells to the widest row
+fn transpose(data: &[Vec<String>]) -> Vec<Vec<String>> {
+    let width = data.iter().map(|row| row.len()).max().unwrap_or(0);
+    (0..width)
+        .map(|col_idx| {
+            data.iter()
+                .map(|row| row.get(col_idx).cloned().unwrap_or_default())
+                .collect()
+        })
+        .collect()
 }
 
-fn process_csv(input_path: &str, output_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+// Where the filtered CSV goes: --in-place overwrites the input after saving
+// it as <input>.bak (.bak.1, ... once taken), --out <path> writes a separate
+// file. One of the two is required.
+#[derive(Debug, PartialEq)]
+enum OutputTarget {
+    InPlace,
+    Path(PathBuf),
+}
+
+impl OutputTarget {
+    fn from_args(args: &[String]) -> Result<Self, Box<dyn std::error::Error>> {
+        let in_place = args.iter().any(|a| a == "--in-place");
+        let out = match args.iter().position(|a| a == "--out") {
+            Some(i) => Some(args.get(i + 1).ok_or("--out needs a path")?),
+            None => None,
+        };
+
+        match (in_place, out) {
+            (true, Some(_)) => Err("--in-place and --out cannot be used together".into()),
+            (true, None) => Ok(OutputTarget::InPlace),
+            (false, Some(path)) => Ok(OutputTarget::Path(PathBuf::from(path))),
+            (false, None) => Err("pass --out <path> or --in-place to say where the output goes".into()),
+        }
+    }
+}
+
+// data.csv -> data.csv.bak, then data.csv.bak.1, data.csv.bak.2, ...
+fn backup_path(input_path: &Path, attempt: usize) -> PathBuf {
+    let mut name = input_path.as_os_str().to_owned();
+    name.push(".bak");
+    if attempt > 0 {
+        name.push(format!(".{}", attempt));
+    }
+    PathBuf::from(name)
+}
+
+// Copy the input to the first backup name not taken yet, so a second
+// --in-place run never overwrites the original kept by the first one
+fn create_backup(input_path: &Path) -> io::Result<PathBuf> {
+    let mut attempt = 0;
+    loop {
+        let backup = backup_path(input_path, attempt);
+        match OpenOptions::new().write(true).create_new(true).open(&backup) {
+            Ok(mut file) => {
+                io::copy(&mut File::open(input_path)?, &mut file)?;
+                return Ok(backup);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => attempt += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// Keep the original as a .bak and write the filtered data over the input.
+// The backup is the one read, so a failed run leaves it untouched.
+fn process_csv_in_place(input_path: &str, options: &TransformOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let backup = create_backup(Path::new(input_path))?;
+    println!("Backup saved to: {}", backup.display());
+    process_csv(backup.to_str().ok_or("Backup path is not valid UTF-8")?, input_path, options)
+}
+
+fn process_csv(input_path: &str, output_path: &str, options: &TransformOptions) -> Result<(), Box<dyn std::error::Error>> {
     let timer = Instant::now();
     println!("Processing file: {}", input_path);
 
@@ -101,6 +248,13 @@ fn process_csv(input_path: &str, output_path: &str) -> Result<(), Box<dyn std::e
         .map(|(idx, _)| *idx)
         .collect();
 
+    let (rows_to_keep, duplicates_removed) = if options.deduplicate_rows {
+        let key_columns = dedupe_key_columns(&data[0], &options.dedupe_key)?;
+        deduplicate_rows(&data, &rows_to_keep, &key_columns)
+    } else {
+        (rows_to_keep, 0)
+    };
+
     // Create output file and write BOM
     let mut output_file = BufWriter::new(File::create(output_path)?);
     output_file.write_all(&[0xEF, 0xBB, 0xBF])?; // Write UTF-8 BOM
@@ -110,7 +264,8 @@ fn process_csv(input_path: &str, output_path: &str) -> Result<(), Box<dyn std::e
         .flexible(true)
         .from_writer(output_file);
 
-    // Write data for kept columns and rows
+    // Collect data for kept columns and rows
+    let mut output_rows: Vec<Vec<String>> = Vec::with_capacity(rows_to_keep.len());
     for &original_row_idx in &rows_to_keep {
         if let Some(row) = data.get(original_row_idx) {
             let filtered_row: Vec<String> = columns_to_keep.iter()
@@ -123,13 +278,26 @@ fn process_csv(input_path: &str, output_path: &str) -> Result<(), Box<dyn std::e
                 })
                 .collect();
             
-            writer.write_record(&filtered_row)?;
+            output_rows.push(filtered_row);
         }
     }
 
+    if options.transpose {
+        output_rows = transpose(&output_rows);
+    }
+
+    for row in &output_rows {
+        writer.write_record(row)?;
+    }
+
     // Flush the writer to ensure all data is written
     writer.flush()?;
 
+    if let Some(report_path) = &options.columns_report {
+        write_columns_report(report_path, &columns_to_keep, &column_empty_percentages)?;
+        println!("Columns report saved to: {}", report_path);
+    }
+
     println!("\nColumn analysis:");
     println!("Original columns: {}", width);
     println!("Columns kept: {}", columns_to_keep.len());
@@ -149,6 +317,9 @@ fn process_csv(input_path: &str, output_path: &str) -> Result<(), Box<dyn std::e
     println!("Original rows: {}", height);
     println!("Rows kept: {}", rows_to_keep.len());
     println!("Rows dropped: {}", height - rows_to_keep.len());
+    if options.deduplicate_rows {
+        println!("Duplicate rows removed: {}", duplicates_removed);
+    }
     println!("Dropped rows (≥90.77% empty):");
 
     for (idx, percentage) in row_empty_percentages.iter() {
@@ -162,25 +333,171 @@ fn process_csv(input_path: &str, output_path: &str) -> Result<(), Box<dyn std::e
     println!("\nProcessing completed in {:?}", timer.elapsed());
     println!("Output saved to: {}", output_path);
 
+    if let (Some(n), Some((header, rows))) = (options.preview, output_rows.split_first()) {
+        println!();
+        preview::write_preview(&mut std::io::stdout().lock(), header, rows, n)?;
+    }
+
     Ok(())
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let files = vec![
-        ("/home/aricept094/mydata/endometriosis/merged_endometriosis_data.csv", "/home/aricept094/mydata/endometriosis/merged_endometriosis_data_cleaned.csv"),
-    ];
-
-    for (input_file, output_name) in files {
-        // Create output path in same directory as input file
-        let input_path = Path::new(input_file);
-        let parent_dir = input_path.parent().unwrap_or_else(|| Path::new(""));
-        let output_path = parent_dir.join(output_name);
-
-        match process_csv(input_file, output_path.to_str().unwrap()) {
-            Ok(_) => println!("\nSuccessfully processed {}", input_file),
-            Err(e) => println!("\nError processing {}: {}", input_file, e),
-        }
+    let args: Vec<String> = std::env::args().collect();
+    let options = TransformOptions::from_args(&args)?;
+
+    // --input <file>: the CSV to filter
+    let input_file = match args.iter().position(|a| a == "--input") {
+        Some(i) => args.get(i + 1).ok_or("--input needs a path")?.clone(),
+        None => return Err("--input <file> is required".into()),
+    };
+
+    let result = match OutputTarget::from_args(&args)? {
+        OutputTarget::InPlace => process_csv_in_place(&input_file, &options),
+        OutputTarget::Path(out) => process_csv(&input_file, out.to_str().ok_or("--out path is not valid UTF-8")?, &options),
+    };
+
+    match result {
+        Ok(_) => println!("\nSuccessfully processed {}", input_file),
+        Err(e) => println!("\nError processing {}: {}", input_file, e),
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(cells: &[&str]) -> Vec<String> {
+        cells.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn test_transpose_swaps_rows_and_columns() {
+        let data = vec![row(&["a", "b", "c"]), row(&["1", "2", "3"])];
+        let transposed = transpose(&data);
+        assert_eq!(transposed, vec![row(&["a", "1"]), row(&["b", "2"]), row(&["c", "3"])]);
+    }
+
+    #[test]
+    fn test_transpose_pads_ragged_rows() {
+        let data = vec![row(&["a", "b", "c"]), row(&["1"])];
+        let transposed = transpose(&data);
+        assert_eq!(transposed, vec![row(&["a", "1"]), row(&["b", ""]), row(&["c", ""])]);
+    }
+
+    #[test]
+    fn test_process_csv_writes_transposed_output_with_bom() {
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("transpose_in_{}.csv", std::process::id()));
+        let output = dir.join(format!("transpose_out_{}.csv", std::process::id()));
+        std::fs::write(&input, "id,age,site\n1,30,A\n").unwrap();
+
+        let options = TransformOptions { transpose: true, ..Default::default() };
+        process_csv(input.to_str().unwrap(), output.to_str().unwrap(), &options).unwrap();
+
+        let bytes = std::fs::read(&output).unwrap();
+        assert_eq!(&bytes[..3], &[0xEF, 0xBB, 0xBF]);
+        let text = String::from_utf8(bytes[3..].to_vec()).unwrap();
+        assert_eq!(text.lines().collect::<Vec<_>>(), vec!["id,1", "age,30", "site,A"]);
+
+        std::fs::remove_file(input).ok();
+        std::fs::remove_file(output).ok();
+    }
+
+    #[test]
+    fn test_in_place_keeps_backup_of_original() {
+        let input = std::env::temp_dir().join(format!("transform_in_place_{}.csv", std::process::id()));
+        let original = "id,notes,age\n1,,30\n2,,41\n3,,29\n4,,35\n";
+        std::fs::write(&input, original).unwrap();
+
+        process_csv_in_place(input.to_str().unwrap(), &TransformOptions::default()).unwrap();
+        let filtered = std::fs::read(&input).unwrap();
+        // A second run keeps the first backup and takes the next name
+        process_csv_in_place(input.to_str().unwrap(), &TransformOptions::default()).unwrap();
+
+        let (first, second) = (backup_path(&input, 0), backup_path(&input, 1));
+        let backup_text = std::fs::read_to_string(&first).unwrap();
+        let second_backup = std::fs::read(&second).unwrap();
+        for path in [&input, &first, &second] {
+            std::fs::remove_file(path).ok();
+        }
+
+        assert_eq!(backup_text, original);
+        assert_eq!(second_backup, filtered);
+        assert!(second.to_str().unwrap().ends_with(".csv.bak.1"));
+        let text = String::from_utf8(filtered[3..].to_vec()).unwrap();
+        assert_eq!(text, "id,age\n1,30\n2,41\n3,29\n4,35\n");
+    }
+
+    #[test]
+    fn test_options_from_args_reads_transpose() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let options = TransformOptions::from_args(&args(&["excel_transform", "--transpose", "--dedupe-key", "id, visit"])).unwrap();
+        assert!(options.transpose);
+        assert!(!options.deduplicate_rows);
+        assert_eq!(options.dedupe_key, vec!["id".to_string(), "visit".to_string()]);
+        assert!(!TransformOptions::from_args(&args(&["excel_transform"])).unwrap().transpose);
+    }
+
+    #[test]
+    fn test_in_place_and_out_conflict() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert!(OutputTarget::from_args(&args(&["--in-place", "--out", "x.csv"])).is_err());
+        assert_eq!(OutputTarget::from_args(&args(&["--in-place"])).unwrap(), OutputTarget::InPlace);
+        assert_eq!(OutputTarget::from_args(&args(&["--out", "x.csv"])).unwrap(), OutputTarget::Path(PathBuf::from("x.csv")));
+        let neither = OutputTarget::from_args(&[]).unwrap_err().to_string();
+        assert!(neither.contains("--out") && neither.contains("--in-place"), "{}", neither);
+    }
+
+    #[test]
+    fn test_deduplicate_rows_keeps_first_copy() {
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("transform_dedupe_in_{}.csv", std::process::id()));
+        let output = dir.join(format!("transform_dedupe_out_{}.csv", std::process::id()));
+        std::fs::write(&input, "id,visit,age\n1,a,30\n2,a,41\n1,a,30\n1,b,30\n").unwrap();
+
+        let options = TransformOptions { deduplicate_rows: true, ..Default::default() };
+        process_csv(input.to_str().unwrap(), output.to_str().unwrap(), &options).unwrap();
+        let bytes = std::fs::read(&output).unwrap();
+        std::fs::remove_file(&input).ok();
+        std::fs::remove_file(&output).ok();
+
+        let text = String::from_utf8(bytes[3..].to_vec()).unwrap();
+        assert_eq!(text, "id,visit,age\n1,a,30\n2,a,41\n1,b,30\n");
+
+        let data = vec![row(&["id", "visit"]), row(&["1", "a"]), row(&["1", "a"]), row(&["1", "b"])];
+        assert_eq!(deduplicate_rows(&data, &[0, 1, 2, 3], &[]), (vec![0, 1, 3], 1));
+        let key = dedupe_key_columns(&data[0], &["id".to_string()]).unwrap();
+        assert_eq!(deduplicate_rows(&data, &[0, 1, 2, 3], &key), (vec![0, 1], 2));
+        assert!(dedupe_key_columns(&data[0], &["site".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_columns_report_traces_kept_columns() {
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("transform_report_in_{}.csv", std::process::id()));
+        let output = dir.join(format!("transform_report_out_{}.csv", std::process::id()));
+        let report = dir.join(format!("transform_report_cols_{}.csv", std::process::id()));
+        std::fs::write(&input, "id,notes,age,extra,site\n1,,30,,A\n2,,41,,B\n3,,29,,C\n4,,35,,D\n").unwrap();
+
+        let options = TransformOptions { columns_report: Some(report.to_str().unwrap().to_string()), ..Default::default() };
+        process_csv(input.to_str().unwrap(), output.to_str().unwrap(), &options).unwrap();
+        let output_bytes = std::fs::read(&output).unwrap();
+        let report_bytes = std::fs::read(&report).unwrap();
+        for path in [&input, &output, &report] {
+            std::fs::remove_file(path).ok();
+        }
+
+        let output_text = String::from_utf8(output_bytes[3..].to_vec()).unwrap();
+        let output_width = output_text.lines().next().unwrap().split(',').count();
+        let report_text = String::from_utf8(report_bytes[3..].to_vec()).unwrap();
+        let rows: Vec<Vec<&str>> = report_text.lines().skip(1).map(|line| line.split(',').collect()).collect();
+
+        assert_eq!(rows.len(), output_width);
+        let original: Vec<usize> = rows.iter().map(|r| r[0].parse().unwrap()).collect();
+        assert!(original.windows(2).all(|w| w[0] < w[1]), "{:?}", original);
+        assert_eq!(rows[1], vec!["2", "C", "1", "0.00"]);
+        assert_eq!(rows[2], vec!["4", "E", "2", "0.00"]);
+    }
+}
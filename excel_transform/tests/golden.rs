@@ -0,0 +1,24 @@
+// End-to-end run of the excel_transform binary over tests/fixtures, diffed
+// against the committed output in tests/golden. After an intended output
+// change, run with UPDATE_GOLDEN=1 to rewrite the golden files and review the diff.
+
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+
+use shared::golden::{assert_matches_golden, tests_dir};
+
+#[test]
+fn test_visits_match_golden() {
+    let output = std::env::temp_dir().join(format!("excel_transform_golden_{}.csv", std::process::id()));
+
+    Command::cargo_bin("excel_transform").unwrap()
+        .arg("--input").arg(tests_dir().join("fixtures/visits.csv"))
+        .arg("--out").arg(&output)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Successfully processed"));
+
+    assert_matches_golden(&output, &tests_dir().join("golden/visits_cleaned.csv"));
+    fs::remove_file(&output).ok();
+}
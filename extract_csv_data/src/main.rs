@@ -1,7 +1,9 @@
 use std::fs::{self, File};
-use std::io::{self, Read, BufRead, BufReader};
+use std::io::{self, BufRead, BufReader};
 use std::path::{Path, PathBuf};
-use csv::{Reader, Writer, ReaderBuilder};
+use csv::{Writer, ReaderBuilder};
+
+use shared::discover::{no_input_files, InputFilter};
 
 const MARKER: &str = "[Axial Keratometric]";
 const ROWS_TO_SKIP: usize = 3;
@@ -29,6 +31,41 @@ impl From<csv::Error> for ProcessingError {
     }
 }
 
+// How many columns of each grid row are kept: --cols N (COLS_TO_KEEP by
+// default). Longer rows are truncated; shorter ones are skipped, or with --pad
+// filled with empty cells up to N so partial rows near the grid edge survive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ColumnWindow {
+    cols: usize,
+    pad: bool,
+}
+
+impl Default for ColumnWindow {
+    fn default() -> Self {
+        ColumnWindow { cols: COLS_TO_KEEP, pad: false }
+    }
+}
+
+impl ColumnWindow {
+    fn from_args(args: &[String]) -> Result<Self, String> {
+        let cols = match args.iter().position(|a| a == "--cols") {
+            None => COLS_TO_KEEP,
+            Some(i) => {
+                let value = args.get(i + 1).map(String::as_str).unwrap_or("");
+                value.parse::<usize>()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| format!("Invalid --cols '{}' (expected a positive column count)", value))?
+            }
+        };
+
+        Ok(ColumnWindow {
+            cols,
+            pad: args.iter().any(|a| a == "--pad"),
+        })
+    }
+}
+
 fn find_marker_position(file_path: &Path) -> Result<usize, ProcessingError> {
     let file = File::open(file_path)?;
     let reader = BufReader::new(file);
@@ -47,7 +84,7 @@ fn find_marker_position(file_path: &Path) -> Result<usize, ProcessingError> {
     })
 }
 
-fn process_csv_file(input_path: &Path, output_dir: &Path) -> Result<(), ProcessingError> {
+fn process_csv_file(input_path: &Path, output_dir: &Path, columns: &ColumnWindow) -> Result<(), ProcessingError> {
     println!("\nProcessing file: {}", input_path.display());
     println!("Output directory: {}", output_dir.display());
 
@@ -78,10 +115,9 @@ fn process_csv_file(input_path: &Path, output_dir: &Path) -> Result<(), Processi
         .has_headers(false)
         .from_reader(file);
 
-    let mut current_row = 0;
     let mut rows_written = 0;
     
-    for result in reader.records() {
+    for (current_row, result) in reader.records().enumerate() {
         let record = result?;
         
         // Stop after we've processed all needed rows
@@ -91,20 +127,23 @@ fn process_csv_file(input_path: &Path, output_dir: &Path) -> Result<(), Processi
         
         // Process rows in our target range
         if current_row >= start_row && current_row < end_row {
-            if record.len() < COLS_TO_KEEP {
-                println!("Warning: Row {} has only {} columns (expected {})", 
-                    current_row + 1, record.len(), COLS_TO_KEEP);
-                continue;
-            }
-            
-            let selected_cols: Vec<String> = record
+            let mut selected_cols: Vec<String> = record
                 .iter()
-                .take(COLS_TO_KEEP)
+                .take(columns.cols)
                 .map(|s| s.to_string())
                 .collect();
+            if record.len() < columns.cols {
+                if !columns.pad {
+                    println!("Warning: Row {} has only {} columns (expected {})", 
+                        current_row + 1, record.len(), columns.cols);
+                    continue;
+                }
+                println!("Padding row {} from {} to {} columns", current_row + 1, record.len(), columns.cols);
+                selected_cols.resize(columns.cols, String::new());
+            }
             
             // Debug print first and last few rows
-            if rows_written < 3 || rows_written >= ROWS_TO_KEEP - 3 {
+            if !(3..ROWS_TO_KEEP - 3).contains(&rows_written) {
                 println!("Writing row {}: First value = {}, Last value = {}", 
                     current_row + 1,
                     selected_cols.first().unwrap_or(&String::from("N/A")),
@@ -114,15 +153,13 @@ fn process_csv_file(input_path: &Path, output_dir: &Path) -> Result<(), Processi
             writer.write_record(&selected_cols)?;
             rows_written += 1;
         }
-        
-        current_row += 1;
     }
 
     println!("Rows written to output: {}", rows_written);
     
     if rows_written == 0 {
         return Err(ProcessingError {
-            message: format!("No rows were written to the output file! Check selection range.")
+            message: "No rows were written to the output file! Check selection range.".to_string()
         });
     }
 
@@ -144,24 +181,29 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     fs::create_dir_all(&output_dir)?;
     println!("Output directory created/verified successfully");
 
+    let args: Vec<String> = std::env::args().collect();
+    let input_filter = InputFilter::from_args(&args);
+    let columns = ColumnWindow::from_args(&args)?;
+
     let mut processed_files = 0;
     let mut failed_files = 0;
     
-    for entry in fs::read_dir(input_dir)? {
-        let entry = entry?;
-        let path = entry.path();
-        
-        if path.extension().and_then(|s| s.to_str()) == Some("csv") {
-            println!("\n=== Processing file: {} ===", path.display());
-            match process_csv_file(&path, &output_dir) {
-                Ok(_) => {
-                    println!("Successfully processed: {}", path.display());
-                    processed_files += 1;
-                },
-                Err(e) => {
-                    eprintln!("Error processing {}: {}", path.display(), e.message);
-                    failed_files += 1;
-                }
+    let files = input_filter.input_files(&input_dir)?;
+    if let Some((message, code)) = no_input_files(&input_dir, &files) {
+        eprintln!("{}", message);
+        std::process::exit(code);
+    }
+
+    for path in &files {
+        println!("\n=== Processing file: {} ===", path.display());
+        match process_csv_file(path, &output_dir, &columns) {
+            Ok(_) => {
+                println!("Successfully processed: {}", path.display());
+                processed_files += 1;
+            },
+            Err(e) => {
+                eprintln!("Error processing {}: {}", path.display(), e.message);
+                failed_files += 1;
             }
         }
     }
@@ -171,4 +213,34 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Failed to process: {} files", failed_files);
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cols_truncates_and_pad_fills_short_rows() {
+        let dir = std::env::temp_dir().join(format!("extract_csv_data_cols_{}", std::process::id()));
+        let out_dir = dir.join("out");
+        fs::create_dir_all(&out_dir).unwrap();
+        let input = dir.join("scan.csv");
+        fs::write(&input, "[Axial Keratometric]\nunits\nmm\n1,2,3,4,5,6\n7,8\n9,10,11,12\n").unwrap();
+
+        let run = |args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+            process_csv_file(&input, &out_dir, &ColumnWindow::from_args(&args).unwrap()).unwrap();
+            fs::read_to_string(out_dir.join("scan.csv")).unwrap()
+        };
+        let truncated = run(&["--cols", "4"]);
+        let padded = run(&["--cols", "4", "--pad"]);
+        fs::remove_dir_all(&dir).ok();
+
+        // The short row is skipped without --pad, and the rows after it stay in the window
+        assert_eq!(truncated, "1,2,3,4\n9,10,11,12\n");
+        assert_eq!(padded, "1,2,3,4\n7,8,,\n9,10,11,12\n");
+
+        assert_eq!(ColumnWindow::from_args(&[]).unwrap(), ColumnWindow { cols: COLS_TO_KEEP, pad: false });
+        assert!(ColumnWindow::from_args(&["--cols".to_string(), "0".to_string()]).is_err());
+    }
+}
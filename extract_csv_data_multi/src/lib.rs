@@ -0,0 +1,692 @@
+use rayon::prelude::*;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+use csv::{ReaderBuilder, WriterBuilder};
+use memmap2::Mmap;
+
+mod validate;
+
+use shared::progress::ProgressStream;
+
+// ----------------- Configuration -----------------
+// Marker -> number-of-rows-to-skip mapping
+static MARKERS_AND_SKIPS: &[(&str, usize)] = &[
+    ("[Pachymetry]", 3),
+    ("[Axial Posterior]", 3),
+    ("[Axial Anterior]", 3),
+    ("[Height Anterior]", 3),
+    ("[Height Posterior]", 3),
+    ("[Axial Keratometric]", 3),
+    ("[Elevation Anterior]", 11),
+    ("[Elevation Posterior]", 11),
+];
+
+const ROWS_TO_KEEP: usize = 256;
+const COLS_TO_KEEP: usize = 32;
+
+// Extra attempts for transient I/O errors when writing an output file (--retries),
+// waiting RETRY_BASE_DELAY_MS, then twice that, and so on between attempts
+const DEFAULT_WRITE_RETRIES: u32 = 3;
+// Ten retries already wait up to 100 ms * 2^9, almost a minute, before the last one
+const MAX_WRITE_RETRIES: u32 = 10;
+const RETRY_BASE_DELAY_MS: u64 = 100;
+
+// Directories to process when no --dir is given
+static DIRECTORIES: &[&str] = &[
+    "/home/aricept094/mydata/casia_more_than_4",
+    "/home/aricept094/mydata/casia_less_than_1",
+    "/home/aricept094/mydata/casia1-2",
+    "/home/aricept094/mydata/casia2-4",
+    "/home/aricept094/mydata/sheets",
+];
+
+// ----------------- Error Handling -----------------
+#[derive(Debug)]
+struct ProcessingError {
+    message: String,
+    // Set when the error is a warning promoted by --fail-on-warning
+    promoted_warning: bool,
+}
+
+impl From<io::Error> for ProcessingError {
+    fn from(error: io::Error) -> Self {
+        ProcessingError {
+            message: error.to_string(),
+            promoted_warning: false,
+        }
+    }
+}
+
+impl From<csv::Error> for ProcessingError {
+    fn from(error: csv::Error) -> Self {
+        ProcessingError {
+            message: error.to_string(),
+            promoted_warning: false,
+        }
+    }
+}
+
+// --------------------------------------------------
+fn find_marker_row_index(csv_path: &Path, marker: &str) -> Result<usize, ProcessingError> {
+    let file = File::open(csv_path)?;
+    let buffered = BufReader::new(file);
+
+    let mut reader = ReaderBuilder::new()
+        .flexible(true)
+        .has_headers(false)
+        .from_reader(buffered);
+
+    for (i, row_result) in reader.records().enumerate() {
+        let row = row_result?;
+        if let Some(first_col) = row.get(0) {
+            if first_col.trim() == marker {
+                return Ok(i);
+            }
+        }
+    }
+
+    Err(marker_not_found(csv_path, marker))
+}
+
+fn marker_not_found(csv_path: &Path, marker: &str) -> ProcessingError {
+    ProcessingError {
+        message: format!("Marker '{}' not found in file: {}", marker, csv_path.display()),
+        promoted_warning: false,
+    }
+}
+
+// --mmap: the same search over a memory-mapped file, comparing the first
+// field of each line as bytes instead of parsing every record. Falls back to
+// the buffered scan when the file cannot be mapped (an empty file, for one).
+fn find_marker_row_index_mmap(csv_path: &Path, marker: &str) -> Result<usize, ProcessingError> {
+    let file = File::open(csv_path)?;
+    // SAFETY: the exports are read-only inputs and are not modified while
+    // they are being scanned
+    let mmap = match unsafe { Mmap::map(&file) } {
+        Ok(mmap) => mmap,
+        Err(_) => return find_marker_row_index(csv_path, marker),
+    };
+    marker_row_in_bytes(&mmap, marker.as_bytes()).ok_or_else(|| marker_not_found(csv_path, marker))
+}
+
+// Row index as the csv reader counts it: blank lines are not records. The
+// exports have no quoted line breaks, so every other line is one record.
+fn marker_row_in_bytes(bytes: &[u8], marker: &[u8]) -> Option<usize> {
+    let mut row = 0;
+    for line in bytes.split(|&b| b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.is_empty() {
+            continue;
+        }
+        let first = line.split(|&b| b == b',').next().unwrap_or(line).trim_ascii();
+        let first = first.strip_prefix(b"\"").and_then(|f| f.strip_suffix(b"\"")).unwrap_or(first);
+        if first.trim_ascii() == marker {
+            return Some(row);
+        }
+        row += 1;
+    }
+    None
+}
+
+// --------------------------------------------------
+// Write rows to `<out_path>.partial` and rename it into place only after a
+// successful flush, so a failed write never leaves a truncated output behind.
+// Transient I/O errors are retried with exponential backoff. Rows may differ
+// in length (metadata rows do).
+fn write_rows_atomically(
+    out_path: &Path,
+    rows: &[Vec<String>],
+    retries: u32,
+) -> Result<(), ProcessingError> {
+    let mut partial_name = out_path.as_os_str().to_owned();
+    partial_name.push(".partial");
+    let partial_path = PathBuf::from(partial_name);
+
+    let mut attempt = 0;
+    loop {
+        let result = (|| -> Result<(), ProcessingError> {
+            let mut writer = WriterBuilder::new()
+                .flexible(true)
+                .from_writer(File::create(&partial_path)?);
+            for row in rows {
+                writer.write_record(row)?;
+            }
+            writer.flush()?;
+            fs::rename(&partial_path, out_path)?;
+            Ok(())
+        })();
+
+        match result {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                let _ = fs::remove_file(&partial_path);
+                if attempt >= retries {
+                    return Err(ProcessingError {
+                        message: format!(
+                            "Failed to write '{}' after {} attempt(s): {}",
+                            out_path.display(),
+                            attempt + 1,
+                            e.message
+                        ),
+                        promoted_warning: false,
+                    });
+                }
+                let delay = Duration::from_millis(RETRY_BASE_DELAY_MS * 2u64.pow(attempt));
+                eprintln!(
+                    "Warning: write to '{}' failed ({}), retrying in {:?}",
+                    out_path.display(),
+                    e.message,
+                    delay
+                );
+                thread::sleep(delay);
+                attempt += 1;
+            }
+        }
+    }
+}
+
+// --------------------------------------------------
+// Print a warning, or with --fail-on-warning turn it into an error that fails
+// this (file, marker) before anything is written.
+fn warn(fail_on_warning: bool, message: String) -> Result<(), ProcessingError> {
+    if fail_on_warning {
+        return Err(ProcessingError { message, promoted_warning: true });
+    }
+    eprintln!("Warning: {}", message);
+    Ok(())
+}
+
+// --------------------------------------------------
+// How each (file, marker) is extracted; set from the command line in main
+#[derive(Debug, Clone, Copy)]
+struct ExtractOptions {
+    fail_on_warning: bool,
+    capture_meta: bool,
+    use_mmap: bool,
+    write_retries: u32,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        ExtractOptions {
+            fail_on_warning: false,
+            capture_meta: false,
+            use_mmap: false,
+            write_retries: DEFAULT_WRITE_RETRIES,
+        }
+    }
+}
+
+// --retries N: 0 disables retrying, at most MAX_WRITE_RETRIES
+fn retries_from_args(args: &[String]) -> Result<u32, String> {
+    match args.iter().position(|a| a == "--retries") {
+        None => Ok(DEFAULT_WRITE_RETRIES),
+        Some(i) => {
+            let value = args.get(i + 1).map(String::as_str).unwrap_or("");
+            value.parse::<u32>()
+                .ok()
+                .filter(|n| *n <= MAX_WRITE_RETRIES)
+                .ok_or_else(|| format!("Invalid --retries '{}' (expected 0-{})", value, MAX_WRITE_RETRIES))
+        }
+    }
+}
+
+// --------------------------------------------------
+fn process_csv_for_marker(
+    input_path: &Path,
+    base_output_dir: &Path,
+    marker: &str,
+    rows_to_skip: usize,
+    options: ExtractOptions,
+) -> Result<(), ProcessingError> {
+    let ExtractOptions { fail_on_warning, capture_meta, use_mmap, write_retries } = options;
+    // 1. Find the row containing the marker
+    let marker_row_index = if use_mmap {
+        find_marker_row_index_mmap(input_path, marker)?
+    } else {
+        find_marker_row_index(input_path, marker)?
+    };
+
+    // 2. Define the range
+    let start_row = marker_row_index + rows_to_skip;
+    let end_row = start_row + ROWS_TO_KEEP;
+
+    // 3. Create term-specific directory within the output directory
+    let term_dir = base_output_dir.join(marker.trim_matches(&['[', ']'][..]));
+    fs::create_dir_all(&term_dir)?;
+
+    // Build a file name that includes the term name at the beginning
+    let marker_label = marker.trim_matches(&['[', ']'][..]).replace(' ', "_");
+    let original_filename = input_path.file_name().unwrap().to_string_lossy();
+    let out_filename = format!("{}_{}", marker_label, original_filename);
+    let out_path = term_dir.join(out_filename);
+    let file_stem = input_path.file_stem().unwrap().to_string_lossy();
+    let meta_path = term_dir.join(format!("{}_{}.meta.csv", marker_label, file_stem));
+
+    // 4. Read CSV again to collect just the target rows
+    let file = File::open(input_path)?;
+    let buffered = BufReader::new(file);
+    let mut reader = ReaderBuilder::new()
+        .flexible(true)
+        .has_headers(false)
+        .from_reader(buffered);
+
+    let mut rows: Vec<Vec<String>> = Vec::with_capacity(ROWS_TO_KEEP);
+    let mut meta_rows: Vec<Vec<String>> = Vec::with_capacity(rows_to_skip);
+    let mut skipped_rows = 0;
+
+    for (i, row_result) in reader.records().enumerate() {
+        if i >= end_row {
+            break;
+        }
+        // The marker row and the metadata rows after it, up to the grid
+        if capture_meta && i >= marker_row_index && i < start_row {
+            meta_rows.push(row_result?.iter().map(|s| s.to_string()).collect());
+            continue;
+        }
+        if i >= start_row && i < end_row {
+            let row = row_result?;
+            if row.len() < COLS_TO_KEEP {
+                warn(fail_on_warning, format!(
+                    "row {} in '{}' has only {} columns (expected {}). Skipping row.",
+                    i + 1,
+                    input_path.display(),
+                    row.len(),
+                    COLS_TO_KEEP
+                ))?;
+                skipped_rows += 1;
+                continue;
+            }
+            let truncated: Vec<String> = row
+                .iter()
+                .take(COLS_TO_KEEP)
+                .map(|s| s.to_string())
+                .collect();
+
+            rows.push(truncated);
+        }
+    }
+
+    let rows_written = rows.len();
+    if rows_written == 0 {
+        return Err(ProcessingError {
+            message: format!(
+                "No rows written for marker '{}' in file '{}'. (start={}, end={})",
+                marker,
+                input_path.display(),
+                start_row,
+                end_row
+            ),
+            promoted_warning: false,
+        });
+    }
+
+    if rows_written + skipped_rows < ROWS_TO_KEEP {
+        warn(fail_on_warning, format!(
+            "For marker '{}', the file ends {} rows into the {}-row window.",
+            marker, rows_written + skipped_rows, ROWS_TO_KEEP
+        ))?;
+    }
+    if rows_written != ROWS_TO_KEEP {
+        warn(fail_on_warning, format!(
+            "For marker '{}', expected to write {} rows, but wrote {}.",
+            marker, ROWS_TO_KEEP, rows_written
+        ))?;
+    }
+
+    // 5. Write the rows; the final file only appears once everything is on disk
+    write_rows_atomically(&out_path, &rows, write_retries)?;
+    if capture_meta {
+        write_rows_atomically(&meta_path, &meta_rows, write_retries)?;
+    }
+
+    println!(
+        "Created '{}', rows written: {}, marker='{}'",
+        out_path.display(),
+        rows_written,
+        marker
+    );
+    Ok(())
+}
+
+// --dir <path>, repeatable: process these directories instead of DIRECTORIES
+fn directories_from_args(args: &[String]) -> Vec<String> {
+    let given: Vec<String> = args.iter()
+        .enumerate()
+        .filter(|(_, a)| *a == "--dir")
+        .filter_map(|(i, _)| args.get(i + 1).cloned())
+        .collect();
+    if given.is_empty() {
+        DIRECTORIES.iter().map(|dir| dir.to_string()).collect()
+    } else {
+        given
+    }
+}
+
+// --------------------------------------------------
+// Returns the number of markers that failed on a warning under
+// --fail-on-warning; any of those makes the whole file count as failed.
+fn process_csv_for_all_markers(input_path: &Path, output_dir: &Path, options: ExtractOptions) -> usize {
+    let mut promoted_failures = 0;
+    for (marker, skip) in MARKERS_AND_SKIPS {
+        match process_csv_for_marker(input_path, output_dir, marker, *skip, options) {
+            Ok(_) => { /* success */ }
+            Err(e) => {
+                if e.promoted_warning {
+                    promoted_failures += 1;
+                }
+                eprintln!(
+                    "Skipping marker '{}' in file '{}': {}",
+                    marker,
+                    input_path.display(),
+                    e.message
+                );
+            }
+        }
+    }
+    promoted_failures
+}
+
+// --------------------------------------------------
+fn process_directory(
+    dir_str: &str,
+    progress_json: bool,
+    validate_first: bool,
+    options: ExtractOptions,
+) -> Result<(usize, usize), Box<dyn std::error::Error>> {
+    let input_dir = PathBuf::from(dir_str);
+    let output_dir = input_dir.join("processed_data");
+    fs::create_dir_all(&output_dir)?;
+
+    // Create directories for each term
+    for (marker, _) in MARKERS_AND_SKIPS {
+        let term_dir = output_dir.join(marker.trim_matches(&['[', ']'][..]));
+        fs::create_dir_all(&term_dir)?;
+    }
+
+    let entries = fs::read_dir(&input_dir)?
+        .filter_map(|res| res.ok())
+        .map(|entry| entry.path())
+        .filter(|p| p.extension().and_then(|x| x.to_str()).is_some_and(|ext| ext.eq_ignore_ascii_case("csv")))
+        .collect::<Vec<_>>();
+
+    if validate_first {
+        let results = entries.par_iter()
+            .map(|path| Ok((path.display().to_string(), validate::validate_file(path)?)))
+            .collect::<io::Result<Vec<_>>>()?;
+        let report_path = output_dir.join("validation_report.csv");
+        validate::write_report(File::create(&report_path)?, &results)?;
+        let problems: usize = results.iter().map(|(_, problems)| problems.len()).sum();
+        let files_with_problems = results.iter().filter(|(_, problems)| !problems.is_empty()).count();
+        println!(
+            "Validated {} files: {} problem rows in {} files, report at '{}'",
+            results.len(), problems, files_with_problems, report_path.display()
+        );
+    }
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    let processed_count = AtomicUsize::new(0);
+    let failed_count = AtomicUsize::new(0);
+    let progress = progress_json.then(|| ProgressStream::start(io::stderr(), entries.len()));
+
+    entries.par_iter().for_each(|path| {
+        let result = std::panic::catch_unwind(|| {
+            process_csv_for_all_markers(path, &output_dir, options)
+        });
+        let status = match result {
+            Ok(0) => {
+                processed_count.fetch_add(1, Ordering::SeqCst);
+                "ok"
+            }
+            Ok(promoted_failures) => {
+                eprintln!(
+                    "{} marker(s) failed on warnings in file {} (--fail-on-warning).",
+                    promoted_failures,
+                    path.display()
+                );
+                failed_count.fetch_add(1, Ordering::SeqCst);
+                "error"
+            }
+            Err(_) => {
+                eprintln!("Panic processing file {}. Skipping.", path.display());
+                failed_count.fetch_add(1, Ordering::SeqCst);
+                "error"
+            }
+        };
+        if let Some(progress) = &progress {
+            progress.report(&path.display().to_string(), status);
+        }
+    });
+
+    if let Some(progress) = progress {
+        progress.finish()?;
+    }
+
+    Ok((
+        processed_count.load(Ordering::SeqCst),
+        failed_count.load(Ordering::SeqCst),
+    ))
+}
+
+// --------------------------------------------------
+// Runs the extraction for a whole command line, program name first, as main
+// gets it; the pipeline binary calls this for its extract_csv_data_multi stages
+pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    // `validate <file>...`: only check the files' structure and print the report
+    if args.get(1).map(String::as_str) == Some("validate") {
+        let results = args[2..].iter()
+            .map(|path| Ok((path.clone(), validate::validate_file(Path::new(path))?)))
+            .collect::<io::Result<Vec<_>>>()?;
+        validate::write_report(io::stdout(), &results)?;
+        let files_with_problems = results.iter().filter(|(_, problems)| !problems.is_empty()).count();
+        if files_with_problems > 0 {
+            return Err(format!("{} of {} files have structural problems", files_with_problems, results.len()).into());
+        }
+        return Ok(());
+    }
+
+    let mut total_processed_files = 0;
+    let mut total_failed_files = 0;
+    let progress_json = args.iter().any(|a| a == "--progress-json");
+    // --validate-first: check every file's structure before processing and
+    // write processed_data/validation_report.csv
+    let validate_first = args.iter().any(|a| a == "--validate-first");
+    let options = ExtractOptions {
+        // --fail-on-warning: a short row, wrong row count or incomplete window
+        // fails that (file, marker) and the file counts as failed
+        fail_on_warning: args.iter().any(|a| a == "--fail-on-warning"),
+        // --capture-meta: also keep each marker's skipped rows (units, scan
+        // parameters) in {marker}_{file}.meta.csv next to the grid
+        capture_meta: args.iter().any(|a| a == "--capture-meta"),
+        // --mmap: find the marker rows in a memory-mapped copy of each file
+        // instead of reading it record by record
+        use_mmap: args.iter().any(|a| a == "--mmap"),
+        write_retries: retries_from_args(args)?,
+    };
+
+    for dir_str in directories_from_args(args) {
+        println!("\n===== Processing directory: {} =====", dir_str);
+        match process_directory(&dir_str, progress_json, validate_first, options) {
+            Ok((processed, failed)) => {
+                println!(
+                    "Finished directory {}: processed {} files, failed {} files.",
+                    dir_str, processed, failed
+                );
+                total_processed_files += processed;
+                total_failed_files += failed;
+            }
+            Err(e) => {
+                eprintln!("Cannot process directory {}: {}", dir_str, e);
+            }
+        }
+    }
+
+    println!(
+        "\n========== Summary ==========\n\
+         Total processed files: {}\n\
+         Total failed files: {}\n",
+        total_processed_files,
+        total_failed_files
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failed_write_leaves_no_output_file() {
+        let dir = std::env::temp_dir().join(format!("extract_atomic_{}", std::process::id()));
+        // A non-empty directory sitting at the output path makes the final rename fail
+        let out_path = dir.join("Pachymetry_scan.csv");
+        fs::create_dir_all(out_path.join("blocker")).unwrap();
+
+        let rows = vec![vec!["1".to_string(), "2".to_string()]];
+        let result = write_rows_atomically(&out_path, &rows, 1);
+
+        assert!(result.is_err());
+        assert!(out_path.is_dir(), "no truncated file replaced the target");
+        assert!(!dir.join("Pachymetry_scan.csv.partial").exists());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_retries_flag_is_validated() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(retries_from_args(&[]), Ok(DEFAULT_WRITE_RETRIES));
+        assert_eq!(retries_from_args(&args(&["--retries", "0"])), Ok(0));
+        assert_eq!(retries_from_args(&args(&["--retries", "7"])), Ok(7));
+        assert!(retries_from_args(&args(&["--retries", "11"])).is_err());
+        assert!(retries_from_args(&args(&["--retries", "-1"])).is_err());
+        assert!(retries_from_args(&args(&["--retries"])).is_err());
+    }
+
+    #[test]
+    fn test_dir_flags_replace_the_built_in_directories() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(directories_from_args(&args(&["--dir", "/a", "--mmap", "--dir", "/b"])), vec!["/a", "/b"]);
+        assert_eq!(directories_from_args(&[]).len(), DIRECTORIES.len());
+    }
+
+    #[test]
+    fn test_progress_json_one_line_per_item() {
+        let dir = std::env::temp_dir().join(format!("extract_progress_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for i in 0..5 {
+            fs::write(dir.join(format!("scan_{}.csv", i)), "no markers here\n").unwrap();
+        }
+        let files: Vec<PathBuf> = (0..5).map(|i| dir.join(format!("scan_{}.csv", i))).collect();
+
+        let progress = ProgressStream::start(Vec::new(), files.len());
+        files.par_iter().for_each(|path| {
+            process_csv_for_all_markers(path, &dir, ExtractOptions::default());
+            progress.report(&path.display().to_string(), "ok");
+        });
+        let output = String::from_utf8(progress.finish().unwrap()).unwrap();
+        fs::remove_dir_all(&dir).ok();
+
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), files.len());
+        for done in 1..=files.len() {
+            assert!(lines.iter().any(|l| l.starts_with(&format!("{{\"done\":{},\"total\":5,\"item\":\"", done))));
+        }
+        assert!(lines.iter().all(|l| l.ends_with("\"status\":\"ok\"}")));
+    }
+
+    #[test]
+    fn test_short_row_fails_only_under_fail_on_warning() {
+        let dir = std::env::temp_dir().join(format!("extract_strict_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        // A [Pachymetry] block with a full window apart from one short row
+        let full_row = vec!["1.0"; COLS_TO_KEEP].join(",");
+        let mut contents = String::from("[Pachymetry]\nskip\nskip\n");
+        for i in 0..ROWS_TO_KEEP {
+            if i == 10 {
+                contents.push_str("1.0,2.0\n");
+            } else {
+                contents.push_str(&full_row);
+                contents.push('\n');
+            }
+        }
+        fs::write(dir.join("scan.csv"), contents).unwrap();
+
+        let (processed, failed) = process_directory(dir.to_str().unwrap(), false, false, ExtractOptions::default()).unwrap();
+        assert_eq!((processed, failed), (1, 0));
+        let out_path = dir.join("processed_data").join("Pachymetry").join("Pachymetry_scan.csv");
+        assert!(out_path.exists());
+
+        fs::remove_file(&out_path).unwrap();
+        let strict = ExtractOptions { fail_on_warning: true, ..Default::default() };
+        let (processed, failed) = process_directory(dir.to_str().unwrap(), false, false, strict).unwrap();
+        assert_eq!((processed, failed), (0, 1));
+        assert!(!out_path.exists(), "nothing is written for a failed marker");
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_capture_meta_keeps_the_skipped_rows() {
+        let dir = std::env::temp_dir().join(format!("extract_meta_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        // An [Elevation Anterior] block skips 11 rows: the marker and ten metadata rows
+        let mut contents = String::from("exported by,Pentacam\n[Elevation Anterior]\n");
+        for i in 1..=10 {
+            contents.push_str(&format!("meta {},unit {}\n", i, i));
+        }
+        let full_row = vec!["1.0"; COLS_TO_KEEP].join(",");
+        for _ in 0..ROWS_TO_KEEP {
+            contents.push_str(&full_row);
+            contents.push('\n');
+        }
+        let input = dir.join("scan.csv");
+        fs::write(&input, contents).unwrap();
+
+        let options = ExtractOptions { fail_on_warning: true, capture_meta: true, ..Default::default() };
+        process_csv_for_marker(&input, &dir, "[Elevation Anterior]", 11, options).unwrap();
+        let term_dir = dir.join("Elevation Anterior");
+        let meta = fs::read_to_string(term_dir.join("Elevation_Anterior_scan.meta.csv")).unwrap();
+        let grid = fs::read_to_string(term_dir.join("Elevation_Anterior_scan.csv")).unwrap();
+        fs::remove_dir_all(&dir).ok();
+
+        let meta_lines: Vec<&str> = meta.lines().collect();
+        assert_eq!(meta_lines.len(), 11);
+        assert_eq!(meta_lines[0], "[Elevation Anterior]");
+        assert_eq!(meta_lines[1], "meta 1,unit 1");
+        assert_eq!(meta_lines[10], "meta 10,unit 10");
+        assert_eq!(grid.lines().count(), ROWS_TO_KEEP);
+        assert!(grid.lines().all(|line| line == full_row));
+    }
+
+    #[test]
+    fn test_mmap_marker_index_matches_buffered() {
+        let dir = std::env::temp_dir().join(format!("extract_mmap_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        // Blank lines, CRLF endings and a quoted marker at known positions
+        let input = dir.join("scan.csv");
+        fs::write(
+            &input,
+            "exported by,Pentacam\r\n\r\n[Pachymetry]\r\nmm,1\n\n2,3\n\"[Axial Anterior]\",x\n4,5\n[Elevation Anterior]\n",
+        )
+        .unwrap();
+        let empty = dir.join("empty.csv");
+        fs::write(&empty, "").unwrap();
+
+        let expected = [("[Pachymetry]", 1), ("[Axial Anterior]", 4), ("[Elevation Anterior]", 6)];
+        for (marker, row) in expected {
+            assert_eq!(find_marker_row_index(&input, marker).unwrap(), row, "{}", marker);
+            assert_eq!(find_marker_row_index_mmap(&input, marker).unwrap(), row, "{}", marker);
+        }
+        assert!(find_marker_row_index_mmap(&input, "[Height Posterior]").is_err());
+        assert!(find_marker_row_index_mmap(&empty, "[Pachymetry]").is_err());
+        fs::remove_dir_all(&dir).ok();
+    }
+}
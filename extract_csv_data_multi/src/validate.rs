@@ -0,0 +1,214 @@
+// Streaming structural check of a CSV file, so shape problems show up with a
+// line number before a run rather than as a csv error halfway through it.
+// Every problem is collected; nothing stops at the first one. Each marker
+// line like [Pachymetry] starts a new section, and field counts are only
+// compared within a section, since every block of an export has its own width.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProblemKind {
+    // A record whose field count differs from the first record of its section
+    FieldCount { expected: usize, found: usize },
+    // A quote inside an unquoted field, or text after a closing quote
+    UnescapedQuote,
+    // A quoted field still open at the end of the file
+    UnterminatedQuote,
+    // U+FEFF anywhere but the very start of the file, e.g. from concatenated exports
+    EmbeddedBom,
+}
+
+impl fmt::Display for ProblemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProblemKind::FieldCount { expected, found } => write!(f, "expected {} fields, found {}", expected, found),
+            ProblemKind::UnescapedQuote => write!(f, "unescaped quote"),
+            ProblemKind::UnterminatedQuote => write!(f, "unterminated quoted field"),
+            ProblemKind::EmbeddedBom => write!(f, "byte order mark inside the file"),
+        }
+    }
+}
+
+// `line` is 1-based: where the record starts, or where the open quote is
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowProblem {
+    pub line: usize,
+    pub kind: ProblemKind,
+}
+
+pub fn validate_file(path: &Path) -> io::Result<Vec<RowProblem>> {
+    validate_csv(BufReader::new(File::open(path)?))
+}
+
+pub fn validate_csv<R: BufRead>(mut reader: R) -> io::Result<Vec<RowProblem>> {
+    let mut problems = Vec::new();
+    let mut expected_fields: Option<usize> = None;
+
+    let mut buf = Vec::new();
+    let mut line = 0;
+    // State of the record being read; a quoted field can span lines
+    let mut record_line = 0;
+    let mut fields = 1;
+    let mut field_start = true;
+    let mut in_quotes = false;
+    let mut after_closing_quote = false;
+    let mut quote_line = 0;
+    let mut quote_reported = false;
+
+    loop {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf)? == 0 {
+            break;
+        }
+        line += 1;
+        // Invalid UTF-8 is not a structural problem; the csv reader reports it
+        let text = String::from_utf8_lossy(&buf);
+
+        if !in_quotes {
+            if text.trim_end_matches(['\r', '\n']).is_empty() {
+                // Blank lines are skipped by the csv reader too
+                continue;
+            }
+            if is_marker_line(&text) {
+                expected_fields = None;
+                continue;
+            }
+            record_line = line;
+            fields = 1;
+            field_start = true;
+            after_closing_quote = false;
+            quote_reported = false;
+        }
+
+        let mut chars = text.chars().enumerate().peekable();
+        while let Some((i, c)) = chars.next() {
+            if c == '\u{FEFF}' && !(line == 1 && i == 0) {
+                problems.push(RowProblem { line, kind: ProblemKind::EmbeddedBom });
+                continue;
+            }
+            if in_quotes {
+                if c == '"' {
+                    if chars.peek().map(|&(_, next)| next) == Some('"') {
+                        chars.next();
+                    } else {
+                        in_quotes = false;
+                        after_closing_quote = true;
+                    }
+                }
+                continue;
+            }
+            match c {
+                ',' => {
+                    fields += 1;
+                    field_start = true;
+                    after_closing_quote = false;
+                }
+                '\r' | '\n' => {}
+                '"' if field_start => {
+                    in_quotes = true;
+                    quote_line = line;
+                    field_start = false;
+                }
+                _ if c == '"' || after_closing_quote => {
+                    if !quote_reported {
+                        problems.push(RowProblem { line, kind: ProblemKind::UnescapedQuote });
+                        quote_reported = true;
+                    }
+                    field_start = false;
+                }
+                _ => field_start = false,
+            }
+        }
+
+        if !in_quotes {
+            match expected_fields {
+                None => expected_fields = Some(fields),
+                Some(expected) if fields != expected => problems.push(RowProblem {
+                    line: record_line,
+                    kind: ProblemKind::FieldCount { expected, found: fields },
+                }),
+                Some(_) => {}
+            }
+        }
+    }
+
+    if in_quotes {
+        problems.push(RowProblem { line: quote_line, kind: ProblemKind::UnterminatedQuote });
+    }
+    Ok(problems)
+}
+
+// A line holding only a section marker such as [Axial Anterior], possibly
+// padded with empty fields by the exporting spreadsheet
+fn is_marker_line(text: &str) -> bool {
+    let content = text.trim_start_matches('\u{FEFF}')
+        .trim_end_matches(['\r', '\n'])
+        .trim_end_matches(',')
+        .trim();
+    content.len() > 2 && content.starts_with('[') && content.ends_with(']') && !content.contains(',')
+}
+
+// One row per problem: File, Line, Problem
+pub fn write_report<W: io::Write>(out: W, results: &[(String, Vec<RowProblem>)]) -> csv::Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+    writer.write_record(["File", "Line", "Problem"])?;
+    for (file, problems) in results {
+        for problem in problems {
+            writer.write_record([file.as_str(), &problem.line.to_string(), &problem.kind.to_string()])?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ragged_row_and_unescaped_quote_are_reported() {
+        let content = "id,k1,k2\n\
+                       1,43.1,43.2\n\
+                       2,43.0\n\
+                       3,4\"3.5,43.6\n\
+                       4,\"43,7\",43.8\n\
+                       \n\
+                       5,\"multi\nline\",43.9\n";
+        let problems = validate_csv(content.as_bytes()).unwrap();
+
+        assert_eq!(problems, vec![
+            RowProblem { line: 3, kind: ProblemKind::FieldCount { expected: 3, found: 2 } },
+            RowProblem { line: 4, kind: ProblemKind::UnescapedQuote },
+        ]);
+    }
+
+    #[test]
+    fn test_field_counts_are_compared_within_each_marker_section() {
+        let content = "[Pachymetry],,\n\
+                       1,2,3\n\
+                       4,5,6\n\
+                       [Elevation Anterior]\n\
+                       1,2\n\
+                       3,4\n\
+                       5\n";
+        let problems = validate_csv(content.as_bytes()).unwrap();
+
+        assert_eq!(problems, vec![
+            RowProblem { line: 7, kind: ProblemKind::FieldCount { expected: 2, found: 1 } },
+        ]);
+    }
+
+    #[test]
+    fn test_unterminated_quote_and_embedded_bom() {
+        let content = "\u{FEFF}id,k\n1,2\n\u{FEFF}id,k\n2,\"43.1\n3,4\n";
+        let problems = validate_csv(content.as_bytes()).unwrap();
+
+        assert_eq!(problems, vec![
+            RowProblem { line: 3, kind: ProblemKind::EmbeddedBom },
+            RowProblem { line: 4, kind: ProblemKind::UnterminatedQuote },
+        ]);
+    }
+}
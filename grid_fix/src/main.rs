@@ -1,27 +1,12 @@
 use std::error::Error;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use csv::{ReaderBuilder, WriterBuilder};
-use std::f64::consts::PI;
 
-// Add Bessel function calculation (simplified first-order)
-fn bessel_j0(x: f64) -> f64 {
-    if x == 0.0 {
-        return 1.0;
-    }
-    (x.sin() / x).cos()
-}
-
-// New function for Fourier-Bessel transform of radial indices
-fn fourier_bessel_transform(radial_index: usize, num_radials: usize) -> f64 {
-    let r_max = 1.0; // Normalized maximum radius
-    let r = (radial_index as f64) / (num_radials as f64 - 1.0);
-    
-    // Calculate the transformed radius using Fourier-Bessel
-    let alpha = PI; // First zero of J0
-    let transformed_r = r * alpha / r_max;
-    bessel_j0(transformed_r)
-}
+use shared::discover::{self, no_input_files};
+use shared::geometry::{ring_geometry, GridConfig, GridOrientation};
+use shared::locale::{parse_number, NumberLocale};
+use shared::number_format::{finite_policy_from_args, format_float_columns, precision_from_args, FinitePolicy, NumberFormat};
 
 struct Stats {
     mean: f64,
@@ -45,9 +30,31 @@ fn calculate_stats(values: &[f64]) -> Stats {
     Stats { mean, std_dev }
 }
 
-fn process_csv_file(input_path: &Path, output_path: &Path) -> Result<(), Box<dyn Error>> {
-    let num_meridians = 256;
-    let num_radials = 32;
+// --start-angle <deg> / --direction {cw,ccw}
+fn orientation_from_args(args: &[String]) -> Result<GridOrientation, String> {
+    let value_of = |flag: &str| args.iter()
+        .position(|a| a == flag)
+        .map(|i| args.get(i + 1).map(String::as_str).unwrap_or(""));
+    GridOrientation::parse(value_of("--start-angle"), value_of("--direction"))
+}
+
+// The .csv files (any case) in dir that pass the --include / --exclude globs,
+// sorted by path; subdirectories are only searched with --recursive
+fn csv_files_in(dir: &Path, include: &[String], exclude: &[String], recursive: bool) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files = discover::discover_files(dir, include, exclude, recursive)?;
+    files.retain(|path| path.extension().and_then(|s| s.to_str()).is_some_and(|ext| ext.eq_ignore_ascii_case("csv")));
+    Ok(files)
+}
+
+fn process_csv_file(
+    input_path: &Path,
+    output_path: &Path,
+    locale: &NumberLocale,
+    orientation: &GridOrientation,
+    number_format: &NumberFormat,
+    require_finite: Option<FinitePolicy>,
+) -> Result<(), Box<dyn Error>> {
+    let grid = GridConfig { num_meridians: 256, num_radials: 32, orientation: *orientation };
     
     let mut k_values = Vec::new();
     let mut rdr = ReaderBuilder::new()
@@ -57,7 +64,8 @@ fn process_csv_file(input_path: &Path, output_path: &Path) -> Result<(), Box<dyn
     for result in rdr.records() {
         let record = result?;
         for value_str in record.iter() {
-            let k_reading: f64 = value_str.parse()?;
+            let k_reading = parse_number(value_str, locale)
+                .ok_or_else(|| format!("Invalid number '{}' in {}", value_str, input_path.display()))?;
             k_values.push(k_reading);
         }
     }
@@ -77,9 +85,7 @@ fn process_csv_file(input_path: &Path, output_path: &Path) -> Result<(), Box<dyn
         .has_headers(false)
         .from_path(output_path)?;
     
-    wtr.write_record(&[
-        "Meridian_Index",
-        "Radial_Index",
+    let float_columns = [
         "Meridian_Angle_Deg",
         "Meridian_Angle_Rad",
         "Normalized_Radius",
@@ -90,32 +96,24 @@ fn process_csv_file(input_path: &Path, output_path: &Path) -> Result<(), Box<dyn
         "Y_Coordinate",
         "Keratometry_Value",
         "KR_scaled",
-    ])?;
+    ];
+    let mut header = vec!["Meridian_Index", "Radial_Index"];
+    header.extend(float_columns);
+    wtr.write_record(&header)?;
     
     let mut meridian_index_1_based = 0;
+    let mut output_row = 0;
     for result in rdr.records() {
         meridian_index_1_based += 1;
         let record = result?;
         
         for (radial_index, value_str) in record.iter().enumerate() {
-            let k_reading: f64 = value_str.parse()?;
+            let k_reading = parse_number(value_str, locale)
+                .ok_or_else(|| format!("Invalid number '{}' in {}", value_str, input_path.display()))?;
             let radial_index_1_based = radial_index + 1;
+            output_row += 1;
             
-            let meridian_angle_deg = (meridian_index_1_based as f64 - 1.0) 
-                * (360.0 / num_meridians as f64);
-            let meridian_angle_rad = meridian_angle_deg.to_radians();
-            let normalized_radius = (radial_index_1_based as f64 - 1.0) 
-                / (num_radials as f64 - 1.0);
-            
-            // Apply Fourier-Bessel transform to the radius
-            let transformed_radius = fourier_bessel_transform(radial_index_1_based, num_radials);
-            
-            let cos_theta = meridian_angle_rad.cos();
-            let sin_theta = meridian_angle_rad.sin();
-            
-            // Use transformed radius for coordinate calculation
-            let x_coordinate = transformed_radius * cos_theta;
-            let y_coordinate = transformed_radius * sin_theta;
+            let cell = ring_geometry(meridian_index_1_based, radial_index_1_based, &grid);
             
             let kr_scaled = if stats.std_dev != 0.0 {
                 (k_reading - stats.mean) / stats.std_dev
@@ -123,20 +121,30 @@ fn process_csv_file(input_path: &Path, output_path: &Path) -> Result<(), Box<dyn
                 0.0
             };
             
-            wtr.write_record(&[
-                meridian_index_1_based.to_string(),
-                radial_index_1_based.to_string(),
-                meridian_angle_deg.to_string(),
-                meridian_angle_rad.to_string(),
-                normalized_radius.to_string(),
-                transformed_radius.to_string(),
-                cos_theta.to_string(),
-                sin_theta.to_string(),
-                x_coordinate.to_string(),
-                y_coordinate.to_string(),
-                k_reading.to_string(),
-                kr_scaled.to_string(),
-            ])?;
+            let values = [
+                cell.angle_deg,
+                cell.angle_rad,
+                cell.normalized_radius,
+                cell.transformed_radius,
+                cell.cos,
+                cell.sin,
+                cell.x,
+                cell.y,
+                k_reading,
+                kr_scaled,
+            ];
+            let formatted = match format_float_columns(&values, &float_columns, output_row, require_finite, number_format) {
+                Ok(formatted) => formatted,
+                Err(e) => {
+                    // Don't leave a partial file behind
+                    drop(wtr);
+                    fs::remove_file(output_path).ok();
+                    return Err(format!("{}: {}", input_path.display(), e).into());
+                }
+            };
+            let mut row = vec![meridian_index_1_based.to_string(), radial_index_1_based.to_string()];
+            row.extend(formatted);
+            wtr.write_record(&row)?;
         }
     }
     
@@ -147,22 +155,37 @@ fn process_csv_file(input_path: &Path, output_path: &Path) -> Result<(), Box<dyn
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let input_dir = Path::new("/home/aricept094/mydata/sheets/conv");
-    let output_dir = Path::new("/home/aricept094/mydata/sheets/conv/transformed2");
+    let args: Vec<String> = std::env::args().collect();
+    // --input-dir / --output-dir: override the configured directories
+    let dir_arg = |flag: &str| args.iter()
+        .position(|a| a == flag)
+        .and_then(|i| args.get(i + 1))
+        .map(PathBuf::from);
+    let input_dir = &dir_arg("--input-dir").unwrap_or_else(|| PathBuf::from("/home/aricept094/mydata/sheets/conv"));
+    let output_dir = &dir_arg("--output-dir").unwrap_or_else(|| PathBuf::from("/home/aricept094/mydata/sheets/conv/transformed2"));
+    
+    let locale = NumberLocale::from_args(&args)?;
+    let orientation = orientation_from_args(&args)?;
+    let number_format = NumberFormat { precision: precision_from_args(&args)?, na_output: None };
+    // --require-finite [--finite-policy {fail,replace}]: check the computed columns of every row
+    let require_finite = finite_policy_from_args(&args)?;
     
     // Create output directory if it doesn't exist
     fs::create_dir_all(output_dir)?;
     
+    // --include / --exclude <glob,...> and --recursive: which files under the input directory are read
+    let include = discover::globs_from_args(&args, "--include");
+    let exclude = discover::globs_from_args(&args, "--exclude");
+    let mut files = csv_files_in(input_dir, &include, &exclude, args.iter().any(|a| a == "--recursive"))?;
+    // The default output folder is inside the input one
+    files.retain(|path| !path.starts_with(output_dir));
+    if let Some((message, code)) = no_input_files(input_dir, &files) {
+        eprintln!("{}", message);
+        std::process::exit(code);
+    }
+
     // Process each CSV file in the input directory
-    for entry in fs::read_dir(input_dir)? {
-        let entry = entry?;
-        let path = entry.path();
-        
-        // Skip if not a CSV file
-        if path.extension().and_then(|s| s.to_str()) != Some("csv") {
-            continue;
-        }
-        
+    for path in files {
         // Create output path with "transformed" added to filename
         let file_stem = path.file_stem()
             .and_then(|s| s.to_str())
@@ -171,9 +194,110 @@ fn main() -> Result<(), Box<dyn Error>> {
         let output_path = output_dir.join(new_filename);
         
         // Process the file
-        process_csv_file(&path, &output_path)?;
+        process_csv_file(&path, &output_path, &locale, &orientation, &number_format, require_finite)?;
     }
     
     println!("All CSV files have been processed successfully!");
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_start_angle_and_direction() {
+        let dir = std::env::temp_dir().join(format!("grid_fix_orientation_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("scan.csv");
+        fs::write(&input, "42.1,42.3\n42.0,42.2\n41.9,42.4\n").unwrap();
+
+        let run = |args: &[&str], name: &str| {
+            let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+            let orientation = orientation_from_args(&args).unwrap();
+            let output = dir.join(name);
+            process_csv_file(&input, &output, &NumberLocale::default(), &orientation, &NumberFormat::default(), None).unwrap();
+            let mut reader = ReaderBuilder::new().from_path(&output).unwrap();
+            reader.records()
+                .map(|r| {
+                    let r = r.unwrap();
+                    (r[2].parse::<f64>().unwrap(), r[7].parse::<f64>().unwrap(), r[6].parse::<f64>().unwrap())
+                })
+                .collect::<Vec<(f64, f64, f64)>>()
+        };
+        let ccw = run(&[], "ccw.csv");
+        let cw = run(&["--direction", "cw"], "cw.csv");
+        let rotated = run(&["--start-angle", "90"], "rotated.csv");
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(rotated[0].0, 90.0);
+        assert_eq!(rotated[2].0, 90.0 + 360.0 / 256.0);
+        for ((_, sin_ccw, cos_ccw), (_, sin_cw, cos_cw)) in ccw.iter().zip(&cw) {
+            assert!((sin_cw + sin_ccw).abs() < 1e-12);
+            assert!((cos_cw - cos_ccw).abs() < 1e-12);
+        }
+        assert!(ccw[2].1 > 0.0);
+        assert!(orientation_from_args(&["--direction".to_string(), "up".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_precision_option() {
+        let dir = std::env::temp_dir().join(format!("grid_fix_precision_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("scan.csv");
+        fs::write(&input, "42.123456789,42.3\n42.0,42.2\n41.9,42.4\n").unwrap();
+
+        let run = |precision: Option<usize>, name: &str| {
+            let output = dir.join(name);
+            process_csv_file(&input, &output, &NumberLocale::default(), &GridOrientation::default(), &NumberFormat { precision, na_output: None }, None).unwrap();
+            let mut reader = ReaderBuilder::new().from_path(&output).unwrap();
+            reader.records().map(|r| r.unwrap().iter().map(str::to_string).collect()).collect::<Vec<Vec<String>>>()
+        };
+        let rounded = run(Some(4), "rounded.csv");
+        let full = run(None, "full.csv");
+        fs::remove_dir_all(&dir).ok();
+
+        // Every float column has exactly four decimals; the indices stay integers
+        for row in &rounded {
+            assert!(row[..2].iter().all(|v| v.parse::<usize>().is_ok()), "{:?}", row);
+            assert!(row[2..].iter().all(|v| v.split_once('.').is_some_and(|(_, d)| d.len() == 4)), "{:?}", row);
+        }
+        assert_eq!(rounded[0][10], "42.1235");
+
+        // The default still writes values that parse back to the same f64
+        let cell = ring_geometry(1, 2, &GridConfig { num_meridians: 256, num_radials: 32, orientation: GridOrientation::default() });
+        assert_eq!(full[0][10].parse::<f64>().unwrap(), 42.123456789);
+        assert_eq!(full[1][4].parse::<f64>().unwrap(), cell.normalized_radius);
+        assert_eq!(full[1][8].parse::<f64>().unwrap(), cell.x);
+    }
+
+    #[test]
+    fn test_require_finite_locates_overflowed_scaling() {
+        let dir = std::env::temp_dir().join(format!("grid_fix_finite_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("scan.csv");
+        // The sum overflows, so the mean and SD are inf and KR_scaled is NaN
+        fs::write(&input, "1.5e308,1.5e308\n1.5e308,1.5e308\n").unwrap();
+        let output = dir.join("scan_transformed.csv");
+
+        let run = |args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+            let policy = finite_policy_from_args(&args).unwrap();
+            process_csv_file(&input, &output, &NumberLocale::default(), &GridOrientation::default(), &NumberFormat::default(), policy)
+        };
+        let failed = run(&["--require-finite"]);
+        let output_after_failure = output.exists();
+        run(&["--require-finite", "--finite-policy", "replace"]).unwrap();
+        let replaced = fs::read_to_string(&output).unwrap();
+        run(&[]).unwrap();
+        let unchecked = fs::read_to_string(&output).unwrap();
+        fs::remove_dir_all(&dir).ok();
+
+        let message = failed.unwrap_err().to_string();
+        assert!(message.contains("non-finite value NaN at row 1, column KR_scaled"), "{}", message);
+        assert!(!output_after_failure);
+        assert!(replaced.lines().skip(1).all(|line| line.ends_with(",")), "{}", replaced);
+        assert!(unchecked.lines().nth(1).unwrap().ends_with(",NaN"));
+        assert!(finite_policy_from_args(&["--require-finite".to_string(), "--finite-policy".to_string(), "zero".to_string()]).is_err());
+    }
+}
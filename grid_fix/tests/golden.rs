@@ -0,0 +1,24 @@
+// End-to-end run of the grid_fix binary over tests/fixtures, diffed against
+// the committed output in tests/golden. After an intended output change, run
+// with UPDATE_GOLDEN=1 to rewrite the golden files and review the diff.
+
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+
+use shared::golden::{assert_matches_golden, tests_dir};
+
+#[test]
+fn test_scan_matches_golden() {
+    let output_dir = std::env::temp_dir().join(format!("grid_fix_golden_{}", std::process::id()));
+
+    Command::cargo_bin("grid_fix").unwrap()
+        .arg("--input-dir").arg(tests_dir().join("fixtures"))
+        .arg("--output-dir").arg(&output_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Sample Size: 12"));
+
+    assert_matches_golden(&output_dir.join("scan_transformed.csv"), &tests_dir().join("golden/scan_transformed.csv"));
+    fs::remove_dir_all(&output_dir).ok();
+}
@@ -0,0 +1,1705 @@
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use clap::Parser;
+use csv::{ReaderBuilder, WriterBuilder};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use shared::fourier::{real_dft_windowed, Window};
+use shared::geometry::{ring_geometry, GridConfig, GridOrientation};
+use shared::number_format::{format_float_cell, FinitePolicy, NumberFormat};
+use shared::percentile::percentile;
+use shared::progress::ProgressStream;
+
+// Parameter files with more values than this use the single-pass statistics
+const STREAMING_STATS_THRESHOLD: usize = 1_000_000;
+
+const NUM_MERIDIANS: usize = 256;
+const NUM_RADIALS: usize = 32;
+const DEFAULT_NAME_TEMPLATE: &str = "{patient}_combined.csv";
+// --qc-threshold default: the usual cut-off for the modified z-score
+const DEFAULT_QC_THRESHOLD: f64 = 3.5;
+
+// The eight parameters, in the order their columns are written
+const PARAMETERS: [&str; 8] = [
+    "Axial_Anterior",
+    "Axial_Posterior",
+    "Elevation_Anterior",
+    "Elevation_Posterior",
+    "Axial_Keratometric",
+    "Height_Anterior",
+    "Height_Posterior",
+    "Pachymetry",
+];
+
+// --scaling / --scaling-map: how a parameter's _Scaled column is computed.
+// zscore is (value - mean) / std_dev; robust is (value - median) / IQR,
+// which a few extreme cells (e.g. peripheral pachymetry) barely move.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum ScalingMode {
+    #[default]
+    ZScore,
+    Robust,
+}
+
+impl ScalingMode {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "zscore" => Ok(ScalingMode::ZScore),
+            "robust" => Ok(ScalingMode::Robust),
+            other => Err(format!("Unknown scaling mode '{}' (expected zscore or robust)", other)),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ScalingMode::ZScore => "zscore",
+            ScalingMode::Robust => "robust",
+        }
+    }
+}
+
+// --input-mode: eight per-parameter folders of {param}_{patient}.csv, one
+// meridian per row (the default), or one wide {patient}.csv per patient
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum InputMode {
+    #[default]
+    Folders,
+    Wide,
+}
+
+impl InputMode {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "folders" => Ok(InputMode::Folders),
+            "wide" => Ok(InputMode::Wide),
+            other => Err(format!("Unknown --input-mode '{}' (expected folders or wide)", other)),
+        }
+    }
+}
+
+// --fill-missing {error,zero,mean,nan}: what to do with a parameter file that
+// holds fewer values than the grid (e.g. the device masked an unreliable
+// periphery). error fails the patient; the others pad the missing trailing
+// cells with 0, the mean of the values present, or NaN. NaN cells stay missing
+// in the _Scaled and _dRadial columns too.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum FillMissing {
+    #[default]
+    Error,
+    Zero,
+    Mean,
+    Nan,
+}
+
+impl FillMissing {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "error" => Ok(FillMissing::Error),
+            "zero" => Ok(FillMissing::Zero),
+            "mean" => Ok(FillMissing::Mean),
+            "nan" => Ok(FillMissing::Nan),
+            other => Err(format!("Unknown --fill-missing '{}' (expected error, zero, mean or nan)", other)),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Stats {
+    mean: f64,
+    std_dev: f64,
+}
+
+// Per-run settings threaded into process_patient_data
+#[derive(Debug, Clone, Default)]
+struct ProcessOptions {
+    // --clip-percentiles lo,hi: winsorize each parameter before computing Stats
+    clip_percentiles: Option<(f64, f64)>,
+    // --validate-grid-completeness: check each file holds exactly one full grid
+    validate_grid: bool,
+    // --non-strict: report grid problems as warnings instead of failing
+    non_strict: bool,
+    // --name-template: output file name with {patient}, {scaling} (see
+    // scaling_name), {meridians} and {radials} placeholders
+    name_template: Option<String>,
+    // --fourier-harmonics N: fit N harmonics around every radial ring of
+    // --fourier-parameter (Axial_Anterior by default) into the output file
+    // name with _harmonics before its extension (see harmonics_output_name)
+    fourier_harmonics: Option<usize>,
+    fourier_parameter: Option<String>,
+    // --fourier-window {none,hann}
+    fourier_window: Window,
+    // --start-angle / --direction: angle of meridian 1 and the way the index runs
+    orientation: GridOrientation,
+    // --input-mode {folders,wide}
+    input_mode: InputMode,
+    // --emit-derivatives: add a {param}_dRadial column, the first derivative of
+    // each parameter along its meridian with respect to normalized radius
+    emit_derivatives: bool,
+    // --patient-id-column: prepend a Patient_ID column, so the combined files
+    // of all patients can be concatenated into one long table
+    patient_id_column: bool,
+    // --precision / --na-output
+    number_format: NumberFormat,
+    // --qc [--qc-threshold K]: after all patients are processed, write
+    // qc_flags.csv of the patients whose parameter means sit more than K
+    // robust z from the cohort
+    qc_threshold: Option<f64>,
+    // --require-finite [--finite-policy {fail,replace}]: check the float
+    // columns of every output row once it is computed
+    require_finite: Option<FinitePolicy>,
+    // --scaling {zscore,robust}: mode of every parameter not in scaling_map
+    scaling: ScalingMode,
+    // --scaling-map param=mode,...: per-parameter overrides of --scaling
+    scaling_map: HashMap<String, ScalingMode>,
+    // --fill-missing {error,zero,mean,nan}
+    fill_missing: FillMissing,
+}
+
+// Command line. Every value is checked while parsing, so a bad one is
+// reported (with --help's usage) before any patient is read.
+#[derive(Debug, Parser)]
+#[command(about = "Combine each patient's parameter grids into one CSV with grid geometry and z-scores")]
+pub struct Args {
+    /// Folder holding the per-parameter folders, or the {patient}.csv files with --input-mode wide
+    #[arg(long)]
+    base_dir: PathBuf,
+    /// Where the combined files go
+    #[arg(long)]
+    out_dir: PathBuf,
+    /// Layout of the input files: folders or wide
+    #[arg(long, value_parser = InputMode::parse, default_value = "folders")]
+    input_mode: InputMode,
+    /// Output file name with {patient}, {scaling} ("mixed" under a --scaling-map that changes a mode), {meridians} and {radials} placeholders
+    #[arg(long)]
+    name_template: Option<String>,
+    /// Winsorize each parameter to these percentiles (lo,hi) before computing its statistics
+    #[arg(long, value_parser = parse_clip_percentiles)]
+    clip_percentiles: Option<(f64, f64)>,
+    /// Check that each file holds exactly one full grid
+    #[arg(long)]
+    validate_grid_completeness: bool,
+    /// Report grid problems as warnings instead of failing
+    #[arg(long)]
+    non_strict: bool,
+    /// Fit this many harmonics around every radial ring into the output file name with _harmonics before its extension
+    #[arg(long, value_parser = parse_fourier_harmonics)]
+    fourier_harmonics: Option<usize>,
+    /// Parameter the harmonics are fitted to (default Axial_Anterior)
+    #[arg(long)]
+    fourier_parameter: Option<String>,
+    /// Taper applied to each ring before the fit: none or hann
+    #[arg(long, value_parser = Window::parse, default_value = "none")]
+    fourier_window: Window,
+    /// Angle of meridian 1 in degrees
+    #[arg(long)]
+    start_angle: Option<String>,
+    /// Way the meridian index runs: cw or ccw
+    #[arg(long)]
+    direction: Option<String>,
+    /// Add a {param}_dRadial column per parameter
+    #[arg(long)]
+    emit_derivatives: bool,
+    /// Prepend a Patient_ID column
+    #[arg(long)]
+    patient_id_column: bool,
+    /// Decimal places of the float columns
+    #[arg(long)]
+    precision: Option<usize>,
+    /// Token written for missing values instead of NaN
+    #[arg(long)]
+    na_output: Option<String>,
+    /// Write qc_flags.csv of patients whose parameter means are outliers in the cohort
+    #[arg(long)]
+    qc: bool,
+    /// Robust z beyond which --qc flags a mean
+    #[arg(long, value_parser = parse_qc_threshold, default_value_t = DEFAULT_QC_THRESHOLD)]
+    qc_threshold: f64,
+    /// Check the float columns of every output row for NaN/Inf
+    #[arg(long)]
+    require_finite: bool,
+    /// What --require-finite does with a NaN/Inf: fail or replace
+    #[arg(long, value_parser = FinitePolicy::parse, default_value = "fail")]
+    finite_policy: FinitePolicy,
+    /// How the _Scaled columns are computed: zscore or robust
+    #[arg(long, value_parser = ScalingMode::parse, default_value = "zscore")]
+    scaling: ScalingMode,
+    /// Per-parameter scaling modes overriding --scaling, e.g. Pachymetry=robust,Axial_Anterior=zscore
+    #[arg(long, value_parser = parse_scaling_map)]
+    scaling_map: Option<HashMap<String, ScalingMode>>,
+    /// Pad parameter files shorter than the grid: error, zero, mean or nan
+    #[arg(long, value_parser = FillMissing::parse, default_value = "error")]
+    fill_missing: FillMissing,
+    /// Stream one JSON progress line per patient to stderr
+    #[arg(long)]
+    progress_json: bool,
+}
+
+fn parse_clip_percentiles(value: &str) -> Result<(f64, f64), String> {
+    let bounds: Vec<f64> = value.split(',')
+        .map(|p| p.trim().parse::<f64>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("Invalid --clip-percentiles '{}' (expected lo,hi)", value))?;
+    match bounds[..] {
+        [lo, hi] if (0.0..=100.0).contains(&lo) && (0.0..=100.0).contains(&hi) && lo < hi => Ok((lo, hi)),
+        _ => Err(format!("Invalid --clip-percentiles '{}' (need 0 <= lo < hi <= 100)", value)),
+    }
+}
+
+fn parse_fourier_harmonics(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        // Above M/2 harmonics the real DFT aliases
+        Ok(n) if (1..NUM_MERIDIANS / 2).contains(&n) => Ok(n),
+        _ => Err(format!("Invalid --fourier-harmonics '{}' (expected 1..{})", value, NUM_MERIDIANS / 2 - 1)),
+    }
+}
+
+fn parse_qc_threshold(value: &str) -> Result<f64, String> {
+    value.parse::<f64>()
+        .ok()
+        .filter(|k| k.is_finite() && *k > 0.0)
+        .ok_or_else(|| format!("Invalid --qc-threshold '{}' (expected a positive number)", value))
+}
+
+fn parse_scaling_map(value: &str) -> Result<HashMap<String, ScalingMode>, String> {
+    let mut map = HashMap::new();
+    for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (param, mode) = entry.split_once('=')
+            .ok_or_else(|| format!("Invalid --scaling-map entry '{}' (expected param=mode)", entry))?;
+        let param = param.trim();
+        if !PARAMETERS.contains(&param) {
+            return Err(format!("Unknown parameter '{}' in --scaling-map (expected one of {})", param, PARAMETERS.join(", ")));
+        }
+        map.insert(param.to_string(), ScalingMode::parse(mode.trim())?);
+    }
+    Ok(map)
+}
+
+impl ProcessOptions {
+    fn from_cli(args: &Args) -> Result<Self, String> {
+        Ok(ProcessOptions {
+            clip_percentiles: args.clip_percentiles,
+            validate_grid: args.validate_grid_completeness,
+            non_strict: args.non_strict,
+            name_template: args.name_template.clone(),
+            fourier_harmonics: args.fourier_harmonics,
+            fourier_parameter: args.fourier_parameter.clone(),
+            fourier_window: args.fourier_window,
+            orientation: GridOrientation::parse(args.start_angle.as_deref(), args.direction.as_deref())?,
+            input_mode: args.input_mode,
+            emit_derivatives: args.emit_derivatives,
+            patient_id_column: args.patient_id_column,
+            number_format: NumberFormat { precision: args.precision, na_output: args.na_output.clone() },
+            qc_threshold: args.qc.then_some(args.qc_threshold),
+            require_finite: args.require_finite.then_some(args.finite_policy),
+            scaling: args.scaling,
+            scaling_map: args.scaling_map.clone().unwrap_or_default(),
+            fill_missing: args.fill_missing,
+        })
+    }
+
+    fn scaling_for(&self, param_name: &str) -> ScalingMode {
+        self.scaling_map.get(param_name).copied().unwrap_or(self.scaling)
+    }
+
+    // The mode {scaling} and the metadata's scaling_mode report: --scaling, or
+    // "mixed" when --scaling-map gives some parameter a different mode
+    fn scaling_name(&self) -> &'static str {
+        if PARAMETERS.iter().any(|p| self.scaling_for(p) != self.scaling) {
+            "mixed"
+        } else {
+            self.scaling.name()
+        }
+    }
+
+    fn name_template(&self) -> &str {
+        self.name_template.as_deref().unwrap_or(DEFAULT_NAME_TEMPLATE)
+    }
+}
+
+// One row per radial ring, fitted across all meridians of that ring
+fn write_harmonics(
+    values: &[f64],
+    num_meridians: usize,
+    num_radials: usize,
+    harmonics: usize,
+    window: Window,
+    number_format: &NumberFormat,
+    output_path: &Path,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut wtr = WriterBuilder::new().from_path(output_path)?;
+
+    let mut header = vec!["Radial_Index".to_string(), "coef_a0".to_string()];
+    for k in 1..=harmonics {
+        header.push(format!("coef_am{}", k));
+        header.push(format!("coef_bm{}", k));
+    }
+    header.push("r2_score".to_string());
+    wtr.write_record(&header)?;
+
+    for radial_index in 0..num_radials {
+        let ring: Vec<f64> = (0..num_meridians)
+            .map(|meridian| values[meridian * num_radials + radial_index])
+            .collect();
+        let (a0, a, b, r2) = real_dft_windowed(&ring, harmonics, window);
+
+        let mut row = vec![(radial_index + 1).to_string(), number_format.format(a0)];
+        for (a, b) in a.iter().zip(&b) {
+            row.push(number_format.format(*a));
+            row.push(number_format.format(*b));
+        }
+        row.push(number_format.format(r2));
+        wtr.write_record(&row)?;
+    }
+
+    wtr.flush()?;
+    Ok(())
+}
+
+fn render_output_name(template: &str, patient_id: &str, scaling: &str, num_meridians: usize, num_radials: usize) -> String {
+    template
+        .replace("{patient}", patient_id)
+        .replace("{scaling}", scaling)
+        .replace("{meridians}", &num_meridians.to_string())
+        .replace("{radials}", &num_radials.to_string())
+}
+
+// The harmonics file of a patient: its --name-template output name with
+// _harmonics before the extension, e.g. P001_combined_harmonics.csv
+fn harmonics_output_name(output_name: &str) -> String {
+    let path = Path::new(output_name);
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or(output_name);
+    let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or("csv");
+    format!("{}_harmonics.{}", stem, extension)
+}
+
+// Every patient must get its own file, otherwise the parallel writers would
+// overwrite each other's output
+fn check_unique_output_names(template: &str, scaling: &str, patient_ids: &[String]) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut seen: HashMap<String, &str> = HashMap::new();
+    for patient_id in patient_ids {
+        let name = render_output_name(template, patient_id, scaling, NUM_MERIDIANS, NUM_RADIALS);
+        if let Some(other) = seen.insert(name.clone(), patient_id) {
+            return Err(format!(
+                "--name-template '{}' gives the same file name '{}' for patients {} and {} (add {{patient}})",
+                template, name, other, patient_id
+            ).into());
+        }
+    }
+    Ok(())
+}
+
+// Provenance written next to each combined CSV so it can be regenerated identically
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct OutputMetadata {
+    patient_id: String,
+    num_meridians: usize,
+    num_radials: usize,
+    bessel_order: u32,
+    bessel_kind: String,
+    // --scaling, or "mixed" when --scaling-map overrides it for some parameter
+    scaling_mode: String,
+    // Mode actually used for each parameter, after --scaling-map
+    parameter_scaling: BTreeMap<String, String>,
+    parameters: Vec<String>,
+    clip_percentiles: Option<(f64, f64)>,
+    clipped_values: BTreeMap<String, usize>,
+    // Cells padded by --fill-missing, per parameter
+    filled_cells: BTreeMap<String, usize>,
+    source_dir: String,
+    generated_at_unix: u64,
+}
+
+// {patient}_combined.csv -> {patient}_combined.meta.json
+fn metadata_path(output_path: &Path) -> PathBuf {
+    output_path.with_extension("meta.json")
+}
+
+fn write_metadata(metadata: &OutputMetadata, path: &Path) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let json = serde_json::to_string_pretty(metadata)?;
+    fs::write(path, json)?;
+    Ok(())
+}
+
+fn calculate_stats(values: &[f64]) -> Result<Stats, Box<dyn Error + Send + Sync>> {
+    if values.is_empty() {
+        return Ok(Stats { mean: 0.0, std_dev: 0.0 });
+    }
+
+    if values.iter().any(|x| x.is_nan()) {
+        return Err("Dataset contains NaN values".into());
+    }
+
+    let count = values.len() as f64;
+    
+    let mean = values.iter()
+        .fold(0.0, |acc, &x| acc + x / count);
+
+    if !mean.is_finite() {
+        return Err("Mean calculation resulted in non-finite value".into());
+    }
+
+    let variance = if values.len() > 1 {
+        values.iter()
+            .fold(0.0, |acc, &x| {
+                let diff = x - mean;
+                acc + (diff * diff) / (count - 1.0)
+            })
+    } else {
+        0.0
+    };
+
+    if !variance.is_finite() || variance < 0.0 {
+        return Err("Variance calculation resulted in invalid value".into());
+    }
+
+    let std_dev = variance.sqrt();
+
+    Ok(Stats { mean, std_dev })
+}
+
+// Welford's online algorithm: one pass over any iterator, without the
+// cancellation a naive sum-of-squares suffers when values share a large offset
+fn calculate_stats_streaming<I>(values: I) -> Result<Stats, Box<dyn Error + Send + Sync>>
+where
+    I: IntoIterator<Item = f64>,
+{
+    let mut count = 0usize;
+    let mut shift = 0.0;
+    let mut mean = 0.0;
+    let mut m2 = 0.0;
+
+    for x in values {
+        if x.is_nan() {
+            return Err("Dataset contains NaN values".into());
+        }
+        // Accumulate around the first value so the running mean stays small
+        if count == 0 {
+            shift = x;
+        }
+        count += 1;
+        let shifted = x - shift;
+        let delta = shifted - mean;
+        mean += delta / count as f64;
+        m2 += delta * (shifted - mean);
+    }
+
+    if count == 0 {
+        return Ok(Stats { mean: 0.0, std_dev: 0.0 });
+    }
+
+    let mean = mean + shift;
+
+    if !mean.is_finite() {
+        return Err("Mean calculation resulted in non-finite value".into());
+    }
+
+    let variance = if count > 1 {
+        m2 / (count as f64 - 1.0)
+    } else {
+        0.0
+    };
+
+    if !variance.is_finite() || variance < 0.0 {
+        return Err("Variance calculation resulted in invalid value".into());
+    }
+
+    let std_dev = variance.sqrt();
+
+    Ok(Stats { mean, std_dev })
+}
+
+// Clamp values to the lo/hi percentiles in place, returning how many changed
+fn winsorize(values: &mut [f64], lo: f64, hi: f64) -> usize {
+    if values.is_empty() {
+        return 0;
+    }
+
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let lower_bound = percentile(&sorted, lo);
+    let upper_bound = percentile(&sorted, hi);
+
+    let mut clipped = 0;
+    for value in values.iter_mut() {
+        let bounded = value.clamp(lower_bound, upper_bound);
+        if bounded != *value {
+            *value = bounded;
+            clipped += 1;
+        }
+    }
+    clipped
+}
+
+// One patient parameter whose mean is out of line with the cohort
+#[derive(Debug, Clone, PartialEq)]
+struct QcFlag {
+    patient_id: String,
+    parameter: String,
+    value: f64,
+    cohort_median: f64,
+    robust_z: f64,
+}
+
+// Every (patient, parameter) whose mean is more than `threshold` robust z from
+// the cohort median of that parameter's means, where robust z is
+// 0.6745 (mean - median) / MAD. A parameter with a MAD of 0 has no spread to
+// judge against and is skipped. Flags come sorted by patient, then parameter.
+fn qc_flags(cohort: &[(String, HashMap<String, Stats>)], threshold: f64) -> Vec<QcFlag> {
+    let mut parameters: Vec<&String> = cohort.iter().flat_map(|(_, stats)| stats.keys()).collect();
+    parameters.sort();
+    parameters.dedup();
+
+    let mut flags = Vec::new();
+    for parameter in parameters {
+        let means: Vec<(&String, f64)> = cohort.iter()
+            .filter_map(|(patient_id, stats)| stats.get(parameter).map(|s| (patient_id, s.mean)))
+            .collect();
+        let mut sorted: Vec<f64> = means.iter().map(|&(_, mean)| mean).collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let median = percentile(&sorted, 50.0);
+        let mut deviations: Vec<f64> = sorted.iter().map(|mean| (mean - median).abs()).collect();
+        deviations.sort_by(|a, b| a.total_cmp(b));
+        let mad = percentile(&deviations, 50.0);
+        if mad == 0.0 {
+            continue;
+        }
+
+        for (patient_id, mean) in means {
+            let robust_z = 0.6745 * (mean - median) / mad;
+            if robust_z.abs() > threshold {
+                flags.push(QcFlag {
+                    patient_id: patient_id.clone(),
+                    parameter: parameter.clone(),
+                    value: mean,
+                    cohort_median: median,
+                    robust_z,
+                });
+            }
+        }
+    }
+    flags.sort_by(|a, b| a.patient_id.cmp(&b.patient_id).then_with(|| a.parameter.cmp(&b.parameter)));
+    flags
+}
+
+fn write_qc_flags(flags: &[QcFlag], output_path: &Path) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut wtr = WriterBuilder::new().from_path(output_path)?;
+    wtr.write_record(["patient", "parameter", "value", "cohort_median", "robust_z"])?;
+    for flag in flags {
+        wtr.write_record(&[
+            flag.patient_id.clone(),
+            flag.parameter.clone(),
+            flag.value.to_string(),
+            flag.cohort_median.to_string(),
+            flag.robust_z.to_string(),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+// Values flattened row by row, plus how many values each source row had.
+// Ragged rows are only accepted (flexible) when they'll be validated afterwards.
+fn read_parameter_file(file_path: &Path, flexible: bool) -> Result<(Vec<f64>, Vec<usize>), Box<dyn Error + Send + Sync>> {
+    let mut values = Vec::new();
+    let mut row_widths = Vec::new();
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(flexible)
+        .from_path(file_path)?;
+
+    for result in rdr.records() {
+        let record = result?;
+        row_widths.push(record.len());
+        for value_str in record.iter() {
+            let value: f64 = value_str.parse()?;
+            if !value.is_finite() {
+                return Err("File contains non-finite values".into());
+            }
+            values.push(value);
+        }
+    }
+    Ok((values, row_widths))
+}
+
+// A wide export: a header row of parameter names, then one row per grid cell
+// in the same meridian-major order the folder files flatten to. Returns one
+// column of values per requested parameter, in the order asked for.
+fn read_wide_file(file_path: &Path, param_names: &[&str]) -> Result<Vec<Vec<f64>>, Box<dyn Error + Send + Sync>> {
+    let mut rdr = ReaderBuilder::new().from_path(file_path)?;
+    let headers = rdr.headers()?.clone();
+    let indices = param_names.iter()
+        .map(|name| headers.iter()
+            .position(|header| header.trim() == *name)
+            .ok_or_else(|| format!("{}: no '{}' column", file_path.display(), name)))
+        .collect::<Result<Vec<usize>, _>>()?;
+
+    let mut columns = vec![Vec::new(); param_names.len()];
+    for result in rdr.records() {
+        let record = result?;
+        for (column, &index) in columns.iter_mut().zip(&indices) {
+            let value: f64 = record.get(index).unwrap_or("").trim().parse()?;
+            if !value.is_finite() {
+                return Err("File contains non-finite values".into());
+            }
+            column.push(value);
+        }
+    }
+    Ok(columns)
+}
+
+// One meridian per row, num_radials values each; an off-by-one anywhere would
+// shift every later value onto the wrong grid point
+fn check_grid_completeness(
+    file_path: &Path,
+    row_widths: &[usize],
+    num_meridians: usize,
+    num_radials: usize,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let expected = num_meridians * num_radials;
+    let actual: usize = row_widths.iter().sum();
+    if actual != expected {
+        problems.push(format!(
+            "{}: expected {} values ({} meridians x {} radials), found {}",
+            file_path.display(), expected, num_meridians, num_radials, actual
+        ));
+    }
+
+    for (row, &width) in row_widths.iter().enumerate() {
+        if width != num_radials {
+            problems.push(format!(
+                "{}: row {} has {} values, expected {}",
+                file_path.display(), row + 1, width, num_radials
+            ));
+        }
+    }
+
+    problems
+}
+
+// Pad values up to the full grid under the --fill-missing policy, returning
+// how many cells were added. `mean` is the mean of the values present.
+fn fill_missing_cells(values: &mut Vec<f64>, grid_cells: usize, policy: FillMissing, mean: f64) -> Result<usize, String> {
+    let missing = grid_cells.saturating_sub(values.len());
+    if missing == 0 {
+        return Ok(0);
+    }
+    let fill = match policy {
+        FillMissing::Error => {
+            return Err(format!("{} values for a {}-cell grid (see --fill-missing)", values.len(), grid_cells));
+        }
+        FillMissing::Zero => 0.0,
+        FillMissing::Mean => mean,
+        FillMissing::Nan => f64::NAN,
+    };
+    values.resize(grid_cells, fill);
+    Ok(missing)
+}
+
+// d(value)/d(normalized radius) at every grid cell, in the same meridian-major
+// layout as `values`: central differences inside each meridian, one-sided
+// differences at the centre and outermost radials. A NaN neighbour (e.g. a
+// --fill-missing nan cell) is treated like the edge of the meridian, and a
+// NaN cell, or one with no neighbour present, has a NaN derivative.
+fn radial_derivatives(values: &[f64], num_meridians: usize, num_radials: usize) -> Vec<f64> {
+    let step = 1.0 / (num_radials as f64 - 1.0);
+    let mut derivatives = Vec::with_capacity(values.len());
+    for meridian in values.chunks(num_radials).take(num_meridians) {
+        let at = |r: Option<usize>| r.and_then(|r| meridian.get(r)).copied().filter(|v| !v.is_nan());
+        for r in 0..num_radials {
+            let derivative = match (at(r.checked_sub(1)), at(Some(r)), at(Some(r + 1))) {
+                (_, None, _) | (None, _, None) => f64::NAN,
+                (Some(inner), _, Some(outer)) => (outer - inner) / (2.0 * step),
+                (Some(inner), Some(value), None) => (value - inner) / step,
+                (None, Some(value), Some(outer)) => (outer - value) / step,
+            };
+            derivatives.push(derivative);
+        }
+    }
+    derivatives
+}
+
+// Centre and spread of a parameter under its scaling mode
+fn scaling_bounds(mode: ScalingMode, values: &[f64], stats: &Stats) -> (f64, f64) {
+    match mode {
+        ScalingMode::ZScore => (stats.mean, stats.std_dev),
+        ScalingMode::Robust if values.is_empty() => (0.0, 0.0),
+        ScalingMode::Robust => {
+            let mut sorted = values.to_vec();
+            sorted.sort_by(|a, b| a.total_cmp(b));
+            (percentile(&sorted, 50.0), percentile(&sorted, 75.0) - percentile(&sorted, 25.0))
+        }
+    }
+}
+
+fn scale_value(value: f64, center: f64, spread: f64) -> f64 {
+    if !value.is_finite() || !center.is_finite() || !spread.is_finite() {
+        return 0.0;
+    }
+
+    if spread <= 0.0 {
+        return 0.0;
+    }
+
+    (value - center) / spread
+}
+
+fn process_patient_data(
+    base_dir: &Path,
+    patient_id: &str,
+    output_dir: &Path,
+    options: &ProcessOptions,
+) -> Result<HashMap<String, Stats>, Box<dyn Error + Send + Sync>> {
+    let num_meridians = NUM_MERIDIANS;
+    let num_radials = NUM_RADIALS;
+
+    let mut stats_map = HashMap::new();
+    let mut clipped_values = BTreeMap::new();
+    let mut filled_cells = BTreeMap::new();
+    let mut parameters: Vec<(&str, Vec<f64>)> = PARAMETERS.iter().map(|&name| (name, Vec::new())).collect();
+    // (centre, spread) of each parameter's _Scaled column, indexed like `parameters`
+    let mut scaling_bounds_by_param = Vec::with_capacity(parameters.len());
+    // Index of the first --fill-missing nan cell of each parameter, indexed like
+    // `parameters`; the grid size when nothing was filled with NaN
+    let mut missing_from = Vec::with_capacity(parameters.len());
+
+    let mut wide_columns = match options.input_mode {
+        InputMode::Folders => None,
+        InputMode::Wide => {
+            let file_path = base_dir.join(format!("{}.csv", patient_id));
+            println!("Reading file: {:?}", file_path);
+            let names: Vec<&str> = parameters.iter().map(|(name, _)| *name).collect();
+            let columns = read_wide_file(&file_path, &names)?;
+            let cells = columns[0].len();
+            if cells != num_meridians * num_radials {
+                let problem = format!(
+                    "{}: expected {} rows ({} meridians x {} radials), found {}",
+                    file_path.display(), num_meridians * num_radials, num_meridians, num_radials, cells
+                );
+                // A short grid can't be indexed, so unless --fill-missing pads it
+                // that is fatal even under --non-strict
+                let short = cells < num_meridians * num_radials && options.fill_missing == FillMissing::Error;
+                if short || (options.validate_grid && !options.non_strict) {
+                    return Err(problem.into());
+                }
+                eprintln!("Warning: {}", problem);
+            }
+            Some(columns.into_iter())
+        }
+    };
+
+    for (param_name, param_data) in parameters.iter_mut() {
+        let values = if let Some(columns) = wide_columns.as_mut() {
+            columns.next().expect("one wide column per parameter")
+        } else {
+            let folder_name = param_name.replace("_", " ");
+            let file_path = base_dir
+                .join(&folder_name)
+                .join(format!("{}_{}.csv", param_name, patient_id));
+
+            println!("Reading file: {:?}", file_path);
+
+            let (values, row_widths) = read_parameter_file(&file_path, options.validate_grid)?;
+            if options.validate_grid {
+                let problems = check_grid_completeness(&file_path, &row_widths, num_meridians, num_radials);
+                // A short grid can't be indexed, so unless --fill-missing pads it
+                // that is fatal even under --non-strict
+                let short = values.len() < num_meridians * num_radials && options.fill_missing == FillMissing::Error;
+                if !problems.is_empty() && (short || !options.non_strict) {
+                    return Err(problems.join("; ").into());
+                }
+                for problem in &problems {
+                    eprintln!("Warning: {}", problem);
+                }
+            }
+            values
+        };
+        *param_data = values;
+        if let Some((lo, hi)) = options.clip_percentiles {
+            let clipped = winsorize(param_data, lo, hi);
+            println!("Clipped {} values of {} to the {}-{} percentile range", clipped, param_name, lo, hi);
+            clipped_values.insert(param_name.to_string(), clipped);
+        }
+        let stats = if param_data.len() > STREAMING_STATS_THRESHOLD {
+            calculate_stats_streaming(param_data.iter().copied())?
+        } else {
+            calculate_stats(param_data)?
+        };
+        let mode = options.scaling_for(param_name);
+        scaling_bounds_by_param.push(scaling_bounds(mode, param_data, &stats));
+        println!("Stats for {}: Mean = {:.6}, StdDev = {:.6}, scaling = {}", 
+                param_name, stats.mean, stats.std_dev, mode.name());
+
+        // Statistics and scaling come from the values present only
+        missing_from.push(match options.fill_missing {
+            FillMissing::Nan => param_data.len(),
+            _ => num_meridians * num_radials,
+        });
+        let filled = fill_missing_cells(param_data, num_meridians * num_radials, options.fill_missing, stats.mean)
+            .map_err(|e| format!("{} of patient {}: {}", param_name, patient_id, e))?;
+        if filled > 0 {
+            println!("Filled {} missing cells of {} ({:?})", filled, param_name, options.fill_missing);
+            filled_cells.insert(param_name.to_string(), filled);
+        }
+        stats_map.insert(param_name.to_string(), stats);
+    }
+
+    let output_name = render_output_name(
+        options.name_template(), patient_id, options.scaling_name(), num_meridians, num_radials,
+    );
+    if let Some(harmonics) = options.fourier_harmonics {
+        let parameter = options.fourier_parameter.as_deref().unwrap_or("Axial_Anterior");
+        let values = parameters.iter()
+            .find(|(name, _)| *name == parameter)
+            .map(|(_, data)| data)
+            .ok_or_else(|| format!("Unknown --fourier-parameter '{}'", parameter))?;
+        let harmonics_path = output_dir.join(harmonics_output_name(&output_name));
+        write_harmonics(values, num_meridians, num_radials, harmonics, options.fourier_window, &options.number_format, &harmonics_path)?;
+        println!("Fitted {} harmonics of {} per ring: {:?}", harmonics, parameter, harmonics_path);
+    }
+
+    let output_path = output_dir.join(output_name);
+    let wtr = Mutex::new(WriterBuilder::new()
+        .has_headers(true)
+        .from_path(&output_path)?);
+
+    let mut header = vec![
+        "Meridian_Index".to_string(),
+        "Radial_Index".to_string(),
+        "Meridian_Angle_Deg".to_string(),
+        "Meridian_Angle_Rad".to_string(),
+        "Normalized_Radius".to_string(),
+        "Transformed_Radius".to_string(),
+        "Cos_Theta".to_string(),
+        "Sin_Theta".to_string(),
+        "X_Coordinate".to_string(),
+        "Y_Coordinate".to_string(),
+        "Alpha_Angle".to_string(), // Add new column for alpha_angle
+    ];
+
+    for (param_name, _) in &parameters {
+        header.push(format!("{}_Value", param_name));
+        header.push(format!("{}_Scaled", param_name));
+        if options.emit_derivatives {
+            header.push(format!("{}_dRadial", param_name));
+        }
+    }
+    // Everything after the two indices
+    let float_columns = header[2..].to_vec();
+    if options.patient_id_column {
+        header.insert(0, "Patient_ID".to_string());
+    }
+
+    wtr.lock().unwrap().write_record(&header)?;
+
+    let header_params: Vec<String> = parameters.iter().map(|(name, _)| name.to_string()).collect();
+    // Indexed like `parameters`; empty unless --emit-derivatives
+    let derivatives: Vec<Vec<f64>> = if options.emit_derivatives {
+        parameters.iter().map(|(_, data)| radial_derivatives(data, num_meridians, num_radials)).collect()
+    } else {
+        Vec::new()
+    };
+    let parameters = parameters.clone();
+    let grid = GridConfig { num_meridians, num_radials, orientation: options.orientation };
+    let number_format = options.number_format.clone();
+    let require_finite = options.require_finite;
+
+    let rows: Result<Vec<_>, String> = (0..num_meridians).into_par_iter().flat_map(move |meridian| {
+        let parameters = parameters.clone();
+        let scaling_bounds_by_param = scaling_bounds_by_param.clone();
+        let missing_from = missing_from.clone();
+        let derivatives = derivatives.clone();
+        let number_format = number_format.clone();
+        let float_columns = float_columns.clone();
+        
+        (0..num_radials).into_par_iter().map(move |radial_index| {
+            let radial_index_1_based = radial_index + 1;
+            let meridian_index_1_based = meridian + 1;
+            let data_index = meridian * num_radials + radial_index;
+            
+            let cell = ring_geometry(meridian_index_1_based, radial_index_1_based, &grid);
+            
+            let missing = |name: &str| PARAMETERS.iter()
+                .position(|&param| param == name)
+                .is_some_and(|i| data_index >= missing_from[i]);
+
+            // Calculate alpha_angle
+            let pachymetry = parameters.iter()
+                .find(|(name, _)| *name == "Pachymetry")
+                .map(|(_, data)| data[data_index])
+                .unwrap_or(0.0);
+
+            let height_posterior = parameters.iter()
+                .find(|(name, _)| *name == "Height_Posterior")
+                .map(|(_, data)| data[data_index])
+                .unwrap_or(0.0);
+
+            let height_anterior = parameters.iter()
+                .find(|(name, _)| *name == "Height_Anterior")
+                .map(|(_, data)| data[data_index])
+                .unwrap_or(0.0);
+
+            let height_diff = height_posterior - height_anterior;
+            let alpha_angle = if height_diff != 0.0 {
+                pachymetry / height_diff
+            } else {
+                f64::NAN // Handle division by zero
+            };
+            
+            let mut values = vec![
+                cell.angle_deg,
+                cell.angle_rad,
+                cell.normalized_radius,
+                cell.transformed_radius,
+                cell.cos,
+                cell.sin,
+                cell.x,
+                cell.y,
+                alpha_angle, // Add alpha_angle to the output
+            ];
+            // Whether each of `values` is a --fill-missing nan cell, which stays
+            // NaN (or the --na-output token) even under --require-finite
+            let mut filled = vec![false; values.len()];
+            filled[8] = ["Pachymetry", "Height_Posterior", "Height_Anterior"].into_iter().any(missing); // Alpha_Angle
+            
+            for (i, (_, param_data)) in parameters.iter().enumerate() {
+                let value = param_data[data_index];
+                let (center, spread) = scaling_bounds_by_param[i];
+                let is_filled = data_index >= missing_from[i];
+                let scaled = if is_filled { f64::NAN } else { scale_value(value, center, spread) };
+                
+                values.push(value);
+                values.push(scaled);
+                if let Some(derivative) = derivatives.get(i) {
+                    values.push(derivative[data_index]);
+                }
+                filled.resize(values.len(), is_filled);
+            }
+            
+            let mut row = vec![meridian_index_1_based.to_string(), radial_index_1_based.to_string()];
+            for ((&value, column), is_filled) in values.iter().zip(&float_columns).zip(filled) {
+                row.push(if is_filled {
+                    number_format.format(value)
+                } else {
+                    format_float_cell(value, column, data_index + 1, require_finite, &number_format)?
+                });
+            }
+            Ok(row)
+        }).collect::<Vec<_>>()
+    }).collect();
+    let rows = match rows {
+        Ok(rows) => rows,
+        Err(e) => {
+            // Don't leave a header-only file behind
+            drop(wtr);
+            fs::remove_file(&output_path).ok();
+            return Err(format!("{}: {}", output_path.display(), e).into());
+        }
+    };
+
+    for mut row in rows {
+        if options.patient_id_column {
+            row.insert(0, patient_id.to_string());
+        }
+        wtr.lock().unwrap().write_record(&row)?;
+    }
+    wtr.lock().unwrap().flush()?;
+
+    let metadata = OutputMetadata {
+        patient_id: patient_id.to_string(),
+        num_meridians,
+        num_radials,
+        bessel_order: 0,
+        bessel_kind: "first".to_string(),
+        scaling_mode: options.scaling_name().to_string(),
+        parameter_scaling: header_params.iter()
+            .map(|name| (name.clone(), options.scaling_for(name).name().to_string()))
+            .collect(),
+        parameters: header_params,
+        clip_percentiles: options.clip_percentiles,
+        clipped_values,
+        filled_cells,
+        source_dir: base_dir.display().to_string(),
+        generated_at_unix: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+    };
+    write_metadata(&metadata, &metadata_path(&output_path))?;
+
+    println!("Created combined file: {:?}", output_path);
+    Ok(stats_map)
+}
+
+
+// Processes every patient under --base-dir; main runs it on the parsed
+// command line, the pipeline binary on its grid_fix_multi stages' args
+pub fn run(args: Args) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let options = ProcessOptions::from_cli(&args)?;
+    let base_dir = args.base_dir.as_path();
+    let output_dir = args.out_dir.as_path();
+
+    println!("Creating output directory: {:?}", output_dir);
+    fs::create_dir_all(output_dir)?;
+
+    // Wide exports sit directly in base_dir as {patient}.csv
+    let (sample_dir, id_prefix) = match options.input_mode {
+        InputMode::Folders => (base_dir.join("Elevation Anterior"), "Elevation_Anterior_"),
+        InputMode::Wide => (base_dir.to_path_buf(), ""),
+    };
+    let mut patient_ids = Vec::new();
+
+    println!("Scanning directory: {:?}", sample_dir);
+
+    for entry in fs::read_dir(sample_dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        
+        if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
+            if file_name.ends_with(".csv") {
+                if let Some(id) = file_name
+                    .strip_prefix(id_prefix)
+                    .and_then(|s| s.strip_suffix(".csv"))
+                {
+                    patient_ids.push(id.to_string());
+                    println!("Found patient ID: {}", id);
+                }
+            }
+        }
+    }
+
+    println!("Found {} patients to process", patient_ids.len());
+    check_unique_output_names(options.name_template(), options.scaling_name(), &patient_ids)?;
+
+    let progress = args.progress_json
+        .then(|| ProgressStream::start(io::stderr(), patient_ids.len()));
+
+    let result: Result<Vec<_>, _> = patient_ids.par_iter().enumerate().map(|(i, patient_id)| {
+        println!("\nProcessing patient {}/{}: {}", 
+                i + 1, patient_ids.len(), patient_id);
+        let result = process_patient_data(base_dir, patient_id, output_dir, &options);
+        if let Some(progress) = &progress {
+            progress.report(patient_id, if result.is_ok() { "ok" } else { "error" });
+        }
+        result.map(|stats| (patient_id.clone(), stats))
+    }).collect();
+    if let Some(progress) = progress {
+        progress.finish()?;
+    }
+    let cohort = result?;
+
+    // Needs every patient's stats, so it runs once all of them are done
+    if let Some(threshold) = options.qc_threshold {
+        let flags = qc_flags(&cohort, threshold);
+        let qc_path = output_dir.join("qc_flags.csv");
+        write_qc_flags(&flags, &qc_path)?;
+        println!("\n{} parameter means flagged beyond {} robust z: {:?}", flags.len(), threshold, qc_path);
+    }
+
+    println!("\nAll patients processed successfully!");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A test command line with the required --base-dir and --out-dir added
+    // unless it gives its own
+    fn with_dirs(args: &[String]) -> Vec<String> {
+        let mut full = args.to_vec();
+        for flag in ["--base-dir", "--out-dir"] {
+            if !args.iter().any(|a| a == flag) {
+                full.extend([flag.to_string(), "unused".to_string()]);
+            }
+        }
+        full
+    }
+
+    fn options_from(args: &[String]) -> Result<ProcessOptions, Box<dyn Error + Send + Sync>> {
+        Ok(ProcessOptions::from_cli(&Args::try_parse_from(with_dirs(args))?)?)
+    }
+
+    // Deterministic pseudo-random values in [0, 1)
+    fn lcg_values(n: usize, seed: u64) -> Vec<f64> {
+        let mut state = seed;
+        (0..n).map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 11) as f64 / (1u64 << 53) as f64
+        }).collect()
+    }
+
+    // A --input-mode wide file, dir/<patient_id>.csv: a header of parameter
+    // names, then one row per grid cell with each column's value for it
+    fn write_wide_patient(dir: &Path, patient_id: &str, columns: &[(&str, &[f64])]) {
+        let mut content = columns.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(",") + "\n";
+        for cell in 0..columns[0].1.len() {
+            let row: Vec<String> = columns.iter().map(|(_, values)| values[cell].to_string()).collect();
+            content.push_str(&row.join(","));
+            content.push('\n');
+        }
+        fs::write(dir.join(format!("{}.csv", patient_id)), content).unwrap();
+    }
+
+    // The --input-mode folders files of one patient: for every parameter,
+    // dir/<Param Name>/<Param>_<patient_id>.csv with one row per meridian
+    fn write_folder_patient<'a>(dir: &Path, patient_id: &str, values_of: impl Fn(&str) -> &'a [f64]) {
+        for param in PARAMETERS {
+            let folder = dir.join(param.replace("_", " "));
+            fs::create_dir_all(&folder).unwrap();
+            let content: String = values_of(param).chunks(NUM_RADIALS)
+                .map(|row| row.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",") + "\n")
+                .collect();
+            fs::write(folder.join(format!("{}_{}.csv", param, patient_id)), content).unwrap();
+        }
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        let tolerance = 1e-9 * b.abs().max(1.0);
+        assert!((a - b).abs() <= tolerance, "{} vs {}", a, b);
+    }
+
+    #[test]
+    fn test_streaming_stats_match_two_pass() {
+        let values: Vec<f64> = lcg_values(200_000, 42).iter().map(|x| x * 100.0 - 50.0).collect();
+        let two_pass = calculate_stats(&values).unwrap();
+        let streaming = calculate_stats_streaming(values.iter().copied()).unwrap();
+
+        assert_close(streaming.mean, two_pass.mean);
+        assert_close(streaming.std_dev, two_pass.std_dev);
+    }
+
+    #[test]
+    fn test_streaming_stats_stable_with_large_offset() {
+        let offset = 1e9;
+        let values: Vec<f64> = lcg_values(100_000, 7).iter().map(|x| offset + x).collect();
+        // Removing the offset is exact, so the shifted data gives the reference answer
+        let shifted: Vec<f64> = values.iter().map(|x| x - offset).collect();
+        let reference = calculate_stats(&shifted).unwrap();
+        let streaming = calculate_stats_streaming(values.iter().copied()).unwrap();
+
+        assert_close(streaming.mean, reference.mean + offset);
+        assert_close(streaming.mean, calculate_stats(&values).unwrap().mean);
+        assert_close(streaming.std_dev, reference.std_dev);
+    }
+
+    #[test]
+    fn test_streaming_stats_guards() {
+        assert!(calculate_stats_streaming(vec![1.0, f64::NAN]).is_err());
+        let empty = calculate_stats_streaming(Vec::new()).unwrap();
+        assert_eq!((empty.mean, empty.std_dev), (0.0, 0.0));
+        let single = calculate_stats_streaming(vec![3.5]).unwrap();
+        assert_eq!((single.mean, single.std_dev), (3.5, 0.0));
+    }
+
+    #[test]
+    fn test_metadata_sidecar_round_trips() {
+        let base_dir = std::env::temp_dir().join(format!("grid_fix_multi_meta_{}", std::process::id()));
+        let output_dir = base_dir.join("combined");
+        fs::create_dir_all(&output_dir).unwrap();
+
+        let values = lcg_values(NUM_MERIDIANS * NUM_RADIALS, 3);
+        write_folder_patient(&base_dir, "P001", |_| &values);
+
+        process_patient_data(&base_dir, "P001", &output_dir, &ProcessOptions::default()).unwrap();
+
+        let csv_path = output_dir.join("P001_combined.csv");
+        let meta_path = metadata_path(&csv_path);
+        assert_eq!(meta_path, output_dir.join("P001_combined.meta.json"));
+
+        let metadata: OutputMetadata = serde_json::from_str(&fs::read_to_string(&meta_path).unwrap()).unwrap();
+        let row_count = ReaderBuilder::new().from_path(&csv_path).unwrap().records().count();
+        fs::remove_dir_all(&base_dir).ok();
+
+        assert_eq!(metadata.num_meridians * metadata.num_radials, row_count);
+        assert_eq!(metadata.parameters.len(), PARAMETERS.len());
+        assert_eq!(metadata.scaling_mode, "zscore");
+
+        let json = serde_json::to_string(&metadata).unwrap();
+        assert_eq!(serde_json::from_str::<OutputMetadata>(&json).unwrap(), metadata);
+    }
+
+    #[test]
+    fn test_winsorize_clips_outlier_to_99th_percentile() {
+        let mut values: Vec<f64> = (1..=100).map(|v| v as f64).collect();
+        values.push(1e6);
+        let unclipped = calculate_stats(&values).unwrap();
+
+        let mut sorted = values.clone();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let p99 = percentile(&sorted, 99.0);
+
+        let clipped = winsorize(&mut values, 1.0, 99.0);
+        let clipped_stats = calculate_stats(&values).unwrap();
+
+        // The outlier and the single value below the 1st percentile
+        assert_eq!(clipped, 2);
+        assert_eq!(values[100], p99);
+        assert_eq!(p99, 100.0);
+        assert!(clipped_stats.std_dev < unclipped.std_dev);
+    }
+
+    #[test]
+    fn test_clip_percentiles_from_args() {
+        let args: Vec<String> = ["grid_fix_multi", "--clip-percentiles", "1,99"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(options_from(&args).unwrap().clip_percentiles, Some((1.0, 99.0)));
+
+        let bad: Vec<String> = ["grid_fix_multi", "--clip-percentiles", "99,1"].iter().map(|s| s.to_string()).collect();
+        assert!(options_from(&bad).is_err());
+    }
+
+    #[test]
+    fn test_name_template_keeps_sweeps_apart() {
+        let args: Vec<String> = ["grid_fix_multi", "--out-dir", "/tmp/sweep", "--name-template", "{patient}_{scaling}_{meridians}x{radials}.csv"]
+            .iter().map(|s| s.to_string()).collect();
+        let options = options_from(&args).unwrap();
+        assert_eq!(Args::try_parse_from(with_dirs(&args)).unwrap().out_dir, PathBuf::from("/tmp/sweep"));
+
+        let zscore = render_output_name(options.name_template(), "P001", "zscore", 256, 32);
+        let robust = render_output_name(options.name_template(), "P001", "robust", 256, 32);
+        assert_eq!(zscore, "P001_zscore_256x32.csv");
+        assert_ne!(zscore, robust);
+        assert_eq!(harmonics_output_name(&zscore), "P001_zscore_256x32_harmonics.csv");
+        assert_ne!(harmonics_output_name(&zscore), harmonics_output_name(&robust));
+        assert_eq!(ProcessOptions::default().name_template(), DEFAULT_NAME_TEMPLATE);
+
+        let patients = vec!["P001".to_string(), "P002".to_string()];
+        assert!(check_unique_output_names(options.name_template(), "zscore", &patients).is_ok());
+        assert!(check_unique_output_names("{scaling}.csv", "zscore", &patients).is_err());
+    }
+
+    #[test]
+    fn test_pure_cosine_ring_fits_first_harmonic() {
+        let ring: Vec<f64> = (0..NUM_MERIDIANS)
+            .map(|j| 5.0 + 3.0 * (2.0 * std::f64::consts::PI * j as f64 / NUM_MERIDIANS as f64).cos())
+            .collect();
+
+        let (a0, a, b, r2) = real_dft_windowed(&ring, 3, Window::None);
+
+        assert_close(a0, 5.0);
+        assert_close(a[0], 3.0);
+        assert!(a[1..].iter().chain(&b).all(|c| c.abs() < 1e-9), "{:?} {:?}", a, b);
+        assert!((r2 - 1.0).abs() < 1e-12);
+
+        let bad: Vec<String> = ["grid_fix_multi", "--fourier-harmonics", "200"].iter().map(|s| s.to_string()).collect();
+        assert!(options_from(&bad).is_err());
+    }
+
+    #[test]
+    fn test_progress_json_one_line_per_item() {
+        let patients: Vec<String> = (1..=40).map(|i| format!("P{:03}", i)).collect();
+        let progress = ProgressStream::start(Vec::new(), patients.len());
+        patients.par_iter().for_each(|patient| progress.report(patient, "ok"));
+        let output = String::from_utf8(progress.finish().unwrap()).unwrap();
+
+        let lines: Vec<serde_json::Value> = output.lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), patients.len());
+        let mut done: Vec<u64> = lines.iter().map(|l| l["done"].as_u64().unwrap()).collect();
+        done.sort();
+        assert_eq!(done, (1..=40).collect::<Vec<u64>>());
+        assert!(lines.iter().all(|l| l["total"] == 40 && l["status"] == "ok"));
+    }
+
+    #[test]
+    fn test_grid_missing_value_is_reported() {
+        let path = std::env::temp_dir().join(format!("grid_fix_multi_incomplete_{}.csv", std::process::id()));
+        let mut content = String::new();
+        for meridian in 0..4 {
+            let width = if meridian == 2 { 2 } else { 3 };
+            let row: Vec<String> = (0..width).map(|r| (meridian * 3 + r).to_string()).collect();
+            content.push_str(&row.join(","));
+            content.push('\n');
+        }
+        fs::write(&path, content).unwrap();
+
+        assert!(read_parameter_file(&path, false).is_err());
+        let (values, row_widths) = read_parameter_file(&path, true).unwrap();
+        let problems = check_grid_completeness(&path, &row_widths, 4, 3);
+        fs::remove_file(&path).ok();
+
+        assert_eq!(values.len(), 11);
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].contains("expected 12 values (4 meridians x 3 radials), found 11"), "{}", problems[0]);
+        assert!(problems[1].contains("row 3 has 2 values, expected 3"), "{}", problems[1]);
+        assert!(check_grid_completeness(&path, &[3, 3, 3, 3], 4, 3).is_empty());
+    }
+
+    #[test]
+    fn test_start_angle_and_direction() {
+        let args: Vec<String> = ["grid_fix_multi", "--start-angle", "90", "--direction", "cw"]
+            .iter().map(|s| s.to_string()).collect();
+        let options = options_from(&args).unwrap();
+        assert_eq!(options.orientation, GridOrientation { start_angle_deg: 90.0, clockwise: true });
+
+        let default = GridOrientation::default();
+        let rotated = GridOrientation { start_angle_deg: 90.0, clockwise: false };
+        assert_eq!(default.meridian_angle_deg(1, NUM_MERIDIANS), 0.0);
+        assert_eq!(rotated.meridian_angle_deg(1, NUM_MERIDIANS), 90.0);
+        assert_eq!(rotated.meridian_angle_deg(193, NUM_MERIDIANS), 0.0);
+
+        let cw = GridOrientation { start_angle_deg: 0.0, clockwise: true };
+        for meridian in 1..=NUM_MERIDIANS {
+            let ccw_rad = default.meridian_angle_deg(meridian, NUM_MERIDIANS).to_radians();
+            let cw_rad = cw.meridian_angle_deg(meridian, NUM_MERIDIANS).to_radians();
+            assert!((cw_rad.sin() + ccw_rad.sin()).abs() < 1e-12, "meridian {}", meridian);
+            assert!((cw_rad.cos() - ccw_rad.cos()).abs() < 1e-12, "meridian {}", meridian);
+        }
+        assert!(options_from(&["grid_fix_multi".to_string(), "--start-angle".to_string(), "north".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_wide_input_matches_folder_input() {
+        let base_dir = std::env::temp_dir().join(format!("grid_fix_multi_wide_{}", std::process::id()));
+        let columns: Vec<Vec<f64>> = (0..PARAMETERS.len())
+            .map(|i| lcg_values(NUM_MERIDIANS * NUM_RADIALS, 11 + i as u64))
+            .collect();
+
+        let folders_dir = base_dir.join("folders");
+        write_folder_patient(&folders_dir, "P001", |param| &columns[PARAMETERS.iter().position(|p| *p == param).unwrap()]);
+
+        // Same data as one wide file, with the columns in a different order
+        let wide_dir = base_dir.join("wide");
+        fs::create_dir_all(&wide_dir).unwrap();
+        let reversed: Vec<(&str, &[f64])> = PARAMETERS.iter().zip(&columns).rev().map(|(p, v)| (*p, &v[..])).collect();
+        write_wide_patient(&wide_dir, "P001", &reversed);
+
+        let folders_out = base_dir.join("folders_out");
+        let wide_out = base_dir.join("wide_out");
+        fs::create_dir_all(&folders_out).unwrap();
+        fs::create_dir_all(&wide_out).unwrap();
+        let args: Vec<String> = ["grid_fix_multi", "--input-mode", "wide"].iter().map(|s| s.to_string()).collect();
+        let wide_options = options_from(&args).unwrap();
+        assert_eq!(wide_options.input_mode, InputMode::Wide);
+
+        process_patient_data(&folders_dir, "P001", &folders_out, &ProcessOptions::default()).unwrap();
+        process_patient_data(&wide_dir, "P001", &wide_out, &wide_options).unwrap();
+
+        let folders_csv = fs::read_to_string(folders_out.join("P001_combined.csv")).unwrap();
+        let wide_csv = fs::read_to_string(wide_out.join("P001_combined.csv")).unwrap();
+        fs::remove_dir_all(&base_dir).ok();
+
+        assert_eq!(folders_csv.lines().count(), NUM_MERIDIANS * NUM_RADIALS + 1);
+        assert_eq!(wide_csv, folders_csv);
+
+        let bad: Vec<String> = ["grid_fix_multi", "--input-mode", "long"].iter().map(|s| s.to_string()).collect();
+        assert!(options_from(&bad).is_err());
+    }
+
+    #[test]
+    fn test_patient_id_column_is_prepended() {
+        let base_dir = std::env::temp_dir().join(format!("grid_fix_multi_patient_id_{}", std::process::id()));
+        fs::create_dir_all(&base_dir).unwrap();
+        let values = lcg_values(NUM_MERIDIANS * NUM_RADIALS, 5);
+        write_wide_patient(&base_dir, "P007", &PARAMETERS.map(|p| (p, &values[..])));
+
+        let args: Vec<String> = ["grid_fix_multi", "--input-mode", "wide", "--patient-id-column"].iter().map(|s| s.to_string()).collect();
+        let options = options_from(&args).unwrap();
+        process_patient_data(&base_dir, "P007", &base_dir, &options).unwrap();
+
+        let mut rdr = ReaderBuilder::new().from_path(base_dir.join("P007_combined.csv")).unwrap();
+        let headers = rdr.headers().unwrap().clone();
+        let ids: Vec<String> = rdr.records().map(|r| r.unwrap()[0].to_string()).collect();
+        fs::remove_dir_all(&base_dir).ok();
+
+        assert_eq!(&headers[0], "Patient_ID");
+        assert_eq!(&headers[1], "Meridian_Index");
+        assert_eq!(ids.len(), NUM_MERIDIANS * NUM_RADIALS);
+        assert!(ids.iter().all(|id| id == "P007"));
+        assert!(!ProcessOptions::default().patient_id_column);
+    }
+
+    #[test]
+    fn test_radial_derivative_of_linear_ramp_is_constant() {
+        // Pachymetry rising 62 µm per radial step, i.e. 62 * 31 per unit of normalized radius
+        let ramp: Vec<f64> = (0..NUM_MERIDIANS * NUM_RADIALS)
+            .map(|cell| 500.0 + 62.0 * (cell % NUM_RADIALS) as f64)
+            .collect();
+        let derivatives = radial_derivatives(&ramp, NUM_MERIDIANS, NUM_RADIALS);
+        assert_eq!(derivatives.len(), ramp.len());
+        assert!(derivatives.iter().all(|d| (d - 62.0 * 31.0).abs() < 1e-9), "{:?}", &derivatives[..NUM_RADIALS]);
+
+        // Quadratic in radius: central differences are exact inside, the ends are one-sided
+        let step = 1.0 / (NUM_RADIALS as f64 - 1.0);
+        let bowl: Vec<f64> = (0..NUM_RADIALS).map(|r| (r as f64 * step).powi(2)).collect();
+        let derivatives = radial_derivatives(&bowl, 1, NUM_RADIALS);
+        assert!((1..NUM_RADIALS - 1).all(|r| (derivatives[r] - 2.0 * r as f64 * step).abs() < 1e-9));
+        assert!((derivatives[0] - step).abs() < 1e-9);
+        assert!((derivatives[NUM_RADIALS - 1] - (2.0 - step)).abs() < 1e-9);
+
+        // The last three radials of the ramp missing (--fill-missing nan): they
+        // stay NaN and the radial next to them falls back to a one-sided difference
+        let mut short = ramp[..NUM_RADIALS].to_vec();
+        short[NUM_RADIALS - 3..].fill(f64::NAN);
+        let derivatives = radial_derivatives(&short, 1, NUM_RADIALS);
+        assert!(derivatives[NUM_RADIALS - 3..].iter().all(|d| d.is_nan()));
+        assert!(derivatives[..NUM_RADIALS - 3].iter().all(|d| (d - 62.0 * 31.0).abs() < 1e-9));
+
+        let args: Vec<String> = ["grid_fix_multi", "--emit-derivatives"].iter().map(|s| s.to_string()).collect();
+        assert!(options_from(&args).unwrap().emit_derivatives);
+    }
+
+    #[test]
+    fn test_precision_rounds_float_columns() {
+        let base_dir = std::env::temp_dir().join(format!("grid_fix_multi_precision_{}", std::process::id()));
+        fs::create_dir_all(&base_dir).unwrap();
+        let values = lcg_values(NUM_MERIDIANS * NUM_RADIALS, 9);
+        write_wide_patient(&base_dir, "P008", &PARAMETERS.map(|p| (p, &values[..])));
+
+        let run = |flags: &[&str], out: &str| {
+            let args: Vec<String> = ["grid_fix_multi", "--input-mode", "wide"].iter().chain(flags).map(|s| s.to_string()).collect();
+            let out_dir = base_dir.join(out);
+            fs::create_dir_all(&out_dir).unwrap();
+            process_patient_data(&base_dir, "P008", &out_dir, &options_from(&args).unwrap()).unwrap();
+            let mut rdr = ReaderBuilder::new().from_path(out_dir.join("P008_combined.csv")).unwrap();
+            rdr.records().map(|r| r.unwrap().iter().map(str::to_string).collect()).collect::<Vec<Vec<String>>>()
+        };
+        let rounded = run(&["--precision", "4"], "rounded");
+        let full = run(&[], "full");
+        fs::remove_dir_all(&base_dir).ok();
+
+        // Alpha_Angle (column 10) is NaN here: both heights are the same value
+        let four_decimals = |v: &String| v.split_once('.').is_some_and(|(_, d)| d.len() == 4);
+        assert!(rounded.iter().all(|row| row[2..10].iter().chain(&row[11..]).all(four_decimals)), "{:?}", rounded[1]);
+        assert!(rounded.iter().all(|row| row[..2].iter().all(|v| v.parse::<usize>().is_ok())));
+        assert!(full.iter().zip(&values).all(|(row, value)| row[11].parse::<f64>().unwrap() == *value));
+
+        let bad: Vec<String> = ["grid_fix_multi", "--precision", "four"].iter().map(|s| s.to_string()).collect();
+        assert!(options_from(&bad).is_err());
+    }
+
+    #[test]
+    fn test_qc_flags_only_the_anomalous_patient() {
+        let patient = |id: &str, pachymetry: f64, axial: f64| {
+            let stats = HashMap::from([
+                ("Pachymetry".to_string(), Stats { mean: pachymetry, std_dev: 30.0 }),
+                ("Axial_Anterior".to_string(), Stats { mean: axial, std_dev: 1.5 }),
+            ]);
+            (id.to_string(), stats)
+        };
+        let cohort = vec![
+            patient("P001", 548.0, 43.1),
+            patient("P002", 552.0, 43.4),
+            patient("P003", 545.0, 42.9),
+            // A scan artifact: pachymetry far too thin, keratometry normal
+            patient("P004", 310.0, 43.2),
+            patient("P005", 556.0, 43.6),
+            patient("P006", 550.0, 43.0),
+        ];
+
+        let flags = qc_flags(&cohort, DEFAULT_QC_THRESHOLD);
+        assert_eq!(flags.len(), 1, "{:?}", flags);
+        assert_eq!(flags[0].patient_id, "P004");
+        assert_eq!(flags[0].parameter, "Pachymetry");
+        assert_eq!(flags[0].value, 310.0);
+        assert_eq!(flags[0].cohort_median, 549.0);
+        assert!(flags[0].robust_z < -DEFAULT_QC_THRESHOLD);
+
+        let path = std::env::temp_dir().join(format!("grid_fix_multi_qc_{}.csv", std::process::id()));
+        write_qc_flags(&flags, &path).unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).ok();
+        assert_eq!(written.lines().next(), Some("patient,parameter,value,cohort_median,robust_z"));
+        assert!(written.lines().nth(1).unwrap().starts_with("P004,Pachymetry,310,549,"));
+
+        let args: Vec<String> = ["grid_fix_multi", "--qc", "--qc-threshold", "5"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(options_from(&args).unwrap().qc_threshold, Some(5.0));
+        assert_eq!(options_from(&args[..2]).unwrap().qc_threshold, Some(DEFAULT_QC_THRESHOLD));
+        assert_eq!(options_from(&args[..1]).unwrap().qc_threshold, None);
+    }
+
+    #[test]
+    fn test_na_output_replaces_only_missing_values() {
+        let args: Vec<String> = ["grid_fix_multi", "--na-output", "NA", "--precision", "2"].iter().map(|s| s.to_string()).collect();
+        let number_format = options_from(&args).unwrap().number_format;
+        assert_eq!(number_format.format(f64::NAN), "NA");
+        assert_eq!(number_format.format(0.0), "0.00");
+        assert_eq!(number_format.format(-0.004), "-0.00");
+        assert_eq!(NumberFormat::default().format(f64::NAN), "NaN");
+        assert_eq!(NumberFormat::default().format(0.0), "0");
+    }
+
+    #[test]
+    fn test_require_finite_locates_nan_alpha_angle() {
+        let base_dir = std::env::temp_dir().join(format!("grid_fix_multi_finite_{}", std::process::id()));
+        fs::create_dir_all(&base_dir).unwrap();
+        // Height_Posterior is one above Height_Anterior except at cell 37
+        // (meridian 2, radial 6), where they coincide and Alpha_Angle is NaN
+        let values = lcg_values(NUM_MERIDIANS * NUM_RADIALS, 11);
+        let posterior: Vec<f64> = values.iter().enumerate().map(|(i, v)| if i == 37 { *v } else { v + 1.0 }).collect();
+        write_wide_patient(&base_dir, "P009", &PARAMETERS.map(|p| (p, if p == "Height_Posterior" { &posterior[..] } else { &values[..] })));
+
+        let run = |flags: &[&str]| {
+            let args: Vec<String> = ["grid_fix_multi", "--input-mode", "wide", "--require-finite"].iter().chain(flags).map(|s| s.to_string()).collect();
+            process_patient_data(&base_dir, "P009", &base_dir, &options_from(&args).unwrap())
+        };
+        let failed = run(&[]);
+        let output_after_failure = base_dir.join("P009_combined.csv").exists();
+        let replaced = run(&["--finite-policy", "replace", "--na-output", "NA"]);
+        let mut rdr = ReaderBuilder::new().from_path(base_dir.join("P009_combined.csv")).unwrap();
+        let rows: Vec<Vec<String>> = rdr.records().map(|r| r.unwrap().iter().map(str::to_string).collect()).collect();
+        fs::remove_dir_all(&base_dir).ok();
+
+        let message = failed.err().unwrap().to_string();
+        assert!(message.contains("non-finite value NaN at row 38, column Alpha_Angle"), "{}", message);
+        assert!(!output_after_failure);
+
+        assert!(replaced.is_ok());
+        assert_eq!((rows[37][0].as_str(), rows[37][1].as_str(), rows[37][10].as_str()), ("2", "6", "NA"));
+        assert_eq!(rows.iter().filter(|row| row[10] == "NA").count(), 1);
+
+        let bad: Vec<String> = ["grid_fix_multi", "--require-finite", "--finite-policy", "zero"].iter().map(|s| s.to_string()).collect();
+        assert!(options_from(&bad).is_err());
+    }
+
+    #[test]
+    fn test_scaling_map_sets_each_parameters_formula() {
+        let base_dir = std::env::temp_dir().join(format!("grid_fix_multi_scaling_{}", std::process::id()));
+        fs::create_dir_all(&base_dir).unwrap();
+        // Skewed values, so the median and IQR differ from the mean and std_dev
+        let values: Vec<f64> = lcg_values(NUM_MERIDIANS * NUM_RADIALS, 5).iter().map(|v| v.powi(3) * 100.0).collect();
+        write_wide_patient(&base_dir, "P010", &PARAMETERS.map(|p| (p, &values[..])));
+
+        let args: Vec<String> = ["grid_fix_multi", "--input-mode", "wide", "--scaling-map", "Pachymetry=robust, Axial_Anterior=zscore"]
+            .iter().map(|s| s.to_string()).collect();
+        let options = options_from(&args).unwrap();
+        process_patient_data(&base_dir, "P010", &base_dir, &options).unwrap();
+        let mut rdr = ReaderBuilder::new().from_path(base_dir.join("P010_combined.csv")).unwrap();
+        let headers = rdr.headers().unwrap().clone();
+        let rows: Vec<csv::StringRecord> = rdr.records().map(|r| r.unwrap()).collect();
+        let metadata: OutputMetadata = serde_json::from_str(
+            &fs::read_to_string(base_dir.join("P010_combined.meta.json")).unwrap(),
+        ).unwrap();
+        fs::remove_dir_all(&base_dir).ok();
+
+        let stats = calculate_stats(&values).unwrap();
+        let mut sorted = values.clone();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let median = percentile(&sorted, 50.0);
+        let iqr = percentile(&sorted, 75.0) - percentile(&sorted, 25.0);
+        assert!((median - stats.mean).abs() > 1.0);
+
+        let column = |name: &str| headers.iter().position(|h| h == name).unwrap();
+        let (axial, pachymetry) = (column("Axial_Anterior_Scaled"), column("Pachymetry_Scaled"));
+        for (row, value) in rows.iter().zip(&values) {
+            assert_close(row[axial].parse().unwrap(), (value - stats.mean) / stats.std_dev);
+            assert_close(row[pachymetry].parse().unwrap(), (value - median) / iqr);
+        }
+        assert_eq!(metadata.scaling_mode, "mixed");
+        assert_eq!(metadata.parameter_scaling["Pachymetry"], "robust");
+        assert_eq!(metadata.parameter_scaling["Height_Anterior"], "zscore");
+
+        // A map that only repeats --scaling leaves the name alone
+        let same: Vec<String> = ["grid_fix_multi", "--scaling-map", "Axial_Anterior=zscore"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(options_from(&same).unwrap().scaling_name(), "zscore");
+        assert_eq!(options.scaling_name(), "mixed");
+
+        for bad in ["Pachymetry", "Cornea=robust", "Pachymetry=minmax"] {
+            let args: Vec<String> = ["grid_fix_multi", "--scaling-map", bad].iter().map(|s| s.to_string()).collect();
+            assert!(options_from(&args).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_fill_missing_mean_pads_short_parameter() {
+        let base_dir = std::env::temp_dir().join(format!("grid_fix_multi_fill_{}", std::process::id()));
+        let output_dir = base_dir.join("combined");
+        fs::create_dir_all(&output_dir).unwrap();
+        // Pachymetry lacks the last six meridians; every other parameter is complete
+        let values = lcg_values(NUM_MERIDIANS * NUM_RADIALS, 13);
+        let present = (NUM_MERIDIANS - 6) * NUM_RADIALS;
+        write_folder_patient(&base_dir, "P011", |param| if param == "Pachymetry" { &values[..present] } else { &values[..] });
+
+        let run = |policy: &str| {
+            let args: Vec<String> = ["grid_fix_multi", "--fill-missing", policy].iter().map(|s| s.to_string()).collect();
+            process_patient_data(&base_dir, "P011", &output_dir, &options_from(&args).unwrap())
+        };
+        let failed = run("error");
+        run("mean").unwrap();
+        let mut rdr = ReaderBuilder::new().from_path(output_dir.join("P011_combined.csv")).unwrap();
+        let column = rdr.headers().unwrap().iter().position(|h| h == "Pachymetry_Value").unwrap();
+        let pachymetry: Vec<f64> = rdr.records().map(|r| r.unwrap()[column].parse().unwrap()).collect();
+        let metadata: OutputMetadata = serde_json::from_str(
+            &fs::read_to_string(output_dir.join("P011_combined.meta.json")).unwrap(),
+        ).unwrap();
+        fs::remove_dir_all(&base_dir).ok();
+
+        let message = failed.err().unwrap().to_string();
+        assert!(message.contains("Pachymetry of patient P011"), "{}", message);
+
+        let mean = calculate_stats(&values[..present]).unwrap().mean;
+        assert_eq!(pachymetry.len(), NUM_MERIDIANS * NUM_RADIALS);
+        assert_eq!(&pachymetry[..present], &values[..present]);
+        assert!(pachymetry[present..].iter().all(|&v| v == mean));
+        assert_eq!(metadata.filled_cells, BTreeMap::from([("Pachymetry".to_string(), 6 * NUM_RADIALS)]));
+
+        let bad: Vec<String> = ["grid_fix_multi", "--fill-missing", "median"].iter().map(|s| s.to_string()).collect();
+        assert!(options_from(&bad).is_err());
+    }
+
+    #[test]
+    fn test_fill_missing_nan_stays_missing_under_require_finite() {
+        let base_dir = std::env::temp_dir().join(format!("grid_fix_multi_fill_nan_{}", std::process::id()));
+        let output_dir = base_dir.join("combined");
+        fs::create_dir_all(&output_dir).unwrap();
+        // Pachymetry lacks the last six meridians; the heights differ by one
+        // everywhere so Alpha_Angle is otherwise finite
+        let values = lcg_values(NUM_MERIDIANS * NUM_RADIALS, 17);
+        let posterior: Vec<f64> = values.iter().map(|v| v + 1.0).collect();
+        let present = (NUM_MERIDIANS - 6) * NUM_RADIALS;
+        write_folder_patient(&base_dir, "P012", |param| match param {
+            "Pachymetry" => &values[..present],
+            "Height_Posterior" => &posterior[..],
+            _ => &values[..],
+        });
+
+        let args: Vec<String> = [
+            "grid_fix_multi", "--fill-missing", "nan", "--require-finite", "--emit-derivatives", "--na-output", "NA",
+        ].iter().map(|s| s.to_string()).collect();
+        let result = process_patient_data(&base_dir, "P012", &output_dir, &options_from(&args).unwrap());
+        let mut rdr = ReaderBuilder::new().from_path(output_dir.join("P012_combined.csv")).unwrap();
+        let header = rdr.headers().unwrap().clone();
+        let rows: Vec<Vec<String>> = rdr.records().map(|r| r.unwrap().iter().map(str::to_string).collect()).collect();
+        fs::remove_dir_all(&base_dir).ok();
+
+        assert!(result.is_ok(), "{:?}", result.err());
+        let column = |name: &str| -> Vec<&str> {
+            let i = header.iter().position(|h| h == name).unwrap();
+            rows.iter().map(|row| row[i].as_str()).collect()
+        };
+        for name in ["Pachymetry_Value", "Pachymetry_Scaled", "Pachymetry_dRadial", "Alpha_Angle"] {
+            let cells = column(name);
+            assert!(cells[present..].iter().all(|&cell| cell == "NA"), "{}", name);
+            assert!(cells[..present].iter().all(|cell| cell.parse::<f64>().unwrap().is_finite()), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_help_and_missing_value() {
+        let help = Args::try_parse_from(["grid_fix_multi", "--help"]).unwrap_err();
+        assert_eq!(help.kind(), clap::error::ErrorKind::DisplayHelp);
+        assert_eq!(help.exit_code(), 0);
+        assert!(help.to_string().contains("--require-finite"));
+
+        let missing = Args::try_parse_from(["grid_fix_multi", "--out-dir"]).unwrap_err();
+        assert_ne!(missing.exit_code(), 0);
+        assert!(missing.to_string().contains("--out-dir"), "{}", missing);
+
+        // There is no default input or output folder
+        let required = Args::try_parse_from(["grid_fix_multi", "--out-dir", "out"]).unwrap_err();
+        assert_eq!(required.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+        assert!(required.to_string().contains("--base-dir"), "{}", required);
+        let required = Args::try_parse_from(["grid_fix_multi", "--base-dir", "in"]).unwrap_err();
+        assert_eq!(required.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+        assert!(required.to_string().contains("--out-dir"), "{}", required);
+
+        let defaults = Args::try_parse_from(["grid_fix_multi", "--base-dir", "in", "--out-dir", "out"]).unwrap();
+        assert_eq!(defaults.base_dir, PathBuf::from("in"));
+        assert_eq!(ProcessOptions::from_cli(&defaults).unwrap().input_mode, InputMode::Folders);
+    }
+}
@@ -45,7 +45,7 @@ impl Book {
 }
 
 // Fixed the lifetime parameter here
-fn find_books_by_author<'a>(books: &'a Vec<Book>, author: &str) -> Vec<&'a Book> {
+fn find_books_by_author<'a>(books: &'a [Book], author: &str) -> Vec<&'a Book> {
     books.iter()
         .filter(|book| book.author.to_lowercase() == author.to_lowercase())
         .collect()
@@ -69,6 +69,11 @@ fn main() {
             Ok(()) => println!("Successfully borrowed the book"),
             Err(e) => println!("Error: {}", e),
         }
+        println!("Attempting to return: {}", book.title);
+        match book.return_book() {
+            Ok(()) => println!("Successfully returned the book"),
+            Err(e) => println!("Error: {}", e),
+        }
     }
 
     // Find books by author
@@ -95,7 +100,7 @@ mod tests {
         let book = Book::new("Test Book", "Test Author");
         assert_eq!(book.title, "Test Book");
         assert_eq!(book.author, "Test Author");
-        assert_eq!(book.is_borrowed, false);
+        assert!(!book.is_borrowed);
         assert_eq!(book.borrow_count, 0);
     }
 
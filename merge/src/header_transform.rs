@@ -0,0 +1,153 @@
+// Readable names for the merged columns. Merged headers are `<file>_<column>`,
+// e.g. `IVF.csv_تعداد فولیکول`, which many analysis tools handle badly.
+
+use std::collections::HashMap;
+use crate::DataError;
+
+// --header-transform {none, strip-ext, slugify, translit}
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum HeaderTransform {
+    #[default]
+    None,
+    // Drop the `.csv` of the file prefix: `IVF_تعداد فولیکول`
+    StripExt,
+    // Lowercase snake_case identifiers; ASCII where the source is, Persian
+    // letters are kept as they are
+    Slugify,
+    // Persian letters to Latin through PERSIAN_TO_LATIN, then slugify
+    Translit,
+}
+
+impl HeaderTransform {
+    pub fn parse(value: &str) -> Result<Self, DataError> {
+        match value {
+            "none" => Ok(HeaderTransform::None),
+            "strip-ext" => Ok(HeaderTransform::StripExt),
+            "slugify" => Ok(HeaderTransform::Slugify),
+            "translit" => Ok(HeaderTransform::Translit),
+            other => Err(DataError::InvalidArgument(
+                format!("--header-transform '{}' (expected none, strip-ext, slugify or translit)", other),
+            )),
+        }
+    }
+
+    fn apply(&self, header: &str) -> String {
+        match self {
+            HeaderTransform::None => header.to_string(),
+            HeaderTransform::StripExt => strip_ext(header),
+            HeaderTransform::Slugify => slugify(&strip_ext(header)),
+            HeaderTransform::Translit => slugify(&transliterate(&strip_ext(header))),
+        }
+    }
+}
+
+// Transform every header; names that end up equal get a _2, _3, ... suffix
+pub fn transform_headers(headers: &[String], transform: HeaderTransform) -> Vec<String> {
+    if transform == HeaderTransform::None {
+        return headers.to_vec();
+    }
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    headers.iter()
+        .map(|header| {
+            let name = transform.apply(header);
+            let count = seen.entry(name.clone()).or_insert(0);
+            *count += 1;
+            if *count == 1 { name } else { format!("{}_{}", name, count) }
+        })
+        .collect()
+}
+
+// Only the extension of the file prefix goes: the first `.csv_`, any case
+fn strip_ext(header: &str) -> String {
+    match header.to_ascii_lowercase().find(".csv_") {
+        Some(i) => format!("{}{}", &header[..i], &header[i + ".csv".len()..]),
+        None => header.to_string(),
+    }
+}
+
+fn slugify(header: &str) -> String {
+    let mut slug = String::with_capacity(header.len());
+    for c in header.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('_') {
+            slug.push('_');
+        }
+    }
+    while slug.ends_with('_') {
+        slug.pop();
+    }
+
+    match slug.chars().next() {
+        None => "column".to_string(),
+        // Identifiers can't start with a digit
+        Some(c) if c.is_numeric() => format!("c_{}", slug),
+        Some(_) => slug,
+    }
+}
+
+// Persian (and the Arabic forms common in Persian text) to Latin
+const PERSIAN_TO_LATIN: &[(char, &str)] = &[
+    ('آ', "a"), ('ا', "a"), ('أ', "a"), ('إ', "e"), ('ب', "b"), ('پ', "p"),
+    ('ت', "t"), ('ث', "s"), ('ج', "j"), ('چ', "ch"), ('ح', "h"), ('خ', "kh"),
+    ('د', "d"), ('ذ', "z"), ('ر', "r"), ('ز', "z"), ('ژ', "zh"), ('س', "s"),
+    ('ش', "sh"), ('ص', "s"), ('ض', "z"), ('ط', "t"), ('ظ', "z"), ('ع', "a"),
+    ('غ', "gh"), ('ف', "f"), ('ق', "gh"), ('ک', "k"), ('ك', "k"), ('گ', "g"),
+    ('ل', "l"), ('م', "m"), ('ن', "n"), ('و', "v"), ('ؤ', "v"), ('ه', "h"),
+    ('ة', "h"), ('ی', "y"), ('ي', "y"), ('ى', "y"), ('ئ', "y"), ('ء', ""),
+    ('۰', "0"), ('۱', "1"), ('۲', "2"), ('۳', "3"), ('۴', "4"),
+    ('۵', "5"), ('۶', "6"), ('۷', "7"), ('۸', "8"), ('۹', "9"),
+    // Zero-width non-joiner, inside words like می‌شود
+    ('\u{200C}', ""),
+];
+
+fn transliterate(header: &str) -> String {
+    header.chars()
+        .map(|c| match PERSIAN_TO_LATIN.iter().find(|(persian, _)| *persian == c) {
+            Some((_, latin)) => latin.to_string(),
+            None => c.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_strip_ext_removes_only_the_extension() {
+        let merged = headers(&["IVF.csv_تعداد فولیکول", "neonate freeze.csv_csv_count", "age"]);
+        assert_eq!(
+            transform_headers(&merged, HeaderTransform::StripExt),
+            headers(&["IVF_تعداد فولیکول", "neonate freeze_csv_count", "age"])
+        );
+    }
+
+    #[test]
+    fn test_slugify_gives_valid_identifiers() {
+        let merged = headers(&["Pickup Transfer.csv_Embryo Count (D3)", "IVF.csv_AMH-level", "1st visit", "IVF.csv_amh level", "%%"]);
+        let slugs = transform_headers(&merged, HeaderTransform::Slugify);
+        assert_eq!(slugs, headers(&["pickup_transfer_embryo_count_d3", "ivf_amh_level", "c_1st_visit", "ivf_amh_level_2", "column"]));
+
+        let is_identifier = |s: &str| {
+            let mut chars = s.chars();
+            chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        };
+        assert!(slugs.iter().all(|s| is_identifier(s)), "{:?}", slugs);
+    }
+
+    #[test]
+    fn test_translit_gives_ascii() {
+        let merged = headers(&["IVF.csv_تعداد فولیکول", "demographic.csv_کد ملی"]);
+        assert_eq!(
+            transform_headers(&merged, HeaderTransform::Translit),
+            headers(&["ivf_tadad_fvlykvl", "demographic_kd_mly"])
+        );
+        assert!(HeaderTransform::parse("camel").is_err());
+    }
+}
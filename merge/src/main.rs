@@ -1,12 +1,20 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use clap::Parser;
 use csv::{ReaderBuilder, WriterBuilder};
 use encoding_rs::UTF_8;
 use encoding_rs_io::DecodeReaderBytesBuilder;
 use thiserror::Error;
 use indicatif::{ProgressBar, ProgressStyle};
+use rusqlite::{params_from_iter, types::Value, Connection};
+
+mod header_transform;
+
+use shared::delimiter;
+use shared::discover::HeaderMatch;
+use header_transform::{transform_headers, HeaderTransform};
 
 #[derive(Debug, Error)]
 enum DataError {
@@ -16,11 +24,125 @@ enum DataError {
     Csv(#[from] csv::Error),
     #[error("Column not found: {0} in file: {1}")]
     ColumnNotFound(String, String),
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("{0} duplicated IDs in reference file (e.g. {1})")]
+    DuplicateIds(usize, String),
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+}
+
+const MAX_DUPLICATE_EXAMPLES: usize = 5;
+
+// IDs from the reference file, with every ID that appeared more than once
+// mapped to its number of occurrences
+struct ReferenceIds {
+    ids: HashSet<String>,
+    duplicates: BTreeMap<String, usize>,
+}
+
+impl ReferenceIds {
+    fn duplicate_examples(&self) -> String {
+        self.duplicates.iter()
+            .take(MAX_DUPLICATE_EXAMPLES)
+            .map(|(id, count)| format!("{} x{}", id, count))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    // Warn about duplicated IDs, or fail when strict
+    fn check_duplicates(&self, strict: bool) -> Result<(), DataError> {
+        if self.duplicates.is_empty() {
+            return Ok(());
+        }
+        if strict {
+            return Err(DataError::DuplicateIds(self.duplicates.len(), self.duplicate_examples()));
+        }
+        println!("Warning: {} duplicated IDs in reference file (e.g. {})",
+            self.duplicates.len(), self.duplicate_examples());
+        Ok(())
+    }
+}
+
+// How the cohort is built from several reference files
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ReferenceOp {
+    Union,
+    Intersection,
+}
+
+impl ReferenceOp {
+    fn parse(value: &str) -> Result<Self, DataError> {
+        match value {
+            "union" => Ok(ReferenceOp::Union),
+            "intersection" => Ok(ReferenceOp::Intersection),
+            other => Err(DataError::InvalidArgument(
+                format!("--reference-op '{}' (expected union or intersection)", other),
+            )),
+        }
+    }
+}
+
+// Cohort IDs across all reference files. Duplicates are those found within a
+// single file; an ID listed once in each of two files is not a duplicate.
+fn combine_reference_ids(references: &[(String, ReferenceIds)], op: ReferenceOp) -> ReferenceIds {
+    let ids: HashSet<String> = match op {
+        ReferenceOp::Union => references.iter()
+            .flat_map(|(_, reference)| reference.ids.iter().cloned())
+            .collect(),
+        ReferenceOp::Intersection => match references.split_first() {
+            Some(((_, first), rest)) => first.ids.iter()
+                .filter(|id| rest.iter().all(|(_, reference)| reference.ids.contains(*id)))
+                .cloned()
+                .collect(),
+            None => HashSet::new(),
+        },
+    };
+
+    let mut duplicates: BTreeMap<String, usize> = BTreeMap::new();
+    for (_, reference) in references {
+        for (id, count) in &reference.duplicates {
+            *duplicates.entry(id.clone()).or_insert(0) += count;
+        }
+    }
+
+    ReferenceIds { ids, duplicates }
+}
+
+// Per file: how many IDs it lists and how many no other reference file has
+fn print_reference_contributions(references: &[(String, ReferenceIds)], op: ReferenceOp, cohort: &ReferenceIds) {
+    println!("Cohort of {} IDs from the {:?} of {} reference files", cohort.ids.len(), op, references.len());
+    for (i, (file_name, reference)) in references.iter().enumerate() {
+        let only_here = reference.ids.iter()
+            .filter(|id| references.iter().enumerate().all(|(j, (_, other))| j == i || !other.ids.contains(*id)))
+            .count();
+        println!("  {}: {} IDs, {} only in this file", file_name, reference.ids.len(), only_here);
+    }
+}
+
+// Final merged output: IDs first, then name columns, then the rest
+struct MergedTable {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+    // Repeated input records skipped under --deduplicate-rows, over all files
+    duplicates_removed: usize,
+    // Every non-empty merged value with the file it came from, for --provenance
+    provenance: Vec<ProvenanceEntry>,
+}
+
+// One merged value: `column` is the header as written in `source_file`
+#[derive(Debug, Clone, PartialEq)]
+struct ProvenanceEntry {
+    national_id: String,
+    column: String,
+    value: String,
+    source_file: String,
 }
 
 
 // Function to create csv reader
-fn create_reader(file_path: &str) -> Result<csv::Reader<impl std::io::Read>, DataError> {
+fn create_reader(file_path: &str, delimiter: Option<u8>) -> Result<csv::Reader<impl std::io::Read>, DataError> {
+    let delimiter = delimiter::resolve_delimiter(file_path, delimiter)?;
     let file = File::open(file_path)?;
     let decoder = DecodeReaderBytesBuilder::new()
         .encoding(Some(UTF_8))
@@ -30,29 +152,104 @@ fn create_reader(file_path: &str) -> Result<csv::Reader<impl std::io::Read>, Dat
     let reader = ReaderBuilder::new()
         .flexible(true)
         .has_headers(true)
+        .delimiter(delimiter)
         .from_reader(decoder);
     
     Ok(reader)
 }
 
 // Function to read national IDs from PCO file
-fn read_pco_national_ids(file_path: &str, id_column_name: &str) -> Result<HashSet<String>, DataError> {
-    let mut reader = create_reader(file_path)?;
+fn read_pco_national_ids(file_path: &str, id_column_name: &str, header_match: HeaderMatch, delimiter: Option<u8>) -> Result<ReferenceIds, DataError> {
+    let mut reader = create_reader(file_path, delimiter)?;
 
     let headers = reader.headers()?;
-    let id_column_index = headers.iter()
-        .position(|h| h == id_column_name)
+    let id_column_index = header_match.position(headers, id_column_name)
         .ok_or_else(|| DataError::ColumnNotFound(id_column_name.to_string(), file_path.to_string()))?;
 
     let mut national_ids = HashSet::new();
+    let mut duplicates: BTreeMap<String, usize> = BTreeMap::new();
     for result in reader.records() {
         let record = result?;
         if let Some(id) = record.get(id_column_index) {
-            national_ids.insert(id.to_string());
+            if !national_ids.insert(id.to_string()) {
+                *duplicates.entry(id.to_string()).or_insert(1) += 1;
+            }
         }
     }
     println!("Found {} national IDs in PCO file", national_ids.len());
-    Ok(national_ids)
+    Ok(ReferenceIds { ids: national_ids, duplicates })
+}
+
+// Function to read only the header row of a file
+fn read_headers(file_path: &str, delimiter: Option<u8>) -> Result<Vec<String>, DataError> {
+    let mut reader = create_reader(file_path, delimiter)?;
+    let headers = reader.headers()?;
+    Ok(headers.iter().map(String::from).collect())
+}
+
+// Headers-only view of the input files, used for the --schema-report dry run
+struct SchemaReport {
+    // Column name -> files that contain it, in config order
+    columns: BTreeMap<String, Vec<String>>,
+    // Files that lack the ID column
+    missing_id_files: Vec<String>,
+    header_match: HeaderMatch,
+}
+
+impl SchemaReport {
+    fn classify(&self, column: &str, id_column_name: &str) -> &'static str {
+        if self.header_match.matches(column, id_column_name) {
+            "id"
+        } else if self.columns.get(column).map_or(0, |files| files.len()) > 1 {
+            "shared"
+        } else {
+            "unique"
+        }
+    }
+}
+
+fn build_schema_report(files: &[(String, String)], id_column_name: &str, header_match: HeaderMatch, delimiter: Option<u8>) -> Result<SchemaReport, DataError> {
+    let mut columns: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut missing_id_files = Vec::new();
+
+    for (file_name, file_path) in files {
+        let headers = read_headers(file_path, delimiter)?;
+        if header_match.position(headers.iter().map(String::as_str), id_column_name).is_none() {
+            missing_id_files.push(file_name.clone());
+        }
+        for header in headers {
+            let files_with_column = columns.entry(header).or_default();
+            if !files_with_column.contains(file_name) {
+                files_with_column.push(file_name.clone());
+            }
+        }
+    }
+
+    Ok(SchemaReport { columns, missing_id_files, header_match })
+}
+
+fn write_schema_report(report: &SchemaReport, id_column_name: &str, output_path: &str) -> Result<(), DataError> {
+    let mut file = File::create(output_path)?;
+    file.write_all(&[0xEF, 0xBB, 0xBF])?;
+
+    let mut wtr = WriterBuilder::new()
+        .has_headers(true)
+        .from_writer(file);
+
+    wtr.write_record(["Column", "Classification", "File Count", "Files"])?;
+    for (column, files) in &report.columns {
+        wtr.write_record([
+            column.as_str(),
+            report.classify(column, id_column_name),
+            &files.len().to_string(),
+            &files.join("; "),
+        ])?;
+    }
+    for file_name in &report.missing_id_files {
+        wtr.write_record([id_column_name, "missing id column", "0", file_name.as_str()])?;
+    }
+    wtr.flush()?;
+    Ok(())
 }
 
 // Helper function to extract data for a record
@@ -61,11 +258,12 @@ fn extract_record_data(
     file_name: &str,
     file_headers: &[String],
     id_column_index: usize,
+    id_column_name: &str,
     national_id: &str
 ) -> HashMap<String, String> {
     let mut row_data = HashMap::new();
     // Generate national ID header with file prefix
-    let id_header = format!("{}_کد ملی", file_name);
+    let id_header = format!("{}_{}", file_name, id_column_name);
     row_data.insert(id_header, national_id.to_string());
 
     for (i, value) in record.iter().enumerate() {
@@ -77,16 +275,37 @@ fn extract_record_data(
     row_data
 }
 
-// Function to process a single file and extract matching records
+// How every input file is read: --case-insensitive-headers, --dedupe-key
+// (None unless --deduplicate-rows) and --delimiter
+#[derive(Debug, Clone, Copy, Default)]
+struct ReadOptions<'a> {
+    header_match: HeaderMatch,
+    dedupe_key: Option<&'a [String]>,
+    delimiter: Option<u8>,
+}
+
+// What process_file collects across the input files
+#[derive(Default)]
+struct MergeState {
+    data_map: HashMap<String, HashMap<String, String>>,
+    id_headers: Vec<String>,
+    name_headers: HashMap<String, Vec<String>>,
+    other_headers: HashMap<String, Vec<String>>,
+}
+
+// Function to process a single file and extract matching records.
+// With a dedupe key, records repeating an earlier one (on the key columns, or
+// on every cell when the key is empty) are skipped; returns how many were.
 fn process_file(
     file_path: &str,
     file_name: &str,
+    id_column_name: &str,
     national_ids: &HashSet<String>,
-    data_map: &mut HashMap<String, HashMap<String, String>>,
-    id_headers: &mut Vec<String>,
-    name_headers: &mut HashMap<String, Vec<String>>,
-    other_headers: &mut HashMap<String, Vec<String>>,
-) -> Result<(), DataError> {
+    options: ReadOptions,
+    state: &mut MergeState,
+) -> Result<usize, DataError> {
+    let ReadOptions { header_match, dedupe_key, delimiter } = options;
+    let MergeState { data_map, id_headers, name_headers, other_headers } = state;
     println!("Processing {}", file_name);
 
     let pb = ProgressBar::new_spinner();
@@ -94,19 +313,18 @@ fn process_file(
         .template("{spinner:.green} [{elapsed_precise}] {msg}")
         .unwrap());
 
-    let mut reader = create_reader(file_path)?;
+    let mut reader = create_reader(file_path, delimiter)?;
 
     // Get headers
     let headers = reader.headers()?;
     let file_headers: Vec<String> = headers.iter().map(String::from).collect();
 
     // Find the index of the national ID column
-    let id_column_index = headers.iter()
-        .position(|h| h == "کد ملی")
-        .ok_or_else(|| DataError::ColumnNotFound("کد ملی".to_string(), file_name.to_string()))?;
+    let id_column_index = header_match.position(headers, id_column_name)
+        .ok_or_else(|| DataError::ColumnNotFound(id_column_name.to_string(), file_name.to_string()))?;
 
     // Add national ID header to the id_headers list
-    let id_header = format!("{}_کد ملی", file_name);
+    let id_header = format!("{}_{}", file_name, id_column_name);
     id_headers.push(id_header);
 
     // Add headers to appropriate maps (excluding the ID column)
@@ -121,70 +339,63 @@ fn process_file(
         }
     }
 
+    let dedupe_columns: Option<Vec<usize>> = dedupe_key
+        .map(|names| names.iter()
+            .map(|name| header_match.position(file_headers.iter().map(String::as_str), name)
+                .ok_or_else(|| DataError::ColumnNotFound(name.clone(), file_name.to_string())))
+            .collect::<Result<_, _>>())
+        .transpose()?;
+    let mut seen_rows: HashSet<Vec<String>> = HashSet::new();
+    let mut duplicates_removed = 0;
+
     // Read records
     let mut records_processed = 0;
     for result in reader.records() {
         let record = result?;
+        if let Some(columns) = &dedupe_columns {
+            let signature: Vec<String> = if columns.is_empty() {
+                record.iter().map(String::from).collect()
+            } else {
+                columns.iter().map(|&i| record.get(i).unwrap_or("").to_string()).collect()
+            };
+            if !seen_rows.insert(signature) {
+                duplicates_removed += 1;
+                continue;
+            }
+        }
         if let Some(id) = record.get(id_column_index) {
             if national_ids.contains(id) {
                 let row_data = data_map.entry(id.to_string()).or_default();
 
-                let extracted_data = extract_record_data(&record, file_name, &file_headers, id_column_index, id);
+                let extracted_data = extract_record_data(&record, file_name, &file_headers, id_column_index, id_column_name, id);
                 row_data.extend(extracted_data);
                 records_processed += 1;
             }
         }
     }
     println!("Processed {} matching records from {}", records_processed, file_name);
-    Ok(())
+    if dedupe_columns.is_some() {
+        println!("Removed {} duplicate rows from {}", duplicates_removed, file_name);
+    }
+    Ok(duplicates_removed)
 }
 
-fn main() -> Result<(), DataError> {
-    let base_path = Path::new("/home/aricept094/mydata/endometriosis");
-
-    // List of all files to process
-    struct Config {
-        files: Vec<&'static str>,
-        id_column_name: String,
-        output_filename: String,
-    }
-
-    let config = Config {
-        files: vec![
-            "demographic.csv",
-            "IUIO.csv",
-            "IVF.csv",
-            "neonate freeze.csv",
-            "paraclinic.csv",
-            "Pickup Transfer.csv",
-            "pregnancy control.csv",
-        ],
-        id_column_name: "کد ملی".to_string(),
-        output_filename: "/home/aricept094/mydata/endometriosis/merged_endometriosis_data.csv".to_string(),
-    };
-
-    // First, read national IDs from PCO file
-    let pco_path = base_path.join("/home/aricept094/mydata/endometriosis/endometrioma.csv");
-    let national_ids = read_pco_national_ids(pco_path.to_str().unwrap(), &config.id_column_name)?;
-
-    let mut data_map: HashMap<String, HashMap<String, String>> = HashMap::new();
-    let mut id_headers: Vec<String> = Vec::new();
-    let mut name_headers: HashMap<String, Vec<String>> = HashMap::new();
-    let mut other_headers: HashMap<String, Vec<String>> = HashMap::new();
+// Merge the matching records of every (file name, path) pair into one table
+fn merge_files(
+    files: &[(String, String)],
+    national_ids: &HashSet<String>,
+    id_column_name: &str,
+    flatten_headers: bool,
+    options: ReadOptions,
+) -> Result<MergedTable, DataError> {
+    let mut state = MergeState::default();
+    let mut duplicates_removed = 0;
 
     // Process each file
-    for file_name in &config.files {
-        let file_path = base_path.join(file_name);
-        process_file(
-            file_path.to_str().unwrap(),
-            file_name,
-            &national_ids,
-            &mut data_map,
-            &mut id_headers,
-            &mut name_headers,
-            &mut other_headers,
-        )?;
+    for (file_name, file_path) in files {
+        duplicates_removed += process_file(file_path, file_name, id_column_name, national_ids, options, &mut state)?;
     }
+    let MergeState { data_map, id_headers, name_headers, other_headers } = state;
 
     println!("Writing merged data...");
     println!("Total ID columns: {}", id_headers.len());
@@ -202,8 +413,75 @@ fn main() -> Result<(), DataError> {
         final_headers.extend(headers.clone());
     }
 
-    // Write merged data to a new CSV file with proper UTF-8 encoding
-    let output_path = base_path.join(&config.output_filename);
+    let rows = data_map.values()
+        .map(|row_data| final_headers.iter()
+            .map(|header| row_data.get(header).cloned().unwrap_or_default())
+            .collect())
+        .collect();
+
+    // Merged header -> (file, column in that file); ID headers are left out,
+    // the ID is already the first field of every provenance entry
+    let mut sources: HashMap<&str, (&str, &str)> = HashMap::new();
+    for (base_name, full_headers) in name_headers.iter().chain(other_headers.iter()) {
+        for full_header in full_headers {
+            if let Some(file_name) = full_header.strip_suffix(&format!("_{}", base_name)) {
+                sources.insert(full_header.as_str(), (file_name, base_name.as_str()));
+            }
+        }
+    }
+    let mut national_ids: Vec<&String> = data_map.keys().collect();
+    national_ids.sort();
+    let mut provenance = Vec::new();
+    for national_id in national_ids {
+        let row_data = &data_map[national_id];
+        for header in &final_headers {
+            let (Some(value), Some((file_name, column))) = (row_data.get(header), sources.get(header.as_str())) else {
+                continue;
+            };
+            if !value.is_empty() {
+                provenance.push(ProvenanceEntry {
+                    national_id: national_id.clone(),
+                    column: column.to_string(),
+                    value: value.clone(),
+                    source_file: file_name.to_string(),
+                });
+            }
+        }
+    }
+
+    if flatten_headers {
+        // Columns whose base name comes from a single file don't need the file prefix
+        let mut base_names: HashMap<&str, &str> = HashMap::new();
+        for (base_name, full_headers) in name_headers.iter().chain(other_headers.iter()) {
+            if let [only] = full_headers.as_slice() {
+                base_names.insert(only.as_str(), base_name.as_str());
+            }
+        }
+        final_headers = final_headers.iter()
+            .map(|header| base_names.get(header.as_str()).map_or_else(|| header.clone(), |base| base.to_string()))
+            .collect();
+    }
+
+    Ok(MergedTable { headers: final_headers, rows, duplicates_removed, provenance })
+}
+
+// --provenance: the long table behind the wide merge, one row per value
+fn write_provenance(table: &MergedTable, id_column_name: &str, output_path: &Path) -> Result<(), DataError> {
+    let mut file = File::create(output_path)?;
+    file.write_all(&[0xEF, 0xBB, 0xBF])?;
+
+    let mut wtr = WriterBuilder::new().from_writer(file);
+    wtr.write_record([id_column_name, "column", "value", "source_file"])?;
+    for entry in &table.provenance {
+        wtr.write_record([&entry.national_id, &entry.column, &entry.value, &entry.source_file])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+// `na_output` is written in place of every empty cell; "0" and other real
+// values are left alone
+fn write_merged_csv(table: &MergedTable, output_path: &Path, na_output: &str) -> Result<(), DataError> {
     let mut file = File::create(output_path)?;
     
     // Write UTF-8 BOM
@@ -214,17 +492,569 @@ fn main() -> Result<(), DataError> {
         .from_writer(file);
 
     // Write headers
-    wtr.write_record(&final_headers)?;
+    wtr.write_record(&table.headers)?;
 
     // Write data
-    for (_id, row_data) in &data_map {
-        let row: Vec<String> = final_headers.iter()
-            .map(|header| {
-                row_data.get(header).cloned().unwrap_or_default()
-            })
-            .collect();
-        wtr.write_record(&row)?;
+    for row in &table.rows {
+        wtr.write_record(row.iter().map(|cell| if cell.is_empty() { na_output } else { cell.as_str() }))?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+// Share of a column's non-empty values that must be numbers for it to get a
+// numeric type; the same >95% rule excel_column_sort and the data dictionary use
+const SQLITE_NUMERIC_THRESHOLD: f64 = 0.95;
+
+// INTEGER when more than 95% of the non-empty values are numbers and all of
+// those are whole, REAL when some aren't, otherwise TEXT. The odd value out in
+// a numeric column ("n/a", "?") is stored as NULL by write_sqlite.
+fn infer_sqlite_type(table: &MergedTable, column: usize) -> &'static str {
+    let mut non_empty = 0;
+    let mut numeric = 0;
+    let mut all_integer = true;
+    for value in table.rows.iter().map(|row| row[column].trim()).filter(|v| !v.is_empty()) {
+        non_empty += 1;
+        if value.parse::<f64>().is_ok() {
+            numeric += 1;
+            all_integer &= value.parse::<i64>().is_ok();
+        }
+    }
+
+    if non_empty == 0 || (numeric as f64 / non_empty as f64) <= SQLITE_NUMERIC_THRESHOLD {
+        "TEXT"
+    } else if all_integer {
+        "INTEGER"
+    } else {
+        "REAL"
+    }
+}
+
+// A cell cast to its column's type; None when it doesn't parse as that type
+fn sqlite_value(value: &str, column_type: &str) -> Option<Value> {
+    let trimmed = value.trim();
+    match column_type {
+        "TEXT" => Some(Value::Text(value.to_string())),
+        _ if trimmed.is_empty() => Some(Value::Null),
+        "INTEGER" => trimmed.parse::<i64>().ok().map(Value::Integer),
+        _ => trimmed.parse::<f64>().ok().map(Value::Real),
+    }
+}
+
+// Write the merged table as `merged` in a SQLite database, one row per ID.
+// Columns are TEXT unless infer_types is set; typed columns store integers and
+// reals as such, and empty or unparseable cells as NULL (counted per column).
+fn write_sqlite(table: &MergedTable, db_path: &Path, infer_types: bool) -> Result<(), DataError> {
+    let mut conn = Connection::open(db_path)?;
+
+    let column_types: Vec<&str> = (0..table.headers.len())
+        .map(|i| if infer_types { infer_sqlite_type(table, i) } else { "TEXT" })
+        .collect();
+
+    // SQLite column names are case-insensitive, so repeated headers get a suffix
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let columns: Vec<String> = table.headers.iter()
+        .map(|header| {
+            let count = seen.entry(header.to_lowercase()).or_insert(0);
+            *count += 1;
+            let name = if *count == 1 { header.clone() } else { format!("{}_{}", header, count) };
+            format!("\"{}\"", name.replace('"', "\"\""))
+        })
+        .collect();
+
+    let column_defs: Vec<String> = columns.iter()
+        .zip(&column_types)
+        .map(|(name, column_type)| format!("{} {}", name, column_type))
+        .collect();
+    let placeholders = vec!["?"; columns.len()].join(", ");
+    let mut unparseable = vec![0usize; columns.len()];
+
+    let tx = conn.transaction()?;
+    tx.execute("DROP TABLE IF EXISTS merged", [])?;
+    tx.execute(&format!("CREATE TABLE merged ({})", column_defs.join(", ")), [])?;
+    {
+        let mut insert = tx.prepare(&format!(
+            "INSERT INTO merged ({}) VALUES ({})",
+            columns.join(", "),
+            placeholders
+        ))?;
+        for row in &table.rows {
+            let values = row.iter().zip(&column_types).zip(unparseable.iter_mut())
+                .map(|((value, column_type), count)| sqlite_value(value, column_type).unwrap_or_else(|| {
+                    *count += 1;
+                    Value::Null
+                }))
+                .collect::<Vec<_>>();
+            insert.execute(params_from_iter(values))?;
+        }
+    }
+    tx.commit()?;
+
+    for ((header, column_type), count) in table.headers.iter().zip(&column_types).zip(&unparseable) {
+        if *count > 0 {
+            println!("SQLite export: {} value(s) of {} are not {} and were stored as NULL", count, header, column_type);
+        }
+    }
+    Ok(())
+}
+
+// Command line: which files are merged on which ID and what gets written.
+// Relative input, reference and output paths are taken from --base-dir.
+#[derive(Debug, Parser)]
+#[command(about = "Merge the study CSVs on their ID column, keeping the IDs of the reference cohort")]
+struct Config {
+    /// Folder the other paths are relative to
+    #[arg(long)]
+    base_dir: PathBuf,
+    /// File to merge (repeatable)
+    #[arg(long = "file", default_values = [
+        "demographic.csv",
+        "IUIO.csv",
+        "IVF.csv",
+        "neonate freeze.csv",
+        "paraclinic.csv",
+        "Pickup Transfer.csv",
+        "pregnancy control.csv",
+    ])]
+    files: Vec<String>,
+    /// File whose IDs define the cohort (repeatable)
+    #[arg(long = "reference", required = true)]
+    references: Vec<String>,
+    /// How several reference files combine: union or intersection
+    #[arg(long, value_parser = ReferenceOp::parse, default_value = "union")]
+    reference_op: ReferenceOp,
+    /// Join key present in every file
+    #[arg(long = "id-column", default_value = "کد ملی")]
+    id_column_name: String,
+    /// Find the ID and key columns ignoring case and surrounding spaces
+    #[arg(long)]
+    case_insensitive_headers: bool,
+    /// Fail instead of warning on duplicated reference IDs
+    #[arg(long)]
+    strict_ids: bool,
+    /// Keep the file prefix only on colliding column names
+    #[arg(long)]
+    flatten_headers: bool,
+    /// Rewrite the output headers: none, strip-ext, slugify or translit
+    #[arg(long, value_parser = HeaderTransform::parse, default_value = "none")]
+    header_transform: HeaderTransform,
+    /// Merged CSV
+    #[arg(long = "output")]
+    output_filename: String,
+    /// Token written for empty cells of the merged CSV, e.g. NA for R
+    #[arg(long, default_value = "")]
+    na_output: String,
+    /// Compare headers only and write this report, skipping the merge
+    #[arg(long)]
+    schema_report: Option<String>,
+    /// Also export the merged table to this SQLite database
+    #[arg(long = "sqlite")]
+    sqlite_output: Option<String>,
+    /// INTEGER/REAL columns in the SQLite export instead of all TEXT
+    #[arg(long)]
+    sqlite_infer_types: bool,
+    /// Also write (ID, column, value, source file) per merged value
+    #[arg(long = "provenance")]
+    provenance_output: Option<String>,
+    /// Skip repeated input records
+    #[arg(long)]
+    deduplicate_rows: bool,
+    /// Compare records on these columns only (comma-separated) instead of every cell
+    #[arg(long, value_delimiter = ',', requires = "deduplicate_rows")]
+    dedupe_key: Vec<String>,
+    /// Field separator of the inputs (a character or "tab"); detected per file when not given
+    #[arg(long, value_parser = delimiter::parse_delimiter)]
+    delimiter: Option<u8>,
+}
+
+impl Config {
+    fn header_match(&self) -> HeaderMatch {
+        if self.case_insensitive_headers {
+            HeaderMatch::CaseInsensitive
+        } else {
+            HeaderMatch::Exact
+        }
+    }
+
+    fn dedupe_key(&self) -> Option<&[String]> {
+        self.deduplicate_rows.then_some(self.dedupe_key.as_slice())
+    }
+
+    fn read_options(&self) -> ReadOptions<'_> {
+        ReadOptions {
+            header_match: self.header_match(),
+            dedupe_key: self.dedupe_key(),
+            delimiter: self.delimiter,
+        }
+    }
+}
+
+fn main() -> Result<(), DataError> {
+    let config = Config::parse();
+    let base_path = config.base_dir.as_path();
+
+    let files: Vec<(String, String)> = config.files.iter()
+        .map(|file_name| (file_name.to_string(), base_path.join(file_name).to_string_lossy().into_owned()))
+        .collect();
+
+    if let Some(report_path) = &config.schema_report {
+        let report = build_schema_report(&files, &config.id_column_name, config.header_match(), config.delimiter)?;
+        write_schema_report(&report, &config.id_column_name, report_path)?;
+
+        let shared = report.columns.keys()
+            .filter(|c| report.classify(c, &config.id_column_name) == "shared")
+            .count();
+        println!("Schema report saved to '{}'", report_path);
+        println!("Columns: {} total, {} shared across files", report.columns.len(), shared);
+        for file_name in &report.missing_id_files {
+            println!("Warning: {} has no '{}' column", file_name, config.id_column_name);
+        }
+        return Ok(());
+    }
+
+    // First, read national IDs from the reference files
+    let references = config.references.iter()
+        .map(|path| {
+            let ids = read_pco_national_ids(base_path.join(path).to_str().unwrap(), &config.id_column_name, config.header_match(), config.delimiter)?;
+            Ok((path.clone(), ids))
+        })
+        .collect::<Result<Vec<_>, DataError>>()?;
+    let reference = combine_reference_ids(&references, config.reference_op);
+    if references.len() > 1 {
+        print_reference_contributions(&references, config.reference_op, &reference);
+    }
+    reference.check_duplicates(config.strict_ids)?;
+
+    let mut table = merge_files(&files, &reference.ids, &config.id_column_name, config.flatten_headers, config.read_options())?;
+    table.headers = transform_headers(&table.headers, config.header_transform);
+    if config.deduplicate_rows {
+        println!("Duplicate rows removed: {}", table.duplicates_removed);
+    }
+
+    // Write merged data to a new CSV file with proper UTF-8 encoding
+    let output_path = base_path.join(&config.output_filename);
+    write_merged_csv(&table, &output_path, &config.na_output)?;
+
+    if let Some(sqlite_path) = &config.sqlite_output {
+        write_sqlite(&table, Path::new(sqlite_path), config.sqlite_infer_types)?;
+        println!("Merged table also exported to SQLite database '{}'", sqlite_path);
+    }
+    if let Some(provenance_path) = &config.provenance_output {
+        write_provenance(&table, &config.id_column_name, Path::new(provenance_path))?;
+        println!("Provenance of {} values saved to '{}'", table.provenance.len(), provenance_path);
     }
     println!("Data has been successfully merged and saved to '{}'", config.output_filename);
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_fixture(name: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(format!("merge_{}_{}", std::process::id(), name));
+        std::fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_schema_report_classifies_shared_and_unique_columns() {
+        let ivf = write_fixture("ivf.csv", "کد ملی,age,embryos\n1,30,2\n");
+        let demo = write_fixture("demo.csv", "کد ملی,age,city\n1,30,Tehran\n");
+        let files = vec![
+            ("IVF.csv".to_string(), ivf.clone()),
+            ("demographic.csv".to_string(), demo.clone()),
+        ];
+
+        let report = build_schema_report(&files, "کد ملی", HeaderMatch::Exact, None).unwrap();
+
+        assert_eq!(report.classify("age", "کد ملی"), "shared");
+        assert_eq!(report.classify("embryos", "کد ملی"), "unique");
+        assert_eq!(report.classify("city", "کد ملی"), "unique");
+        assert_eq!(report.columns["age"], vec!["IVF.csv", "demographic.csv"]);
+        assert!(report.missing_id_files.is_empty());
+
+        std::fs::remove_file(ivf).ok();
+        std::fs::remove_file(demo).ok();
+    }
+
+    #[test]
+    fn test_sqlite_export_of_merged_fixtures() {
+        let ivf = write_fixture("sqlite_ivf.csv", "کد ملی,age,embryos\n1,30,2\n2,41,\n3,28,1\n");
+        let demo = write_fixture("sqlite_demo.csv", "کد ملی,city\n1,Tehran\n2,Shiraz\n");
+        let files = vec![
+            ("IVF.csv".to_string(), ivf.clone()),
+            ("demographic.csv".to_string(), demo.clone()),
+        ];
+        let national_ids: HashSet<String> = ["1", "2"].iter().map(|s| s.to_string()).collect();
+
+        let table = merge_files(&files, &national_ids, "کد ملی", false, ReadOptions::default()).unwrap();
+        let db_path = std::env::temp_dir().join(format!("merge_{}_merged.sqlite", std::process::id()));
+        write_sqlite(&table, &db_path, true).unwrap();
+
+        let conn = Connection::open(&db_path).unwrap();
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM merged", [], |row| row.get(0)).unwrap();
+        let city: String = conn.query_row(
+            "SELECT \"demographic.csv_city\" FROM merged WHERE \"IVF.csv_age\" = 41",
+            [],
+            |row| row.get(0),
+        ).unwrap();
+        let embryos: Option<i64> = conn.query_row(
+            "SELECT \"IVF.csv_embryos\" FROM merged WHERE \"demographic.csv_city\" = 'Shiraz'",
+            [],
+            |row| row.get(0),
+        ).unwrap();
+        drop(conn);
+
+        std::fs::remove_file(&db_path).ok();
+        std::fs::remove_file(ivf).ok();
+        std::fs::remove_file(demo).ok();
+
+        assert_eq!(count, 2);
+        assert_eq!(city, "Shiraz");
+        assert_eq!(embryos, None);
+    }
+
+    #[test]
+    fn test_duplicate_reference_ids_are_reported() {
+        let reference = write_fixture("reference_dupes.csv", "کد ملی,name\n1,a\n2,b\n1,c\n3,d\n1,e\n");
+
+        let ids = read_pco_national_ids(&reference, "کد ملی", HeaderMatch::Exact, None).unwrap();
+        std::fs::remove_file(reference).ok();
+
+        assert_eq!(ids.ids.len(), 3);
+        assert_eq!(ids.duplicates.len(), 1);
+        assert_eq!(ids.duplicates["1"], 3);
+        assert!(ids.check_duplicates(false).is_ok());
+
+        let err = ids.check_duplicates(true).unwrap_err();
+        assert_eq!(err.to_string(), "1 duplicated IDs in reference file (e.g. 1 x3)");
+    }
+
+    #[test]
+    fn test_union_and_intersection_of_reference_files() {
+        let first = write_fixture("reference_a.csv", "کد ملی,name\n1,a\n2,b\n3,c\n");
+        let second = write_fixture("reference_b.csv", "name,کد ملی\nb,2\nc,3\nd,4\ne,5\n");
+        let references = vec![
+            ("reference_a.csv".to_string(), read_pco_national_ids(&first, "کد ملی", HeaderMatch::Exact, None).unwrap()),
+            ("reference_b.csv".to_string(), read_pco_national_ids(&second, "کد ملی", HeaderMatch::Exact, None).unwrap()),
+        ];
+        std::fs::remove_file(first).ok();
+        std::fs::remove_file(second).ok();
+
+        let union = combine_reference_ids(&references, ReferenceOp::Union);
+        let intersection = combine_reference_ids(&references, ReferenceOp::Intersection);
+
+        assert_eq!(union.ids.len(), 5);
+        assert_eq!(intersection.ids.len(), 2);
+        assert!(intersection.ids.contains("2") && intersection.ids.contains("3"));
+        assert!(union.duplicates.is_empty());
+        assert_eq!(ReferenceOp::parse("intersection").unwrap(), ReferenceOp::Intersection);
+        assert!(ReferenceOp::parse("both").is_err());
+    }
+
+    #[test]
+    fn test_flatten_headers_keeps_prefix_only_on_collisions() {
+        let ivf = write_fixture("flatten_ivf.csv", "کد ملی,age,embryos\n1,30,2\n");
+        let demo = write_fixture("flatten_demo.csv", "کد ملی,age,city\n1,30,Tehran\n");
+        let files = vec![
+            ("IVF.csv".to_string(), ivf.clone()),
+            ("demographic.csv".to_string(), demo.clone()),
+        ];
+        let national_ids: HashSet<String> = ["1".to_string()].into_iter().collect();
+
+        let table = merge_files(&files, &national_ids, "کد ملی", true, ReadOptions::default()).unwrap();
+        std::fs::remove_file(ivf).ok();
+        std::fs::remove_file(demo).ok();
+
+        let has = |name: &str| table.headers.iter().any(|h| h == name);
+        assert!(has("embryos"));
+        assert!(has("city"));
+        assert!(has("IVF.csv_age"));
+        assert!(has("demographic.csv_age"));
+        assert!(!has("age"));
+        assert!(has("IVF.csv_کد ملی"));
+
+        let city = table.headers.iter().position(|h| h == "city").unwrap();
+        assert_eq!(table.rows[0][city], "Tehran");
+    }
+
+    #[test]
+    fn test_deduplicate_rows_drops_repeated_records() {
+        let paraclinic = write_fixture("dedupe_paraclinic.csv", "کد ملی,test,result\n1,AMH,2.1\n1,AMH,2.1\n2,AMH,3.4\n");
+        let files = vec![("paraclinic.csv".to_string(), paraclinic.clone())];
+        let national_ids: HashSet<String> = ["1", "2"].iter().map(|s| s.to_string()).collect();
+
+        let deduplicated = merge_files(&files, &national_ids, "کد ملی", true, ReadOptions { dedupe_key: Some(&[]), ..Default::default() }).unwrap();
+        let by_id = merge_files(&files, &national_ids, "کد ملی", true, ReadOptions { dedupe_key: Some(&["کد ملی".to_string()]), ..Default::default() }).unwrap();
+        let untouched = merge_files(&files, &national_ids, "کد ملی", true, ReadOptions::default()).unwrap();
+        let missing_key = merge_files(&files, &national_ids, "کد ملی", true, ReadOptions { dedupe_key: Some(&["visit".to_string()]), ..Default::default() });
+        std::fs::remove_file(paraclinic).ok();
+
+        assert_eq!(deduplicated.duplicates_removed, 1);
+        assert_eq!(deduplicated.rows.len(), 2);
+        let result = deduplicated.headers.iter().position(|h| h == "result").unwrap();
+        let mut results: Vec<&str> = deduplicated.rows.iter().map(|row| row[result].as_str()).collect();
+        results.sort();
+        assert_eq!(results, vec!["2.1", "3.4"]);
+        assert_eq!(by_id.duplicates_removed, 1);
+        assert_eq!(untouched.duplicates_removed, 0);
+        assert!(matches!(missing_key, Err(DataError::ColumnNotFound(..))));
+    }
+
+    #[test]
+    fn test_provenance_attributes_values_to_source_file() {
+        let ivf = write_fixture("provenance_ivf.csv", "کد ملی,age,embryos\n1,30,2\n2,41,\n");
+        let demo = write_fixture("provenance_demo.csv", "کد ملی,age,city\n1,31,Tehran\n");
+        let files = vec![
+            ("IVF.csv".to_string(), ivf.clone()),
+            ("demographic.csv".to_string(), demo.clone()),
+        ];
+        let national_ids: HashSet<String> = ["1", "2"].iter().map(|s| s.to_string()).collect();
+
+        let table = merge_files(&files, &national_ids, "کد ملی", false, ReadOptions::default()).unwrap();
+        let output = std::env::temp_dir().join(format!("merge_{}_provenance.csv", std::process::id()));
+        write_provenance(&table, "کد ملی", &output).unwrap();
+        let written = std::fs::read_to_string(&output).unwrap();
+        std::fs::remove_file(&output).ok();
+        std::fs::remove_file(ivf).ok();
+        std::fs::remove_file(demo).ok();
+
+        let mut entries: Vec<(&str, &str, &str, &str)> = table.provenance.iter()
+            .map(|e| (e.national_id.as_str(), e.column.as_str(), e.value.as_str(), e.source_file.as_str()))
+            .collect();
+        entries.sort();
+        // `age` is in both files and keeps both values apart; the empty embryos cell has no entry
+        assert_eq!(entries, vec![
+            ("1", "age", "30", "IVF.csv"),
+            ("1", "age", "31", "demographic.csv"),
+            ("1", "city", "Tehran", "demographic.csv"),
+            ("1", "embryos", "2", "IVF.csv"),
+            ("2", "age", "41", "IVF.csv"),
+        ]);
+        assert!(written.starts_with("\u{FEFF}کد ملی,column,value,source_file\n"));
+        assert_eq!(written.lines().count(), entries.len() + 1);
+    }
+
+    #[test]
+    fn test_case_insensitive_headers_find_id_column() {
+        let reference = write_fixture("case_reference.csv", "National_ID,diagnosis\n1,endometrioma\n2,endometrioma\n");
+        let ivf = write_fixture("case_ivf.csv", " national_id ,age\n1,30\n3,35\n");
+        let files = vec![("IVF.csv".to_string(), ivf.clone())];
+
+        let exact = merge_files(&files, &HashSet::from(["1".to_string()]), "National_ID", false, ReadOptions::default());
+        let ids = read_pco_national_ids(&reference, "national_id", HeaderMatch::CaseInsensitive, None).unwrap();
+        let table = merge_files(&files, &ids.ids, "National_ID", false, ReadOptions { header_match: HeaderMatch::CaseInsensitive, ..Default::default() }).unwrap();
+        let report = build_schema_report(&files, "National_ID", HeaderMatch::CaseInsensitive, None).unwrap();
+        std::fs::remove_file(reference).ok();
+        std::fs::remove_file(ivf).ok();
+
+        assert!(matches!(exact, Err(DataError::ColumnNotFound(..))));
+        assert_eq!(ids.ids.len(), 2);
+        assert_eq!(table.rows.len(), 1);
+        let age = table.headers.iter().position(|h| h == "IVF.csv_age").unwrap();
+        assert_eq!(table.rows[0][age], "30");
+        assert!(report.missing_id_files.is_empty());
+        assert_eq!(report.classify(" national_id ", "National_ID"), "id");
+    }
+
+    #[test]
+    fn test_na_output_fills_blank_cells_only() {
+        let ivf = write_fixture("na_ivf.csv", "کد ملی,age,embryos\n1,30,0\n2,41,\n");
+        let demo = write_fixture("na_demo.csv", "کد ملی,city\n1,Tehran\n");
+        let files = vec![
+            ("IVF.csv".to_string(), ivf.clone()),
+            ("demographic.csv".to_string(), demo.clone()),
+        ];
+        let national_ids: HashSet<String> = ["1", "2"].iter().map(|s| s.to_string()).collect();
+        let table = merge_files(&files, &national_ids, "کد ملی", false, ReadOptions::default()).unwrap();
+
+        let output = std::env::temp_dir().join(format!("merge_{}_na.csv", std::process::id()));
+        write_merged_csv(&table, &output, "NA").unwrap();
+        let mut reader = ReaderBuilder::new().from_path(&output).unwrap();
+        let headers: Vec<String> = reader.headers().unwrap().iter().map(|h| h.trim_start_matches('\u{FEFF}').to_string()).collect();
+        let rows: Vec<Vec<String>> = reader.records().map(|r| r.unwrap().iter().map(str::to_string).collect()).collect();
+        std::fs::remove_file(&output).ok();
+        std::fs::remove_file(ivf).ok();
+        std::fs::remove_file(demo).ok();
+
+        let column = |name: &str| headers.iter().position(|h| h == name).unwrap();
+        let row = |id: &str| rows.iter().find(|row| row[column("IVF.csv_کد ملی")] == id).unwrap();
+        // Patient 1's zero embryos is a real value; patient 2 has no embryo count and no demographic row
+        assert_eq!(row("1")[column("IVF.csv_embryos")], "0");
+        assert_eq!(row("1")[column("demographic.csv_city")], "Tehran");
+        assert_eq!(row("2")[column("IVF.csv_embryos")], "NA");
+        assert_eq!(row("2")[column("demographic.csv_city")], "NA");
+        assert_eq!(row("2")[column("IVF.csv_age")], "41");
+    }
+
+    #[test]
+    fn test_sqlite_schema_gets_inferred_affinities() {
+        let mut lab = String::from("کد ملی,embryos,amh,clinic\n");
+        for id in 1..=25 {
+            // One unreadable embryo count among otherwise whole numbers
+            let embryos = if id == 7 { "n/a".to_string() } else { (id % 4).to_string() };
+            lab.push_str(&format!("{},{},{}.{},Clinic {}\n", id, embryos, id % 6, id % 10, id % 3));
+        }
+        let lab = write_fixture("sqlite_types_lab.csv", &lab);
+        let files = vec![("lab.csv".to_string(), lab.clone())];
+        let national_ids: HashSet<String> = (1..=25).map(|id| id.to_string()).collect();
+
+        let table = merge_files(&files, &national_ids, "کد ملی", true, ReadOptions::default()).unwrap();
+        let db_path = std::env::temp_dir().join(format!("merge_{}_types.sqlite", std::process::id()));
+        write_sqlite(&table, &db_path, true).unwrap();
+
+        let conn = Connection::open(&db_path).unwrap();
+        let schema: HashMap<String, String> = conn.prepare("PRAGMA table_info(merged)").unwrap()
+            .query_map([], |row| Ok((row.get(1)?, row.get(2)?))).unwrap()
+            .map(Result::unwrap)
+            .collect();
+        let stored: Vec<(String, String, String)> = conn.prepare("SELECT typeof(embryos), typeof(amh), typeof(clinic) FROM merged").unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap()
+            .map(Result::unwrap)
+            .collect();
+        drop(conn);
+        std::fs::remove_file(&db_path).ok();
+        std::fs::remove_file(lab).ok();
+
+        assert_eq!(schema["embryos"], "INTEGER");
+        assert_eq!(schema["amh"], "REAL");
+        assert_eq!(schema["clinic"], "TEXT");
+        assert_eq!(stored.iter().filter(|(embryos, _, _)| embryos == "null").count(), 1);
+        assert_eq!(stored.iter().filter(|(embryos, _, _)| embryos == "integer").count(), 24);
+        assert!(stored.iter().all(|(_, amh, clinic)| amh == "real" && clinic == "text"));
+        assert_eq!(sqlite_value("4", "INTEGER"), Some(Value::Integer(4)));
+        assert_eq!(sqlite_value("4.5", "INTEGER"), None);
+        assert_eq!(sqlite_value(" ", "REAL"), Some(Value::Null));
+    }
+
+    #[test]
+    fn test_help_and_missing_value() {
+        let help = Config::try_parse_from(["merge", "--help"]).unwrap_err();
+        assert_eq!(help.kind(), clap::error::ErrorKind::DisplayHelp);
+        assert_eq!(help.exit_code(), 0);
+        assert!(help.to_string().contains("--reference-op"));
+
+        let missing = Config::try_parse_from(["merge", "--id-column"]).unwrap_err();
+        assert_ne!(missing.exit_code(), 0);
+        assert!(missing.to_string().contains("--id-column"), "{}", missing);
+        let required = ["merge", "--base-dir", "data", "--reference", "a.csv", "--output", "merged.csv"];
+        assert!(Config::try_parse_from(required.iter().chain(&["--reference-op", "xor"])).is_err());
+        assert!(Config::try_parse_from(required.iter().chain(&["--dedupe-key", "id"])).is_err());
+
+        // There is no default folder, reference or output file
+        for flag in ["--base-dir", "--reference", "--output"] {
+            let i = required.iter().position(|a| *a == flag).unwrap();
+            let without: Vec<&str> = required.iter().enumerate().filter(|(j, _)| *j != i && *j != i + 1).map(|(_, a)| *a).collect();
+            let error = Config::try_parse_from(without).unwrap_err();
+            assert_eq!(error.kind(), clap::error::ErrorKind::MissingRequiredArgument, "{}", flag);
+            assert!(error.to_string().contains(flag), "{}", error);
+        }
+
+        let config = Config::try_parse_from(required.iter().chain(&["--reference", "b.csv", "--deduplicate-rows", "--dedupe-key", "id,visit"])).unwrap();
+        assert_eq!(config.references, vec!["a.csv", "b.csv"]);
+        assert_eq!(config.dedupe_key(), Some(&["id".to_string(), "visit".to_string()][..]));
+        assert_eq!(config.files.len(), 7);
+        assert_eq!(config.header_match(), HeaderMatch::Exact);
+    }
+}
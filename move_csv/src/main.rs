@@ -9,13 +9,13 @@ fn main() -> io::Result<()> {
     let dest_dir = "/home/aricept094/mydata/casia_less_than_1";  // Corrected username
 
     println!("Checking source directory...");
-    match fs::read_dir(&source_dir) {
+    match fs::read_dir(source_dir) {
         Ok(entries) => {
             let csv_count = entries
                 .filter_map(|e| e.ok())
                 .filter(|e| {
                     e.path().extension()
-                        .map_or(false, |ext| ext.to_string_lossy().to_lowercase() == "csv")
+                        .is_some_and(|ext| ext.to_string_lossy().to_lowercase() == "csv")
                 })
                 .count();
             println!("Found {} CSV files in source directory", csv_count);
@@ -28,7 +28,7 @@ fn main() -> io::Result<()> {
 
     // Create destination directory if it doesn't exist
     println!("Creating destination directory if it doesn't exist...");
-    match fs::create_dir_all(&dest_dir) {
+    match fs::create_dir_all(dest_dir) {
         Ok(_) => println!("Destination directory ready: {}", dest_dir),
         Err(e) => {
             eprintln!("Error creating destination directory: {}", e);
@@ -45,7 +45,7 @@ fn main() -> io::Result<()> {
     println!("Destination directory: {}", dest_dir);
 
     // Walk through the source directory recursively
-    for entry in WalkDir::new(&source_dir)
+    for entry in WalkDir::new(source_dir)
         .follow_links(true)
         .into_iter()
         .filter_map(|e| match e {
@@ -59,7 +59,7 @@ fn main() -> io::Result<()> {
         let path = entry.path();
         
         // Check if the file is a CSV
-        if path.is_file() && path.extension().map_or(false, |ext| ext.to_string_lossy().to_lowercase() == "csv") {
+        if path.is_file() && path.extension().is_some_and(|ext| ext.to_string_lossy().to_lowercase() == "csv") {
             // Get the file name
             let file_name = path.file_name().unwrap();
             
@@ -89,13 +89,13 @@ fn main() -> io::Result<()> {
     println!("Total files processed: {}", copied_files + failed_files);
 
     // Verify destination
-    match fs::read_dir(&dest_dir) {
+    match fs::read_dir(dest_dir) {
         Ok(entries) => {
             let copied_count = entries
                 .filter_map(|e| e.ok())
                 .filter(|e| {
                     e.path().extension()
-                        .map_or(false, |ext| ext.to_string_lossy().to_lowercase() == "csv")
+                        .is_some_and(|ext| ext.to_string_lossy().to_lowercase() == "csv")
                 })
                 .count();
             println!("\nVerification: Found {} CSV files in destination directory", copied_count);
@@ -1,14 +1,23 @@
-use calamine::{open_workbook, Reader, Xlsx};
-use std::fs::{self, create_dir_all};
+use calamine::{open_workbook, Data, Range, Reader, Xlsx};
+use std::fs::create_dir_all;
 use std::path::Path;
+use std::time::{Duration, Instant};
 use csv::Writer;
 use anyhow::{Result, Context};
+use rayon::prelude::*;
+
+use shared::layout::SheetLayout;
 
 fn main() -> Result<()> {
     // Define input and output paths
     let input_path = "/home/aricept094/mydata/Book2.xlsx";
     let output_dir = "/home/aricept094/mydata/sheets";
 
+    // --skip-rows / --header-row: title/metadata rows above the real header,
+    // dropped from the CSVs; the default copies every row
+    let args: Vec<String> = std::env::args().collect();
+    let layout = SheetLayout::from_args(&args).map_err(anyhow::Error::msg)?;
+
     // Create output directory if it doesn't exist
     create_dir_all(output_dir)?;
 
@@ -19,9 +28,17 @@ fn main() -> Result<()> {
     // Get all sheet names
     let sheet_names = workbook.sheet_names().to_vec();
 
-    // Process each sheet
-    for sheet_name in sheet_names {
-        process_sheet(&mut workbook, &sheet_name, output_dir)?;
+    // --parallel-sheets: read every sheet first, then write the CSVs concurrently
+    if args.iter().any(|a| a == "--parallel-sheets") {
+        let sheets = read_sheets(&mut workbook, &sheet_names)?;
+        for (sheet_name, elapsed) in write_sheets_parallel(&sheets, output_dir, layout)? {
+            println!("Processed sheet: {} ({:.2?})", sheet_name, elapsed);
+        }
+    } else {
+        // Process each sheet
+        for sheet_name in sheet_names {
+            process_sheet(&mut workbook, &sheet_name, output_dir, layout)?;
+        }
     }
 
     println!("All sheets have been successfully converted to CSV!");
@@ -30,18 +47,56 @@ fn main() -> Result<()> {
 
 fn process_sheet(workbook: &mut Xlsx<impl std::io::Read + std::io::Seek>, 
                 sheet_name: &str, 
-                output_dir: &str) -> Result<()> {
+                output_dir: &str,
+                layout: SheetLayout) -> Result<()> {
     // Get the sheet
     let range = workbook.worksheet_range(sheet_name)
         .with_context(|| format!("Failed to read sheet {}", sheet_name))?;
 
-    // Create CSV writer
     let output_path = Path::new(output_dir).join(format!("{}.csv", sheet_name));
-    let mut writer = Writer::from_path(&output_path)
+    write_range_to_csv(&range, &output_path, layout)?;
+
+    println!("Processed sheet: {}", sheet_name);
+    Ok(())
+}
+
+// The workbook isn't Sync, so the ranges are loaded one at a time into owned data
+fn read_sheets(workbook: &mut Xlsx<impl std::io::Read + std::io::Seek>,
+               sheet_names: &[String]) -> Result<Vec<(String, Range<Data>)>> {
+    sheet_names.iter()
+        .map(|sheet_name| {
+            let range = workbook.worksheet_range(sheet_name)
+                .with_context(|| format!("Failed to read sheet {}", sheet_name))?;
+            Ok((sheet_name.clone(), range))
+        })
+        .collect()
+}
+
+// Each sheet goes to its own file, so the writes run in parallel; returns the
+// time spent writing each sheet, in workbook order
+fn write_sheets_parallel(sheets: &[(String, Range<Data>)],
+                         output_dir: &str,
+                         layout: SheetLayout) -> Result<Vec<(String, Duration)>> {
+    sheets.par_iter()
+        .map(|(sheet_name, range)| {
+            let started = Instant::now();
+            let output_path = Path::new(output_dir).join(format!("{}.csv", sheet_name));
+            write_range_to_csv(range, &output_path, layout)?;
+            Ok((sheet_name.clone(), started.elapsed()))
+        })
+        .collect()
+}
+
+fn write_range_to_csv(range: &Range<Data>, output_path: &Path, layout: SheetLayout) -> Result<()> {
+    // Create CSV writer
+    let mut writer = Writer::from_path(output_path)
         .with_context(|| format!("Failed to create CSV writer for {}", output_path.display()))?;
 
+    // The header (if any) becomes the first CSV row; everything above it is dropped
+    let first_row = layout.skip_rows + layout.header_row.unwrap_or(0);
+
     // Process each row
-    for row in range.rows() {
+    for row in range.rows().skip(first_row) {
         // Convert each cell to string
         let row_data: Vec<String> = row.iter()
             .map(|cell| cell.to_string())
@@ -55,6 +110,71 @@ fn process_sheet(workbook: &mut Xlsx<impl std::io::Read + std::io::Seek>,
     writer.flush()
         .with_context(|| format!("Failed to flush CSV writer for {}", output_path.display()))?;
 
-    println!("Processed sheet: {}", sheet_name);
     Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_header_row_skips_junk_rows() {
+        // Two junk rows, then a header, then one data row
+        let mut range = Range::new((0, 0), (3, 1));
+        range.set_value((0, 0), Data::String("Exported report".to_string()));
+        range.set_value((1, 0), Data::String("Generated 2024-01-01".to_string()));
+        range.set_value((2, 0), Data::String("ID".to_string()));
+        range.set_value((2, 1), Data::String("Age".to_string()));
+        range.set_value((3, 0), Data::Int(1));
+        range.set_value((3, 1), Data::Int(30));
+
+        let output_path = std::env::temp_dir()
+            .join(format!("sheet_layout_{}.csv", std::process::id()));
+        let layout = SheetLayout { skip_rows: 2, header_row: Some(0) };
+        write_range_to_csv(&range, &output_path, layout).unwrap();
+
+        let written = fs::read_to_string(&output_path).unwrap();
+        assert_eq!(written.lines().collect::<Vec<_>>(), vec!["ID,Age", "1,30"]);
+        fs::remove_file(output_path).ok();
+    }
+
+    #[test]
+    fn test_parallel_sheets_match_sequential_output() {
+        let sheets: Vec<(String, Range<Data>)> = (0..4)
+            .map(|i| {
+                let mut range = Range::new((0, 0), (2, 1));
+                range.set_value((0, 0), Data::String("ID".to_string()));
+                range.set_value((0, 1), Data::String(format!("Value_{}", i)));
+                range.set_value((1, 0), Data::Int(1));
+                range.set_value((1, 1), Data::Float(i as f64 + 0.5));
+                range.set_value((2, 0), Data::Int(2));
+                range.set_value((2, 1), Data::String(format!("sheet {}", i)));
+                (format!("Sheet{}", i), range)
+            })
+            .collect();
+
+        let base = std::env::temp_dir().join(format!("parallel_sheets_{}", std::process::id()));
+        let sequential_dir = base.join("sequential");
+        let parallel_dir = base.join("parallel");
+        create_dir_all(&sequential_dir).unwrap();
+        create_dir_all(&parallel_dir).unwrap();
+
+        let layout = SheetLayout::default();
+        for (sheet_name, range) in &sheets {
+            write_range_to_csv(range, &sequential_dir.join(format!("{}.csv", sheet_name)), layout).unwrap();
+        }
+        let timings = write_sheets_parallel(&sheets, parallel_dir.to_str().unwrap(), layout).unwrap();
+
+        let names: Vec<&str> = timings.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["Sheet0", "Sheet1", "Sheet2", "Sheet3"]);
+        for (sheet_name, _) in &sheets {
+            let file_name = format!("{}.csv", sheet_name);
+            let sequential = fs::read(sequential_dir.join(&file_name)).unwrap();
+            let parallel = fs::read(parallel_dir.join(&file_name)).unwrap();
+            assert!(!parallel.is_empty());
+            assert_eq!(sequential, parallel, "{} differs", file_name);
+        }
+        fs::remove_dir_all(&base).ok();
+    }
 }
\ No newline at end of file
@@ -0,0 +1,214 @@
+// Runs the processing steps (extract_csv_data_multi → grid_fix_multi) in the
+// order given by a TOML config. Each stage calls that crate's library with
+// the arguments from the config, as if they were given on its command line.
+// move_csv, csv_to_8, csv_filter and descriptive_multi still read and write
+// fixed directories, so they are run on their own.
+//
+//   pipeline <config.toml>
+//
+//   [[stage]]
+//   name = "grid"
+//   program = "grid_fix_multi"          # one of STAGE_PROGRAMS
+//   args = ["--base-dir", "/data/extracted/processed_data", "--out-dir", "/data/grid"]
+//   output = "/data/grid"               # optional: must exist afterwards
+
+use clap::Parser;
+use serde::Deserialize;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+// The entry point of one stage: the stage's command line, program name first
+type StageFn = fn(&[String]) -> Result<(), String>;
+
+const STAGE_PROGRAMS: &[(&str, StageFn)] = &[
+    ("extract_csv_data_multi", |args| extract_csv_data_multi::run(args).map_err(|e| e.to_string())),
+    ("grid_fix_multi", |args| {
+        let args = grid_fix_multi::Args::try_parse_from(args).map_err(|e| e.to_string())?;
+        grid_fix_multi::run(args).map_err(|e| e.to_string())
+    }),
+];
+
+#[derive(Debug, Deserialize)]
+struct PipelineConfig {
+    #[serde(rename = "stage", default)]
+    stages: Vec<Stage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Stage {
+    name: String,
+    program: String,
+    #[serde(default)]
+    args: Vec<String>,
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+struct StageResult {
+    name: String,
+    elapsed: Duration,
+    error: Option<String>,
+}
+
+fn load_config(path: &Path) -> Result<PipelineConfig, Box<dyn Error>> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    let config: PipelineConfig = toml::from_str(&text).map_err(|e| format!("Invalid pipeline config {}: {}", path.display(), e))?;
+    if config.stages.is_empty() {
+        return Err(format!("{} defines no [[stage]] entries", path.display()).into());
+    }
+    // A typo in a later stage's program is reported before anything runs
+    for stage in &config.stages {
+        stage_program(&stage.program)?;
+    }
+    Ok(config)
+}
+
+fn stage_program(program: &str) -> Result<StageFn, String> {
+    STAGE_PROGRAMS.iter()
+        .find(|(name, _)| *name == program)
+        .map(|(_, run)| *run)
+        .ok_or_else(|| {
+            let known: Vec<&str> = STAGE_PROGRAMS.iter().map(|(name, _)| *name).collect();
+            format!("Unknown stage program '{}' (expected one of {})", program, known.join(", "))
+        })
+}
+
+fn run_stage(stage: &Stage) -> Result<(), String> {
+    let run = stage_program(&stage.program)?;
+    let command_line: Vec<String> = std::iter::once(stage.program.clone())
+        .chain(stage.args.iter().cloned())
+        .collect();
+    run(&command_line).map_err(|e| format!("'{}' failed: {}", stage.program, e))?;
+    if let Some(output) = &stage.output {
+        if !output.exists() {
+            return Err(format!("expected output {} was not produced", output.display()));
+        }
+    }
+    Ok(())
+}
+
+// Runs the stages in order and stops at the first one that fails; the
+// results cover every stage that was started
+fn run_pipeline(config: &PipelineConfig) -> Vec<StageResult> {
+    let mut results = Vec::new();
+    for (i, stage) in config.stages.iter().enumerate() {
+        println!("\n=== Stage {}/{}: {} ===", i + 1, config.stages.len(), stage.name);
+        let timer = Instant::now();
+        let outcome = run_stage(stage);
+        let failed = outcome.is_err();
+        results.push(StageResult {
+            name: stage.name.clone(),
+            elapsed: timer.elapsed(),
+            error: outcome.err(),
+        });
+        if failed {
+            break;
+        }
+    }
+    results
+}
+
+fn print_summary(results: &[StageResult], total_stages: usize) {
+    println!("\nPipeline summary:");
+    for result in results {
+        let status = match &result.error {
+            Some(error) => format!("FAILED ({})", error),
+            None => "ok".to_string(),
+        };
+        println!("  {:<24} {:>10.2?}  {}", result.name, result.elapsed, status);
+    }
+    if results.len() < total_stages {
+        println!("  {} stage(s) not run", total_stages - results.len());
+    }
+    let total: Duration = results.iter().map(|r| r.elapsed).sum();
+    println!("  {:<24} {:>10.2?}", "total", total);
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    let config_path = args.get(1).ok_or("Usage: pipeline <config.toml>")?;
+
+    let config = load_config(Path::new(config_path))?;
+    let results = run_pipeline(&config);
+    print_summary(&results, config.stages.len());
+
+    if results.iter().any(|r| r.error.is_some()) {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // tests/fixtures/P1.csv is one raw export: every marker section with its
+    // metadata rows and the first two rows of its grid
+    #[test]
+    fn test_pipeline_extracts_and_combines_and_stops_on_failure() {
+        let dir = std::env::temp_dir().join(format!("pipeline_test_{}", std::process::id()));
+        fs::create_dir_all(dir.join("raw")).unwrap();
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/P1.csv");
+        fs::copy(fixture, dir.join("raw/P1.csv")).unwrap();
+
+        let config_path = dir.join("pipeline.toml");
+        fs::write(
+            &config_path,
+            r#"
+[[stage]]
+name = "extract"
+program = "extract_csv_data_multi"
+args = ["--dir", "RAW"]
+output = "EXTRACTED"
+
+[[stage]]
+name = "grid"
+program = "grid_fix_multi"
+args = ["--base-dir", "RAW/processed_data", "--out-dir", "GRID", "--fill-missing", "zero"]
+output = "COMBINED"
+"#
+            .replace("EXTRACTED", &dir.join("raw/processed_data/Pachymetry/Pachymetry_P1.csv").display().to_string())
+            .replace("RAW", &dir.join("raw").display().to_string())
+            .replace("COMBINED", &dir.join("grid/P1_combined.csv").display().to_string())
+            .replace("GRID", &dir.join("grid").display().to_string()),
+        )
+        .unwrap();
+        let mut config = load_config(&config_path).unwrap();
+        let results = run_pipeline(&config);
+        let combined = fs::read_to_string(dir.join("grid/P1_combined.csv")).unwrap();
+
+        // A failing first stage leaves the others unstarted
+        config.stages.swap(0, 1);
+        fs::remove_dir_all(dir.join("raw/processed_data")).unwrap();
+        let failed = run_pipeline(&config);
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.error.is_none()), "{:?}", results);
+        let mut rows = csv::Reader::from_reader(combined.as_bytes());
+        let headers = rows.headers().unwrap().clone();
+        let pachymetry = headers.iter().position(|h| h == "Pachymetry_Value").unwrap();
+        let records: Vec<csv::StringRecord> = rows.records().map(Result::unwrap).collect();
+        assert_eq!(records.len(), 256 * 32);
+        assert_eq!(&records[0][pachymetry], "10");
+        assert_eq!(&records[1][pachymetry], "10.01");
+
+        assert_eq!(failed.len(), 1);
+        assert!(failed[0].error.as_deref().unwrap().contains("'grid_fix_multi' failed"));
+    }
+
+    #[test]
+    fn test_unknown_program_is_rejected_before_running() {
+        let dir = std::env::temp_dir().join(format!("pipeline_unknown_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("pipeline.toml");
+        fs::write(&config_path, "[[stage]]\nname = \"copy\"\nprogram = \"cp\"\n").unwrap();
+
+        let error = load_config(&config_path).unwrap_err().to_string();
+        fs::remove_dir_all(&dir).ok();
+
+        assert!(error.contains("Unknown stage program 'cp'"), "{}", error);
+    }
+}
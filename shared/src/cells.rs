@@ -0,0 +1,26 @@
+// Cell cleanup for --normalize-whitespace.
+
+// Trim, collapse any run of inner whitespace to one space and drop zero-width
+// spaces, word joiners and stray BOMs. The zero-width (non-)joiner is kept: in
+// Persian text it is part of the spelling, not noise.
+pub fn normalize_cell(value: &str) -> String {
+    value.chars()
+        .filter(|c| !matches!(c, '\u{200B}' | '\u{2060}' | '\u{FEFF}'))
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_cell() {
+        assert_eq!(normalize_cell("  A  "), "A");
+        assert_eq!(normalize_cell("left \t  eye\u{200B}"), "left eye");
+        assert_eq!(normalize_cell("\u{FEFF}B"), "B");
+        assert_eq!(normalize_cell("می\u{200C}خواهم"), "می\u{200C}خواهم");
+    }
+}
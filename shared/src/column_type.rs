@@ -0,0 +1,20 @@
+// Numeric vs categorical column classification, as excel_column_sort orders
+// columns and excel_count_values_all's --dictionary reports them.
+
+// Numeric when more than 95% of the non-empty values parse as numbers; a
+// column with no values is categorical
+pub fn is_numeric_column(numeric_count: usize, non_empty_count: usize) -> bool {
+    non_empty_count > 0 && numeric_count as f64 / non_empty_count as f64 > 0.95
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_numeric_needs_more_than_95_percent() {
+        assert!(is_numeric_column(96, 100));
+        assert!(!is_numeric_column(95, 100));
+        assert!(!is_numeric_column(0, 0));
+    }
+}
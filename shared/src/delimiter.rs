@@ -0,0 +1,114 @@
+// Delimiter autodetection for the CSV readers: comma, semicolon, tab and pipe
+// exports are read without a --delimiter.
+
+use std::fs::File;
+use std::io::{self, Read};
+
+// Bytes read from the start of a file to guess its delimiter
+const SAMPLE_BYTES: usize = 8 * 1024;
+// In order of preference when two split the sample equally well
+const CANDIDATES: [u8; 4] = [b',', b';', b'\t', b'|'];
+
+// --delimiter: "," ";" "tab" (or "\t") or any other single ASCII character
+pub fn parse_delimiter(value: &str) -> Result<u8, String> {
+    match value {
+        "tab" | "\\t" | "\t" => Ok(b'\t'),
+        _ if value.len() == 1 && value.is_ascii() => Ok(value.as_bytes()[0]),
+        _ => Err(format!("Invalid --delimiter '{}' (expected a single character or tab)", value)),
+    }
+}
+
+pub fn delimiter_name(delimiter: u8) -> String {
+    match delimiter {
+        b'\t' => "tab".to_string(),
+        other => format!("'{}'", other as char),
+    }
+}
+
+// Field counts of the complete records in the sample, ignoring delimiters and
+// line breaks inside quotes
+fn field_counts(sample: &[u8], delimiter: u8) -> Vec<usize> {
+    let mut counts = Vec::new();
+    let mut fields = 1;
+    let mut in_quotes = false;
+    for &byte in sample {
+        match byte {
+            b'"' => in_quotes = !in_quotes,
+            b'\n' if !in_quotes => {
+                counts.push(fields);
+                fields = 1;
+            }
+            _ if byte == delimiter && !in_quotes => fields += 1,
+            _ => {}
+        }
+    }
+    counts
+}
+
+// The candidate that splits the header into more than one field and the most
+// following records into the same number of fields as the header. Comma when
+// none does.
+pub fn detect_delimiter(sample: &[u8]) -> u8 {
+    let sample = sample.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(sample);
+    let mut best = (b',', 0);
+    for delimiter in CANDIDATES {
+        let mut counts = field_counts(sample, delimiter);
+        if counts.is_empty() {
+            // A sample without a line break is a lone header
+            counts.push(1 + sample.iter().filter(|&&b| b == delimiter).count());
+        }
+        let header_fields = counts[0];
+        if header_fields < 2 {
+            continue;
+        }
+        let consistent = counts.iter().filter(|&&n| n == header_fields).count();
+        if consistent > best.1 {
+            best = (delimiter, consistent);
+        }
+    }
+    best.0
+}
+
+// The --delimiter override, or the one detected from the first few KB
+pub fn resolve_delimiter(file_path: &str, delimiter: Option<u8>) -> io::Result<u8> {
+    if let Some(delimiter) = delimiter {
+        return Ok(delimiter);
+    }
+    let mut sample = Vec::with_capacity(SAMPLE_BYTES);
+    File::open(file_path)?.take(SAMPLE_BYTES as u64).read_to_end(&mut sample)?;
+    let detected = detect_delimiter(&sample);
+    println!("Detected delimiter {} in {}", delimiter_name(detected), file_path);
+    Ok(detected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_semicolon_and_comma_files() {
+        let dir = std::env::temp_dir().join(format!("delimiter_detect_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // Decimal commas inside the semicolon export, a quoted semicolon in the comma one
+        let semicolon = dir.join("semicolon.csv");
+        std::fs::write(&semicolon, "\u{feff}id;weight;note\n1;61,5;a\n2;70,25;b\n3;58;c\n").unwrap();
+        let comma = dir.join("comma.csv");
+        std::fs::write(&comma, "id,weight,note\n1,61.5,\"x; y\"\n2,70.25,b\n").unwrap();
+
+        let detected_semicolon = resolve_delimiter(semicolon.to_str().unwrap(), None).unwrap();
+        let detected_comma = resolve_delimiter(comma.to_str().unwrap(), None).unwrap();
+        let overridden = resolve_delimiter(semicolon.to_str().unwrap(), Some(b',')).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(detected_semicolon, b';');
+        assert_eq!(detected_comma, b',');
+        assert_eq!(overridden, b',');
+        assert_eq!(detect_delimiter(b"a\tb\tc\n1\t2\t3\n"), b'\t');
+        assert_eq!(detect_delimiter(b"a|b\n1|2\n"), b'|');
+        assert_eq!(detect_delimiter(b"single column\n1\n"), b',');
+
+        assert_eq!(parse_delimiter("tab"), Ok(b'\t'));
+        assert_eq!(parse_delimiter(";"), Ok(b';'));
+        assert!(parse_delimiter(";;").is_err());
+    }
+}
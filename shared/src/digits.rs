@@ -0,0 +1,32 @@
+// Persian/Arabic digit normalization for number parsing.
+
+use std::borrow::Cow;
+
+// Persian (۰-۹) and Arabic-Indic (٠-٩) digits, plus the Arabic decimal and
+// thousands separators, mapped to ASCII so the values parse as numbers
+pub fn normalize_persian_digits(value: &str) -> Cow<'_, str> {
+    if value.is_ascii() {
+        return Cow::Borrowed(value);
+    }
+
+    Cow::Owned(value.chars().map(|c| match c {
+        '\u{06F0}'..='\u{06F9}' => char::from(b'0' + (c as u32 - 0x06F0) as u8),
+        '\u{0660}'..='\u{0669}' => char::from(b'0' + (c as u32 - 0x0660) as u8),
+        '\u{066B}' => '.',
+        '\u{066C}' => ',',
+        _ => c,
+    }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_persian_and_arabic_digits_map_to_ascii() {
+        assert_eq!(normalize_persian_digits("۱۲۳"), "123");
+        assert_eq!(normalize_persian_digits("٤٥٫٦"), "45.6");
+        assert_eq!(normalize_persian_digits("۱٬۲۳۴"), "1,234");
+        assert!(matches!(normalize_persian_digits("42"), Cow::Borrowed(_)));
+    }
+}
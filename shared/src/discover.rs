@@ -0,0 +1,286 @@
+// Input file discovery with --ext / --all-files, --include / --exclude globs
+// and --recursive, and header lookup with --case-insensitive-headers and
+// --require-columns.
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use csv::StringRecord;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use walkdir::WalkDir;
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet, Box<dyn Error>> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).map_err(|e| format!("Invalid glob '{}': {}", pattern, e))?);
+    }
+    Ok(builder.build()?)
+}
+
+// A pattern matches an entry through either its path relative to the root
+// or its bare file name, so `*_L_*.csv` works at any depth and `old` or
+// `old/**` both exclude that subdirectory
+fn matches(set: &GlobSet, relative: &Path) -> bool {
+    set.is_match(relative) || relative.file_name().is_some_and(|name| set.is_match(name))
+}
+
+// Files under root, sorted by path. With no include globs every file is
+// kept; an excluded directory is not descended into. Without recursive only
+// the root's own entries are looked at.
+pub fn discover_files(root: &Path, include_globs: &[String], exclude_globs: &[String], recursive: bool) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let include = glob_set(include_globs)?;
+    let exclude = glob_set(exclude_globs)?;
+
+    let walker = WalkDir::new(root)
+        .min_depth(1)
+        .max_depth(if recursive { usize::MAX } else { 1 })
+        .into_iter()
+        .filter_entry(|entry| !matches(&exclude, entry.path().strip_prefix(root).unwrap_or(entry.path())));
+
+    let mut files = Vec::new();
+    for entry in walker {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(root).unwrap_or(entry.path());
+        if entry.path().is_file() && (include_globs.is_empty() || matches(&include, relative)) {
+            files.push(entry.into_path());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// Comma-separated values of a repeatable flag: --include a,b --include c
+pub fn globs_from_args(args: &[String], flag: &str) -> Vec<String> {
+    args.iter()
+        .enumerate()
+        .filter(|(_, a)| *a == flag)
+        .filter_map(|(i, _)| args.get(i + 1))
+        .flat_map(|list| list.split(','))
+        .map(|pattern| pattern.trim().to_string())
+        .filter(|pattern| !pattern.is_empty())
+        .collect()
+}
+
+// Which directory entries are treated as CSV input: extensions are matched
+// case-insensitively (--ext csv,txt), or every file with --all-files; the
+// --include / --exclude globs and --recursive decide which files are looked at
+pub struct InputFilter {
+    extensions: Vec<String>,
+    all_files: bool,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    recursive: bool,
+}
+
+impl InputFilter {
+    pub fn from_args(args: &[String]) -> Self {
+        let extensions = match args.iter().position(|a| a == "--ext") {
+            Some(i) => args.get(i + 1)
+                .map(|list| list.split(',')
+                    .map(|ext| ext.trim().trim_start_matches('.').to_string())
+                    .filter(|ext| !ext.is_empty())
+                    .collect())
+                .unwrap_or_default(),
+            None => vec!["csv".to_string()],
+        };
+
+        InputFilter {
+            extensions,
+            all_files: args.iter().any(|a| a == "--all-files"),
+            include: globs_from_args(args, "--include"),
+            exclude: globs_from_args(args, "--exclude"),
+            recursive: args.iter().any(|a| a == "--recursive"),
+        }
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        if !path.is_file() {
+            return false;
+        }
+        if self.all_files {
+            return true;
+        }
+        path.extension()
+            .and_then(|s| s.to_str())
+            .is_some_and(|ext| self.extensions.iter().any(|e| e.eq_ignore_ascii_case(ext)))
+    }
+
+    // Matching files in dir, sorted by path
+    pub fn input_files(&self, dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let mut files = discover_files(dir, &self.include, &self.exclude, self.recursive)?;
+        files.retain(|path| self.matches(path));
+        Ok(files)
+    }
+}
+
+// Exit status when the input directory holds nothing to process, so scripts
+// can tell a misconfigured path apart from a failed run
+pub const EXIT_NO_INPUT_FILES: i32 = 3;
+
+// Message and exit status for main when the scan found no input files
+pub fn no_input_files(dir: &Path, files: &[PathBuf]) -> Option<(String, i32)> {
+    files.is_empty()
+        .then(|| (format!("no CSV files found in {}", dir.display()), EXIT_NO_INPUT_FILES))
+}
+
+// How a column name given on the command line is compared with the file's
+// headers: exactly, or with --case-insensitive-headers after trimming and
+// case-folding both, so " radial_index" still finds Radial_Index
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum HeaderMatch {
+    #[default]
+    Exact,
+    CaseInsensitive,
+}
+
+impl HeaderMatch {
+    pub fn from_args(args: &[String]) -> Self {
+        if args.iter().any(|a| a == "--case-insensitive-headers") {
+            HeaderMatch::CaseInsensitive
+        } else {
+            HeaderMatch::Exact
+        }
+    }
+
+    pub fn matches(&self, header: &str, name: &str) -> bool {
+        match self {
+            HeaderMatch::Exact => header == name,
+            HeaderMatch::CaseInsensitive => header.trim().to_lowercase() == name.trim().to_lowercase(),
+        }
+    }
+
+    pub fn position<'a>(&self, headers: impl IntoIterator<Item = &'a str>, name: &str) -> Option<usize> {
+        headers.into_iter().position(|header| self.matches(header, name))
+    }
+}
+
+// Expected headers given with --require-columns a,b,c; empty when not set
+pub fn required_columns_from_args(args: &[String]) -> Vec<String> {
+    args.iter()
+        .position(|a| a == "--require-columns")
+        .and_then(|i| args.get(i + 1))
+        .map(|list| list.split(',')
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .collect())
+        .unwrap_or_default()
+}
+
+// Fail before any output is written when the file doesn't have the expected schema
+pub fn check_required_columns(
+    headers: &StringRecord,
+    required_columns: &[String],
+    header_match: HeaderMatch,
+    input_path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let missing: Vec<&str> = required_columns.iter()
+        .filter(|name| header_match.position(headers, name).is_none())
+        .map(|name| name.as_str())
+        .collect();
+
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} is missing required column(s): {} (is this the right input directory?)",
+            input_path.display(),
+            missing.join(", ")
+        ).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_include_glob_with_excluded_subdirectory() {
+        let root = std::env::temp_dir().join(format!("discover_files_{}", std::process::id()));
+        std::fs::create_dir_all(root.join("visits")).unwrap();
+        std::fs::create_dir_all(root.join("old")).unwrap();
+        for name in ["P1_L_axial.csv", "P1_R_axial.csv", "visits/P2_L_axial.csv", "old/P3_L_axial.csv", "notes_L_.txt"] {
+            std::fs::write(root.join(name), "a\n1\n").unwrap();
+        }
+
+        let include = vec!["*_L_*.csv".to_string()];
+        let exclude = vec!["old".to_string()];
+        let recursive = discover_files(&root, &include, &exclude, true).unwrap();
+        let flat = discover_files(&root, &include, &exclude, false).unwrap();
+        let unfiltered = discover_files(&root, &[], &[], true).unwrap();
+        std::fs::remove_dir_all(&root).ok();
+
+        assert_eq!(recursive, vec![root.join("P1_L_axial.csv"), root.join("visits/P2_L_axial.csv")]);
+        assert_eq!(flat, vec![root.join("P1_L_axial.csv")]);
+        assert_eq!(unfiltered.len(), 5);
+        assert!(discover_files(&root, &["[".to_string()], &[], false).is_err());
+
+        let args: Vec<String> = ["--include", "*_L_*.csv, *_R_*.csv", "--include", "*.txt"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(globs_from_args(&args, "--include"), vec!["*_L_*.csv", "*_R_*.csv", "*.txt"]);
+    }
+
+    #[test]
+    fn test_uppercase_csv_extension_is_discovered() {
+        let dir = std::env::temp_dir().join(format!("discover_ext_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let upper = dir.join("scan.CSV");
+        let text = dir.join("scan.txt");
+        std::fs::write(&upper, "a,b\n1,2\n").unwrap();
+        std::fs::write(&text, "a,b\n1,2\n").unwrap();
+
+        let default_filter = InputFilter::from_args(&[]);
+        let txt_filter = InputFilter::from_args(&["--ext".to_string(), "csv,.txt".to_string()]);
+        let all_filter = InputFilter::from_args(&["--all-files".to_string()]);
+        let default_files = default_filter.input_files(&dir).unwrap();
+
+        assert!(default_filter.matches(&upper));
+        assert!(!default_filter.matches(&text));
+        assert!(txt_filter.matches(&text));
+        assert!(all_filter.matches(&text));
+        assert!(!all_filter.matches(&dir));
+        assert_eq!(default_files, vec![upper]);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_empty_directory_reports_no_csv_files() {
+        let dir = std::env::temp_dir().join(format!("discover_empty_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a csv").unwrap();
+
+        let files = InputFilter::from_args(&[]).input_files(&dir).unwrap();
+        let (message, code) = no_input_files(&dir, &files).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert!(files.is_empty());
+        assert_eq!(message, format!("no CSV files found in {}", dir.display()));
+        assert_eq!(code, EXIT_NO_INPUT_FILES);
+        assert!(no_input_files(&dir, &[dir.join("scan.csv")]).is_none());
+    }
+
+    #[test]
+    fn test_header_match_trims_and_folds_case() {
+        let headers = ["ID", " radial_INDEX ", "Axial"];
+        let args = vec!["--case-insensitive-headers".to_string()];
+
+        assert_eq!(HeaderMatch::from_args(&[]), HeaderMatch::Exact);
+        assert_eq!(HeaderMatch::Exact.position(headers, "Radial_Index"), None);
+        assert_eq!(HeaderMatch::from_args(&args).position(headers, "Radial_Index"), Some(1));
+        assert_eq!(HeaderMatch::CaseInsensitive.position(headers, "axial "), Some(2));
+    }
+
+    #[test]
+    fn test_required_columns_follow_header_match() {
+        let args: Vec<String> = ["--require-columns", "Radial_Index, Axial,,Elevation"].iter().map(|s| s.to_string()).collect();
+        let required = required_columns_from_args(&args);
+        let headers = StringRecord::from(vec!["radial_index", "Axial"]);
+        let path = Path::new("P_001.csv");
+
+        assert_eq!(required, vec!["Radial_Index", "Axial", "Elevation"]);
+        assert!(required_columns_from_args(&[]).is_empty());
+        let err = check_required_columns(&headers, &required, HeaderMatch::CaseInsensitive, path).unwrap_err();
+        assert_eq!(err.to_string(), "P_001.csv is missing required column(s): Elevation (is this the right input directory?)");
+        assert!(check_required_columns(&headers, &required[..2], HeaderMatch::CaseInsensitive, path).is_ok());
+        assert!(check_required_columns(&headers, &required[..2], HeaderMatch::Exact, path).is_err());
+    }
+}
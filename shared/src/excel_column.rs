@@ -0,0 +1,30 @@
+// Spreadsheet-style column letters for 0-based column indexes: 0 is A, 25 is
+// Z, 26 is AA.
+
+pub fn number_to_excel_column(mut n: usize) -> String {
+    let mut result = String::new();
+    n += 1;
+
+    while n > 0 {
+        n -= 1;
+        let remainder = n % 26;
+        result.insert(0, (b'A' + remainder as u8) as char);
+        n /= 26;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_to_excel_column() {
+        assert_eq!(number_to_excel_column(0), "A");
+        assert_eq!(number_to_excel_column(25), "Z");
+        assert_eq!(number_to_excel_column(26), "AA");
+        assert_eq!(number_to_excel_column(701), "ZZ");
+        assert_eq!(number_to_excel_column(702), "AAA");
+    }
+}
@@ -0,0 +1,134 @@
+// Harmonic decomposition of one ring of values sampled at evenly spaced
+// meridians, as written to grid_fix_multi's harmonics file.
+
+use std::f64::consts::PI;
+
+// --fourier-window {none,hann}: taper applied to the ring before the DFT
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Window {
+    #[default]
+    None,
+    // Periodic Hann, 0.5 (1 - cos(2πj/m)); less leakage from a ring that
+    // doesn't close on itself, at the cost of spreading each harmonic into
+    // its neighbours
+    Hann,
+}
+
+impl Window {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "none" => Ok(Window::None),
+            "hann" => Ok(Window::Hann),
+            other => Err(format!("Unknown --fourier-window '{}' (expected none or hann)", other)),
+        }
+    }
+
+    fn weights(&self, m: usize) -> Vec<f64> {
+        match self {
+            Window::None => vec![1.0; m],
+            Window::Hann => (0..m).map(|j| 0.5 * (1.0 - (2.0 * PI * j as f64 / m as f64).cos())).collect(),
+        }
+    }
+}
+
+// Real DFT keeping the first `num_harmonics` terms of
+// y(θ) = a0 + Σ a_k cos(kθ) + b_k sin(kθ), returned as (a0, a, b, r2) in the
+// coef_a0 / coef_amK / coef_bmK layout the descriptive binary reads.
+// R² measures how much of the ring the fit explains.
+pub fn real_dft(ring: &[f64], num_harmonics: usize) -> (f64, Vec<f64>, Vec<f64>, f64) {
+    real_dft_windowed(ring, num_harmonics, Window::None)
+}
+
+// real_dft with a --fourier-window taper. a0 is the plain mean; the window is
+// applied to the deviations from it (a windowed constant would otherwise show
+// up as harmonic 1), and windowed sums are divided by the window's sum instead
+// of m so amplitudes stay on the ring's scale. R² is still measured on the
+// unwindowed ring.
+pub fn real_dft_windowed(ring: &[f64], num_harmonics: usize, window: Window) -> (f64, Vec<f64>, Vec<f64>, f64) {
+    let m = ring.len();
+    let angle = |j: usize, k: usize| 2.0 * PI * (k * j) as f64 / m as f64;
+    let weights = window.weights(m);
+    let weight_sum: f64 = weights.iter().sum();
+
+    let a0 = ring.iter().sum::<f64>() / m as f64;
+    let (a, b): (Vec<f64>, Vec<f64>) = (1..=num_harmonics)
+        .map(|k| {
+            let (cos_sum, sin_sum) = ring.iter().zip(&weights).enumerate()
+                .fold((0.0, 0.0), |(c, s), (j, (&y, &w))| {
+                    let deviation = w * (y - a0);
+                    (c + deviation * angle(j, k).cos(), s + deviation * angle(j, k).sin())
+                });
+            (2.0 * cos_sum / weight_sum, 2.0 * sin_sum / weight_sum)
+        })
+        .unzip();
+
+    let mut ss_res = 0.0;
+    let mut ss_tot = 0.0;
+    for (j, &y) in ring.iter().enumerate() {
+        let fitted = a0 + (1..=num_harmonics)
+            .map(|k| a[k - 1] * angle(j, k).cos() + b[k - 1] * angle(j, k).sin())
+            .sum::<f64>();
+        ss_res += (y - fitted).powi(2);
+        ss_tot += (y - a0).powi(2);
+    }
+    let r2 = if ss_tot > 0.0 { 1.0 - ss_res / ss_tot } else { 1.0 };
+
+    (a0, a, b, r2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring(f: impl Fn(f64) -> f64) -> Vec<f64> {
+        (0..256).map(|j| f(2.0 * PI * j as f64 / 256.0)).collect()
+    }
+
+    #[test]
+    fn test_constant_ring_has_only_a0() {
+        for window in [Window::None, Window::Hann] {
+            let (a0, a, b, r2) = real_dft_windowed(&ring(|_| 42.5), 5, window);
+            assert!((a0 - 42.5).abs() < 1e-9, "{:?}", window);
+            assert!(a.iter().chain(&b).all(|c| c.abs() < 1e-9), "{:?}: {:?} {:?}", window, a, b);
+            assert_eq!(r2, 1.0);
+        }
+    }
+
+    #[test]
+    fn test_pure_cosine_ring_fits_first_harmonic() {
+        let (a0, a, b, r2) = real_dft(&ring(|theta| 5.0 + 3.0 * theta.cos()), 3);
+        assert!((a0 - 5.0).abs() < 1e-9);
+        assert!((a[0] - 3.0).abs() < 1e-9);
+        assert!(a[1..].iter().chain(&b).all(|c| c.abs() < 1e-9), "{:?} {:?}", a, b);
+        assert!((r2 - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_pure_sine_ring_fits_first_harmonic() {
+        let (a0, a, b, r2) = real_dft(&ring(|theta| 2.0 * theta.sin()), 3);
+        assert!(a0.abs() < 1e-9);
+        assert!((b[0] - 2.0).abs() < 1e-9);
+        assert!(a.iter().chain(&b[1..]).all(|c| c.abs() < 1e-9), "{:?} {:?}", a, b);
+        assert!((r2 - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_hann_window_keeps_first_harmonic_dominant() {
+        let (cosine_a0, cosine_a, cosine_b, cosine_r2) = real_dft_windowed(&ring(|theta| 5.0 + 3.0 * theta.cos()), 3, Window::Hann);
+        let (_, sine_a, sine_b, sine_r2) = real_dft_windowed(&ring(|theta| 2.0 * theta.sin()), 3, Window::Hann);
+
+        // Hann keeps the amplitude of harmonic 1 but leaks half of it into harmonic 2
+        assert!((cosine_a0 - 5.0).abs() < 1e-9);
+        assert!((cosine_a[0] - 3.0).abs() < 1e-9, "{:?}", cosine_a);
+        assert!((cosine_a[1] + 1.5).abs() < 1e-9, "{:?}", cosine_a);
+        assert!(cosine_a[0].abs() > cosine_a[1].abs() && cosine_a[0].abs() > cosine_a[2].abs(), "{:?}", cosine_a);
+        assert!(cosine_b.iter().all(|c| c.abs() < 1e-9), "{:?}", cosine_b);
+        assert!(sine_b[0].abs() > sine_b[1].abs() && sine_b[0].abs() > sine_b[2].abs(), "{:?}", sine_b);
+        assert!(sine_a.iter().all(|c| c.abs() < 1e-9), "{:?}", sine_a);
+        assert!(cosine_r2 > 0.0 && cosine_r2 <= 1.0, "{}", cosine_r2);
+        assert!(sine_r2 > 0.0 && sine_r2 <= 1.0, "{}", sine_r2);
+
+        assert_eq!(Window::parse("hann"), Ok(Window::Hann));
+        assert!(Window::parse("hamming").is_err());
+    }
+}
@@ -0,0 +1,146 @@
+// Where each grid cell sits: meridian angle, radius and X/Y coordinates.
+
+use std::f64::consts::PI;
+
+// Add Bessel function calculation (simplified first-order)
+fn bessel_j0(x: f64) -> f64 {
+    if x == 0.0 {
+        return 1.0;
+    }
+    (x.sin() / x).cos()
+}
+
+// Fourier-Bessel transform of a 1-based radial index
+fn fourier_bessel_transform(radial_index: usize, num_radials: usize) -> f64 {
+    let r_max = 1.0; // Normalized maximum radius
+    let r = (radial_index as f64) / (num_radials as f64 - 1.0);
+
+    let alpha = PI; // First zero of J0
+    let transformed_r = r * alpha / r_max;
+    bessel_j0(transformed_r)
+}
+
+// How meridian indices map to angles: --start-angle <deg> is the angle of
+// meridian 1 and --direction {cw,ccw} the way the index runs. The default
+// (0°, counter-clockwise) is the layout the scans were written with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridOrientation {
+    pub start_angle_deg: f64,
+    pub clockwise: bool,
+}
+
+impl Default for GridOrientation {
+    fn default() -> Self {
+        GridOrientation { start_angle_deg: 0.0, clockwise: false }
+    }
+}
+
+impl GridOrientation {
+    // From the --start-angle and --direction values, when given; each binary
+    // reads them from its own command line
+    pub fn parse(start_angle: Option<&str>, direction: Option<&str>) -> Result<Self, String> {
+        let start_angle_deg = match start_angle {
+            None => 0.0,
+            Some(value) => value.parse::<f64>()
+                .ok()
+                .filter(|angle| angle.is_finite())
+                .ok_or_else(|| format!("Invalid --start-angle '{}' (expected degrees)", value))?,
+        };
+        let clockwise = match direction {
+            None | Some("ccw") => false,
+            Some("cw") => true,
+            Some(other) => return Err(format!("Unknown --direction '{}' (expected cw or ccw)", other)),
+        };
+
+        Ok(GridOrientation { start_angle_deg, clockwise })
+    }
+
+    // Angle of a 1-based meridian index, in [0, 360)
+    pub fn meridian_angle_deg(&self, meridian_index_1_based: usize, num_meridians: usize) -> f64 {
+        let step = (meridian_index_1_based as f64 - 1.0) * (360.0 / num_meridians as f64);
+        let angle = if self.clockwise { self.start_angle_deg - step } else { self.start_angle_deg + step };
+        angle.rem_euclid(360.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GridConfig {
+    pub num_meridians: usize,
+    pub num_radials: usize,
+    pub orientation: GridOrientation,
+}
+
+// The geometry columns of one cell. X/Y use the transformed radius; the
+// physical position is normalized_radius * (cos, sin).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RingGeometry {
+    pub angle_deg: f64,
+    pub angle_rad: f64,
+    pub normalized_radius: f64,
+    pub transformed_radius: f64,
+    pub cos: f64,
+    pub sin: f64,
+    pub x: f64,
+    pub y: f64,
+}
+
+// Both indices are 1-based, as written in the Meridian_Index/Radial_Index columns
+pub fn ring_geometry(meridian: usize, radial: usize, grid: &GridConfig) -> RingGeometry {
+    let angle_deg = grid.orientation.meridian_angle_deg(meridian, grid.num_meridians);
+    let angle_rad = angle_deg.to_radians();
+    let normalized_radius = (radial as f64 - 1.0) / (grid.num_radials as f64 - 1.0);
+    let transformed_radius = fourier_bessel_transform(radial, grid.num_radials);
+    let cos = angle_rad.cos();
+    let sin = angle_rad.sin();
+
+    RingGeometry {
+        angle_deg,
+        angle_rad,
+        normalized_radius,
+        transformed_radius,
+        cos,
+        sin,
+        x: transformed_radius * cos,
+        y: transformed_radius * sin,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_cell_geometry() {
+        let grid = GridConfig { num_meridians: 256, num_radials: 32, orientation: GridOrientation::default() };
+
+        // Meridian 65 of 256 is a quarter turn
+        let cell = ring_geometry(65, 32, &grid);
+        assert_eq!(cell.angle_deg, 90.0);
+        assert!((cell.angle_rad - PI / 2.0).abs() < 1e-12);
+        assert_eq!(cell.normalized_radius, 1.0);
+        let expected_transformed = bessel_j0(32.0 / 31.0 * PI);
+        assert_eq!(cell.transformed_radius, expected_transformed);
+        assert!(cell.x.abs() < 1e-12);
+        assert!((cell.y - expected_transformed).abs() < 1e-12);
+        // Physical position of the outermost ring at 90° is (0, 1)
+        assert!((cell.normalized_radius * cell.cos).abs() < 1e-12);
+        assert!((cell.normalized_radius * cell.sin - 1.0).abs() < 1e-12);
+
+        // Centre of the grid: zero physical radius, J0(π/31) after the transform
+        let centre = ring_geometry(1, 1, &grid);
+        assert_eq!((centre.angle_deg, centre.normalized_radius), (0.0, 0.0));
+        assert_eq!(centre.transformed_radius, bessel_j0(PI / 31.0));
+        assert_eq!((centre.x, centre.y), (centre.transformed_radius, 0.0));
+    }
+
+    #[test]
+    fn test_orientation_parse() {
+        let orientation = GridOrientation::parse(Some("90"), Some("cw")).unwrap();
+        assert_eq!(orientation, GridOrientation { start_angle_deg: 90.0, clockwise: true });
+        assert_eq!(orientation.meridian_angle_deg(2, 4), 0.0);
+        assert_eq!(orientation.meridian_angle_deg(3, 4), 270.0);
+        assert_eq!(GridOrientation::parse(None, None).unwrap(), GridOrientation::default());
+        assert!(GridOrientation::parse(None, Some("up")).is_err());
+        assert!(GridOrientation::parse(Some("north"), None).is_err());
+    }
+}
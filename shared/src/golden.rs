@@ -0,0 +1,27 @@
+// Golden-file support for the end-to-end tests in each crate's tests/.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// tests/ of the crate being tested; cargo sets CARGO_MANIFEST_DIR when it
+// runs a test binary
+pub fn tests_dir() -> PathBuf {
+    let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR is not set; run the tests with cargo test");
+    PathBuf::from(manifest_dir).join("tests")
+}
+
+// Reports the first differing line; with UPDATE_GOLDEN set, rewrites the
+// golden file from the produced one instead
+pub fn assert_matches_golden(produced: &Path, golden: &Path) {
+    let actual = fs::read_to_string(produced).unwrap();
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(golden, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(golden)
+        .unwrap_or_else(|e| panic!("cannot read golden file {}: {}", golden.display(), e));
+    for (line, (a, e)) in actual.lines().zip(expected.lines()).enumerate() {
+        assert_eq!(a, e, "{} differs from {} at line {}", produced.display(), golden.display(), line + 1);
+    }
+    assert_eq!(actual, expected);
+}
@@ -0,0 +1,53 @@
+// Where the real table starts in sheets that carry title/metadata rows above
+// it: --skip-rows and --header-row.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SheetLayout {
+    pub skip_rows: usize,          // --skip-rows: junk rows at the top, never read
+    pub header_row: Option<usize>, // --header-row: header index counted after the skipped rows
+}
+
+impl SheetLayout {
+    // The default (neither flag) treats every row as part of the table
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let count_of = |flag: &str| -> Result<Option<usize>, String> {
+            match args.iter().position(|a| a == flag) {
+                None => Ok(None),
+                Some(i) => {
+                    let value = args.get(i + 1).map(String::as_str).unwrap_or("");
+                    value.parse::<usize>()
+                        .map(Some)
+                        .map_err(|_| format!("Invalid {} '{}' (expected a row count)", flag, value))
+                }
+            }
+        };
+
+        Ok(SheetLayout {
+            skip_rows: count_of("--skip-rows")?.unwrap_or(0),
+            header_row: count_of("--header-row")?,
+        })
+    }
+
+    // First row counted as data: the one after the header, if there is one
+    pub fn data_start(&self) -> usize {
+        self.skip_rows + self.header_row.map_or(0, |h| h + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_from_args() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(SheetLayout::from_args(&args(&["--skip-rows", "2", "--header-row", "0"])),
+                   Ok(SheetLayout { skip_rows: 2, header_row: Some(0) }));
+        assert_eq!(SheetLayout::from_args(&args(&["--header-row", "1"])),
+                   Ok(SheetLayout { skip_rows: 0, header_row: Some(1) }));
+        assert_eq!(SheetLayout::from_args(&[]), Ok(SheetLayout::default()));
+        assert!(SheetLayout::from_args(&args(&["--skip-rows", "-1"])).is_err());
+        assert!(SheetLayout::from_args(&args(&["--header-row"])).is_err());
+    }
+}
@@ -0,0 +1,19 @@
+// Code used by more than one of the binaries in this repository, so they all
+// behave the same way where their options overlap.
+
+pub mod cells;
+pub mod column_type;
+pub mod delimiter;
+pub mod digits;
+pub mod discover;
+pub mod excel_column;
+pub mod fourier;
+pub mod geometry;
+pub mod golden;
+pub mod layout;
+pub mod locale;
+pub mod number_format;
+pub mod percentage;
+pub mod percentile;
+pub mod preview;
+pub mod progress;
@@ -0,0 +1,88 @@
+// How numbers are written in the input files: --decimal and --thousands.
+
+use std::error::Error;
+
+// How numbers are written in the input: --decimal {dot,comma} and --thousands <char>.
+// The default (dot decimal, no thousands separator) is plain `str::parse`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NumberLocale {
+    pub decimal: char,
+    pub thousands: Option<char>,
+}
+
+impl Default for NumberLocale {
+    fn default() -> Self {
+        NumberLocale { decimal: '.', thousands: None }
+    }
+}
+
+impl NumberLocale {
+    pub fn from_args(args: &[String]) -> Result<Self, Box<dyn Error>> {
+        let value_of = |flag: &str| args.iter()
+            .position(|a| a == flag)
+            .map(|i| args.get(i + 1).map(String::as_str).unwrap_or(""));
+
+        let decimal = match value_of("--decimal") {
+            None | Some("dot") => '.',
+            Some("comma") => ',',
+            Some(other) => return Err(format!("Unknown --decimal '{}' (expected dot or comma)", other).into()),
+        };
+
+        let thousands = match value_of("--thousands") {
+            None => None,
+            Some(sep) => {
+                let mut chars = sep.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if c != decimal => Some(c),
+                    _ => return Err(format!("--thousands must be a single character other than the decimal separator, got '{}'", sep).into()),
+                }
+            }
+        };
+
+        Ok(NumberLocale { decimal, thousands })
+    }
+}
+
+pub fn parse_number(s: &str, locale: &NumberLocale) -> Option<f64> {
+    let s = s.trim();
+    let mut normalized = String::with_capacity(s.len());
+    for c in s.chars() {
+        if Some(c) == locale.thousands {
+            continue;
+        } else if c == locale.decimal {
+            normalized.push('.');
+        } else if c == '.' {
+            // A dot that is neither the decimal nor the thousands separator
+            return None;
+        } else {
+            normalized.push(c);
+        }
+    }
+    normalized.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_number_dot_and_comma_decimal() {
+        let dot = NumberLocale { decimal: '.', thousands: Some(',') };
+        let comma = NumberLocale { decimal: ',', thousands: Some('.') };
+
+        assert_eq!(parse_number("1,234.56", &dot), Some(1234.56));
+        assert_eq!(parse_number("1.234,56", &comma), Some(1234.56));
+        assert_eq!(parse_number("-1,5", &NumberLocale { decimal: ',', thousands: None }), Some(-1.5));
+        assert_eq!(parse_number("1,5", &NumberLocale::default()), None);
+        assert_eq!(parse_number("1.234,56", &NumberLocale::default()), None);
+        assert_eq!(parse_number(" 42.5 ", &NumberLocale::default()), Some(42.5));
+    }
+
+    #[test]
+    fn test_locale_from_args() {
+        let args: Vec<String> = ["descriptive", "--decimal", "comma", "--thousands", "."]
+            .iter().map(|s| s.to_string()).collect();
+        assert_eq!(NumberLocale::from_args(&args).unwrap(), NumberLocale { decimal: ',', thousands: Some('.') });
+        assert!(NumberLocale::from_args(&["--thousands".to_string(), ".".to_string()]).is_err());
+    }
+}
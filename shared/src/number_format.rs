@@ -0,0 +1,129 @@
+// How floats are written to the output files, and what --require-finite does
+// with the ones that are NaN/Inf.
+
+use std::fmt::Display;
+
+// How floats are written to the output files
+#[derive(Debug, Clone, Default)]
+pub struct NumberFormat {
+    // --precision N: N decimal places instead of the shortest form that
+    // parses back to the same f64
+    pub precision: Option<usize>,
+    // --na-output <token>: written for missing values (NaN, e.g. Alpha_Angle
+    // where the two heights coincide) instead of "NaN"
+    pub na_output: Option<String>,
+}
+
+impl NumberFormat {
+    pub fn format(&self, value: f64) -> String {
+        match (&self.na_output, self.precision) {
+            (Some(token), _) if value.is_nan() => token.clone(),
+            (_, Some(places)) => format!("{:.*}", places, value),
+            (_, None) => value.to_string(),
+        }
+    }
+}
+
+// --precision N: write floats with N decimal places. Without it they keep the
+// shortest form that parses back to the same f64, as before.
+pub fn precision_from_args(args: &[String]) -> Result<Option<usize>, String> {
+    match args.iter().position(|a| a == "--precision") {
+        None => Ok(None),
+        Some(i) => {
+            let value = args.get(i + 1).map(String::as_str).unwrap_or("");
+            value.parse::<usize>()
+                .map(Some)
+                .map_err(|_| format!("Invalid --precision '{}' (expected a number of decimal places)", value))
+        }
+    }
+}
+
+// What --require-finite does with a NaN/Inf in a computed column (e.g. a
+// KR_scaled from stats that overflowed, or Alpha_Angle where the two heights
+// coincide): --finite-policy fail (the default) stops at that row and column,
+// replace writes the --na-output token, or an empty cell without one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FinitePolicy {
+    Fail,
+    Replace,
+}
+
+impl FinitePolicy {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "fail" => Ok(FinitePolicy::Fail),
+            "replace" => Ok(FinitePolicy::Replace),
+            other => Err(format!("Unknown --finite-policy '{}' (expected fail or replace)", other)),
+        }
+    }
+}
+
+// --require-finite [--finite-policy {fail,replace}]; None without --require-finite
+pub fn finite_policy_from_args(args: &[String]) -> Result<Option<FinitePolicy>, String> {
+    if !args.iter().any(|a| a == "--require-finite") {
+        return Ok(None);
+    }
+    match args.iter().position(|a| a == "--finite-policy") {
+        None => Ok(Some(FinitePolicy::Fail)),
+        Some(i) => FinitePolicy::parse(args.get(i + 1).map(String::as_str).unwrap_or("")).map(Some),
+    }
+}
+
+// Format one float cell of an output row (1-based, below the header),
+// applying --require-finite when it is set
+pub fn format_float_cell(
+    value: f64,
+    column: impl Display,
+    row: usize,
+    require_finite: Option<FinitePolicy>,
+    number_format: &NumberFormat,
+) -> Result<String, String> {
+    match require_finite {
+        Some(FinitePolicy::Fail) if !value.is_finite() => {
+            Err(format!("non-finite value {} at row {}, column {}", value, row, column))
+        }
+        Some(FinitePolicy::Replace) if !value.is_finite() => Ok(number_format.na_output.clone().unwrap_or_default()),
+        _ => Ok(number_format.format(value)),
+    }
+}
+
+// format_float_cell over the float columns of one output row
+pub fn format_float_columns(
+    values: &[f64],
+    columns: &[impl Display],
+    row: usize,
+    require_finite: Option<FinitePolicy>,
+    number_format: &NumberFormat,
+) -> Result<Vec<String>, String> {
+    values.iter().zip(columns)
+        .map(|(&value, column)| format_float_cell(value, column, row, require_finite, number_format))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_precision_from_args() {
+        assert_eq!(precision_from_args(&["--precision".to_string(), "4".to_string()]), Ok(Some(4)));
+        assert_eq!(precision_from_args(&[]), Ok(None));
+        assert!(precision_from_args(&["--precision".to_string(), "-1".to_string()]).is_err());
+        assert!(precision_from_args(&["--precision".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_replace_writes_the_na_output_token() {
+        let values = [1.5, f64::NAN, f64::INFINITY];
+        let columns = ["a", "b", "c"];
+        let plain = NumberFormat::default();
+        let with_token = NumberFormat { precision: Some(1), na_output: Some("NA".to_string()) };
+
+        let replace = Some(FinitePolicy::Replace);
+        assert_eq!(format_float_columns(&values, &columns, 1, replace, &plain).unwrap(), ["1.5", "", ""]);
+        assert_eq!(format_float_columns(&values, &columns, 1, replace, &with_token).unwrap(), ["1.5", "NA", "NA"]);
+        assert_eq!(format_float_columns(&values, &columns, 1, None, &plain).unwrap(), ["1.5", "NaN", "inf"]);
+        let message = format_float_columns(&values, &columns, 3, Some(FinitePolicy::Fail), &plain).unwrap_err();
+        assert_eq!(message, "non-finite value NaN at row 3, column b");
+    }
+}
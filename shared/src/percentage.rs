@@ -0,0 +1,21 @@
+// Percentages for the summary and recommendation columns.
+
+// Whole-number percentage of total; 0 rather than NaN when there are no rows
+pub fn percentage(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    (count as f64 / total as f64 * 100.0).round()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentage_rounds_and_handles_empty_totals() {
+        assert_eq!(percentage(1, 3), 33.0);
+        assert_eq!(percentage(2, 3), 67.0);
+        assert_eq!(percentage(0, 0), 0.0);
+    }
+}
@@ -0,0 +1,80 @@
+// Percentiles and quantiles of already sorted values.
+
+// --quantile-method: how a quantile that falls between two ranks is taken,
+// with numpy's names and conventions (position q * (n - 1) in sorted data)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum QuantileMethod {
+    // Interpolate between the two ranks (numpy's default)
+    #[default]
+    Linear,
+    Lower,
+    Higher,
+    // Closer rank; halfway rounds to the even rank, as numpy does
+    Nearest,
+    // Mean of the two ranks
+    Midpoint,
+}
+
+impl QuantileMethod {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "linear" => Ok(QuantileMethod::Linear),
+            "lower" => Ok(QuantileMethod::Lower),
+            "higher" => Ok(QuantileMethod::Higher),
+            "nearest" => Ok(QuantileMethod::Nearest),
+            "midpoint" => Ok(QuantileMethod::Midpoint),
+            other => Err(format!(
+                "Unknown --quantile-method '{}' (expected linear, lower, higher, nearest or midpoint)", other
+            )),
+        }
+    }
+
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        match args.iter().position(|a| a == "--quantile-method") {
+            Some(i) => QuantileMethod::parse(args.get(i + 1).map(String::as_str).unwrap_or("")),
+            None => Ok(QuantileMethod::default()),
+        }
+    }
+
+    // The q-th quantile (0 to 1) of already sorted values
+    pub fn quantile(&self, sorted: &[f64], q: f64) -> f64 {
+        let pos = q * (sorted.len() - 1) as f64;
+        let lower = sorted[pos.floor() as usize];
+        let upper = sorted[pos.ceil() as usize];
+        match self {
+            QuantileMethod::Linear => lower + (upper - lower) * pos.fract(),
+            QuantileMethod::Lower => lower,
+            QuantileMethod::Higher => upper,
+            QuantileMethod::Nearest => sorted[pos.round_ties_even() as usize],
+            QuantileMethod::Midpoint => (lower + upper) / 2.0,
+        }
+    }
+}
+
+// Linear interpolation between closest ranks of already sorted values
+pub fn percentile(sorted: &[f64], p: f64) -> f64 {
+    QuantileMethod::Linear.quantile(sorted, p / 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile_interpolates_between_ranks() {
+        let sorted = [1.0, 2.0, 3.0, 4.0, 5.0];
+        assert_eq!(percentile(&sorted, 0.0), 1.0);
+        assert_eq!(percentile(&sorted, 25.0), 2.0);
+        assert_eq!(percentile(&sorted, 50.0), 3.0);
+        assert_eq!(percentile(&sorted, 90.0), 4.6);
+        assert_eq!(percentile(&[7.0], 75.0), 7.0);
+    }
+
+    #[test]
+    fn test_quantile_method_from_args() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(QuantileMethod::from_args(&args(&["--quantile-method", "midpoint"])), Ok(QuantileMethod::Midpoint));
+        assert_eq!(QuantileMethod::from_args(&args(&[])), Ok(QuantileMethod::Linear));
+        assert!(QuantileMethod::from_args(&args(&["--quantile-method"])).is_err());
+    }
+}
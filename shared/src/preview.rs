@@ -0,0 +1,143 @@
+// --preview N: print the first N data rows of an output as an aligned table,
+// for a quick look without opening the file.
+
+use std::io::{self, Write};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+// Longer cells are cut to this many terminal columns, the last one an ellipsis
+const MAX_CELL_WIDTH: usize = 24;
+
+pub fn preview_rows_from_args(args: &[String]) -> Result<Option<usize>, String> {
+    match args.iter().position(|a| a == "--preview") {
+        None => Ok(None),
+        Some(i) => {
+            let value = args.get(i + 1).map(String::as_str).unwrap_or("");
+            value.parse::<usize>()
+                .map(Some)
+                .map_err(|_| format!("Invalid --preview '{}' (expected a row count)", value))
+        }
+    }
+}
+
+// Columns a string takes in a terminal, which is neither its byte length nor
+// its char count: CJK is two columns wide, ZWNJ and combining marks zero
+pub fn display_width(s: &str) -> usize {
+    UnicodeWidthStr::width(s)
+}
+
+// Pad with spaces up to `width` display columns
+pub fn pad_to_width(s: &str, width: usize) -> String {
+    format!("{}{}", s, " ".repeat(width.saturating_sub(display_width(s))))
+}
+
+// Hebrew, Arabic (Persian included) and the other right-to-left blocks
+fn is_rtl(c: char) -> bool {
+    matches!(c, '\u{0590}'..='\u{08FF}' | '\u{FB1D}'..='\u{FDFF}' | '\u{FE70}'..='\u{FEFF}')
+}
+
+// Wrap right-to-left text in first-strong isolate marks, so the terminal's bidi
+// reordering stays inside the cell instead of pulling the borders around.
+// The marks take no display width.
+fn isolate_rtl(cell: &str) -> String {
+    if cell.chars().any(is_rtl) {
+        format!("\u{2068}{}\u{2069}", cell)
+    } else {
+        cell.to_string()
+    }
+}
+
+fn truncate_cell(cell: &str) -> String {
+    if display_width(cell) <= MAX_CELL_WIDTH {
+        return cell.to_string();
+    }
+    let mut truncated = String::new();
+    let mut width = 0;
+    for c in cell.chars() {
+        width += c.width().unwrap_or(0);
+        if width > MAX_CELL_WIDTH - 1 {
+            break;
+        }
+        truncated.push(c);
+    }
+    truncated.push('…');
+    truncated
+}
+
+// `header` and up to `n` of `rows`, every column padded to its widest cell
+pub fn write_preview<W: Write>(out: &mut W, header: &[String], rows: &[Vec<String>], n: usize) -> io::Result<()> {
+    let shown: Vec<Vec<String>> = std::iter::once(header)
+        .chain(rows.iter().take(n).map(Vec::as_slice))
+        .map(|row| row.iter().map(|cell| isolate_rtl(&truncate_cell(cell))).collect())
+        .collect();
+
+    let columns = shown.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|c| shown.iter().filter_map(|row| row.get(c)).map(|cell| display_width(cell)).max().unwrap_or(0))
+        .collect();
+
+    let separator: String = widths.iter().map(|w| format!("+{}", "-".repeat(w + 2))).collect::<String>() + "+";
+    writeln!(out, "{}", separator)?;
+    for (i, row) in shown.iter().enumerate() {
+        let mut line = String::new();
+        for (c, width) in widths.iter().enumerate() {
+            let cell = row.get(c).map(String::as_str).unwrap_or("");
+            line.push_str(&format!("| {} ", pad_to_width(cell, *width)));
+        }
+        writeln!(out, "{}|", line)?;
+        if i == 0 {
+            writeln!(out, "{}", separator)?;
+        }
+    }
+    writeln!(out, "{}", separator)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_shows_header_and_n_aligned_rows() {
+        let header: Vec<String> = ["ID", "Diagnosis", "Age"].iter().map(|s| s.to_string()).collect();
+        let rows: Vec<Vec<String>> = (1..=5)
+            .map(|i| vec![i.to_string(), "endometrioma ".repeat(i), (30 + i).to_string()])
+            .collect();
+
+        let mut out = Vec::new();
+        write_preview(&mut out, &header, &rows, 3).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        // Separator, header, separator, 3 rows, separator
+        assert_eq!(lines.len(), 7);
+        assert!(lines[1].starts_with("| ID | Diagnosis"));
+        let data_lines: Vec<&&str> = lines.iter().filter(|l| l.starts_with("| ")).skip(1).collect();
+        assert_eq!(data_lines.len(), 3);
+        assert!(data_lines[2].contains("endometrioma endometrio…"));
+        assert!(!text.contains("| 4 "));
+
+        // Every line has its column borders in the same places
+        let borders = |line: &str| line.chars().enumerate().filter(|&(_, c)| c == '|' || c == '+').map(|(i, _)| i).collect::<Vec<_>>();
+        assert!(lines.iter().all(|line| borders(line) == borders(lines[0])), "{}", text);
+
+        assert_eq!(preview_rows_from_args(&["--preview".to_string(), "10".to_string()]), Ok(Some(10)));
+        assert!(preview_rows_from_args(&["--preview".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_padding_uses_display_width() {
+        // 5 letters, 10 bytes; the ZWNJ in می‌شود takes no column
+        assert_eq!(pad_to_width("فاطمه", 8), "فاطمه   ");
+        assert_eq!(display_width("می\u{200C}شود"), 5);
+        assert_eq!(pad_to_width("眼科", 6), "眼科  ");
+
+        let header = vec!["نام".to_string(), "Eye".to_string()];
+        let rows = vec![vec!["فاطمه".to_string(), "OD".to_string()], vec!["Ali".to_string(), "眼科".to_string()]];
+        let mut out = Vec::new();
+        write_preview(&mut out, &header, &rows, 2).unwrap();
+        let text = String::from_utf8(out).unwrap();
+
+        assert!(text.contains("| \u{2068}فاطمه\u{2069} | OD   |"), "{}", text);
+        assert!(text.lines().all(|line| display_width(line) == display_width("+-------+------+")), "{}", text);
+    }
+}
@@ -0,0 +1,61 @@
+// --progress-json output of the batch binaries: one JSON object per line.
+
+use std::io::{self, Write};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+// --progress-json: one JSON line per finished item on stderr, e.g.
+// {"done":3,"total":10,"item":"P001","status":"ok"}. Workers only send on a
+// channel and a single thread does the writing, so lines never interleave.
+pub struct ProgressStream<W> {
+    sender: Sender<(String, &'static str)>,
+    writer: thread::JoinHandle<io::Result<W>>,
+}
+
+impl<W: Write + Send + 'static> ProgressStream<W> {
+    pub fn start(mut out: W, total: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<(String, &'static str)>();
+        let writer = thread::spawn(move || {
+            for (done, (item, status)) in receiver.into_iter().enumerate() {
+                writeln!(
+                    out,
+                    "{{\"done\":{},\"total\":{},\"item\":{},\"status\":{}}}",
+                    done + 1, total, serde_json::to_string(&item)?, serde_json::to_string(status)?
+                )?;
+                out.flush()?;
+            }
+            Ok(out)
+        });
+        ProgressStream { sender, writer }
+    }
+
+    pub fn report(&self, item: &str, status: &'static str) {
+        // The writer only goes away on an I/O error, and progress is best effort
+        let _ = self.sender.send((item.to_string(), status));
+    }
+
+    // Waits until every reported line is written and hands the sink back
+    pub fn finish(self) -> io::Result<W> {
+        drop(self.sender);
+        self.writer.join().map_err(|_| io::Error::other("progress writer panicked"))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_items_are_escaped_as_json_strings() {
+        let progress = ProgressStream::start(Vec::new(), 2);
+        progress.report("a\"b", "ok");
+        progress.report("C:\\scans\tx\u{1}", "error");
+        let out = String::from_utf8(progress.finish().unwrap()).unwrap();
+
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], r#"{"done":1,"total":2,"item":"a\"b","status":"ok"}"#);
+        assert_eq!(lines[1], r#"{"done":2,"total":2,"item":"C:\\scans\tx\u0001","status":"error"}"#);
+        let parsed: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(parsed["item"], "C:\\scans\tx\u{1}");
+    }
+}
@@ -2,8 +2,18 @@ use rand::prelude::*;
 use rand::rngs::StdRng;
 use rand_distr::{Normal, StandardNormal};
 use rayon::prelude::*;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
 use std::sync::Arc;
+use serde::{Deserialize, Serialize};
 
+// Seed for the synthetic dataset when --seed isn't given; the test set uses seed + 1
+const DEFAULT_DATA_SEED: u64 = 42;
+const DEFAULT_CHECKPOINT_EVERY: usize = 50;
+
+#[derive(Clone)]
 struct Individual {
     params: Vec<f64>,
     sigmas: Vec<f64>,
@@ -26,7 +36,8 @@ impl Individual {
                 .map(|(w, xi)| w * xi)
                 .sum::<f64>() + self.params.last().unwrap();
             
-            let prob = 1.0 / (1.0 + (-logit).exp()).clamp(1e-15, 1.0 - 1e-15);
+            // Clamp the probability, not the denominator, so ln() never sees 0
+            let prob = (1.0 / (1.0 + (-logit).exp())).clamp(1e-15, 1.0 - 1e-15);
             loss += - (y * prob.ln() + (1.0 - y) * (1.0 - prob).ln());
         }
         
@@ -48,36 +59,104 @@ impl Individual {
     }
 }
 
-fn main() {
-    let (train_features, train_targets) = generate_data(1000);
-    let (test_features, test_targets) = generate_data(200);
-    
-    let features = Arc::new(train_features);
-    let targets = Arc::new(train_targets);
-    let param_count = features[0].len() + 1;
+struct EsConfig {
+    mu: usize,
+    lambda: usize,
+    generations: usize,
+    rho: usize,
+    tau: f64,
+    seed: u64,
+}
 
-    // ES configuration
-    let mu = 50;
-    let lambda = 200;
-    let generations = 1000;
-    let rho = 15;
-    let tau = 0.1;
+// State after `generation` completed generations
+struct Checkpoint {
+    seed: u64,
+    generation: usize,
+    population: Vec<Individual>,
+}
 
-    let population: Vec<Individual> = (0..mu)
+// An individual as written to the checkpoint file. The floats are stored as
+// their f64::to_bits patterns, so a resumed run starts from bit-identical
+// values whatever the JSON parser does with decimal floats.
+#[derive(Serialize, Deserialize)]
+struct SavedIndividual {
+    params: Vec<u64>,
+    sigmas: Vec<u64>,
+    fitness: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedCheckpoint {
+    seed: u64,
+    generation: usize,
+    population: Vec<SavedIndividual>,
+}
+
+fn save_checkpoint(path: &Path, checkpoint: &Checkpoint) -> io::Result<()> {
+    let to_bits = |values: &[f64]| values.iter().map(|v| v.to_bits()).collect();
+    let saved = SavedCheckpoint {
+        seed: checkpoint.seed,
+        generation: checkpoint.generation,
+        population: checkpoint.population.iter()
+            .map(|ind| SavedIndividual { params: to_bits(&ind.params), sigmas: to_bits(&ind.sigmas), fitness: ind.fitness.to_bits() })
+            .collect(),
+    };
+    fs::write(path, serde_json::to_string(&saved)?)
+}
+
+fn load_checkpoint(path: &Path) -> io::Result<Checkpoint> {
+    let saved: SavedCheckpoint = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let from_bits = |bits: Vec<u64>| bits.into_iter().map(f64::from_bits).collect();
+    Ok(Checkpoint {
+        seed: saved.seed,
+        generation: saved.generation,
+        population: saved.population.into_iter()
+            .map(|ind| Individual { params: from_bits(ind.params), sigmas: from_bits(ind.sigmas), fitness: f64::from_bits(ind.fitness) })
+            .collect(),
+    })
+}
+
+// Every individual draws from its own stream, derived from the run seed, the
+// generation and its index, so a run doesn't depend on thread scheduling and a
+// resumed run continues exactly as the uninterrupted one would have
+fn individual_rng(seed: u64, generation: usize, index: usize) -> StdRng {
+    let stream = ((generation as u64) << 32) | index as u64;
+    StdRng::seed_from_u64(seed ^ stream.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+}
+
+fn initial_population(features: &[Vec<f64>], targets: &[f64], config: &EsConfig) -> Vec<Individual> {
+    let param_count = features[0].len() + 1;
+    (0..config.mu)
         .into_par_iter()
-        .map_init(|| StdRng::from_entropy(), |rng, _| {
+        .map(|k| {
+            let mut rng = individual_rng(config.seed, 0, k);
             let params = (0..param_count).map(|_| rng.gen_range(-1.0..1.0)).collect();
             let sigmas = vec![0.2; param_count];
             let mut ind = Individual::new(params, sigmas);
-            ind.evaluate(&features, &targets);
+            ind.evaluate(features, targets);
             ind
         })
-        .collect();
+        .collect()
+}
+
+// Runs the given generations, saving a checkpoint every K of them when asked
+fn evolve(
+    features: &[Vec<f64>],
+    targets: &[f64],
+    config: &EsConfig,
+    mut population: Vec<Individual>,
+    generations: Range<usize>,
+    checkpoint: Option<(&Path, usize)>,
+) -> io::Result<Vec<Individual>> {
+    let param_count = features[0].len() + 1;
+    let (mu, lambda, rho, tau) = (config.mu, config.lambda, config.rho, config.tau);
 
-    let (population, _) = (0..generations).fold((population, 0), |(pop, _), gen| {
+    for gen in generations {
+        let pop = population;
         let offspring: Vec<Individual> = (0..lambda)
             .into_par_iter()
-            .map_init(|| StdRng::from_entropy(), |rng, _| {
+            .map(|k| {
+                let mut rng = individual_rng(config.seed, gen + 1, k);
                 let mut candidates = pop.iter().collect::<Vec<_>>();
                 candidates.sort_by(|a, b| a.fitness.partial_cmp(&b.fitness).unwrap());
                 let parents = &candidates[..rho];
@@ -107,17 +186,16 @@ fn main() {
                 for i in 0..param_count {
                     child_sigmas[i] *= (tau * rng.sample::<f64, _>(StandardNormal))
                         .exp()
-                        .max(0.5)
-                        .min(2.0);
+                        .clamp(0.5, 2.0);
                     child_sigmas[i] = child_sigmas[i].clamp(1e-3, 0.5);
                     
                     let normal = Normal::new(0.0, child_sigmas[i]).unwrap();
-                    child_params[i] += normal.sample(rng);
+                    child_params[i] += normal.sample(&mut rng);
                     child_params[i] = child_params[i].clamp(-3.0, 3.0);
                 }
 
                 let mut ind = Individual::new(child_params, child_sigmas);
-                ind.evaluate(&features, &targets);
+                ind.evaluate(features, targets);
                 ind
             })
             .collect();
@@ -134,8 +212,81 @@ fn main() {
             );
         }
 
-        (new_pop, gen)
-    });
+        population = new_pop;
+
+        if let Some((path, every)) = checkpoint {
+            if (gen + 1) % every == 0 {
+                save_checkpoint(path, &Checkpoint { seed: config.seed, generation: gen + 1, population: population.clone() })?;
+            }
+        }
+    }
+
+    Ok(population)
+}
+
+fn main() -> io::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    let value_of = |flag: &str| args.iter()
+        .position(|a| a == flag)
+        .and_then(|i| args.get(i + 1));
+
+    // --resume <path>: continue a checkpointed run; its seed overrides --seed so
+    // the data and the remaining generations are the ones it started with
+    let resumed = value_of("--resume").map(|path| load_checkpoint(Path::new(path))).transpose()?;
+
+    // --seed <n>: the same seed always yields the same train/test data and run
+    let seed = match (&resumed, value_of("--seed")) {
+        (Some(checkpoint), _) => checkpoint.seed,
+        (None, Some(s)) => s.parse::<u64>()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid --seed '{}'", s)))?,
+        (None, None) => DEFAULT_DATA_SEED,
+    };
+
+    // ES configuration
+    let config = EsConfig {
+        mu: 50,
+        lambda: 200,
+        generations: 1000,
+        rho: 15,
+        tau: 0.1,
+        seed,
+    };
+
+    let (train_features, train_targets) = generate_data(1000, seed);
+    let (test_features, test_targets) = generate_data(200, seed.wrapping_add(1));
+
+    // --write-data <dir>: save train.csv and test.csv for inspection
+    if let Some(dir) = value_of("--write-data") {
+        let dir = Path::new(dir);
+        fs::create_dir_all(dir)?;
+        write_data(&dir.join("train.csv"), &train_features, &train_targets)?;
+        write_data(&dir.join("test.csv"), &test_features, &test_targets)?;
+        println!("Dataset (seed {}) written to {}", seed, dir.display());
+    }
+    
+    let features = Arc::new(train_features);
+    let targets = Arc::new(train_targets);
+    let param_count = features[0].len() + 1;
+
+    // --checkpoint <path>: save the population every --checkpoint-every K generations (default 50)
+    let checkpoint_path = value_of("--checkpoint").map(Path::new);
+    let checkpoint_every = match value_of("--checkpoint-every") {
+        Some(k) => k.parse::<usize>()
+            .ok()
+            .filter(|&k| k > 0)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid --checkpoint-every '{}'", k)))?,
+        None => DEFAULT_CHECKPOINT_EVERY,
+    };
+
+    let (population, start) = match resumed {
+        Some(checkpoint) => {
+            println!("Resuming from generation {} (seed {})", checkpoint.generation, checkpoint.seed);
+            (checkpoint.population, checkpoint.generation)
+        }
+        None => (initial_population(&features, &targets, &config), 0),
+    };
+    let checkpoint = checkpoint_path.map(|path| (path, checkpoint_every));
+    let population = evolve(&features, &targets, &config, population, start..config.generations, checkpoint)?;
 
     let best = &population[0];
     let predictions = best.predict(&test_features);
@@ -147,14 +298,18 @@ fn main() {
     println!("Accuracy: {:.2}%", accuracy * 100.0);
     println!("Weights: {:.2?}", &best.params[..param_count-1]);
     println!("Bias: {:.4}", best.params[param_count-1]);
+    Ok(())
 }
 
-fn generate_data(n: usize) -> (Vec<Vec<f64>>, Vec<f64>) {
-    let mut rng = StdRng::from_entropy();
+// n points of the benchmark problem: x1, x2 uniform in [-2, 2), labelled by
+// the sign of 0.5*x1 + 1.5*x2 - 0.3 plus N(0, 0.5) noise. Deterministic for
+// a given seed, so runs (and the reported accuracy) can be reproduced.
+pub fn generate_data(n: usize, seed: u64) -> (Vec<Vec<f64>>, Vec<f64>) {
+    let mut rng = StdRng::seed_from_u64(seed);
     let mut features = Vec::with_capacity(n);
     let mut targets = Vec::with_capacity(n);
     
-    let true_weights = vec![0.5, 1.5];
+    let true_weights = [0.5, 1.5];
     let true_bias = -0.3;
     
     for _ in 0..n {
@@ -171,4 +326,87 @@ fn generate_data(n: usize) -> (Vec<Vec<f64>>, Vec<f64>) {
     }
     
     (features, targets)
-}
\ No newline at end of file
+}
+
+// One row per point: x1, x2 and the 0/1 target
+fn write_data(path: &Path, features: &[Vec<f64>], targets: &[f64]) -> io::Result<()> {
+    let mut content = String::from("x1,x2,y\n");
+    for (x, y) in features.iter().zip(targets) {
+        content.push_str(&format!("{},{},{}\n", x[0], x[1], y));
+    }
+    fs::write(path, content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_gives_same_data() {
+        let (features_a, targets_a) = generate_data(500, 7);
+        let (features_b, targets_b) = generate_data(500, 7);
+        let (features_c, _) = generate_data(500, 8);
+
+        assert_eq!(features_a, features_b);
+        assert_eq!(targets_a, targets_b);
+        assert_ne!(features_a, features_c);
+        assert!(features_a.iter().flatten().all(|x| (-2.0..2.0).contains(x)));
+    }
+
+    #[test]
+    fn test_class_balance_is_roughly_centered() {
+        let (_, targets) = generate_data(5000, DEFAULT_DATA_SEED);
+        let positive_rate = targets.iter().sum::<f64>() / targets.len() as f64;
+
+        assert!(targets.iter().all(|&y| y == 0.0 || y == 1.0));
+        // The -0.3 bias tips the balance slightly towards class 0
+        assert!((0.40..0.50).contains(&positive_rate), "positive rate {}", positive_rate);
+    }
+
+    #[test]
+    fn test_log_loss_is_finite_and_clamped() {
+        let features = vec![vec![1.0], vec![-1.0]];
+        let targets = vec![1.0, 0.0];
+
+        // A zero model predicts 0.5 for both: ln 2 per sample, no regularization
+        let mut zero = Individual::new(vec![0.0, 0.0], vec![0.1, 0.1]);
+        zero.evaluate(&features, &targets);
+        assert!((zero.fitness - std::f64::consts::LN_2).abs() < 1e-12, "{}", zero.fitness);
+
+        // A confidently wrong model saturates the sigmoid; the clamp caps the
+        // loss near -ln(1e-15) per sample instead of ln(0)
+        let mut wrong = Individual::new(vec![-1000.0, 0.0], vec![0.1, 0.1]);
+        wrong.evaluate(&features, &targets);
+        let capped = -(1e-15f64).ln() - (1.0 - (1.0 - 1e-15f64)).ln();
+        let regularization = 1.0 * 1000.0f64.powi(2) + 0.5 * 1000.0;
+        assert!(wrong.fitness.is_finite());
+        assert!((wrong.fitness - (capped + regularization) / 2.0).abs() < 1e-9, "{}", wrong.fitness);
+    }
+
+    #[test]
+    fn test_resumed_run_matches_uninterrupted_run() {
+        let (features, targets) = generate_data(200, 3);
+        let config = EsConfig { mu: 10, lambda: 20, generations: 60, rho: 3, tau: 0.1, seed: 11 };
+        let path = std::env::temp_dir().join(format!("test_es_checkpoint_{}.json", std::process::id()));
+
+        let uninterrupted = evolve(&features, &targets, &config,
+            initial_population(&features, &targets, &config), 0..60, None).unwrap();
+
+        evolve(&features, &targets, &config,
+            initial_population(&features, &targets, &config), 0..50, Some((&path, 25))).unwrap();
+        let checkpoint = load_checkpoint(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(checkpoint.generation, 50);
+        assert_eq!(checkpoint.seed, 11);
+
+        let resumed = evolve(&features, &targets, &config,
+            checkpoint.population, checkpoint.generation..60, None).unwrap();
+
+        assert_eq!(resumed.len(), uninterrupted.len());
+        for (a, b) in resumed.iter().zip(&uninterrupted) {
+            assert_eq!(a.params, b.params);
+            assert_eq!(a.sigmas, b.sigmas);
+            assert_eq!(a.fitness, b.fitness);
+        }
+    }
+}
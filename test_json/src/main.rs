@@ -1,8 +1,11 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
 use std::env;
+use std::error::Error;
 use std::fs::File;
-use std::io::Read;
-use std::path::Path;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
 
 #[derive(Deserialize)]
 struct PatientData {
@@ -15,27 +18,165 @@ struct PatientData {
     question30: Option<f64>, // How many years did you smoke?
     #[serde(rename = "question29")]
     question29: Option<f64>, // How many years since you quit smoking?
+    // Every other answer, including the daily amount smoked and its unit (see
+    // SmokingFields)
+    #[serde(flatten)]
+    other: HashMap<String, Value>,
 }
 
-fn is_indicated_for_lung_cancer_screening(patient_data: &PatientData) -> bool {
+// Cigarettes made from one gram of loose tobacco, unless --cigarettes-per-gram
+// is given
+const DEFAULT_CIGARETTES_PER_GRAM: f64 = 1.0;
+
+const CIGARETTES_PER_PACK: f64 = 20.0;
+
+// Amount smoked per day, in the unit the patient answered with
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SmokingIntensity {
+    Cigarettes(f64),
+    Packs(f64),
+    Grams(f64),
+}
+
+impl SmokingIntensity {
+    // The amount and its unit: "Item 1" (or "cigarettes") is cigarettes/day,
+    // "Item 2" ("packs") packs/day and "Item 3" ("grams") grams of
+    // tobacco/day. None when no amount was given.
+    fn from_answers(amount: Option<f64>, unit: Option<&str>) -> Result<Option<Self>, String> {
+        let amount = match amount {
+            Some(amount) => amount,
+            None => return Ok(None),
+        };
+        match unit.map(str::trim) {
+            Some("Item 1") | Some("cigarettes") => Ok(Some(SmokingIntensity::Cigarettes(amount))),
+            Some("Item 2") | Some("packs") | None => Ok(Some(SmokingIntensity::Packs(amount))),
+            Some("Item 3") | Some("grams") => Ok(Some(SmokingIntensity::Grams(amount))),
+            Some(other) => Err(format!("Unknown smoking amount unit '{}' (expected cigarettes, packs or grams)", other)),
+        }
+    }
+
+    fn packs_per_day(&self, cigarettes_per_gram: f64) -> f64 {
+        match *self {
+            SmokingIntensity::Cigarettes(cigarettes) => cigarettes / CIGARETTES_PER_PACK,
+            SmokingIntensity::Packs(packs) => packs,
+            SmokingIntensity::Grams(grams) => grams * cigarettes_per_gram / CIGARETTES_PER_PACK,
+        }
+    }
+}
+
+// --amount-field / --unit-field: the questions holding the amount smoked per
+// day and its unit, question31 and question32 unless the questionnaire is
+// numbered differently
+#[derive(Debug, Clone, PartialEq)]
+struct SmokingFields {
+    amount: String,
+    unit: String,
+}
+
+impl Default for SmokingFields {
+    fn default() -> Self {
+        SmokingFields { amount: "question31".to_string(), unit: "question32".to_string() }
+    }
+}
+
+impl SmokingFields {
+    fn from_args(args: &[String]) -> Result<Self, String> {
+        let value_of = |flag: &str| match args.iter().position(|a| a == flag) {
+            Some(i) => args.get(i + 1).cloned().map(Some).ok_or_else(|| format!("{} needs a field name", flag)),
+            None => Ok(None),
+        };
+        let defaults = SmokingFields::default();
+        Ok(SmokingFields {
+            amount: value_of("--amount-field")?.unwrap_or(defaults.amount),
+            unit: value_of("--unit-field")?.unwrap_or(defaults.unit),
+        })
+    }
+
+    fn intensity(&self, patient_data: &PatientData) -> Result<Option<SmokingIntensity>, String> {
+        let amount = match patient_data.other.get(&self.amount) {
+            None | Some(Value::Null) => None,
+            Some(value) => Some(value.as_f64().ok_or_else(|| format!("{} is not a number: {}", self.amount, value))?),
+        };
+        let unit = match patient_data.other.get(&self.unit) {
+            None | Some(Value::Null) => None,
+            Some(value) => Some(value.as_str().ok_or_else(|| format!("{} is not text: {}", self.unit, value))?),
+        };
+        SmokingIntensity::from_answers(amount, unit)
+    }
+}
+
+// --cigarettes-per-gram X: conversion used for answers given in grams
+fn cigarettes_per_gram_from_args(args: &[String]) -> Result<f64, String> {
+    match args.iter().position(|a| a == "--cigarettes-per-gram") {
+        Some(i) => {
+            let value = args.get(i + 1).map(String::as_str).unwrap_or("");
+            value.parse::<f64>()
+                .ok()
+                .filter(|v| v.is_finite() && *v > 0.0)
+                .ok_or_else(|| format!("Invalid --cigarettes-per-gram '{}' (expected a positive number)", value))
+        }
+        None => Ok(DEFAULT_CIGARETTES_PER_GRAM),
+    }
+}
+
+// Years smoked times packs per day; one pack a day when no amount was given
+fn pack_years(patient_data: &PatientData, fields: &SmokingFields, cigarettes_per_gram: f64) -> Result<f64, String> {
+    let years_smoked = patient_data.question30.unwrap_or(0.0);
+    let packs_per_day = fields.intensity(patient_data)?
+        .map_or(1.0, |intensity| intensity.packs_per_day(cigarettes_per_gram));
+    Ok(years_smoked * packs_per_day)
+}
+
+// The answers the decision is based on, the decision and the criterion that
+// settled it; one line of --jsonl per patient
+#[derive(Debug, PartialEq, Serialize)]
+struct ScreeningResult {
+    age: i32,
+    current_smoker: bool,
+    former_smoker: bool,
+    years_smoked: f64,
+    years_since_quit: f64,
+    pack_years: f64,
+    indicated: bool,
+    reason: &'static str,
+}
+
+fn screen(patient_data: &PatientData, fields: &SmokingFields, cigarettes_per_gram: f64) -> Result<ScreeningResult, String> {
     // Extract relevant data
     let age = patient_data.question2;
     let currently_smokes = patient_data.question4.as_deref() == Some("Item 2");
     let previously_smoked = patient_data.question28.as_deref() == Some("Item 2");
-    let years_smoked = patient_data.question30.unwrap_or(0.0);
+    let pack_years = pack_years(patient_data, fields, cigarettes_per_gram)?;
     let years_since_quit = patient_data.question29.unwrap_or(0.0);
 
-    // Apply the screening criteria
-    if age >= 50 && age <= 80 {
-        if currently_smokes || (previously_smoked && years_since_quit <= 15.0) {
-            let pack_years = years_smoked; // Assuming 1 pack per day
-            if pack_years >= 20.0 {
-                return true;
-            }
-        }
-    }
+    // Apply the screening criteria: age 50-80, a current smoker or one who
+    // quit at most 15 years ago, and at least 20 pack-years
+    let (indicated, reason) = if !(50..=80).contains(&age) {
+        (false, "age outside 50-80")
+    } else if !currently_smokes && !previously_smoked {
+        (false, "no smoking history")
+    } else if !currently_smokes && years_since_quit > 15.0 {
+        (false, "quit more than 15 years ago")
+    } else if pack_years < 20.0 {
+        (false, "fewer than 20 pack-years")
+    } else {
+        (true, "meets the age, smoking history and pack-year criteria")
+    };
+
+    Ok(ScreeningResult {
+        age,
+        current_smoker: currently_smokes,
+        former_smoker: previously_smoked,
+        years_smoked: patient_data.question30.unwrap_or(0.0),
+        years_since_quit,
+        pack_years,
+        indicated,
+        reason,
+    })
+}
 
-    false
+fn is_indicated_for_lung_cancer_screening(patient_data: &PatientData, fields: &SmokingFields, cigarettes_per_gram: f64) -> Result<bool, String> {
+    Ok(screen(patient_data, fields, cigarettes_per_gram)?.indicated)
 }
 
 fn read_json_from_file<P: AsRef<Path>>(path: P) -> Result<PatientData, Box<dyn std::error::Error>> {
@@ -46,18 +187,164 @@ fn read_json_from_file<P: AsRef<Path>>(path: P) -> Result<PatientData, Box<dyn s
     Ok(data)
 }
 
+// --on-error {skip,abort}: what a batch run does with a file that can't be
+// read or parsed; skip logs it and records it in the CSV, abort stops there
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OnError {
+    Skip,
+    Abort,
+}
+
+impl OnError {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "skip" => Ok(OnError::Skip),
+            "abort" => Ok(OnError::Abort),
+            other => Err(format!("Unknown --on-error '{}' (expected skip or abort)", other)),
+        }
+    }
+
+    fn from_args(args: &[String]) -> Result<Self, String> {
+        match args.iter().position(|a| a == "--on-error") {
+            Some(i) => OnError::parse(args.get(i + 1).ok_or("--on-error needs skip or abort")?),
+            None => Ok(OnError::Skip),
+        }
+    }
+}
+
+// Where batch mode writes its results unless --out is given
+const DEFAULT_BATCH_OUTPUT: &str = "screening_results.csv";
+
+// The .json files (any case) directly inside dir, sorted by name
+fn json_files_in(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().and_then(|s| s.to_str()).is_some_and(|ext| ext.eq_ignore_ascii_case("json")) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// One --jsonl line: the file and either its screening result or the error
+// that kept it from being screened
+#[derive(Serialize)]
+struct JsonlRecord<'a> {
+    file: &'a str,
+    #[serde(flatten)]
+    result: Option<&'a ScreeningResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+fn write_jsonl_line<W: Write>(out: &mut W, record: &JsonlRecord) -> Result<(), Box<dyn Error>> {
+    serde_json::to_writer(&mut *out, record)?;
+    out.write_all(b"\n")?;
+    Ok(())
+}
+
+// Screen every JSON file in dir into a file,status,indicated,error CSV and,
+// with jsonl_path, one JSON object per file there too; returns how many files
+// were skipped. With OnError::Abort the first bad file ends the run with an
+// error; the results before it are still written.
+fn run_batch(
+    dir: &Path,
+    output_path: &Path,
+    jsonl_path: Option<&Path>,
+    on_error: OnError,
+    fields: &SmokingFields,
+    cigarettes_per_gram: f64,
+) -> Result<usize, Box<dyn Error>> {
+    let mut writer = csv::Writer::from_path(output_path)?;
+    writer.write_record(["file", "status", "indicated", "error"])?;
+    let mut jsonl = jsonl_path.map(File::create).transpose()?.map(BufWriter::new);
+
+    let mut skipped = 0;
+    for path in json_files_in(dir)? {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let screened = read_json_from_file(&path)
+            .and_then(|patient_data| Ok(screen(&patient_data, fields, cigarettes_per_gram)?));
+        match screened {
+            Ok(result) => {
+                writer.write_record([file_name.as_str(), "ok", if result.indicated { "true" } else { "false" }, ""])?;
+                if let Some(out) = jsonl.as_mut() {
+                    write_jsonl_line(out, &JsonlRecord { file: &file_name, result: Some(&result), error: None })?;
+                }
+            }
+            Err(err) if on_error == OnError::Skip => {
+                eprintln!("Skipping {}: {}", file_name, err);
+                writer.write_record([file_name.as_str(), "skipped", "", &err.to_string()])?;
+                if let Some(out) = jsonl.as_mut() {
+                    write_jsonl_line(out, &JsonlRecord { file: &file_name, result: None, error: Some(err.to_string()) })?;
+                }
+                skipped += 1;
+            }
+            Err(err) => {
+                writer.flush()?;
+                if let Some(out) = jsonl.as_mut() {
+                    out.flush()?;
+                }
+                return Err(format!("{}: {}", file_name, err).into());
+            }
+        }
+    }
+
+    writer.flush()?;
+    if let Some(out) = jsonl.as_mut() {
+        out.flush()?;
+    }
+    Ok(skipped)
+}
+
 fn main() {
-    // Get the file path from command-line arguments
+    // Get the file path from command-line arguments; a directory is screened
+    // file by file into a CSV (--out <path>, --on-error {skip,abort}) and,
+    // with --jsonl <path>, into JSON Lines as well
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: {} <path_to_json_file>", args[0]);
+    if args.len() < 2 {
+        eprintln!("Usage: {} <path_to_json_file | directory> [--out <csv>] [--jsonl <path>] [--on-error skip|abort] [--cigarettes-per-gram X] [--amount-field <question>] [--unit-field <question>]", args[0]);
         std::process::exit(1);
     }
     let file_path = &args[1];
+    let settings = cigarettes_per_gram_from_args(&args)
+        .and_then(|cigarettes_per_gram| Ok((cigarettes_per_gram, SmokingFields::from_args(&args)?)));
+    let (cigarettes_per_gram, fields) = match settings {
+        Ok(settings) => settings,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    if Path::new(file_path).is_dir() {
+        let output_path = args.iter()
+            .position(|a| a == "--out")
+            .and_then(|i| args.get(i + 1))
+            .map_or(DEFAULT_BATCH_OUTPUT, String::as_str);
+        let jsonl_path = args.iter()
+            .position(|a| a == "--jsonl")
+            .and_then(|i| args.get(i + 1))
+            .map(Path::new);
+        let result = OnError::from_args(&args)
+            .map_err(|e| e.into())
+            .and_then(|on_error| run_batch(Path::new(file_path), Path::new(output_path), jsonl_path, on_error, &fields, cigarettes_per_gram));
+        match result {
+            Ok(skipped) => println!("Results saved to {} ({} file(s) skipped)", output_path, skipped),
+            Err(err) => {
+                eprintln!("Batch stopped: {}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
 
-    match read_json_from_file(file_path) {
-        Ok(patient_data) => {
-            if is_indicated_for_lung_cancer_screening(&patient_data) {
+    let screened = read_json_from_file(file_path)
+        .and_then(|patient_data| Ok(is_indicated_for_lung_cancer_screening(&patient_data, &fields, cigarettes_per_gram)?));
+    match screened {
+        Ok(indicated) => {
+            if indicated {
                 println!("Patient is indicated for lung cancer screening.");
             } else {
                 println!("Patient is not indicated for lung cancer screening.");
@@ -68,4 +355,114 @@ fn main() {
             std::process::exit(1);
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_malformed_file_skipped_or_aborts() {
+        let dir = std::env::temp_dir().join(format!("test_json_batch_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.json"), r#"{"question2": 60, "question4": "Item 2", "question30": 30}"#).unwrap();
+        std::fs::write(dir.join("b.json"), r#"{"question2": 60, "question4": "#).unwrap();
+        std::fs::write(dir.join("c.json"), r#"{"question2": 45}"#).unwrap();
+        let output = dir.join("results.out");
+
+        let skipped = run_batch(&dir, &output, None, OnError::Skip, &SmokingFields::default(), DEFAULT_CIGARETTES_PER_GRAM).unwrap();
+        let skip_csv = std::fs::read_to_string(&output).unwrap();
+        let aborted = run_batch(&dir, &output, None, OnError::Abort, &SmokingFields::default(), DEFAULT_CIGARETTES_PER_GRAM);
+        let abort_csv = std::fs::read_to_string(&output).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(skipped, 1);
+        let lines: Vec<&str> = skip_csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "file,status,indicated,error");
+        assert_eq!(lines[1], "a.json,ok,true,");
+        assert!(lines[2].starts_with("b.json,skipped,,"), "{}", lines[2]);
+        assert_eq!(lines[3], "c.json,ok,false,");
+
+        let err = aborted.unwrap_err().to_string();
+        assert!(err.starts_with("b.json: "), "{}", err);
+        assert_eq!(abort_csv, "file,status,indicated,error\na.json,ok,true,\n");
+
+        assert_eq!(OnError::from_args(&[]), Ok(OnError::Skip));
+        assert!(OnError::parse("retry").is_err());
+    }
+
+    #[test]
+    fn test_jsonl_one_object_per_input_file() {
+        let dir = std::env::temp_dir().join(format!("test_json_jsonl_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.json"), r#"{"question2": 60, "question4": "Item 2", "question30": 30}"#).unwrap();
+        std::fs::write(dir.join("b.json"), r#"{"question2": 60, "question4": "#).unwrap();
+        std::fs::write(dir.join("c.json"), r#"{"question2": 58, "question28": "Item 2", "question29": 20, "question30": 30}"#).unwrap();
+        let output = dir.join("results.out");
+        let jsonl = dir.join("results.jsonl.out");
+
+        run_batch(&dir, &output, Some(&jsonl), OnError::Skip, &SmokingFields::default(), DEFAULT_CIGARETTES_PER_GRAM).unwrap();
+        let written = std::fs::read_to_string(&jsonl).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+
+        let lines: Vec<serde_json::Value> = written.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["file"], "a.json");
+        assert_eq!(lines[0]["pack_years"], 30.0);
+        assert_eq!(lines[0]["indicated"], true);
+        assert_eq!(lines[1]["file"], "b.json");
+        assert!(lines[1]["error"].is_string());
+        assert!(lines[1].get("indicated").is_none());
+        assert_eq!(lines[2]["indicated"], false);
+        assert_eq!(lines[2]["reason"], "quit more than 15 years ago");
+    }
+
+    fn patient(json: &str) -> PatientData {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_cigarettes_per_day_converted_to_pack_years() {
+        let heavy = patient(r#"{"question2": 62, "question4": "Item 2", "question30": 10, "question31": 40, "question32": "Item 1"}"#);
+        assert_eq!(pack_years(&heavy, &SmokingFields::default(), DEFAULT_CIGARETTES_PER_GRAM), Ok(20.0));
+        assert_eq!(is_indicated_for_lung_cancer_screening(&heavy, &SmokingFields::default(), DEFAULT_CIGARETTES_PER_GRAM), Ok(true));
+
+        let light = patient(r#"{"question2": 62, "question4": "Item 2", "question30": 10, "question31": 20, "question32": "cigarettes"}"#);
+        assert_eq!(pack_years(&light, &SmokingFields::default(), DEFAULT_CIGARETTES_PER_GRAM), Ok(10.0));
+        assert_eq!(is_indicated_for_lung_cancer_screening(&light, &SmokingFields::default(), DEFAULT_CIGARETTES_PER_GRAM), Ok(false));
+
+        // No amount keeps the old one-pack-a-day assumption
+        let legacy = patient(r#"{"question2": 62, "question4": "Item 2", "question30": 25}"#);
+        assert_eq!(pack_years(&legacy, &SmokingFields::default(), DEFAULT_CIGARETTES_PER_GRAM), Ok(25.0));
+
+        assert_eq!(SmokingIntensity::Packs(1.5).packs_per_day(DEFAULT_CIGARETTES_PER_GRAM), 1.5);
+        assert_eq!(SmokingIntensity::Grams(20.0).packs_per_day(0.5), 0.5);
+        assert!(SmokingIntensity::from_answers(Some(3.0), Some("Item 9")).is_err());
+        assert_eq!(SmokingIntensity::from_answers(None, Some("Item 1")), Ok(None));
+
+        let args: Vec<String> = ["test_json", "--cigarettes-per-gram", "0.75"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(cigarettes_per_gram_from_args(&args), Ok(0.75));
+        assert!(cigarettes_per_gram_from_args(&["--cigarettes-per-gram".to_string(), "0".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_smoking_fields_can_be_remapped() {
+        let args: Vec<String> = ["test_json", "--amount-field", "q_daily", "--unit-field", "q_daily_unit"]
+            .iter().map(|s| s.to_string()).collect();
+        let fields = SmokingFields::from_args(&args).unwrap();
+        assert_eq!(fields, SmokingFields { amount: "q_daily".to_string(), unit: "q_daily_unit".to_string() });
+        assert_eq!(SmokingFields::from_args(&args[..1]), Ok(SmokingFields::default()));
+        assert!(SmokingFields::from_args(&["--unit-field".to_string()]).is_err());
+
+        // 40 cigarettes/day over 10 years, asked as q_daily / q_daily_unit
+        let renumbered = patient(r#"{"question2": 62, "question4": "Item 2", "question30": 10, "q_daily": 40, "q_daily_unit": "Item 1"}"#);
+        assert_eq!(pack_years(&renumbered, &fields, DEFAULT_CIGARETTES_PER_GRAM), Ok(20.0));
+        assert_eq!(is_indicated_for_lung_cancer_screening(&renumbered, &fields, DEFAULT_CIGARETTES_PER_GRAM), Ok(true));
+        // The default mapping doesn't see those answers: one pack a day
+        assert_eq!(pack_years(&renumbered, &SmokingFields::default(), DEFAULT_CIGARETTES_PER_GRAM), Ok(10.0));
+
+        let bad = patient(r#"{"question2": 62, "question4": "Item 2", "question30": 10, "q_daily": "forty"}"#);
+        assert!(pack_years(&bad, &fields, DEFAULT_CIGARETTES_PER_GRAM).is_err());
+    }
+}